@@ -138,6 +138,10 @@ pub struct MemoryBackendWithPersistedGraph<P: PersistedGraph + 'static> {
     persist_queue1: ConcurrentQueue<TaskId>,
     persist_queue1_queued: DashSet<TaskId>,
     need_persisting: DashSet<TaskId>,
+    /// Persistent tasks that depend on state outside of the graph and need to
+    /// be re-executed when the graph is restored. Entries are taken when the
+    /// task is persisted, as every execution marks the task again.
+    session_dependent_tasks: DashSet<TaskId>,
     /// Task sorted by importance, sharded to avoid lock contention
     persist_queue_by_duration: [Mutex<BinaryHeap<(Duration, TaskId)>>; 64],
     persist_capacity: AtomicUsize,
@@ -166,6 +170,7 @@ impl<P: PersistedGraph> MemoryBackendWithPersistedGraph<P> {
             persist_queue1: ConcurrentQueue::unbounded(),
             persist_queue1_queued: DashSet::new(),
             need_persisting: DashSet::new(),
+            session_dependent_tasks: DashSet::new(),
             persist_queue_by_duration: [(); 64].map(|_| Mutex::new(BinaryHeap::new())),
             persist_capacity: AtomicUsize::new(num_cpus::get()),
             persist_job,
@@ -814,9 +819,12 @@ impl<P: PersistedGraph> MemoryBackendWithPersistedGraph<P> {
                                         };
                                         let externally_active =
                                             task_info.active_parents.load(Ordering::Acquire) > 0;
+                                        let session_dependent =
+                                            self.session_dependent_tasks.remove(&task).is_some();
                                         let task_state =
                                             nxpkg_tasks::persisted_graph::PersistTaskState {
                                                 externally_active,
+                                                session_dependent,
                                             };
                                         if let Some(PersistResult {
                                             tasks_to_activate,
@@ -839,6 +847,9 @@ impl<P: PersistedGraph> MemoryBackendWithPersistedGraph<P> {
                                             }
                                             return true;
                                         } else {
+                                            if session_dependent {
+                                                self.session_dependent_tasks.insert(task);
+                                            }
                                             println!(
                                                 "task {task} failed to persist: {:?}",
                                                 task_info.task_type
@@ -1000,6 +1011,17 @@ impl<P: PersistedGraph> Backend for MemoryBackendWithPersistedGraph<P> {
         self.pg_stop(nxpkg_tasks);
     }
 
+    fn idle_start(
+        &self,
+        nxpkg_tasks: &dyn NxpkgTasksBackendApi<MemoryBackendWithPersistedGraph<P>>,
+    ) {
+        // Nothing is executing, so use all available workers to write back
+        // pending tasks to the persisted graph.
+        if self.has_persist_work() {
+            self.increase_persist_workers(num_cpus::get(), nxpkg_tasks);
+        }
+    }
+
     fn invalidate_task(
         &self,
         task: TaskId,
@@ -1604,6 +1626,18 @@ impl<P: PersistedGraph> Backend for MemoryBackendWithPersistedGraph<P> {
         task
     }
 
+    fn mark_own_task_as_session_dependent(
+        &self,
+        task: TaskId,
+        _nxpkg_tasks: &dyn NxpkgTasksBackendApi<MemoryBackendWithPersistedGraph<P>>,
+    ) {
+        // Only persistent tasks are persisted, which takes the entry again
+        let task_info = self.tasks.get(*task).unwrap();
+        if let TaskType::Persistent(_) = task_info.task_type {
+            self.session_dependent_tasks.insert(task);
+        }
+    }
+
     fn dispose_root_task(&self, _task: TaskId, _nxpkg_tasks: &dyn NxpkgTasksBackendApi<Self>) {
         todo!()
     }
@@ -1648,10 +1682,15 @@ impl<'a, P: PersistedGraph> PersistedGraphApi for MemoryBackendPersistedGraphApi
     }
 
     fn lookup_task_type(&self, id: TaskId) -> &PersistentTaskType {
-        let task = self.backend.tasks.get(*id).unwrap();
+        self.try_lookup_task_type(id)
+            .expect("lookup_task_type should only be used for PersistentTaskType")
+    }
+
+    fn try_lookup_task_type(&self, id: TaskId) -> Option<&PersistentTaskType> {
+        let task = self.backend.tasks.get(*id)?;
         match &task.task_type {
-            TaskType::Persistent(ty) => ty,
-            _ => panic!("lookup_task_type should only be used for PersistentTaskType"),
+            TaskType::Persistent(ty) => Some(ty),
+            _ => None,
         }
     }
 }
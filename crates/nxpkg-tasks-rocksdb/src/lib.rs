@@ -0,0 +1,907 @@
+//! A [PersistedGraph] implementation that stores the task graph in a RocksDB
+//! database on disk. This allows to restore task outputs and cells from a
+//! previous session instead of starting from a cold graph.
+//!
+//! Activeness of tasks is session state and is not stored. It's rebuilt while
+//! the consumer graph reconnects to the persisted tasks. Tasks that depend on
+//! state outside of the graph (e. g. the filesystem) are marked as session
+//! dependent and are considered dirty when the graph is opened again.
+
+mod mapping;
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use anyhow::{Context, Result};
+use dashmap::DashMap;
+use nxpkg_tasks::{
+    backend::PersistentTaskType,
+    persisted_graph::{
+        ActivateResult, DeactivateResult, PersistResult, PersistTaskState, PersistedGraph,
+        PersistedGraphApi, ReadTaskState, TaskData,
+    },
+    registry, with_task_id_mapping, RawVc, TaskId,
+};
+use parking_lot::Mutex;
+use rocksdb::{
+    ColumnFamily, ColumnFamilyDescriptor, Direction, IteratorMode, Options, WriteBatch, DB,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::mapping::{BackwardMapping, ForwardMapping};
+
+/// Bump this when the layout of the stored data changes. Databases with a
+/// different version are discarded when opened.
+const FORMAT_VERSION: u32 = 1;
+
+/// (persisted id) => (serialized PersistentTaskType)
+const TYPES: &str = "types";
+/// (serialized PersistentTaskType) => (persisted id)
+const CACHE: &str = "cache";
+/// (persisted id) => (serialized TaskData)
+const DATA: &str = "data";
+/// (persisted id) => (StoredTaskState)
+const STATE: &str = "state";
+/// (dependency key, dependent persisted id) => ()
+const DEPENDENTS: &str = "dependents";
+/// (name) => (value)
+const META: &str = "meta";
+
+const COLUMN_FAMILIES: [&str; 6] = [TYPES, CACHE, DATA, STATE, DEPENDENTS, META];
+
+const META_VERSION: &[u8] = b"version";
+const META_NEXT_ID: &[u8] = b"next_id";
+
+/// The part of the task state that is written to disk.
+#[derive(Serialize, Deserialize, Default)]
+struct StoredTaskState {
+    clean: bool,
+    session_dependent: bool,
+    children: Vec<u64>,
+    /// Keys of the dependencies, see [dependency_key].
+    dependencies: Vec<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct TaskState {
+    persisted: bool,
+    stored: StoredTaskState,
+
+    // Session state, not written to disk
+    /// The consumer graph keeps this task active.
+    externally_active: bool,
+    /// Number of active persisted tasks that have this task as child.
+    internal_active_parents: u32,
+    /// The children of this task are counted as active.
+    active: bool,
+}
+
+impl TaskState {
+    fn should_be_active(&self) -> bool {
+        self.externally_active || self.internal_active_parents > 0
+    }
+}
+
+#[derive(Default)]
+struct Graph {
+    states: HashMap<u64, TaskState>,
+}
+
+impl Graph {
+    /// Returns true when the task need to be activated.
+    fn increment_active_parents(&mut self, pid: u64) -> bool {
+        let state = self.states.entry(pid).or_default();
+        state.internal_active_parents += 1;
+        state.internal_active_parents == 1 && !state.active
+    }
+
+    /// Returns true when the task need to be deactivated.
+    fn decrement_active_parents(&mut self, pid: u64) -> bool {
+        let state = self.states.entry(pid).or_default();
+        state.internal_active_parents = state.internal_active_parents.saturating_sub(1);
+        state.internal_active_parents == 0 && (state.active || !state.persisted)
+    }
+}
+
+pub struct RocksDbPersistedGraph {
+    db: DB,
+    path: PathBuf,
+    next_id: AtomicU64,
+    graph: Mutex<Graph>,
+    allocate_lock: Mutex<()>,
+    pub(crate) task_to_pid: DashMap<TaskId, u64>,
+    pub(crate) pid_to_task: DashMap<u64, TaskId>,
+}
+
+fn open_db(path: &Path) -> Result<DB> {
+    let mut options = Options::default();
+    options.create_if_missing(true);
+    options.create_missing_column_families(true);
+    options.set_compression_type(rocksdb::DBCompressionType::Lz4);
+    let column_families = COLUMN_FAMILIES
+        .iter()
+        .map(|name| ColumnFamilyDescriptor::new(*name, Options::default()));
+    DB::open_cf_descriptors(&options, path, column_families)
+        .with_context(|| format!("failed to open persisted graph at {}", path.display()))
+}
+
+fn pid_key(pid: u64) -> [u8; 8] {
+    pid.to_be_bytes()
+}
+
+fn decode_pid(bytes: &[u8]) -> Result<u64> {
+    Ok(u64::from_be_bytes(
+        bytes.try_into().context("invalid persisted task id")?,
+    ))
+}
+
+/// Encodes a [RawVc] with persisted task ids. Cells are keyed by the global
+/// name of their value type, since [nxpkg_tasks::ValueTypeId]s are not stable
+/// across sessions.
+fn dependency_key(vc: &RawVc, pid: u64) -> Vec<u8> {
+    match vc {
+        RawVc::TaskOutput(_) => {
+            let mut key = vec![0];
+            key.extend_from_slice(&pid_key(pid));
+            key
+        }
+        RawVc::TaskCell(_, cell) => {
+            let mut key = vec![1];
+            key.extend_from_slice(&pid_key(pid));
+            key.extend_from_slice(&cell.index.to_be_bytes());
+            key.extend_from_slice(registry::get_value_type_global_name(cell.type_id).as_bytes());
+            key
+        }
+    }
+}
+
+/// The dependency key is length prefixed so that no key is a prefix of
+/// another one when scanning for dependents.
+fn dependents_prefix(dependency_key: &[u8]) -> Vec<u8> {
+    let mut prefix = Vec::with_capacity(dependency_key.len() + 2);
+    prefix.extend_from_slice(&(dependency_key.len() as u16).to_be_bytes());
+    prefix.extend_from_slice(dependency_key);
+    prefix
+}
+
+fn dependents_key(dependency_key: &[u8], dependent: u64) -> Vec<u8> {
+    let mut key = dependents_prefix(dependency_key);
+    key.extend_from_slice(&pid_key(dependent));
+    key
+}
+
+impl RocksDbPersistedGraph {
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut db = open_db(path)?;
+        let version = db
+            .get_cf(db.cf_handle(META).unwrap(), META_VERSION)?
+            .map(|bytes| bytes.as_slice() == FORMAT_VERSION.to_be_bytes().as_slice());
+        if version == Some(false) {
+            // Incompatible data from another version, start from scratch
+            drop(db);
+            DB::destroy(&Options::default(), path)?;
+            db = open_db(path)?;
+        }
+        db.put_cf(
+            db.cf_handle(META).unwrap(),
+            META_VERSION,
+            FORMAT_VERSION.to_be_bytes(),
+        )?;
+
+        let next_id = match db.get_cf(db.cf_handle(META).unwrap(), META_NEXT_ID)? {
+            Some(bytes) => decode_pid(&bytes)?,
+            // 0 is used as placeholder in mappings
+            None => 1,
+        };
+
+        let mut graph = Graph::default();
+        let mut batch = WriteBatch::default();
+        let state_cf = db.cf_handle(STATE).unwrap();
+        for entry in db.iterator_cf(state_cf, IteratorMode::Start) {
+            let (key, value) = entry?;
+            let pid = decode_pid(&key)?;
+            let Ok(mut stored) = postcard::from_bytes::<StoredTaskState>(&value) else {
+                // Ignore broken entries, the task will be recomputed
+                continue;
+            };
+            if stored.session_dependent && stored.clean {
+                // The task depends on state that might have changed while no
+                // session was running
+                stored.clean = false;
+                batch.put_cf(state_cf, key, postcard::to_allocvec(&stored)?);
+            }
+            graph.states.insert(
+                pid,
+                TaskState {
+                    persisted: true,
+                    stored,
+                    ..Default::default()
+                },
+            );
+        }
+        db.write(batch)?;
+
+        Ok(Self {
+            db,
+            path: path.to_path_buf(),
+            next_id: AtomicU64::new(next_id),
+            graph: Mutex::new(graph),
+            allocate_lock: Mutex::new(()),
+            task_to_pid: DashMap::new(),
+            pid_to_task: DashMap::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn cf(&self, name: &str) -> &ColumnFamily {
+        self.db.cf_handle(name).unwrap()
+    }
+
+    /// Serializes a value with persisted task ids. When `allocate` is true,
+    /// missing ids are allocated, otherwise None is returned for values that
+    /// reference tasks which are not persisted.
+    /// Also returns None for values that are not serializable.
+    fn serialize<T: Serialize>(
+        &self,
+        value: &T,
+        api: &dyn PersistedGraphApi,
+        allocate: bool,
+    ) -> Result<Option<Vec<u8>>> {
+        loop {
+            let mapping = ForwardMapping::new(self);
+            let result = with_task_id_mapping(&mapping, || postcard::to_allocvec(value));
+            let missing = mapping.missing.into_inner();
+            if missing.is_empty() {
+                return Ok(result.ok());
+            }
+            for task in missing {
+                if self.pid_for_task(task, api, allocate)?.is_none() {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// Deserializes a value with persisted task ids. Returns None when the
+    /// value can't be restored, e. g. because it references functions or value
+    /// types that no longer exist.
+    fn deserialize<T: DeserializeOwned>(
+        &self,
+        bytes: &[u8],
+        api: &dyn PersistedGraphApi,
+    ) -> Result<Option<T>> {
+        loop {
+            let mapping = BackwardMapping::new(self);
+            let result = with_task_id_mapping(&mapping, || postcard::from_bytes::<T>(bytes));
+            let missing = mapping.missing.into_inner();
+            if missing.is_empty() {
+                return Ok(result.ok());
+            }
+            for pid in missing {
+                if self.task_for_pid(pid, api)?.is_none() {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    fn pid_for_task(
+        &self,
+        task: TaskId,
+        api: &dyn PersistedGraphApi,
+        allocate: bool,
+    ) -> Result<Option<u64>> {
+        if let Some(pid) = self.task_to_pid.get(&task) {
+            return Ok(Some(*pid));
+        }
+        let Some(task_type) = api.try_lookup_task_type(task) else {
+            return Ok(None);
+        };
+        let Some(key) = self.serialize(task_type, api, allocate)? else {
+            return Ok(None);
+        };
+        let _guard = self.allocate_lock.lock();
+        if let Some(pid) = self.task_to_pid.get(&task) {
+            return Ok(Some(*pid));
+        }
+        let pid = if let Some(bytes) = self.db.get_cf(self.cf(CACHE), &key)? {
+            decode_pid(&bytes)?
+        } else if allocate {
+            let pid = self.next_id.fetch_add(1, Ordering::AcqRel);
+            let mut batch = WriteBatch::default();
+            batch.put_cf(self.cf(TYPES), pid_key(pid), &key);
+            batch.put_cf(self.cf(CACHE), &key, pid_key(pid));
+            batch.put_cf(self.cf(META), META_NEXT_ID, pid_key(pid + 1));
+            self.db.write(batch)?;
+            pid
+        } else {
+            return Ok(None);
+        };
+        self.task_to_pid.insert(task, pid);
+        self.pid_to_task.insert(pid, task);
+        Ok(Some(pid))
+    }
+
+    fn task_for_pid(&self, pid: u64, api: &dyn PersistedGraphApi) -> Result<Option<TaskId>> {
+        if let Some(task) = self.pid_to_task.get(&pid) {
+            return Ok(Some(*task));
+        }
+        let Some(bytes) = self.db.get_cf(self.cf(TYPES), pid_key(pid))? else {
+            return Ok(None);
+        };
+        let Some(task_type) = self.deserialize::<PersistentTaskType>(&bytes, api)? else {
+            return Ok(None);
+        };
+        let task = api.get_or_create_task_type(task_type);
+        self.pid_to_task.insert(pid, task);
+        self.task_to_pid.entry(task).or_insert(pid);
+        Ok(Some(task))
+    }
+
+    fn tasks_for_pids(
+        &self,
+        pids: impl IntoIterator<Item = u64>,
+        api: &dyn PersistedGraphApi,
+    ) -> Result<Vec<TaskId>> {
+        let mut tasks = Vec::new();
+        for pid in pids {
+            if let Some(task) = self.task_for_pid(pid, api)? {
+                tasks.push(task);
+            }
+        }
+        Ok(tasks)
+    }
+
+    fn write_state(&self, pid: u64, stored: &StoredTaskState) -> Result<()> {
+        self.db
+            .put_cf(self.cf(STATE), pid_key(pid), postcard::to_allocvec(stored)?)?;
+        Ok(())
+    }
+
+    fn dependents(&self, dependency_key: &[u8]) -> Result<Vec<u64>> {
+        let prefix = dependents_prefix(dependency_key);
+        let mut dependents = Vec::new();
+        for entry in self.db.iterator_cf(
+            self.cf(DEPENDENTS),
+            IteratorMode::From(&prefix, Direction::Forward),
+        ) {
+            let (key, _) = entry?;
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            dependents.push(decode_pid(&key[prefix.len()..])?);
+        }
+        Ok(dependents)
+    }
+}
+
+impl PersistedGraph for RocksDbPersistedGraph {
+    fn read(
+        &self,
+        task: TaskId,
+        api: &dyn PersistedGraphApi,
+    ) -> Result<Option<(TaskData, ReadTaskState)>> {
+        let Some(pid) = self.pid_for_task(task, api, false)? else {
+            return Ok(None);
+        };
+        let Some(bytes) = self.db.get_cf(self.cf(DATA), pid_key(pid))? else {
+            return Ok(None);
+        };
+        let Some(data) = self.deserialize::<TaskData>(&bytes, api)? else {
+            let mut graph = self.graph.lock();
+            if let Some(state) = graph.states.get_mut(&pid) {
+                state.persisted = false;
+            }
+            return Ok(None);
+        };
+        let graph = self.graph.lock();
+        let Some(state) = graph.states.get(&pid).filter(|state| state.persisted) else {
+            return Ok(None);
+        };
+        Ok(Some((
+            data,
+            ReadTaskState {
+                clean: state.stored.clean,
+                keeps_external_active: state.internal_active_parents > 0,
+            },
+        )))
+    }
+
+    fn lookup(
+        &self,
+        _partial_task_type: &PersistentTaskType,
+        _api: &dyn PersistedGraphApi,
+    ) -> Result<bool> {
+        // Keys are not sorted by their partial type, so every entry is looked
+        // up on its own
+        Ok(false)
+    }
+
+    fn lookup_one(
+        &self,
+        task_type: &PersistentTaskType,
+        api: &dyn PersistedGraphApi,
+    ) -> Result<Option<TaskId>> {
+        let Some(key) = self.serialize(task_type, api, false)? else {
+            return Ok(None);
+        };
+        let Some(bytes) = self.db.get_cf(self.cf(CACHE), &key)? else {
+            return Ok(None);
+        };
+        let pid = decode_pid(&bytes)?;
+        if !self
+            .graph
+            .lock()
+            .states
+            .get(&pid)
+            .map_or(false, |state| state.persisted)
+        {
+            return Ok(None);
+        }
+        let task = api.get_or_create_task_type(task_type.clone());
+        self.pid_to_task.insert(pid, task);
+        self.task_to_pid.insert(task, pid);
+        Ok(Some(task))
+    }
+
+    fn is_persisted(&self, task: TaskId, api: &dyn PersistedGraphApi) -> Result<bool> {
+        let Some(pid) = self.pid_for_task(task, api, false)? else {
+            return Ok(false);
+        };
+        Ok(self
+            .graph
+            .lock()
+            .states
+            .get(&pid)
+            .map_or(false, |state| state.persisted))
+    }
+
+    fn persist(
+        &self,
+        task: TaskId,
+        data: TaskData,
+        state: PersistTaskState,
+        api: &dyn PersistedGraphApi,
+    ) -> Result<Option<PersistResult>> {
+        let Some(pid) = self.pid_for_task(task, api, true)? else {
+            return Ok(None);
+        };
+        let mut children = Vec::with_capacity(data.children.len());
+        for &child in data.children.iter() {
+            let Some(child_pid) = self.pid_for_task(child, api, true)? else {
+                return Ok(None);
+            };
+            children.push((child_pid, child));
+        }
+        let Some(bytes) = self.serialize(&data, api, true)? else {
+            return Ok(None);
+        };
+        // Serialization has allocated ids for all dependencies
+        let dependencies = data
+            .dependencies
+            .iter()
+            .filter_map(|vc| {
+                let pid = *self.task_to_pid.get(&vc.get_task_id())?;
+                Some(dependency_key(vc, pid))
+            })
+            .collect::<Vec<_>>();
+
+        let mut tasks_to_activate = Vec::new();
+        let mut tasks_to_deactivate = Vec::new();
+        let mut batch = WriteBatch::default();
+        let mut graph = self.graph.lock();
+        let task_state = graph.states.entry(pid).or_default();
+        for old in task_state.stored.dependencies.iter() {
+            batch.delete_cf(self.cf(DEPENDENTS), dependents_key(old, pid));
+        }
+        for new in dependencies.iter() {
+            batch.put_cf(self.cf(DEPENDENTS), dependents_key(new, pid), b"");
+        }
+        let was_active = task_state.active;
+        let old_children = std::mem::replace(
+            &mut task_state.stored.children,
+            children.iter().map(|(pid, _)| *pid).collect(),
+        );
+        task_state.persisted = true;
+        task_state.stored.clean = true;
+        task_state.stored.session_dependent = state.session_dependent;
+        task_state.stored.dependencies = dependencies;
+        task_state.externally_active = state.externally_active;
+        let is_active = task_state.should_be_active();
+        task_state.active = is_active;
+        batch.put_cf(
+            self.cf(STATE),
+            pid_key(pid),
+            postcard::to_allocvec(&task_state.stored)?,
+        );
+        batch.put_cf(self.cf(DATA), pid_key(pid), bytes);
+
+        let mut deactivated_pids = Vec::new();
+        if was_active {
+            for old_child in old_children.iter() {
+                if (!is_active || !children.iter().any(|(pid, _)| pid == old_child))
+                    && graph.decrement_active_parents(*old_child)
+                {
+                    deactivated_pids.push(*old_child);
+                }
+            }
+        }
+        if is_active {
+            for &(child_pid, child) in children.iter() {
+                if (!was_active || !old_children.contains(&child_pid))
+                    && graph.increment_active_parents(child_pid)
+                {
+                    tasks_to_activate.push(child);
+                }
+            }
+        }
+        self.db.write(batch)?;
+        drop(graph);
+        tasks_to_deactivate.extend(self.tasks_for_pids(deactivated_pids, api)?);
+
+        Ok(Some(PersistResult {
+            tasks_to_activate,
+            tasks_to_deactivate,
+        }))
+    }
+
+    fn activate_when_needed(
+        &self,
+        task: TaskId,
+        api: &dyn PersistedGraphApi,
+    ) -> Result<Option<ActivateResult>> {
+        let Some(pid) = self.pid_for_task(task, api, false)? else {
+            return Ok(None);
+        };
+        let mut graph = self.graph.lock();
+        let state = graph.states.entry(pid).or_default();
+        let keeps_external_active = state.internal_active_parents > 0;
+        if !state.persisted {
+            return Ok(Some(ActivateResult {
+                keeps_external_active,
+                external: true,
+                dirty: false,
+                more_tasks_to_activate: Vec::new(),
+            }));
+        }
+        let mut activated_pids = Vec::new();
+        if !state.active && state.should_be_active() {
+            state.active = true;
+            let children = state.stored.children.clone();
+            for child in children {
+                if graph.increment_active_parents(child) {
+                    activated_pids.push(child);
+                }
+            }
+        }
+        let state = &graph.states[&pid];
+        let dirty = state.active && !state.stored.clean;
+        drop(graph);
+        Ok(Some(ActivateResult {
+            keeps_external_active,
+            external: false,
+            dirty,
+            more_tasks_to_activate: self.tasks_for_pids(activated_pids, api)?,
+        }))
+    }
+
+    fn deactivate_when_needed(
+        &self,
+        task: TaskId,
+        api: &dyn PersistedGraphApi,
+    ) -> Result<Option<DeactivateResult>> {
+        let Some(pid) = self.pid_for_task(task, api, false)? else {
+            return Ok(None);
+        };
+        let mut graph = self.graph.lock();
+        let state = graph.states.entry(pid).or_default();
+        let mut deactivated_pids = Vec::new();
+        if state.persisted && state.active && !state.should_be_active() {
+            state.active = false;
+            let children = state.stored.children.clone();
+            for child in children {
+                if graph.decrement_active_parents(child) {
+                    deactivated_pids.push(child);
+                }
+            }
+        }
+        if graph.states[&pid].internal_active_parents > 0 {
+            // Still kept active by persisted parents
+            return Ok(None);
+        }
+        drop(graph);
+        Ok(Some(DeactivateResult {
+            more_tasks_to_deactivate: self.tasks_for_pids(deactivated_pids, api)?,
+        }))
+    }
+
+    fn set_externally_active(&self, task: TaskId, api: &dyn PersistedGraphApi) -> Result<bool> {
+        let Some(pid) = self.pid_for_task(task, api, false)? else {
+            return Ok(false);
+        };
+        let mut graph = self.graph.lock();
+        let state = graph.states.entry(pid).or_default();
+        state.externally_active = true;
+        Ok(state.persisted && !state.active)
+    }
+
+    fn unset_externally_active(&self, task: TaskId, api: &dyn PersistedGraphApi) -> Result<bool> {
+        let Some(pid) = self.pid_for_task(task, api, false)? else {
+            return Ok(false);
+        };
+        let mut graph = self.graph.lock();
+        let state = graph.states.entry(pid).or_default();
+        state.externally_active = false;
+        Ok(state.active && !state.should_be_active())
+    }
+
+    fn remove_outdated_externally_active(
+        &self,
+        _api: &dyn PersistedGraphApi,
+    ) -> Result<Vec<TaskId>> {
+        // External activeness is not restored between sessions, so it can't
+        // be outdated
+        Ok(Vec::new())
+    }
+
+    fn make_dirty(&self, task: TaskId, api: &dyn PersistedGraphApi) -> Result<bool> {
+        let Some(pid) = self.pid_for_task(task, api, false)? else {
+            return Ok(false);
+        };
+        let mut graph = self.graph.lock();
+        let Some(state) = graph.states.get_mut(&pid).filter(|state| state.persisted) else {
+            return Ok(false);
+        };
+        if state.stored.clean {
+            state.stored.clean = false;
+            self.write_state(pid, &state.stored)?;
+        }
+        Ok(state.active)
+    }
+
+    fn make_clean(&self, task: TaskId, api: &dyn PersistedGraphApi) -> Result<()> {
+        let Some(pid) = self.pid_for_task(task, api, false)? else {
+            return Ok(());
+        };
+        let mut graph = self.graph.lock();
+        if let Some(state) = graph.states.get_mut(&pid).filter(|state| state.persisted) {
+            if !state.stored.clean {
+                state.stored.clean = true;
+                self.write_state(pid, &state.stored)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn make_dependent_dirty(&self, vc: RawVc, api: &dyn PersistedGraphApi) -> Result<Vec<TaskId>> {
+        let Some(pid) = self.pid_for_task(vc.get_task_id(), api, false)? else {
+            return Ok(Vec::new());
+        };
+        let dependents = self.dependents(&dependency_key(&vc, pid))?;
+        let mut active_pids = Vec::new();
+        {
+            let mut graph = self.graph.lock();
+            for dependent in dependents {
+                let Some(state) = graph
+                    .states
+                    .get_mut(&dependent)
+                    .filter(|state| state.persisted)
+                else {
+                    continue;
+                };
+                if state.stored.clean {
+                    state.stored.clean = false;
+                    self.write_state(dependent, &state.stored)?;
+                    if state.active {
+                        active_pids.push(dependent);
+                    }
+                }
+            }
+        }
+        self.tasks_for_pids(active_pids, api)
+    }
+
+    fn get_active_external_tasks(&self, _api: &dyn PersistedGraphApi) -> Result<Vec<TaskId>> {
+        // Nothing is active when the graph is opened
+        Ok(Vec::new())
+    }
+
+    fn get_dirty_active_tasks(&self, _api: &dyn PersistedGraphApi) -> Result<Vec<TaskId>> {
+        // Nothing is active when the graph is opened. Dirty tasks are
+        // scheduled when they are activated again.
+        Ok(Vec::new())
+    }
+
+    fn get_pending_active_update(
+        &self,
+        _api: &dyn PersistedGraphApi,
+    ) -> Result<(Vec<TaskId>, Vec<TaskId>)> {
+        Ok((Vec::new(), Vec::new()))
+    }
+
+    fn stop(&self, _api: &dyn PersistedGraphApi) -> Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nxpkg_tasks::{persisted_graph::TaskCell, CellId, TraitType, ValueType};
+
+    use super::*;
+
+    /// Hands out a [TaskId] per task type, like the backend does.
+    #[derive(Default)]
+    struct TestApi {
+        task_types: Mutex<Vec<&'static PersistentTaskType>>,
+    }
+
+    impl PersistedGraphApi for TestApi {
+        fn get_or_create_task_type(&self, ty: PersistentTaskType) -> TaskId {
+            let mut task_types = self.task_types.lock();
+            let index = match task_types.iter().position(|existing| **existing == ty) {
+                Some(index) => index,
+                None => {
+                    task_types.push(Box::leak(Box::new(ty)));
+                    task_types.len() - 1
+                }
+            };
+            TaskId::from(index + 1)
+        }
+
+        fn lookup_task_type(&self, id: TaskId) -> &PersistentTaskType {
+            self.try_lookup_task_type(id).unwrap()
+        }
+
+        fn try_lookup_task_type(&self, id: TaskId) -> Option<&PersistentTaskType> {
+            let task_types = self.task_types.lock();
+            task_types.get(*id - 1).copied()
+        }
+    }
+
+    #[test]
+    fn test_persist_and_read() -> Result<()> {
+        let trait_type = Box::leak(Box::new(TraitType::new("Test".to_string())));
+        registry::register_trait_type("nxpkg-tasks-rocksdb::tests::Test", trait_type);
+        let trait_type_id = registry::get_trait_type_id(trait_type);
+        let value_type = Box::leak(Box::new(ValueType::new::<()>()));
+        registry::register_value_type("nxpkg-tasks-rocksdb::tests::Value", value_type);
+        let cell = CellId {
+            type_id: registry::get_value_type_id(value_type),
+            index: 1,
+        };
+        let task_type = |method: &'static str| {
+            PersistentTaskType::ResolveTrait(trait_type_id, method.into(), Vec::new())
+        };
+        let task_data = |child: TaskId| TaskData {
+            children: vec![child],
+            dependencies: vec![RawVc::TaskOutput(child), RawVc::TaskCell(child, cell)],
+            cells: vec![(cell, TaskCell::NeedComputation)],
+            output: RawVc::TaskCell(child, cell),
+        };
+        let assert_data = |data: &TaskData, child: TaskId| {
+            assert_eq!(data.children, vec![child]);
+            assert_eq!(data.dependencies, task_data(child).dependencies);
+            assert_eq!(data.output, RawVc::TaskCell(child, cell));
+            assert!(matches!(
+                data.cells.as_slice(),
+                [(id, TaskCell::NeedComputation)] if *id == cell
+            ));
+        };
+
+        let dir = tempfile::tempdir()?;
+        {
+            let api = TestApi::default();
+            let parent = api.get_or_create_task_type(task_type("parent"));
+            let child = api.get_or_create_task_type(task_type("child"));
+            let pg = RocksDbPersistedGraph::new(dir.path())?;
+            pg.persist(
+                parent,
+                task_data(child),
+                PersistTaskState {
+                    externally_active: false,
+                    session_dependent: false,
+                },
+                &api,
+            )?
+            .unwrap();
+
+            let (data, state) = pg.read(parent, &api)?.unwrap();
+            assert_data(&data, child);
+            assert!(state.clean);
+            assert!(pg.read(child, &api)?.is_none());
+        }
+
+        // Task ids differ between sessions, so the tasks are restored from
+        // their persisted types
+        let api = TestApi::default();
+        api.get_or_create_task_type(task_type("unrelated"));
+        let parent = api.get_or_create_task_type(task_type("parent"));
+        let pg = RocksDbPersistedGraph::new(dir.path())?;
+        let (data, state) = pg.read(parent, &api)?.unwrap();
+        let child = api.get_or_create_task_type(task_type("child"));
+        assert_eq!(*child, 3);
+        assert_data(&data, child);
+        assert!(state.clean);
+        assert!(!state.keeps_external_active);
+        Ok(())
+    }
+
+    #[test]
+    fn test_session_dependent_tasks_are_dirty_after_reopen() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        {
+            let pg = RocksDbPersistedGraph::new(dir.path())?;
+            for (pid, session_dependent) in [(1, true), (2, false)] {
+                pg.write_state(
+                    pid,
+                    &StoredTaskState {
+                        clean: true,
+                        session_dependent,
+                        ..Default::default()
+                    },
+                )?;
+            }
+            pg.db.flush()?;
+        }
+        let pg = RocksDbPersistedGraph::new(dir.path())?;
+        let graph = pg.graph.lock();
+        assert!(!graph.states[&1].stored.clean);
+        assert!(graph.states[&2].stored.clean);
+        assert!(graph.states.values().all(|state| !state.active));
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_mismatch_discards_data() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        {
+            let pg = RocksDbPersistedGraph::new(dir.path())?;
+            pg.write_state(1, &StoredTaskState::default())?;
+            pg.db.put_cf(
+                pg.cf(META),
+                META_VERSION,
+                (FORMAT_VERSION + 1).to_be_bytes(),
+            )?;
+        }
+        let pg = RocksDbPersistedGraph::new(dir.path())?;
+        assert!(pg.graph.lock().states.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dependents_prefix_is_unambiguous() {
+        let short = dependents_key(&[0, 1], 7);
+        let long = dependents_key(&[0, 1, 2], 7);
+        assert!(!long.starts_with(&dependents_prefix(&[0, 1])));
+        assert!(short.starts_with(&dependents_prefix(&[0, 1])));
+    }
+
+    #[test]
+    fn test_graph_active_parents() {
+        let mut graph = Graph::default();
+        graph.states.insert(
+            1,
+            TaskState {
+                persisted: true,
+                ..Default::default()
+            },
+        );
+        assert!(graph.increment_active_parents(1));
+        assert!(!graph.increment_active_parents(1));
+        assert!(!graph.decrement_active_parents(1));
+        // persisted but not activated yet
+        assert!(!graph.decrement_active_parents(1));
+        // not persisted tasks are tracked by the consumer graph
+        assert!(graph.increment_active_parents(2));
+        assert!(graph.decrement_active_parents(2));
+    }
+}
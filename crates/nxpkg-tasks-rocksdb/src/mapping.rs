@@ -0,0 +1,74 @@
+use std::cell::RefCell;
+
+use nxpkg_tasks::{IdMapping, TaskId};
+
+use crate::RocksDbPersistedGraph;
+
+/// Maps in-memory [TaskId]s to persisted ids during serialization.
+///
+/// The mapping must not touch the database, since it's called while the
+/// serializer holds the thread local mapping. Unknown tasks are collected
+/// instead and the caller resolves them and serializes again.
+pub(crate) struct ForwardMapping<'a> {
+    pub graph: &'a RocksDbPersistedGraph,
+    pub missing: RefCell<Vec<TaskId>>,
+}
+
+impl<'a> ForwardMapping<'a> {
+    pub fn new(graph: &'a RocksDbPersistedGraph) -> Self {
+        Self {
+            graph,
+            missing: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+impl IdMapping<TaskId> for ForwardMapping<'_> {
+    fn forward(&self, id: TaskId) -> usize {
+        if let Some(pid) = self.graph.task_to_pid.get(&id) {
+            *pid as usize
+        } else {
+            self.missing.borrow_mut().push(id);
+            0
+        }
+    }
+
+    fn backward(&self, _id: usize) -> TaskId {
+        unreachable!("ForwardMapping is only used for serialization")
+    }
+}
+
+/// Maps persisted ids back to in-memory [TaskId]s during deserialization.
+///
+/// Like [ForwardMapping] unknown ids are collected and resolved by the
+/// caller.
+pub(crate) struct BackwardMapping<'a> {
+    pub graph: &'a RocksDbPersistedGraph,
+    pub missing: RefCell<Vec<u64>>,
+}
+
+impl<'a> BackwardMapping<'a> {
+    pub fn new(graph: &'a RocksDbPersistedGraph) -> Self {
+        Self {
+            graph,
+            missing: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+impl IdMapping<TaskId> for BackwardMapping<'_> {
+    fn forward(&self, _id: TaskId) -> usize {
+        unreachable!("BackwardMapping is only used for deserialization")
+    }
+
+    fn backward(&self, id: usize) -> TaskId {
+        let pid = id as u64;
+        if let Some(task) = self.graph.pid_to_task.get(&pid) {
+            *task
+        } else {
+            self.missing.borrow_mut().push(pid);
+            // Placeholder, the value is thrown away and deserialized again
+            TaskId::from(usize::MAX)
+        }
+    }
+}
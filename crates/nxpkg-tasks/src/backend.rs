@@ -340,6 +340,18 @@ pub trait Backend: Sync + Send {
         // Do nothing by default
     }
 
+    /// Marks a task as depending on state outside of the task graph (e. g.
+    /// the filesystem). Such tasks need to be re-executed when restoring the
+    /// graph in a new session, since their inputs might have changed while no
+    /// process was watching them.
+    fn mark_own_task_as_session_dependent(
+        &self,
+        _task: TaskId,
+        _nxpkg_tasks: &dyn NxpkgTasksBackendApi<Self>,
+    ) {
+        // Do nothing by default
+    }
+
     fn create_transient_task(
         &self,
         task_type: TransientTaskType,
@@ -87,10 +87,10 @@ pub use invalidation::{
 pub use join_iter_ext::{JoinIterExt, TryFlatJoinIterExt, TryJoinIterExt};
 pub use keyed_cell::{global_keyed_cell, keyed_cell};
 pub use manager::{
-    dynamic_call, emit, get_invalidator, mark_finished, mark_stateful, run_once,
-    run_once_with_reason, spawn_blocking, spawn_thread, trait_call, nxpkg_tasks, CurrentCellRef,
-    Invalidator, StatsType, TaskIdProvider, NxpkgTasks, NxpkgTasksApi, NxpkgTasksBackendApi,
-    NxpkgTasksCallApi, Unused, UpdateInfo,
+    dynamic_call, emit, get_invalidator, mark_finished, mark_session_dependent, mark_stateful,
+    run_once, run_once_with_reason, spawn_blocking, spawn_thread, trait_call, nxpkg_tasks,
+    CurrentCellRef, Invalidator, StatsType, TaskIdProvider, NxpkgTasks, NxpkgTasksApi,
+    NxpkgTasksBackendApi, NxpkgTasksCallApi, Unused, UpdateInfo,
 };
 pub use native_function::NativeFunction;
 use nohash_hasher::BuildNoHashHasher;
@@ -118,6 +118,7 @@ pub trait NxpkgTasksApi: NxpkgTasksCallApi + Sync + Send {
     fn read_own_task_cell(&self, task: TaskId, index: CellId) -> Result<CellContent>;
     fn update_own_task_cell(&self, task: TaskId, index: CellId, content: CellContent);
     fn mark_own_task_as_finished(&self, task: TaskId);
+    fn mark_own_task_as_session_dependent(&self, task: TaskId);
 
     fn connect_task(&self, task: TaskId);
 
@@ -1020,6 +1021,10 @@ impl<B: Backend + 'static> NxpkgTasksApi for NxpkgTasks<B> {
         self.backend.mark_own_task_as_finished(task, self);
     }
 
+    fn mark_own_task_as_session_dependent(&self, task: TaskId) {
+        self.backend.mark_own_task_as_session_dependent(task, self);
+    }
+
     fn detached(
         &self,
         f: Pin<Box<dyn Future<Output = Result<()>> + Send + 'static>>,
@@ -1376,15 +1381,28 @@ pub fn current_task_for_testing() -> TaskId {
 
 /// Get an [Invalidator] that can be used to invalidate the current [Task]
 /// based on external events.
+///
+/// This also marks the current task as session dependent, since the external
+/// events can't be observed while no session is running.
 pub fn get_invalidator() -> Invalidator {
     let handle = Handle::current();
+    let task = current_task("nxpkg_tasks::get_invalidator()");
+    with_nxpkg_tasks(|tt| tt.mark_own_task_as_session_dependent(task));
     Invalidator {
-        task: current_task("nxpkg_tasks::get_invalidator()"),
+        task,
         nxpkg_tasks: weak_nxpkg_tasks(),
         handle,
     }
 }
 
+/// Marks the current task as depending on state outside of the task graph.
+/// It will be re-executed when the graph is restored in a new session.
+pub fn mark_session_dependent() {
+    with_nxpkg_tasks(|tt| {
+        tt.mark_own_task_as_session_dependent(current_task("nxpkg_tasks::mark_session_dependent()"))
+    });
+}
+
 /// Marks the current task as finished. This excludes it from waiting for
 /// strongly consistency.
 pub fn mark_finished() {
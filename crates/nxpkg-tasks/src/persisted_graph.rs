@@ -32,6 +32,9 @@ pub struct ReadTaskState {
 
 pub struct PersistTaskState {
     pub externally_active: bool,
+    /// The task depends on state outside of the graph and need to be
+    /// re-executed in a new session.
+    pub session_dependent: bool,
 }
 
 /*
@@ -200,6 +203,10 @@ pub trait PersistedGraphApi {
     fn get_or_create_task_type(&self, ty: PersistentTaskType) -> TaskId;
 
     fn lookup_task_type(&self, id: TaskId) -> &PersistentTaskType;
+
+    /// Like [PersistedGraphApi::lookup_task_type], but returns None for tasks
+    /// that are not persistent (e. g. root tasks).
+    fn try_lookup_task_type(&self, id: TaskId) -> Option<&PersistentTaskType>;
 }
 
 /*
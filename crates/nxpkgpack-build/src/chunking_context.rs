@@ -19,11 +19,13 @@ use nxpkgpack_core::{
 use nxpkgpack_ecmascript::{
     chunk::{EcmascriptChunk, EcmascriptChunkPlaceable, EcmascriptChunkingContext},
     manifest::{chunk_asset::ManifestAsyncModule, loader_item::ManifestLoaderChunkItem},
+    EcmascriptModuleAsset,
 };
 use nxpkgpack_ecmascript_runtime::RuntimeType;
 
-use crate::ecmascript::node::{
-    chunk::EcmascriptBuildNodeChunk, entry::chunk::EcmascriptBuildNodeEntryChunk,
+use crate::ecmascript::{
+    library::{module::EcmascriptLibraryModule, LibraryOptions},
+    node::{chunk::EcmascriptBuildNodeChunk, entry::chunk::EcmascriptBuildNodeEntryChunk},
 };
 
 #[derive(
@@ -69,6 +71,13 @@ impl BuildChunkingContextBuilder {
         self
     }
 
+    /// Emits every module as a standalone ES module instead of chunks, see
+    /// [`BuildChunkingContext::entry_chunk_group`].
+    pub fn library(mut self, options: Vc<LibraryOptions>) -> Self {
+        self.chunking_context.library = Some(options);
+        self
+    }
+
     /// Builds the chunking context.
     pub fn build(self) -> Vc<BuildChunkingContext> {
         BuildChunkingContext::new(Value::new(self.chunking_context))
@@ -98,6 +107,8 @@ pub struct BuildChunkingContext {
     runtime_type: RuntimeType,
     /// Whether to minify resulting chunks
     minify_type: MinifyType,
+    /// Emits entries as a library instead of chunks
+    library: Option<Vc<LibraryOptions>>,
 }
 
 impl BuildChunkingContext {
@@ -121,6 +132,7 @@ impl BuildChunkingContext {
                 environment,
                 runtime_type: Default::default(),
                 minify_type: MinifyType::Minify,
+                library: None,
             },
         }
     }
@@ -138,6 +150,10 @@ impl BuildChunkingContext {
     pub fn minify_type(&self) -> MinifyType {
         self.minify_type
     }
+
+    pub(crate) fn chunk_root_path(&self) -> Vc<FileSystemPath> {
+        self.chunk_root_path
+    }
 }
 
 #[nxpkg_tasks::value_impl]
@@ -156,6 +172,10 @@ impl BuildChunkingContext {
     /// * evaluates the given assets; and
     /// * exports the result of evaluating the given module as a CommonJS
     ///   default export.
+    ///
+    /// In library mode, the given module is emitted as an ES module instead,
+    /// with the modules it imports next to it. Its path is relative to the
+    /// context path, so `path` and the evaluatable assets are not used.
     #[nxpkg_tasks::function]
     pub async fn entry_chunk_group(
         self: Vc<Self>,
@@ -163,6 +183,17 @@ impl BuildChunkingContext {
         module: Vc<Box<dyn EcmascriptChunkPlaceable>>,
         evaluatable_assets: Vc<EvaluatableAssets>,
     ) -> Result<Vc<Box<dyn OutputAsset>>> {
+        if let Some(library) = self.await?.library {
+            let Some(module) =
+                Vc::try_resolve_downcast_type::<EcmascriptModuleAsset>(module).await?
+            else {
+                bail!("Library mode only supports ES modules as entries");
+            };
+            return Ok(Vc::upcast(EcmascriptLibraryModule::new(
+                self, module, library,
+            )));
+        }
+
         let availability_info = AvailabilityInfo::Root;
 
         let MakeChunkGroupResult { chunks } = make_chunk_group(
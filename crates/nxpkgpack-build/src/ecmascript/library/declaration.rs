@@ -0,0 +1,918 @@
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+};
+
+use anyhow::Result;
+use swc_core::{
+    common::DUMMY_SP,
+    ecma::{
+        ast::{
+            BindingIdent, Class, ClassMember, Decl, DefaultDecl, ExportDecl, ExportDefaultExpr,
+            ExportSpecifier, Expr, Function, Ident, Lit, MethodKind, Module, ModuleDecl,
+            ModuleExportName, ModuleItem, NamedExport, ObjectPatProp, ParamOrTsParamProp, Pat,
+            Program, PropName, Stmt, TsFnOrConstructorType, TsFnParam, TsFnType, TsKeywordType,
+            TsKeywordTypeKind, TsModuleDecl, TsNamespaceBody, TsParamPropParam, TsType, TsTypeAnn,
+            TsTypeParamDecl, VarDecl, VarDeclKind, VarDeclarator,
+        },
+        codegen::{text_writer::JsWriter, Emitter},
+        visit::{Visit, VisitMutWith, VisitWith},
+    },
+};
+use nxpkg_tasks::{Value, Vc};
+use nxpkg_tasks_fs::{rope::Rope, File, FileContent, FileSystemPath};
+use nxpkgpack_core::{
+    asset::{Asset, AssetContent},
+    ident::AssetIdent,
+    issue::{Issue, IssueExt, IssueSeverity, StyledString},
+    output::{OutputAsset, OutputAssets},
+};
+use nxpkgpack_ecmascript::{
+    parse::{parse, ParseResult},
+    EcmascriptInputTransforms, EcmascriptModuleAsset, EcmascriptModuleAssetType,
+};
+
+use super::{
+    is_typescript, library_path, resolve_library_requests, LibraryOptions, RewriteModuleRequests,
+};
+use crate::BuildChunkingContext;
+
+/// The `.d.mts` declaration file of a TypeScript module in library mode.
+/// Declaration files in the module graph are emitted as they are, other
+/// TypeScript modules are stripped down to their declarations. Requests are
+/// rewritten to the emitted modules, which TypeScript resolves to the
+/// declaration files next to them.
+///
+/// Like with TypeScript's `isolatedDeclarations`, types aren't inferred:
+/// values and return types without a type annotation are declared as
+/// `unknown`, which is reported for each export it affects.
+#[nxpkg_tasks::value(shared)]
+pub(crate) struct EcmascriptLibraryDeclaration {
+    chunking_context: Vc<BuildChunkingContext>,
+    module: Vc<EcmascriptModuleAsset>,
+    options: Vc<LibraryOptions>,
+}
+
+#[nxpkg_tasks::value_impl]
+impl EcmascriptLibraryDeclaration {
+    /// Creates a new [`Vc<EcmascriptLibraryDeclaration>`].
+    #[nxpkg_tasks::function]
+    pub fn new(
+        chunking_context: Vc<BuildChunkingContext>,
+        module: Vc<EcmascriptModuleAsset>,
+        options: Vc<LibraryOptions>,
+    ) -> Vc<Self> {
+        EcmascriptLibraryDeclaration {
+            chunking_context,
+            module,
+            options,
+        }
+        .cell()
+    }
+
+    /// Parses the module without its transforms, which would remove the
+    /// types.
+    #[nxpkg_tasks::function]
+    async fn parse_types(&self) -> Result<Vc<ParseResult>> {
+        let module = self.module.await?;
+        let ty = match module.ty {
+            EcmascriptModuleAssetType::TypescriptDeclaration => {
+                EcmascriptModuleAssetType::TypescriptDeclaration
+            }
+            _ => EcmascriptModuleAssetType::Typescript,
+        };
+        Ok(parse(
+            module.source,
+            Value::new(ty),
+            EcmascriptInputTransforms::empty(),
+        ))
+    }
+}
+
+#[nxpkg_tasks::value_impl]
+impl OutputAsset for EcmascriptLibraryDeclaration {
+    #[nxpkg_tasks::function]
+    fn ident(&self) -> Vc<AssetIdent> {
+        AssetIdent::from_path(library_path(
+            self.chunking_context,
+            Vc::upcast(self.module),
+            ".d.mts".to_string(),
+        ))
+    }
+
+    #[nxpkg_tasks::function]
+    async fn references(self: Vc<Self>) -> Result<Vc<OutputAssets>> {
+        let this = self.await?;
+        let mut references: Vec<Vc<Box<dyn OutputAsset>>> = Vec::new();
+        let requests = resolve_library_requests(this.module, self.parse_types(), this.options);
+        for &target in requests.await?.modules.values() {
+            if is_typescript(target.await?.ty) {
+                references.push(Vc::upcast(EcmascriptLibraryDeclaration::new(
+                    this.chunking_context,
+                    target,
+                    this.options,
+                )));
+            }
+        }
+        Ok(Vc::cell(references))
+    }
+}
+
+#[nxpkg_tasks::value_impl]
+impl Asset for EcmascriptLibraryDeclaration {
+    #[nxpkg_tasks::function]
+    async fn content(self: Vc<Self>) -> Result<Vc<AssetContent>> {
+        let this = self.await?;
+        let path = self.ident().path();
+        let parsed = self.parse_types();
+        let ParseResult::Ok {
+            program,
+            comments,
+            source_map,
+            ..
+        } = &*parsed.await?
+        else {
+            // Parse errors are reported by parsing
+            return Ok(AssetContent::file(FileContent::NotFound.cell()));
+        };
+
+        let rewrites = resolve_library_requests(this.module, parsed, this.options)
+            .await?
+            .rewrites(this.chunking_context, path)
+            .await?;
+
+        let mut program = program.clone();
+        if !matches!(
+            this.module.await?.ty,
+            EcmascriptModuleAssetType::TypescriptDeclaration
+        ) {
+            let module = strip_to_declarations(program);
+            for export in untyped_exports(&module) {
+                UntypedExportIssue {
+                    path: this.module.ident().path(),
+                    export,
+                }
+                .cell()
+                .emit();
+            }
+            program = Program::Module(module);
+        }
+        program.visit_mut_with(&mut RewriteModuleRequests(|request: &str| {
+            rewrites
+                .iter()
+                .find(|(from, _)| from == request)
+                .map(|(_, to)| to.clone())
+        }));
+
+        let mut bytes: Vec<u8> = vec![];
+        let comments = comments.consumable();
+        let mut emitter = Emitter {
+            cfg: swc_core::ecma::codegen::Config::default(),
+            cm: source_map.clone(),
+            comments: Some(&comments),
+            wr: JsWriter::new(source_map.clone(), "\n", &mut bytes, None),
+        };
+        emitter.emit_program(&program)?;
+
+        Ok(AssetContent::file(File::from(Rope::from(bytes)).into()))
+    }
+}
+
+/// Strips a TypeScript program down to its declarations. A program without
+/// imports or exports becomes a module, as its declarations are local to it
+/// once it's imported.
+fn strip_to_declarations(program: Program) -> Module {
+    let mut module = match program {
+        Program::Module(module) => module,
+        Program::Script(script) => Module {
+            span: script.span,
+            body: script.body.into_iter().map(ModuleItem::Stmt).collect(),
+            shebang: script.shebang,
+        },
+    };
+    module.shebang = None;
+    module.body = strip_items(mem::take(&mut module.body), true);
+    if !module
+        .body
+        .iter()
+        .any(|item| matches!(item, ModuleItem::ModuleDecl(_)))
+    {
+        module
+            .body
+            .push(ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(
+                NamedExport {
+                    span: DUMMY_SP,
+                    specifiers: vec![],
+                    src: None,
+                    type_only: false,
+                    with: None,
+                },
+            )));
+    }
+    module
+}
+
+/// Keeps the declarations, imports and exports of `items`, without their
+/// implementations. `declare` is whether declarations need a `declare`
+/// modifier, which they don't in an ambient namespace.
+fn strip_items(items: Vec<ModuleItem>, declare: bool) -> Vec<ModuleItem> {
+    // The implementation of an overloaded function isn't part of its
+    // declaration
+    let overloaded: HashSet<String> = items
+        .iter()
+        .filter_map(|item| match item {
+            ModuleItem::Stmt(Stmt::Decl(Decl::Fn(function)))
+            | ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(swc_core::ecma::ast::ExportDecl {
+                decl: Decl::Fn(function),
+                ..
+            })) if function.function.body.is_none() => Some(function.ident.sym.to_string()),
+            _ => None,
+        })
+        .collect();
+
+    let mut stripped = Vec::new();
+    for item in items {
+        match item {
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(mut export)) => {
+                if let Some(decl) = strip_decl(export.decl, declare, &overloaded) {
+                    export.decl = decl;
+                    stripped.push(ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export)));
+                }
+            }
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(mut export)) => {
+                match &mut export.decl {
+                    DefaultDecl::Class(class) => strip_class(&mut class.class),
+                    DefaultDecl::Fn(function) => strip_function(&mut function.function, true),
+                    DefaultDecl::TsInterfaceDecl(_) => {}
+                }
+                stripped.push(ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(
+                    export,
+                )));
+            }
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(export))
+                if !matches!(*export.expr, Expr::Ident(_)) =>
+            {
+                // Other expressions are declared as a `_default` binding that
+                // is exported instead
+                let ident = Ident::new("_default".into(), DUMMY_SP);
+                stripped.push(ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(VarDecl {
+                    span: DUMMY_SP,
+                    kind: VarDeclKind::Const,
+                    declare,
+                    decls: vec![VarDeclarator {
+                        span: DUMMY_SP,
+                        name: Pat::Ident(BindingIdent {
+                            id: ident.clone(),
+                            type_ann: Some(type_ann(value_type(&export.expr))),
+                        }),
+                        init: None,
+                        definite: false,
+                    }],
+                })))));
+                stripped.push(ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(
+                    ExportDefaultExpr {
+                        span: export.span,
+                        expr: Box::new(Expr::Ident(ident)),
+                    },
+                )));
+            }
+            ModuleItem::ModuleDecl(decl) => stripped.push(ModuleItem::ModuleDecl(decl)),
+            ModuleItem::Stmt(Stmt::Decl(decl)) => {
+                if let Some(decl) = strip_decl(decl, declare, &overloaded) {
+                    stripped.push(ModuleItem::Stmt(Stmt::Decl(decl)));
+                }
+            }
+            ModuleItem::Stmt(_) => {}
+        }
+    }
+    stripped
+}
+
+fn strip_decl(decl: Decl, declare: bool, overloaded: &HashSet<String>) -> Option<Decl> {
+    match decl {
+        Decl::Fn(mut function) => {
+            if function.function.body.is_some()
+                && overloaded.contains(&function.ident.sym.to_string())
+            {
+                return None;
+            }
+            function.declare = declare;
+            strip_function(&mut function.function, true);
+            Some(Decl::Fn(function))
+        }
+        Decl::Class(mut class) => {
+            class.declare = declare;
+            strip_class(&mut class.class);
+            Some(Decl::Class(class))
+        }
+        Decl::Var(mut var) => {
+            var.declare = declare;
+            let kind = var.kind;
+            var.decls = mem::take(&mut var.decls)
+                .into_iter()
+                .flat_map(|declarator| strip_var_declarator(declarator, kind))
+                .collect();
+            (!var.decls.is_empty()).then_some(Decl::Var(var))
+        }
+        Decl::TsEnum(mut ts_enum) => {
+            ts_enum.declare = declare;
+            Some(Decl::TsEnum(ts_enum))
+        }
+        Decl::TsModule(mut module) => {
+            strip_ts_module(&mut module, declare);
+            Some(Decl::TsModule(module))
+        }
+        decl @ (Decl::TsInterface(_) | Decl::TsTypeAlias(_)) => Some(decl),
+        Decl::Using(_) => None,
+    }
+}
+
+fn strip_ts_module(module: &mut TsModuleDecl, declare: bool) {
+    // Ambient namespaces only contain declarations already
+    if module.declare {
+        return;
+    }
+    module.declare = declare;
+    if let Some(TsNamespaceBody::TsModuleBlock(block)) = &mut module.body {
+        block.body = strip_items(mem::take(&mut block.body), false);
+    }
+}
+
+/// Strips the body of a function. `annotate_return` is whether a missing
+/// return type is declared as `unknown`, which setters can't have.
+fn strip_function(function: &mut Function, annotate_return: bool) {
+    function.body = None;
+    function.is_async = false;
+    function.is_generator = false;
+    function.decorators.clear();
+    for (index, param) in function.params.iter_mut().enumerate() {
+        param.decorators.clear();
+        strip_param(&mut param.pat, index);
+    }
+    if annotate_return && function.return_type.is_none() {
+        function.return_type = Some(type_ann(unknown_type()));
+    }
+}
+
+/// Turns a parameter into an identifier or rest parameter with its type.
+/// Initializers aren't allowed in declarations, so a parameter with one
+/// becomes optional. Destructuring patterns can have initializers too, so
+/// they are replaced by an identifier.
+fn strip_param(pat: &mut Pat, index: usize) {
+    let mut optional = false;
+    let left = match pat {
+        Pat::Assign(assign) => Some((*assign.left).clone()),
+        _ => None,
+    };
+    if let Some(left) = left {
+        *pat = left;
+        optional = true;
+    }
+    match pat {
+        Pat::Ident(ident) => ident.id.optional |= optional,
+        Pat::Rest(_) => {}
+        _ => {
+            let type_ann = match pat {
+                Pat::Array(array) => array.type_ann.take(),
+                Pat::Object(object) => object.type_ann.take(),
+                _ => None,
+            };
+            *pat = Pat::Ident(BindingIdent {
+                id: Ident {
+                    optional,
+                    ..Ident::new(format!("param{index}").into(), DUMMY_SP)
+                },
+                type_ann,
+            });
+        }
+    }
+}
+
+fn strip_class(class: &mut Class) {
+    class.decorators.clear();
+    let overloaded: HashSet<String> = class
+        .body
+        .iter()
+        .filter_map(|member| match member {
+            ClassMember::Method(method) if method.function.body.is_none() => prop_name(&method.key),
+            _ => None,
+        })
+        .collect();
+    let constructor_overloaded = class.body.iter().any(
+        |member| matches!(member, ClassMember::Constructor(constructor) if constructor.body.is_none()),
+    );
+
+    class.body.retain_mut(|member| match member {
+        ClassMember::Constructor(constructor) => {
+            if constructor.body.is_some() && constructor_overloaded {
+                return false;
+            }
+            constructor.body = None;
+            for (index, param) in constructor.params.iter_mut().enumerate() {
+                match param {
+                    ParamOrTsParamProp::Param(param) => {
+                        param.decorators.clear();
+                        strip_param(&mut param.pat, index);
+                    }
+                    ParamOrTsParamProp::TsParamProp(prop) => {
+                        prop.decorators.clear();
+                        let ident = match &prop.param {
+                            TsParamPropParam::Assign(assign) => match &*assign.left {
+                                Pat::Ident(ident) => Some(ident.clone()),
+                                _ => None,
+                            },
+                            TsParamPropParam::Ident(_) => None,
+                        };
+                        if let Some(mut ident) = ident {
+                            ident.id.optional = true;
+                            prop.param = TsParamPropParam::Ident(ident);
+                        }
+                    }
+                }
+            }
+            true
+        }
+        ClassMember::Method(method) => {
+            if method.function.body.is_some()
+                && prop_name(&method.key).is_some_and(|name| overloaded.contains(&name))
+            {
+                return false;
+            }
+            strip_function(&mut method.function, method.kind != MethodKind::Setter);
+            true
+        }
+        ClassMember::ClassProp(prop) => {
+            prop.decorators.clear();
+            prop.definite = false;
+            let value = prop.value.take();
+            if prop.type_ann.is_none() {
+                prop.type_ann = Some(type_ann(
+                    value.as_deref().map_or_else(unknown_type, value_type),
+                ));
+            }
+            true
+        }
+        ClassMember::AutoAccessor(accessor) => {
+            accessor.decorators.clear();
+            accessor.value = None;
+            true
+        }
+        ClassMember::TsIndexSignature(_) => true,
+        ClassMember::PrivateMethod(_)
+        | ClassMember::PrivateProp(_)
+        | ClassMember::StaticBlock(_)
+        | ClassMember::Empty(_) => false,
+    });
+}
+
+fn prop_name(key: &PropName) -> Option<String> {
+    match key {
+        PropName::Ident(ident) => Some(ident.sym.to_string()),
+        PropName::Str(str) => Some(str.value.to_string()),
+        _ => None,
+    }
+}
+
+/// Removes the initializer of a variable. Variables without a type
+/// annotation get the type of their initializer, except for a `const` with a
+/// literal, which keeps it as its type is the literal. A destructuring
+/// declaration becomes a declaration of each of its bindings as `unknown`,
+/// as their types can't be known without inference.
+fn strip_var_declarator(mut declarator: VarDeclarator, kind: VarDeclKind) -> Vec<VarDeclarator> {
+    let Pat::Ident(name) = &mut declarator.name else {
+        let mut bindings = Vec::new();
+        pat_bindings(&declarator.name, &mut bindings);
+        return bindings
+            .into_iter()
+            .map(|id| VarDeclarator {
+                span: DUMMY_SP,
+                name: Pat::Ident(BindingIdent {
+                    id,
+                    type_ann: Some(type_ann(unknown_type())),
+                }),
+                init: None,
+                definite: false,
+            })
+            .collect();
+    };
+    declarator.definite = false;
+    let init = declarator.init.take();
+    if name.type_ann.is_none() {
+        match init {
+            Some(init)
+                if kind == VarDeclKind::Const
+                    && matches!(
+                        *init,
+                        Expr::Lit(Lit::Str(_) | Lit::Num(_) | Lit::Bool(_) | Lit::BigInt(_))
+                    ) =>
+            {
+                declarator.init = Some(init);
+            }
+            init => {
+                name.type_ann = Some(type_ann(
+                    init.as_deref().map_or_else(unknown_type, value_type),
+                ));
+            }
+        }
+    }
+    vec![declarator]
+}
+
+/// Collects the identifiers bound by a destructuring pattern.
+fn pat_bindings(pat: &Pat, bindings: &mut Vec<Ident>) {
+    match pat {
+        Pat::Ident(ident) => bindings.push(Ident::new(ident.id.sym.clone(), DUMMY_SP)),
+        Pat::Array(array) => {
+            for elem in array.elems.iter().flatten() {
+                pat_bindings(elem, bindings);
+            }
+        }
+        Pat::Object(object) => {
+            for prop in &object.props {
+                match prop {
+                    ObjectPatProp::KeyValue(prop) => pat_bindings(&prop.value, bindings),
+                    ObjectPatProp::Assign(prop) => {
+                        bindings.push(Ident::new(prop.key.sym.clone(), DUMMY_SP))
+                    }
+                    ObjectPatProp::Rest(rest) => pat_bindings(&rest.arg, bindings),
+                }
+            }
+        }
+        Pat::Rest(rest) => pat_bindings(&rest.arg, bindings),
+        Pat::Assign(assign) => pat_bindings(&assign.left, bindings),
+        Pat::Invalid(_) | Pat::Expr(_) => {}
+    }
+}
+
+/// The declared type of a value without a type annotation. Only literals,
+/// `as` expressions and functions have a type that's known without
+/// inference.
+fn value_type(expr: &Expr) -> TsType {
+    match expr {
+        Expr::Paren(paren) => value_type(&paren.expr),
+        Expr::TsAs(ts_as) => (*ts_as.type_ann).clone(),
+        Expr::Lit(Lit::Str(_)) => keyword_type(TsKeywordTypeKind::TsStringKeyword),
+        Expr::Lit(Lit::Num(_)) => keyword_type(TsKeywordTypeKind::TsNumberKeyword),
+        Expr::Lit(Lit::Bool(_)) => keyword_type(TsKeywordTypeKind::TsBooleanKeyword),
+        Expr::Lit(Lit::BigInt(_)) => keyword_type(TsKeywordTypeKind::TsBigIntKeyword),
+        Expr::Arrow(arrow) => fn_type(
+            arrow.params.clone(),
+            arrow.type_params.clone(),
+            arrow.return_type.clone(),
+        ),
+        Expr::Fn(function) => fn_type(
+            function
+                .function
+                .params
+                .iter()
+                .map(|param| param.pat.clone())
+                .collect(),
+            function.function.type_params.clone(),
+            function.function.return_type.clone(),
+        ),
+        _ => unknown_type(),
+    }
+}
+
+fn fn_type(
+    params: Vec<Pat>,
+    type_params: Option<Box<TsTypeParamDecl>>,
+    return_type: Option<Box<TsTypeAnn>>,
+) -> TsType {
+    let params = params
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut pat)| {
+            strip_param(&mut pat, index);
+            match pat {
+                Pat::Rest(rest) => TsFnParam::Rest(rest),
+                Pat::Ident(ident) => TsFnParam::Ident(ident),
+                _ => unreachable!("parameters are stripped to identifiers or rest parameters"),
+            }
+        })
+        .collect();
+    TsType::TsFnOrConstructorType(TsFnOrConstructorType::TsFnType(TsFnType {
+        span: DUMMY_SP,
+        params,
+        type_params,
+        type_ann: return_type.unwrap_or_else(|| type_ann(unknown_type())),
+    }))
+}
+
+/// Returns the exports of a module stripped by [strip_to_declarations] that
+/// are declared with an `unknown` type, because they don't have a type
+/// annotation. Members of exported classes are named after their class.
+fn untyped_exports(module: &Module) -> Vec<String> {
+    // Local bindings exported by name, and the names they're exported as
+    let mut local_exports: HashMap<String, Vec<String>> = HashMap::new();
+    for item in &module.body {
+        match item {
+            ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(NamedExport {
+                specifiers,
+                src: None,
+                ..
+            })) => {
+                for specifier in specifiers {
+                    if let ExportSpecifier::Named(named) = specifier {
+                        let local = export_name(&named.orig);
+                        let exported = named.exported.as_ref().map_or(local.clone(), export_name);
+                        local_exports.entry(local).or_default().push(exported);
+                    }
+                }
+            }
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(export)) => {
+                if let Expr::Ident(ident) = &*export.expr {
+                    local_exports
+                        .entry(ident.sym.to_string())
+                        .or_default()
+                        .push("default".to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut untyped = Vec::new();
+    let mut push = |name: String| {
+        if !untyped.contains(&name) {
+            untyped.push(name);
+        }
+    };
+    for item in &module.body {
+        match item {
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export)) => {
+                for (name, member) in untyped_bindings(&export.decl) {
+                    push(member_name(&name, member.as_deref()));
+                }
+            }
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(export)) => match &export.decl {
+                DefaultDecl::Fn(function) if declares_unknown(&*function.function) => {
+                    push("default".to_string())
+                }
+                DefaultDecl::Class(class) => {
+                    for member in untyped_members(&class.class) {
+                        push(member_name("default", Some(&member)));
+                    }
+                }
+                _ => {}
+            },
+            ModuleItem::Stmt(Stmt::Decl(decl)) => {
+                for (local, member) in untyped_bindings(decl) {
+                    for name in local_exports.get(&local).into_iter().flatten() {
+                        push(member_name(name, member.as_deref()));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    untyped
+}
+
+fn export_name(name: &ModuleExportName) -> String {
+    match name {
+        ModuleExportName::Ident(ident) => ident.sym.to_string(),
+        ModuleExportName::Str(str) => str.value.to_string(),
+    }
+}
+
+fn member_name(name: &str, member: Option<&str>) -> String {
+    match member {
+        Some(member) => format!("{name}.{member}"),
+        None => name.to_string(),
+    }
+}
+
+/// The bindings of a stripped declaration that are declared as `unknown`,
+/// along with the class member that is, for classes.
+fn untyped_bindings(decl: &Decl) -> Vec<(String, Option<String>)> {
+    match decl {
+        Decl::Fn(function) if declares_unknown(&*function.function) => {
+            vec![(function.ident.sym.to_string(), None)]
+        }
+        Decl::Class(class) => untyped_members(&class.class)
+            .into_iter()
+            .map(|member| (class.ident.sym.to_string(), Some(member)))
+            .collect(),
+        Decl::Var(var) => var
+            .decls
+            .iter()
+            .filter(|declarator| declares_unknown(*declarator))
+            .filter_map(|declarator| match &declarator.name {
+                Pat::Ident(ident) => Some((ident.id.sym.to_string(), None)),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn untyped_members(class: &Class) -> Vec<String> {
+    class
+        .body
+        .iter()
+        .filter(|member| declares_unknown(*member))
+        .filter_map(|member| match member {
+            ClassMember::Method(method) => prop_name(&method.key),
+            ClassMember::ClassProp(prop) => prop_name(&prop.key),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether a node has an `unknown` type that was declared by stripping it,
+/// rather than written in the source. Only declared types don't have a span.
+fn declares_unknown<N: VisitWith<DeclaredUnknown>>(node: &N) -> bool {
+    let mut visitor = DeclaredUnknown(false);
+    node.visit_with(&mut visitor);
+    visitor.0
+}
+
+struct DeclaredUnknown(bool);
+
+impl Visit for DeclaredUnknown {
+    fn visit_ts_keyword_type(&mut self, ty: &TsKeywordType) {
+        self.0 |= ty.kind == TsKeywordTypeKind::TsUnknownKeyword && ty.span.is_dummy();
+    }
+}
+
+fn unknown_type() -> TsType {
+    keyword_type(TsKeywordTypeKind::TsUnknownKeyword)
+}
+
+fn keyword_type(kind: TsKeywordTypeKind) -> TsType {
+    TsType::TsKeywordType(TsKeywordType {
+        span: DUMMY_SP,
+        kind,
+    })
+}
+
+fn type_ann(ty: TsType) -> Box<TsTypeAnn> {
+    Box::new(TsTypeAnn {
+        span: DUMMY_SP,
+        type_ann: Box::new(ty),
+    })
+}
+
+#[nxpkg_tasks::value(shared)]
+struct UntypedExportIssue {
+    path: Vc<FileSystemPath>,
+    export: String,
+}
+
+#[nxpkg_tasks::value_impl]
+impl Issue for UntypedExportIssue {
+    #[nxpkg_tasks::function]
+    fn severity(&self) -> Vc<IssueSeverity> {
+        IssueSeverity::Warning.cell()
+    }
+
+    #[nxpkg_tasks::function]
+    fn title(&self) -> Vc<String> {
+        Vc::cell(format!("{} is declared as unknown", self.export))
+    }
+
+    #[nxpkg_tasks::function]
+    fn category(&self) -> Vc<String> {
+        Vc::cell("code generation".to_string())
+    }
+
+    #[nxpkg_tasks::function]
+    fn file_path(&self) -> Vc<FileSystemPath> {
+        self.path
+    }
+
+    #[nxpkg_tasks::function]
+    fn description(&self) -> Vc<StyledString> {
+        StyledString::Text(
+            "Types aren't inferred when emitting declarations, so exports without a type \
+             annotation are declared as `unknown`. Add a type annotation to declare its type."
+                .to_string(),
+        )
+        .cell()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_core::ecma::ast::{ModuleDecl, ModuleItem, Program};
+
+    use super::{strip_to_declarations, untyped_exports};
+    use crate::ecmascript::library::tests::transform;
+
+    fn declarations(source: &str) -> String {
+        transform(source, |program| {
+            Program::Module(strip_to_declarations(program))
+        })
+    }
+
+    #[test]
+    fn test_strip_to_declarations() {
+        assert_eq!(
+            declarations(
+                r#"
+import { dep } from "./dep";
+export const name = "lib";
+export let count = 1;
+export const parse = (input: string): number => Number(input);
+export function format(value: number) {
+    return String(value);
+}
+export class Counter {
+    #count = 0;
+    step = 1;
+    increment(by: number = 1): void {
+        this.#count += by;
+    }
+}
+export default dep;
+console.log(name);
+"#
+            ),
+            r#"import { dep } from "./dep";
+export declare const name = "lib";
+export declare let count: number;
+export declare const parse: (input: string) => number;
+export declare function format(value: number): unknown;
+export declare class Counter {
+    step: number;
+    increment(by?: number): void;
+}
+export default dep;
+"#
+        );
+    }
+
+    #[test]
+    fn test_strip_to_declarations_script() {
+        let mut module = None;
+        let code = transform("const local = 1;\n", |program| {
+            let stripped = strip_to_declarations(program);
+            module = Some(stripped.clone());
+            Program::Module(stripped)
+        });
+        assert!(code.starts_with("declare const local = 1;\n"));
+        // The declarations are kept local by an empty export
+        assert!(matches!(
+            module.unwrap().body.last(),
+            Some(ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(export)))
+                if export.specifiers.is_empty() && export.src.is_none()
+        ));
+    }
+
+    #[test]
+    fn test_strip_var_declarator_destructuring() {
+        assert_eq!(
+            declarations(
+                r#"
+export const { a, b: [c, ...d], ...e } = value;
+export const [f = 1] = values;
+"#
+            ),
+            r#"export declare const a: unknown, c: unknown, d: unknown, e: unknown;
+export declare const f: unknown;
+"#
+        );
+    }
+
+    #[test]
+    fn test_untyped_exports() {
+        let mut untyped = None;
+        transform(
+            r#"
+export const typed: number = 1, untyped = compute();
+export const literal = "lib";
+export const cast = value as unknown;
+export function format(value: number) {
+    return String(value);
+}
+export class Counter {
+    step = 1;
+    value;
+    increment(): void {}
+    reset() {}
+}
+const local = compute();
+const hidden = compute();
+export { local as renamed };
+export default function () {}
+"#,
+            |program| {
+                let module = strip_to_declarations(program);
+                untyped = Some(untyped_exports(&module));
+                Program::Module(module)
+            },
+        );
+        assert_eq!(
+            untyped.unwrap(),
+            [
+                "untyped",
+                "format",
+                "Counter.value",
+                "Counter.reset",
+                "renamed",
+                "default",
+            ]
+        );
+    }
+}
@@ -0,0 +1,561 @@
+//! The library output of [BuildChunkingContext]. Instead of chunks in the
+//! runtime format, every module is emitted as its own ES module next to the
+//! others, with its imports rewritten to the emitted files. Imported modules
+//! that aren't ES modules, like CSS, JSON or other assets, are copied next to
+//! them as they are.
+
+pub(crate) mod declaration;
+pub(crate) mod module;
+
+use anyhow::{Context, Result};
+use indexmap::{IndexMap, IndexSet};
+use swc_core::ecma::{
+    ast::{
+        CallExpr, Callee, ExportAll, Expr, ExprOrSpread, ImportDecl, Lit, NamedExport, Str,
+        TsExternalModuleRef, TsImportType,
+    },
+    visit::{Visit, VisitMut, VisitMutWith, VisitWith},
+};
+use nxpkg_tasks::{Value, Vc};
+use nxpkg_tasks_fs::FileSystemPath;
+use nxpkgpack_core::{
+    asset::AssetContent,
+    chunk::ChunkingContext,
+    issue::{Issue, IssueExt, IssueSeverity, StyledString},
+    module::Module,
+    output::OutputAsset,
+    package_json::read_package_json,
+    reference_type::EcmaScriptModulesReferenceSubType,
+    resolve::parse::Request,
+    virtual_output::VirtualOutputAsset,
+};
+use nxpkgpack_ecmascript::{
+    parse::ParseResult, resolve::esm_resolve, EcmascriptModuleAsset, EcmascriptModuleAssetType,
+};
+
+use crate::BuildChunkingContext;
+
+/// Options for the library output of [BuildChunkingContext].
+#[nxpkg_tasks::value(shared)]
+#[derive(Debug, Clone, Default)]
+pub struct LibraryOptions {
+    /// Packages that are imported by the emitted modules instead of being
+    /// emitted themselves. Subpaths of the packages are external as well.
+    pub externals: Vec<String>,
+    /// Whether to emit a `.d.mts` declaration file for every TypeScript
+    /// module.
+    pub declarations: bool,
+}
+
+#[nxpkg_tasks::value_impl]
+impl LibraryOptions {
+    /// Creates options that keep the `dependencies` and `peerDependencies` of
+    /// a package.json external.
+    #[nxpkg_tasks::function]
+    pub async fn from_package_json(
+        package_json: Vc<FileSystemPath>,
+        declarations: bool,
+    ) -> Result<Vc<Self>> {
+        let mut externals = Vec::new();
+        if let Some(package_json) = &*read_package_json(package_json).await? {
+            for field in ["dependencies", "peerDependencies"] {
+                if let Some(dependencies) = package_json[field].as_object() {
+                    externals.extend(dependencies.keys().cloned());
+                }
+            }
+        }
+        Ok(LibraryOptions {
+            externals,
+            declarations,
+        }
+        .cell())
+    }
+}
+
+impl LibraryOptions {
+    /// Whether a request is left as is, because it's an external package or
+    /// a `node:` builtin.
+    pub(crate) fn is_external(&self, request: &str) -> bool {
+        request.starts_with("node:")
+            || self.externals.iter().any(|name| {
+                request
+                    .strip_prefix(name.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+    }
+}
+
+/// Whether declarations can be emitted for a module.
+pub(crate) fn is_typescript(ty: EcmascriptModuleAssetType) -> bool {
+    matches!(
+        ty,
+        EcmascriptModuleAssetType::Typescript
+            | EcmascriptModuleAssetType::TypescriptWithTypes
+            | EcmascriptModuleAssetType::TypescriptDeclaration
+    )
+}
+
+/// The directory in the output root that modules of bundled packages are
+/// emitted to.
+const VENDOR_DIR: &str = "vendor";
+
+/// Returns the path a module is emitted to in library mode, with its
+/// extension replaced by `extension`. Modules in the context path keep their
+/// path relative to it. Modules of packages that aren't external are emitted
+/// to the vendor directory with their path relative to `node_modules`.
+#[nxpkg_tasks::function]
+pub(crate) async fn library_path(
+    chunking_context: Vc<BuildChunkingContext>,
+    module: Vc<Box<dyn Module>>,
+    extension: String,
+) -> Result<Vc<FileSystemPath>> {
+    let context_path = chunking_context.context_path().await?;
+    let path = module.ident().path();
+    let stem = match library_stem(&context_path.path, &path.await?.path) {
+        LibraryStem::Context(stem) | LibraryStem::Vendor(stem) => stem,
+        LibraryStem::Outside(stem) => {
+            LibraryPathIssue {
+                path,
+                context_path: context_path.path.clone(),
+                emitted_path: format!("{stem}{extension}"),
+            }
+            .cell()
+            .emit();
+            stem
+        }
+    };
+    Ok(chunking_context
+        .await?
+        .chunk_root_path()
+        .join(format!("{stem}{extension}")))
+}
+
+/// The path of a library module relative to the output root, without its
+/// extension.
+#[derive(Debug, PartialEq, Eq)]
+enum LibraryStem {
+    /// A module in the context path.
+    Context(String),
+    /// A module in `node_modules`, emitted to the vendor directory.
+    Vendor(String),
+    /// A module outside of the context path that isn't in `node_modules`.
+    /// It's emitted to the vendor directory with its full path.
+    Outside(String),
+}
+
+fn library_stem(context_path: &str, path: &str) -> LibraryStem {
+    let stem = [".d.ts", ".d.mts", ".d.cts"]
+        .into_iter()
+        .find_map(|suffix| path.strip_suffix(suffix))
+        .unwrap_or_else(|| {
+            let file_name = path.rsplit('/').next().unwrap_or(path);
+            match file_name.rfind('.') {
+                Some(index) if index > 0 => &path[..path.len() - file_name.len() + index],
+                _ => path,
+            }
+        });
+    if let Some((_, package_path)) = format!("/{stem}").rsplit_once("/node_modules/") {
+        return LibraryStem::Vendor(format!("{VENDOR_DIR}/{package_path}"));
+    }
+    let relative = if context_path.is_empty() {
+        Some(stem)
+    } else {
+        stem.strip_prefix(context_path)
+            .and_then(|rest| rest.strip_prefix('/'))
+    };
+    match relative {
+        Some(relative) => LibraryStem::Context(relative.to_string()),
+        None => LibraryStem::Outside(format!("{VENDOR_DIR}/{stem}")),
+    }
+}
+
+/// The path a module that isn't an ES module is copied to in library mode.
+/// Like [library_path], but the module keeps its extension.
+#[nxpkg_tasks::function]
+pub(crate) async fn library_asset_path(
+    chunking_context: Vc<BuildChunkingContext>,
+    module: Vc<Box<dyn Module>>,
+) -> Result<Vc<FileSystemPath>> {
+    let extension = module
+        .ident()
+        .path()
+        .await?
+        .extension_ref()
+        .map(|extension| format!(".{extension}"))
+        .unwrap_or_default();
+    Ok(library_path(chunking_context, module, extension))
+}
+
+/// The copy of a module that isn't an ES module, emitted from its file as is.
+/// Bundlers consuming the library process it like any other import of
+/// theirs.
+#[nxpkg_tasks::function]
+pub(crate) fn library_asset(
+    chunking_context: Vc<BuildChunkingContext>,
+    module: Vc<Box<dyn Module>>,
+) -> Vc<Box<dyn OutputAsset>> {
+    Vc::upcast(VirtualOutputAsset::new(
+        library_asset_path(chunking_context, module),
+        AssetContent::file(module.ident().path().read()),
+    ))
+}
+
+/// The modules that the requests of a library module resolve to, keyed by
+/// request.
+#[nxpkg_tasks::value(shared)]
+pub(crate) struct LibraryRequests {
+    /// Requests of ES modules, which are emitted as library modules
+    pub modules: IndexMap<String, Vc<EcmascriptModuleAsset>>,
+    /// Requests of other modules, which are copied with [library_asset]
+    pub assets: IndexMap<String, Vc<Box<dyn Module>>>,
+}
+
+impl LibraryRequests {
+    /// Returns each request along with the request that replaces it in a
+    /// module emitted at `from`.
+    pub(crate) async fn rewrites(
+        &self,
+        chunking_context: Vc<BuildChunkingContext>,
+        from: Vc<FileSystemPath>,
+    ) -> Result<Vec<(String, String)>> {
+        let mut rewrites = Vec::new();
+        for (request, &target) in &self.modules {
+            let target_path =
+                library_path(chunking_context, Vc::upcast(target), ".mjs".to_string());
+            rewrites.push((request.clone(), relative_request(from, target_path).await?));
+        }
+        for (request, &target) in &self.assets {
+            let target_path = library_asset_path(chunking_context, target);
+            rewrites.push((request.clone(), relative_request(from, target_path).await?));
+        }
+        Ok(rewrites)
+    }
+}
+
+/// Resolves the requests in `parsed`, which is parsed from the source of
+/// `module`. External requests and requests that don't resolve to a module
+/// are left out.
+#[nxpkg_tasks::function]
+pub(crate) async fn resolve_library_requests(
+    module: Vc<EcmascriptModuleAsset>,
+    parsed: Vc<ParseResult>,
+    options: Vc<LibraryOptions>,
+) -> Result<Vc<LibraryRequests>> {
+    let mut resolved = LibraryRequests {
+        modules: IndexMap::new(),
+        assets: IndexMap::new(),
+    };
+    let ParseResult::Ok { program, .. } = &*parsed.await? else {
+        return Ok(resolved.cell());
+    };
+    let mut requests = ModuleRequests::default();
+    program.visit_with(&mut requests);
+
+    let options = options.await?;
+    for request in requests.0 {
+        if options.is_external(&request) {
+            continue;
+        }
+        let result = esm_resolve(
+            Vc::upcast(module),
+            Request::parse_string(request.clone()),
+            Value::new(EcmaScriptModulesReferenceSubType::Undefined),
+            IssueSeverity::Error.cell(),
+            None,
+        );
+        let Some(target) = *result.first_module().await? else {
+            continue;
+        };
+        match Vc::try_resolve_downcast_type::<EcmascriptModuleAsset>(target).await? {
+            Some(target) => {
+                resolved.modules.insert(request, target);
+            }
+            None => {
+                resolved.assets.insert(request, target);
+            }
+        }
+    }
+    Ok(resolved.cell())
+}
+
+/// Collects the module requests of a program: imports, re-exports, dynamic
+/// `import()`s of a string and imports in types.
+#[derive(Default)]
+struct ModuleRequests(IndexSet<String>);
+
+impl Visit for ModuleRequests {
+    fn visit_import_decl(&mut self, import: &ImportDecl) {
+        self.0.insert(import.src.value.to_string());
+    }
+
+    fn visit_named_export(&mut self, export: &NamedExport) {
+        if let Some(src) = &export.src {
+            self.0.insert(src.value.to_string());
+        }
+    }
+
+    fn visit_export_all(&mut self, export: &ExportAll) {
+        self.0.insert(export.src.value.to_string());
+    }
+
+    fn visit_call_expr(&mut self, call: &CallExpr) {
+        if let Some(src) = dynamic_import_request(call) {
+            self.0.insert(src.value.to_string());
+        }
+        call.visit_children_with(self);
+    }
+
+    fn visit_ts_import_type(&mut self, import: &TsImportType) {
+        self.0.insert(import.arg.value.to_string());
+        import.visit_children_with(self);
+    }
+
+    fn visit_ts_external_module_ref(&mut self, module_ref: &TsExternalModuleRef) {
+        self.0.insert(module_ref.expr.value.to_string());
+    }
+}
+
+fn dynamic_import_request(call: &CallExpr) -> Option<&Str> {
+    if !matches!(call.callee, Callee::Import(_)) {
+        return None;
+    }
+    match call.args.first()? {
+        ExprOrSpread { spread: None, expr } => match &**expr {
+            Expr::Lit(Lit::Str(src)) => Some(src),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Replaces the module requests found by [ModuleRequests] with the
+/// replacements returned by a function.
+pub(crate) struct RewriteModuleRequests<F>(pub F);
+
+impl<F: Fn(&str) -> Option<String>> RewriteModuleRequests<F> {
+    fn rewrite(&self, src: &mut Str) {
+        if let Some(replacement) = (self.0)(&src.value) {
+            src.value = replacement.into();
+            src.raw = None;
+        }
+    }
+}
+
+impl<F: Fn(&str) -> Option<String>> VisitMut for RewriteModuleRequests<F> {
+    fn visit_mut_import_decl(&mut self, import: &mut ImportDecl) {
+        self.rewrite(&mut import.src);
+    }
+
+    fn visit_mut_named_export(&mut self, export: &mut NamedExport) {
+        if let Some(src) = &mut export.src {
+            self.rewrite(src);
+        }
+    }
+
+    fn visit_mut_export_all(&mut self, export: &mut ExportAll) {
+        self.rewrite(&mut export.src);
+    }
+
+    fn visit_mut_call_expr(&mut self, call: &mut CallExpr) {
+        if matches!(call.callee, Callee::Import(_)) {
+            if let Some(ExprOrSpread { spread: None, expr }) = call.args.first_mut() {
+                if let Expr::Lit(Lit::Str(src)) = &mut **expr {
+                    self.rewrite(src);
+                }
+            }
+        }
+        call.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_ts_import_type(&mut self, import: &mut TsImportType) {
+        self.rewrite(&mut import.arg);
+        import.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_ts_external_module_ref(&mut self, module_ref: &mut TsExternalModuleRef) {
+        self.rewrite(&mut module_ref.expr);
+    }
+}
+
+/// Returns the request for `target` from a module emitted at `from`.
+pub(crate) async fn relative_request(
+    from: Vc<FileSystemPath>,
+    target: Vc<FileSystemPath>,
+) -> Result<String> {
+    let target = target.await?;
+    from.parent()
+        .await?
+        .get_relative_path_to(&target)
+        .with_context(|| format!("{} is not on the output filesystem", target.path))
+}
+
+#[nxpkg_tasks::value(shared)]
+struct LibraryPathIssue {
+    path: Vc<FileSystemPath>,
+    context_path: String,
+    emitted_path: String,
+}
+
+#[nxpkg_tasks::value_impl]
+impl Issue for LibraryPathIssue {
+    #[nxpkg_tasks::function]
+    fn severity(&self) -> Vc<IssueSeverity> {
+        IssueSeverity::Warning.cell()
+    }
+
+    #[nxpkg_tasks::function]
+    fn title(&self) -> Vc<String> {
+        Vc::cell("Module is outside of the context path".to_string())
+    }
+
+    #[nxpkg_tasks::function]
+    fn category(&self) -> Vc<String> {
+        Vc::cell("code generation".to_string())
+    }
+
+    #[nxpkg_tasks::function]
+    fn file_path(&self) -> Vc<FileSystemPath> {
+        self.path
+    }
+
+    #[nxpkg_tasks::function]
+    fn description(&self) -> Vc<StyledString> {
+        StyledString::Text(format!(
+            "The module is neither in the context path {} nor in node_modules, so it's emitted to \
+             {} in the output directory.",
+            self.context_path, self.emitted_path
+        ))
+        .cell()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_core::{
+        common::{sync::Lrc, FileName, SourceMap},
+        ecma::{
+            ast::{EsVersion, Program},
+            codegen::{text_writer::JsWriter, Emitter},
+            parser::{parse_file_as_program, Syntax, TsConfig},
+            visit::VisitMutWith,
+        },
+    };
+
+    use super::{library_stem, LibraryOptions, LibraryStem, RewriteModuleRequests};
+
+    /// Parses TypeScript source and passes the program to `f`, returning the
+    /// code emitted for the program it returns.
+    pub(super) fn transform(source: &str, f: impl FnOnce(Program) -> Program) -> String {
+        let cm: Lrc<SourceMap> = Default::default();
+        let file = cm.new_source_file(FileName::Anon, source.to_string());
+        let program = parse_file_as_program(
+            &file,
+            Syntax::Typescript(TsConfig::default()),
+            EsVersion::latest(),
+            None,
+            &mut vec![],
+        )
+        .unwrap();
+        let program = f(program);
+
+        let mut bytes = vec![];
+        let mut emitter = Emitter {
+            cfg: Default::default(),
+            cm: cm.clone(),
+            comments: None,
+            wr: JsWriter::new(cm, "\n", &mut bytes, None),
+        };
+        emitter.emit_program(&program).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_is_external() {
+        let options = LibraryOptions {
+            externals: vec!["react".to_string(), "@scope/pkg".to_string()],
+            declarations: false,
+        };
+        assert!(options.is_external("react"));
+        assert!(options.is_external("react/jsx-runtime"));
+        assert!(options.is_external("@scope/pkg/sub"));
+        assert!(options.is_external("node:fs"));
+        assert!(!options.is_external("react-dom"));
+        assert!(!options.is_external("@scope/pkg-utils"));
+        assert!(!options.is_external("@scope"));
+        assert!(!options.is_external("./react"));
+    }
+
+    #[test]
+    fn test_rewrite_module_requests() {
+        let code = transform(
+            r#"
+import a from "a";
+import b from "react";
+export { c } from "./c";
+export * from "./d";
+const e = import("./e");
+const f = import(name);
+type G = import("./g").G;
+"#,
+            |mut program| {
+                program.visit_mut_with(&mut RewriteModuleRequests(|request: &str| {
+                    (request != "react").then(|| format!("./out/{request}.mjs"))
+                }));
+                program
+            },
+        );
+        assert_eq!(
+            code,
+            r#"import a from "./out/a.mjs";
+import b from "react";
+export { c } from "./out/./c.mjs";
+export * from "./out/./d.mjs";
+const e = import("./out/./e.mjs");
+const f = import(name);
+type G = import("./out/./g.mjs").G;
+"#
+        );
+    }
+
+    #[test]
+    fn test_library_stem() {
+        assert_eq!(
+            library_stem("packages/lib", "packages/lib/src/index.ts"),
+            LibraryStem::Context("src/index".to_string())
+        );
+        assert_eq!(
+            library_stem("packages/lib", "packages/lib/src/types.d.ts"),
+            LibraryStem::Context("src/types".to_string())
+        );
+        assert_eq!(
+            library_stem("", "src/.hidden"),
+            LibraryStem::Context("src/.hidden".to_string())
+        );
+        assert_eq!(
+            library_stem("packages/lib", "packages/library/index.ts"),
+            LibraryStem::Outside("vendor/packages/library/index".to_string())
+        );
+    }
+
+    #[test]
+    fn test_library_stem_node_modules() {
+        assert_eq!(
+            library_stem(
+                "packages/lib",
+                "node_modules/.pnpm/a@1.0.0/node_modules/a/index.js"
+            ),
+            LibraryStem::Vendor("vendor/a/index".to_string())
+        );
+        assert_eq!(
+            library_stem(
+                "packages/lib",
+                "packages/lib/node_modules/@scope/b/dist/b.mjs"
+            ),
+            LibraryStem::Vendor("vendor/@scope/b/dist/b".to_string())
+        );
+        assert_eq!(
+            library_stem("packages/lib", "packages/lib/src/my_node_modules/c.js"),
+            LibraryStem::Context("src/my_node_modules/c".to_string())
+        );
+    }
+}
@@ -0,0 +1,405 @@
+use std::io::Write;
+
+use anyhow::Result;
+use indexmap::{IndexMap, IndexSet};
+use swc_core::{
+    common::{Mark, DUMMY_SP, GLOBALS},
+    ecma::{
+        ast::{
+            AssignExpr, Bool, CallExpr, Callee, ComputedPropName, Expr, ExprOrSpread, Ident, Lit,
+            MemberExpr, MemberProp,
+        },
+        codegen::{text_writer::JsWriter, Emitter},
+        transforms::base::{fixer::fixer, hygiene::hygiene},
+        visit::{VisitMut, VisitMutWith},
+    },
+};
+use nxpkg_tasks::{ValueToString, Vc};
+use nxpkg_tasks_fs::{rope::Rope, File, FileSystemPath};
+use nxpkgpack_core::{
+    asset::{Asset, AssetContent},
+    chunk::ChunkingContext,
+    code_builder::{Code, CodeBuilder},
+    compile_time_info::CompileTimeDefineValue,
+    ident::AssetIdent,
+    introspect::{module::IntrospectableModule, Introspectable, IntrospectableChildren},
+    issue::{Issue, IssueExt, IssueSeverity, StyledString},
+    module::Module,
+    output::{OutputAsset, OutputAssets},
+    source_map::{GenerateSourceMap, OptionSourceMap, SourceMapAsset},
+};
+use nxpkgpack_ecmascript::{
+    chunk::{EcmascriptChunkPlaceable, EcmascriptExports},
+    parse::ParseResult,
+    utils::StringifyJs,
+    EcmascriptModuleAsset, EcmascriptModuleAssetType, ParseResultSourceMap,
+};
+
+use super::{
+    declaration::EcmascriptLibraryDeclaration, is_typescript, library_asset, library_path,
+    resolve_library_requests, LibraryOptions, RewriteModuleRequests,
+};
+use crate::{chunking_context::MinifyType, ecmascript::minify::minify, BuildChunkingContext};
+
+/// A module emitted as a standalone ES module in library mode. Its imports
+/// of other modules are rewritten to the library modules emitted for them,
+/// external imports are kept as is.
+///
+/// The module is emitted from its parsed and transformed source, so it keeps
+/// its `import` and `export` statements. Compile time defines are replaced,
+/// other code generation of the module's references doesn't apply. CommonJS
+/// modules can't be emitted this way and are reported as an error.
+#[nxpkg_tasks::value(shared)]
+pub(crate) struct EcmascriptLibraryModule {
+    chunking_context: Vc<BuildChunkingContext>,
+    module: Vc<EcmascriptModuleAsset>,
+    options: Vc<LibraryOptions>,
+}
+
+#[nxpkg_tasks::value_impl]
+impl EcmascriptLibraryModule {
+    /// Creates a new [`Vc<EcmascriptLibraryModule>`].
+    #[nxpkg_tasks::function]
+    pub fn new(
+        chunking_context: Vc<BuildChunkingContext>,
+        module: Vc<EcmascriptModuleAsset>,
+        options: Vc<LibraryOptions>,
+    ) -> Vc<Self> {
+        EcmascriptLibraryModule {
+            chunking_context,
+            module,
+            options,
+        }
+        .cell()
+    }
+
+    #[nxpkg_tasks::function]
+    async fn code(self: Vc<Self>) -> Result<Vc<Code>> {
+        let this = self.await?;
+        let path_vc = self.ident().path();
+        let path = path_vc.await?;
+
+        let mut code = CodeBuilder::default();
+        let parsed = this.module.parse();
+        if matches!(
+            *this.module.get_exports().await?,
+            EcmascriptExports::CommonJs
+        ) {
+            CommonJsLibraryModuleIssue {
+                path: this.module.ident().path(),
+            }
+            .cell()
+            .emit();
+            write!(
+                code,
+                "throw new Error({});",
+                StringifyJs(&format!(
+                    "CommonJS module '{}' can't be emitted as a library module",
+                    this.module.ident().path().to_string().await?
+                ))
+            )?;
+        } else if let ParseResult::Ok {
+            program,
+            comments,
+            eval_context,
+            globals,
+            source_map,
+        } = &*parsed.await?
+        {
+            let rewrites = resolve_library_requests(this.module, parsed, this.options)
+                .await?
+                .rewrites(this.chunking_context, path_vc)
+                .await?;
+
+            let defines = this.module.await?.compile_time_info.await?.defines.await?;
+            let mut program = program.clone();
+            GLOBALS.set(globals, || {
+                program.visit_mut_with(&mut ReplaceDefines {
+                    defines: &defines,
+                    unresolved_mark: eval_context.unresolved_mark(),
+                });
+                program.visit_mut_with(&mut RewriteModuleRequests(|request: &str| {
+                    rewrites
+                        .iter()
+                        .find(|(from, _)| from == request)
+                        .map(|(_, to)| to.clone())
+                }));
+                program.visit_mut_with(&mut hygiene());
+                program.visit_mut_with(&mut fixer(None));
+            });
+
+            let mut bytes: Vec<u8> = vec![];
+            let mut mappings = vec![];
+            let comments = comments.consumable();
+            let mut emitter = Emitter {
+                cfg: swc_core::ecma::codegen::Config::default(),
+                cm: source_map.clone(),
+                comments: Some(&comments),
+                wr: JsWriter::new(source_map.clone(), "\n", &mut bytes, Some(&mut mappings)),
+            };
+            emitter.emit_program(&program)?;
+
+            let source_map = ParseResultSourceMap::new(source_map.clone(), mappings).cell();
+            code.push_source(&Rope::from(bytes), Some(Vc::upcast(source_map)));
+        } else {
+            write!(
+                code,
+                "const e = new Error({});\ne.code = 'MODULE_UNPARSEABLE';\nthrow e;",
+                StringifyJs(&format!(
+                    "Could not parse module '{}'",
+                    this.module.ident().path().to_string().await?
+                ))
+            )?;
+        }
+
+        if code.has_source_map() {
+            write!(code, "\n\n//# sourceMappingURL={}.map", path.file_name())?;
+        }
+
+        let code = code.build().cell();
+        if matches!(
+            this.chunking_context.await?.minify_type(),
+            MinifyType::Minify
+        ) {
+            return Ok(minify(path_vc, code));
+        }
+
+        Ok(code)
+    }
+}
+
+/// Replaces the compile time defines in a program with their values. Only
+/// references to unresolved globals are replaced, like in the code generation
+/// of chunks.
+struct ReplaceDefines<'a> {
+    defines: &'a IndexMap<Vec<String>, CompileTimeDefineValue>,
+    unresolved_mark: Mark,
+}
+
+impl ReplaceDefines<'_> {
+    fn value(&self, mut expr: &Expr) -> Option<&CompileTimeDefineValue> {
+        let mut path = Vec::new();
+        loop {
+            match expr {
+                Expr::Member(MemberExpr { obj, prop, .. }) => {
+                    match prop {
+                        MemberProp::Ident(ident) => path.push(ident.sym.to_string()),
+                        MemberProp::Computed(ComputedPropName { expr, .. }) => match &**expr {
+                            Expr::Lit(Lit::Str(str)) => path.push(str.value.to_string()),
+                            _ => return None,
+                        },
+                        MemberProp::PrivateName(_) => return None,
+                    }
+                    expr = &**obj;
+                }
+                Expr::Ident(ident) if ident.span.ctxt.outer() == self.unresolved_mark => {
+                    path.push(ident.sym.to_string());
+                    break;
+                }
+                _ => return None,
+            }
+        }
+        path.reverse();
+        self.defines.get(&path)
+    }
+
+    fn value_expr(&self, value: &CompileTimeDefineValue) -> Expr {
+        match value {
+            CompileTimeDefineValue::Bool(value) => Expr::Lit(Lit::Bool(Bool {
+                span: DUMMY_SP,
+                value: *value,
+            })),
+            CompileTimeDefineValue::String(value) => value.clone().into(),
+            CompileTimeDefineValue::JSON(value) => Expr::Call(CallExpr {
+                span: DUMMY_SP,
+                callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
+                    span: DUMMY_SP,
+                    obj: Box::new(Expr::Ident(Ident::new(
+                        "JSON".into(),
+                        DUMMY_SP.apply_mark(self.unresolved_mark),
+                    ))),
+                    prop: MemberProp::Ident(Ident::new("parse".into(), DUMMY_SP)),
+                }))),
+                args: vec![ExprOrSpread {
+                    spread: None,
+                    expr: Box::new(value.clone().into()),
+                }],
+                type_args: None,
+            }),
+        }
+    }
+}
+
+impl VisitMut for ReplaceDefines<'_> {
+    fn visit_mut_expr(&mut self, expr: &mut Expr) {
+        if let Some(value) = self.value(expr) {
+            *expr = self.value_expr(value);
+        } else {
+            expr.visit_mut_children_with(self);
+        }
+    }
+
+    fn visit_mut_assign_expr(&mut self, assign: &mut AssignExpr) {
+        // Assignments to a define are left as is
+        assign.right.visit_mut_with(self);
+    }
+}
+
+#[nxpkg_tasks::value_impl]
+impl ValueToString for EcmascriptLibraryModule {
+    #[nxpkg_tasks::function]
+    async fn to_string(&self) -> Result<Vc<String>> {
+        Ok(Vc::cell("Ecmascript Library Module".to_string()))
+    }
+}
+
+#[nxpkg_tasks::value_impl]
+impl OutputAsset for EcmascriptLibraryModule {
+    #[nxpkg_tasks::function]
+    fn ident(&self) -> Vc<AssetIdent> {
+        AssetIdent::from_path(library_path(
+            self.chunking_context,
+            Vc::upcast(self.module),
+            ".mjs".to_string(),
+        ))
+    }
+
+    #[nxpkg_tasks::function]
+    async fn references(self: Vc<Self>) -> Result<Vc<OutputAssets>> {
+        let this = self.await?;
+        let mut references: Vec<Vc<Box<dyn OutputAsset>>> = Vec::new();
+
+        let requests =
+            resolve_library_requests(this.module, this.module.parse(), this.options).await?;
+        for &target in requests.modules.values() {
+            // Declaration files don't have any code, they are only referenced
+            // from declarations
+            if !matches!(
+                target.await?.ty,
+                EcmascriptModuleAssetType::TypescriptDeclaration
+            ) {
+                references.push(Vc::upcast(EcmascriptLibraryModule::new(
+                    this.chunking_context,
+                    target,
+                    this.options,
+                )));
+            }
+        }
+        for &target in requests.assets.values() {
+            references.push(library_asset(this.chunking_context, target));
+        }
+
+        if this.options.await?.declarations && is_typescript(this.module.await?.ty) {
+            references.push(Vc::upcast(EcmascriptLibraryDeclaration::new(
+                this.chunking_context,
+                this.module,
+                this.options,
+            )));
+        }
+
+        if *this
+            .chunking_context
+            .reference_chunk_source_maps(Vc::upcast(self))
+            .await?
+        {
+            references.push(Vc::upcast(SourceMapAsset::new(Vc::upcast(self))));
+        }
+
+        Ok(Vc::cell(references))
+    }
+}
+
+#[nxpkg_tasks::value_impl]
+impl Asset for EcmascriptLibraryModule {
+    #[nxpkg_tasks::function]
+    async fn content(self: Vc<Self>) -> Result<Vc<AssetContent>> {
+        let code = self.code().await?;
+        Ok(AssetContent::file(
+            File::from(code.source_code().clone()).into(),
+        ))
+    }
+}
+
+#[nxpkg_tasks::value_impl]
+impl GenerateSourceMap for EcmascriptLibraryModule {
+    #[nxpkg_tasks::function]
+    fn generate_source_map(self: Vc<Self>) -> Vc<OptionSourceMap> {
+        self.code().generate_source_map()
+    }
+}
+
+#[nxpkg_tasks::function]
+fn introspectable_type() -> Vc<String> {
+    Vc::cell("ecmascript library module".to_string())
+}
+
+#[nxpkg_tasks::function]
+fn introspectable_details() -> Vc<String> {
+    Vc::cell("generates a standalone ES module of a module for a library".to_string())
+}
+
+#[nxpkg_tasks::value_impl]
+impl Introspectable for EcmascriptLibraryModule {
+    #[nxpkg_tasks::function]
+    fn ty(&self) -> Vc<String> {
+        introspectable_type()
+    }
+
+    #[nxpkg_tasks::function]
+    fn title(self: Vc<Self>) -> Vc<String> {
+        self.ident().to_string()
+    }
+
+    #[nxpkg_tasks::function]
+    fn details(&self) -> Vc<String> {
+        introspectable_details()
+    }
+
+    #[nxpkg_tasks::function]
+    async fn children(&self) -> Result<Vc<IntrospectableChildren>> {
+        let mut children = IndexSet::new();
+        children.insert((
+            Vc::cell("module".to_string()),
+            IntrospectableModule::new(Vc::upcast(self.module)),
+        ));
+        Ok(Vc::cell(children))
+    }
+}
+
+#[nxpkg_tasks::value(shared)]
+struct CommonJsLibraryModuleIssue {
+    path: Vc<FileSystemPath>,
+}
+
+#[nxpkg_tasks::value_impl]
+impl Issue for CommonJsLibraryModuleIssue {
+    #[nxpkg_tasks::function]
+    fn severity(&self) -> Vc<IssueSeverity> {
+        IssueSeverity::Error.cell()
+    }
+
+    #[nxpkg_tasks::function]
+    fn title(&self) -> Vc<String> {
+        Vc::cell("CommonJS modules can't be emitted as library modules".to_string())
+    }
+
+    #[nxpkg_tasks::function]
+    fn category(&self) -> Vc<String> {
+        Vc::cell("code generation".to_string())
+    }
+
+    #[nxpkg_tasks::function]
+    fn file_path(&self) -> Vc<FileSystemPath> {
+        self.path
+    }
+
+    #[nxpkg_tasks::function]
+    fn description(&self) -> Vc<StyledString> {
+        StyledString::Text(
+            "Library modules are emitted as ES modules, which can't use `require`, `module` or \
+             `exports`. Add the package to the externals, or convert the module to an ES module."
+                .to_string(),
+        )
+        .cell()
+    }
+}
@@ -1,2 +1,3 @@
+pub(crate) mod library;
 pub(crate) mod minify;
 pub(crate) mod node;
@@ -6,6 +6,7 @@ pub(crate) mod chunking_context;
 pub(crate) mod ecmascript;
 
 pub use chunking_context::{BuildChunkingContext, BuildChunkingContextBuilder, MinifyType};
+pub use ecmascript::library::LibraryOptions;
 
 pub fn register() {
     nxpkg_tasks::register();
@@ -6,6 +6,8 @@ use std::{
 use clap::{Args, Parser};
 use nxpkgpack_cli_utils::issue::IssueSeverityCliOption;
 
+use crate::build::stats::StatsFormat;
+
 #[derive(Debug, Parser)]
 #[clap(author, version, about, long_about = None)]
 pub enum Arguments {
@@ -89,6 +91,22 @@ pub struct DevArguments {
     #[clap(long)]
     pub no_open: bool,
 
+    /// Serve HTTPS and HTTP/2. Unless `--https-cert` and `--https-key` are
+    /// provided, a certificate is generated for the hostname. It's signed by a
+    /// local certificate authority, which is added to the trusted certificates
+    /// when it's created.
+    #[clap(long)]
+    pub https: bool,
+
+    /// The PEM file of the certificate chain to serve HTTPS with. Implies
+    /// `--https`.
+    #[clap(long, value_parser, requires = "https_key")]
+    pub https_cert: Option<PathBuf>,
+
+    /// The PEM file of the private key of `--https-cert`.
+    #[clap(long, value_parser, requires = "https_cert")]
+    pub https_key: Option<PathBuf>,
+
     // ==
     // = Inherited options from next-dev, need revisit later.
     // ==
@@ -107,4 +125,59 @@ pub struct BuildArguments {
     /// Don't minify build output.
     #[clap(long)]
     pub no_minify: bool,
+
+    /// Write a description of the build output to `dist/stats.json`. The
+    /// `webpack` format, selected with `--stats=webpack`, is compatible with
+    /// tools that read webpack's stats.
+    #[clap(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "nxpkgpack"
+    )]
+    pub stats: Option<StatsFormat>,
+
+    /// Emit every module as a standalone ES module for publishing a package,
+    /// instead of chunks. The `dependencies` and `peerDependencies` of the
+    /// project's package.json are kept as imports.
+    #[clap(long)]
+    pub library: bool,
+
+    /// Emit a `.d.mts` declaration file for every TypeScript module in
+    /// library mode.
+    #[clap(long, requires = "library")]
+    pub declarations: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::Arguments;
+    use crate::build::stats::StatsFormat;
+
+    fn parse_build(args: &[&str]) -> super::BuildArguments {
+        let args = ["nxpkgpack", "build"].iter().chain(args);
+        match Arguments::try_parse_from(args).unwrap() {
+            Arguments::Build(args) => args,
+            Arguments::Dev(_) => panic!("expected build arguments"),
+        }
+    }
+
+    #[test]
+    fn test_stats_doesnt_take_entries() {
+        let args = parse_build(&["--stats", "src/index.js"]);
+        assert_eq!(args.stats, Some(StatsFormat::Nxpkgpack));
+        assert_eq!(args.common.entries, Some(vec!["src/index.js".to_string()]));
+    }
+
+    #[test]
+    fn test_stats_format() {
+        assert_eq!(
+            parse_build(&["--stats=webpack"]).stats,
+            Some(StatsFormat::Webpack)
+        );
+        assert_eq!(parse_build(&[]).stats, None);
+    }
 }
@@ -7,17 +7,17 @@ use std::{
 
 use anyhow::{bail, Context, Result};
 use nxpkg_tasks::{TransientInstance, TryJoinIterExt, NxpkgTasks, Value, Vc};
-use nxpkg_tasks_fs::FileSystem;
+use nxpkg_tasks_fs::{File, FileContent, FileSystem};
 use nxpkg_tasks_memory::MemoryBackend;
 use nxpkgpack::ecmascript::EcmascriptModuleAsset;
-use nxpkgpack_build::{BuildChunkingContext, MinifyType};
+use nxpkgpack_build::{BuildChunkingContext, LibraryOptions, MinifyType};
 use nxpkgpack_cli_utils::issue::{ConsoleUi, LogOptions};
 use nxpkgpack_core::{
     asset::Asset,
     chunk::{ChunkableModule, ChunkingContextExt, EvaluatableAssets},
-    environment::{BrowserEnvironment, Environment, ExecutionEnvironment},
+    introspect::stats::collect_stats,
     issue::{handle_issues, IssueReporter, IssueSeverity},
-    module::Module,
+    module::{Module, Modules},
     output::OutputAsset,
     reference::all_assets_from_entries,
     reference_type::{EntryReferenceSubType, ReferenceType},
@@ -29,6 +29,7 @@ use nxpkgpack_core::{
 use nxpkgpack_env::dotenv::load_env;
 use nxpkgpack_node::execution_context::ExecutionContext;
 
+use self::stats::{stats_to_json, StatsFormat};
 use crate::{
     arguments::BuildArguments,
     contexts::{get_client_asset_context, get_client_compile_time_info, NodeEnv},
@@ -38,6 +39,8 @@ use crate::{
     },
 };
 
+pub(crate) mod stats;
+
 pub fn register() {
     nxpkgpack::register();
     include!(concat!(env!("OUT_DIR"), "/register.rs"));
@@ -53,6 +56,9 @@ pub struct NxpkgpackBuildBuilder {
     show_all: bool,
     log_detail: bool,
     minify_type: MinifyType,
+    stats: Option<StatsFormat>,
+    library: bool,
+    declarations: bool,
 }
 
 impl NxpkgpackBuildBuilder {
@@ -71,6 +77,9 @@ impl NxpkgpackBuildBuilder {
             show_all: false,
             log_detail: false,
             minify_type: MinifyType::Minify,
+            stats: None,
+            library: false,
+            declarations: false,
         }
     }
 
@@ -104,6 +113,21 @@ impl NxpkgpackBuildBuilder {
         self
     }
 
+    pub fn stats(mut self, stats: StatsFormat) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    pub fn library(mut self, library: bool) -> Self {
+        self.library = library;
+        self
+    }
+
+    pub fn declarations(mut self, declarations: bool) -> Self {
+        self.declarations = declarations;
+        self
+    }
+
     pub async fn build(self) -> Result<()> {
         let task = self.nxpkg_tasks.spawn_once_task::<(), _>(async move {
             let build_result = build_internal(
@@ -119,6 +143,9 @@ impl NxpkgpackBuildBuilder {
                 .cell(),
                 self.browserslist_query,
                 self.minify_type,
+                self.stats,
+                self.library,
+                self.declarations,
             );
 
             // Await the result to propagate any errors.
@@ -158,16 +185,10 @@ async fn build_internal(
     entry_requests: Vc<EntryRequests>,
     browserslist_query: String,
     minify_type: MinifyType,
+    stats: Option<StatsFormat>,
+    library: bool,
+    declarations: bool,
 ) -> Result<Vc<()>> {
-    let env = Environment::new(Value::new(ExecutionEnvironment::Browser(
-        BrowserEnvironment {
-            dom: true,
-            web_worker: false,
-            service_worker: false,
-            browserslist_query: browserslist_query.clone(),
-        }
-        .into(),
-    )));
     let output_fs = output_fs(project_dir.clone());
     let project_fs = project_fs(root_dir.clone());
     let project_relative = project_dir.strip_prefix(&root_dir).unwrap();
@@ -178,21 +199,29 @@ async fn build_internal(
     let project_path = project_fs.root().join(project_relative);
     let build_output_root = output_fs.root().join("dist".to_string());
 
-    let chunking_context = Vc::upcast(
-        BuildChunkingContext::builder(
-            project_path,
-            build_output_root,
-            build_output_root,
-            build_output_root,
-            build_output_root,
-            env,
-        )
-        .minify_type(minify_type)
-        .build(),
-    );
-
     let node_env = NodeEnv::Production.cell();
-    let compile_time_info = get_client_compile_time_info(browserslist_query, node_env);
+    let compile_time_info =
+        get_client_compile_time_info(project_path, browserslist_query, node_env);
+    let env = compile_time_info.environment();
+
+    let mut chunking_context_builder = BuildChunkingContext::builder(
+        project_path,
+        build_output_root,
+        build_output_root,
+        build_output_root,
+        build_output_root,
+        env,
+    )
+    .minify_type(minify_type);
+    if library {
+        chunking_context_builder =
+            chunking_context_builder.library(LibraryOptions::from_package_json(
+                project_path.join("package.json".to_string()),
+                declarations,
+            ));
+    }
+    let chunking_context = Vc::upcast(chunking_context_builder.build());
+
     let execution_context =
         ExecutionContext::new(project_path, chunking_context, load_env(project_path));
     let asset_context =
@@ -238,6 +267,7 @@ async fn build_internal(
         .try_join()
         .await?;
 
+    let entry_modules: Vc<Modules> = Vc::cell(entries.clone());
     let entry_chunk_groups = entries
         .into_iter()
         .map(|entry_module| async move {
@@ -293,6 +323,19 @@ async fn build_internal(
         .try_join()
         .await?;
 
+    if let Some(format) = stats {
+        let stats = collect_stats(
+            build_output_root,
+            entry_modules,
+            Vc::cell(chunks.into_iter().collect()),
+        )
+        .await?;
+        build_output_root
+            .join("stats.json".to_string())
+            .write(FileContent::Content(File::from(stats_to_json(&stats, format)?)).cell())
+            .await?;
+    }
+
     Ok(Default::default())
 }
 
@@ -320,7 +363,13 @@ pub async fn build(args: &BuildArguments) -> Result<()> {
         } else {
             MinifyType::Minify
         })
-        .show_all(args.common.show_all);
+        .show_all(args.common.show_all)
+        .library(args.library)
+        .declarations(args.declarations);
+
+    if let Some(stats) = args.stats {
+        builder = builder.stats(stats);
+    }
 
     for entry in normalize_entries(&args.common.entries) {
         builder = builder.entry_request(EntryRequest::Relative(entry));
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use nxpkg_tasks::{trace::TraceRawVcs, TaskInput};
+use nxpkgpack_core::introspect::stats::{ModuleStats, Stats};
+
+/// The format of the stats written by `--stats`.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    TaskInput,
+    Serialize,
+    Deserialize,
+    TraceRawVcs,
+    clap::ValueEnum,
+)]
+pub enum StatsFormat {
+    /// Output assets, the modules in them, why each module was included and
+    /// duplicate packages.
+    Nxpkgpack,
+    /// The `assets`, `chunks` and `modules` of webpack's stats format, as read
+    /// by bundle size tools.
+    Webpack,
+}
+
+pub(super) fn stats_to_json(stats: &Stats, format: StatsFormat) -> Result<String> {
+    let stats = match format {
+        StatsFormat::Nxpkgpack => serde_json::to_value(stats)?,
+        StatsFormat::Webpack => webpack_stats(stats),
+    };
+    Ok(serde_json::to_string_pretty(&stats)?)
+}
+
+// Every chunk is an output asset in nxpkgpack, so chunks use the path of their
+// output asset as id
+fn webpack_stats(stats: &Stats) -> JsonValue {
+    let module_ids: HashMap<&str, usize> = stats
+        .modules
+        .iter()
+        .enumerate()
+        .map(|(id, module)| (module.ident.as_str(), id))
+        .collect();
+    let modules: Vec<_> = stats
+        .modules
+        .iter()
+        .enumerate()
+        .map(|(id, module)| webpack_module(id, module, &module_ids))
+        .collect();
+
+    let assets: Vec<_> = stats
+        .output_assets
+        .iter()
+        .map(|asset| {
+            let chunks = if asset.modules.is_empty() {
+                vec![]
+            } else {
+                vec![&asset.path]
+            };
+            json!({
+                "name": asset.path,
+                "size": asset.size,
+                "chunks": chunks,
+                "chunkNames": [],
+                "emitted": true,
+            })
+        })
+        .collect();
+
+    let chunks: Vec<_> = stats
+        .output_assets
+        .iter()
+        .filter(|asset| !asset.modules.is_empty())
+        .map(|asset| {
+            let ids: Vec<_> = asset
+                .modules
+                .iter()
+                .filter_map(|ident| module_ids.get(ident.as_str()).copied())
+                .collect();
+            json!({
+                "id": asset.path,
+                "names": [],
+                "files": [asset.path],
+                "size": asset.size,
+                "entry": ids.iter().any(|&id| stats.modules[id].entry),
+                "modules": ids.iter().map(|&id| &modules[id]).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    json!({
+        "assets": assets,
+        "chunks": chunks,
+        "modules": modules,
+    })
+}
+
+fn webpack_module(id: usize, module: &ModuleStats, module_ids: &HashMap<&str, usize>) -> JsonValue {
+    let issuer_path: Vec<_> = module
+        .reference_chain
+        .iter()
+        .map(|ident| {
+            json!({
+                "id": module_ids.get(ident.as_str()),
+                "identifier": ident,
+                "name": ident,
+            })
+        })
+        .collect();
+    let reasons: Vec<_> = module
+        .reasons
+        .iter()
+        .map(|reason| {
+            json!({
+                "moduleId": module_ids.get(reason.module.as_str()),
+                "moduleIdentifier": reason.module,
+                "moduleName": reason.module,
+                "type": reason.ty,
+            })
+        })
+        .collect();
+    json!({
+        "id": id,
+        "identifier": module.ident,
+        "name": module.ident,
+        "size": module.output_size,
+        "chunks": module.chunks,
+        "issuerPath": issuer_path,
+        "reasons": reasons,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use nxpkgpack_core::introspect::stats::{ModuleReason, ModuleStats, OutputAssetStats, Stats};
+
+    use super::webpack_stats;
+
+    fn module(ident: &str, entry: bool, chunks: &[&str], reasons: &[&str]) -> ModuleStats {
+        ModuleStats {
+            ident: ident.to_string(),
+            entry,
+            original_size: 100,
+            output_size: 10,
+            chunks: chunks.iter().map(|chunk| chunk.to_string()).collect(),
+            reasons: reasons
+                .iter()
+                .map(|module| ModuleReason {
+                    module: module.to_string(),
+                    ty: "import".to_string(),
+                })
+                .collect(),
+            reference_chain: reasons.iter().map(|module| module.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_webpack_stats() {
+        let stats = Stats {
+            output_assets: vec![
+                OutputAssetStats {
+                    path: "index.js".to_string(),
+                    ty: "ecmascript chunk".to_string(),
+                    size: 20,
+                    modules: vec![
+                        "[project]/index.js".to_string(),
+                        "[project]/a.js".to_string(),
+                    ],
+                },
+                OutputAssetStats {
+                    path: "logo.svg".to_string(),
+                    ty: "static asset".to_string(),
+                    size: 5,
+                    modules: vec![],
+                },
+            ],
+            modules: vec![
+                module("[project]/index.js", true, &["index.js"], &[]),
+                module(
+                    "[project]/a.js",
+                    false,
+                    &["index.js"],
+                    &["[project]/index.js"],
+                ),
+            ],
+            duplicate_packages: vec![],
+        };
+
+        let index_module = json!({
+            "id": 0,
+            "identifier": "[project]/index.js",
+            "name": "[project]/index.js",
+            "size": 10,
+            "chunks": ["index.js"],
+            "issuerPath": [],
+            "reasons": [],
+        });
+        let a_module = json!({
+            "id": 1,
+            "identifier": "[project]/a.js",
+            "name": "[project]/a.js",
+            "size": 10,
+            "chunks": ["index.js"],
+            "issuerPath": [{
+                "id": 0,
+                "identifier": "[project]/index.js",
+                "name": "[project]/index.js",
+            }],
+            "reasons": [{
+                "moduleId": 0,
+                "moduleIdentifier": "[project]/index.js",
+                "moduleName": "[project]/index.js",
+                "type": "import",
+            }],
+        });
+        assert_eq!(
+            webpack_stats(&stats),
+            json!({
+                "assets": [
+                    {
+                        "name": "index.js",
+                        "size": 20,
+                        "chunks": ["index.js"],
+                        "chunkNames": [],
+                        "emitted": true,
+                    },
+                    {
+                        "name": "logo.svg",
+                        "size": 5,
+                        "chunks": [],
+                        "chunkNames": [],
+                        "emitted": true,
+                    },
+                ],
+                "chunks": [{
+                    "id": "index.js",
+                    "names": [],
+                    "files": ["index.js"],
+                    "size": 20,
+                    "entry": true,
+                    "modules": [index_module, a_module],
+                }],
+                "modules": [index_module, a_module],
+            })
+        );
+    }
+
+    #[test]
+    fn test_webpack_stats_unknown_modules() {
+        // Chunks can list modules that aren't in the module graph, and
+        // reasons can come from them
+        let stats = Stats {
+            output_assets: vec![OutputAssetStats {
+                path: "index.js".to_string(),
+                ty: "ecmascript chunk".to_string(),
+                size: 20,
+                modules: vec![
+                    "[project]/index.js".to_string(),
+                    "[project]/b.js".to_string(),
+                ],
+            }],
+            modules: vec![module(
+                "[project]/index.js",
+                false,
+                &["index.js"],
+                &["[project]/b.js"],
+            )],
+            duplicate_packages: vec![],
+        };
+
+        let stats = webpack_stats(&stats);
+        assert_eq!(stats["chunks"][0]["entry"], false);
+        assert_eq!(stats["chunks"][0]["modules"].as_array().unwrap().len(), 1);
+        assert_eq!(stats["modules"][0]["reasons"][0]["moduleId"], json!(null));
+        assert_eq!(stats["modules"][0]["issuerPath"][0]["id"], json!(null));
+    }
+}
@@ -0,0 +1,484 @@
+//! `nxpkgpack.config.json`, read from the project directory. The file is read
+//! through the project filesystem, so `dev` picks up changes to it
+//! incrementally.
+
+use anyhow::Result;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use nxpkg_tasks::{trace::TraceRawVcs, Vc};
+use nxpkg_tasks_fs::{
+    glob::Glob, json::parse_json_rope_with_source_context, FileContent, FileSystemPath,
+};
+use nxpkgpack::{
+    css::{CssInputTransform, CssModuleAssetType},
+    module_options::{
+        LoaderRuleItem, ModuleRule, ModuleRuleCondition, ModuleRuleEffect, ModuleType,
+        PostCssTransformOptions, WebpackLoadersOptions,
+    },
+};
+use nxpkgpack_core::{
+    compile_time_info::CompileTimeDefineValue,
+    error::PrettyPrintError,
+    issue::{Issue, IssueExt, StyledString},
+    resolve::options::{ImportMap, ImportMapping},
+};
+use nxpkgpack_node::transforms::webpack::WebpackLoaderItem;
+
+pub const CONFIG_FILE_NAME: &str = "nxpkgpack.config.json";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+#[nxpkg_tasks::value(serialization = "custom")]
+pub struct NxpkgpackConfig {
+    pub resolve: ResolveConfig,
+    /// Compile-time defines, keyed by the dotted expression they replace, e.
+    /// g. `process.env.API_URL`.
+    #[nxpkg_tasks(trace_ignore)]
+    pub define: IndexMap<String, serde_json::Value>,
+    /// The browserslist query used for preset-env and as the target
+    /// environment. Overrides the CLI's default query.
+    pub browserslist: Option<String>,
+    pub postcss: PostCssConfig,
+    /// Webpack loaders to apply, keyed by a glob. Globs without a `/` match
+    /// the file name, others match the path relative to the project.
+    pub webpack_loaders: IndexMap<String, LoaderRuleConfig>,
+    /// Module types for matching files. Later rules take precedence over
+    /// earlier ones and over the built-in rules.
+    pub rules: Vec<RuleConfig>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, TraceRawVcs, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResolveConfig {
+    /// Aliases from a request to one or more alternatives, which are resolved
+    /// from the project directory. A `*` in both the request and the
+    /// alternatives captures the rest of the request.
+    pub alias: IndexMap<String, AliasTarget>,
+    /// Conditions for `exports` and `imports` fields in addition to
+    /// `development`.
+    pub conditions: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, TraceRawVcs, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AliasTarget {
+    Single(String),
+    Alternatives(Vec<String>),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, TraceRawVcs, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PostCssConfig {
+    /// The request of the `postcss` package, resolved from the project
+    /// directory.
+    pub package: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, TraceRawVcs, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoaderRuleConfig {
+    pub loaders: Vec<LoaderConfig>,
+    /// Renames the output of the loaders, e. g. `*.js`, which decides how it
+    /// is processed further.
+    #[serde(default, rename = "as")]
+    pub rename_as: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, TraceRawVcs, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LoaderConfig {
+    Name(String),
+    WithOptions {
+        loader: String,
+        #[serde(default)]
+        #[nxpkg_tasks(trace_ignore)]
+        options: serde_json::Map<String, serde_json::Value>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, TraceRawVcs, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    /// A glob, matched like the keys of `webpackLoaders`.
+    pub test: String,
+    #[serde(rename = "type")]
+    pub ty: RuleModuleType,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TraceRawVcs, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RuleModuleType {
+    Json,
+    Raw,
+    Static,
+    Css,
+    CssModule,
+}
+
+impl From<RuleModuleType> for ModuleType {
+    fn from(ty: RuleModuleType) -> Self {
+        match ty {
+            RuleModuleType::Json => ModuleType::Json,
+            RuleModuleType::Raw => ModuleType::Raw,
+            RuleModuleType::Static => ModuleType::Static,
+            RuleModuleType::Css => ModuleType::CssGlobal,
+            RuleModuleType::CssModule => ModuleType::CssModule,
+        }
+    }
+}
+
+impl NxpkgpackConfig {
+    pub fn insert_aliases(&self, import_map: &mut ImportMap, project_path: Vc<FileSystemPath>) {
+        for (request, target) in &self.resolve.alias {
+            let alternatives = match target {
+                AliasTarget::Single(alternative) => vec![alternative.clone()],
+                AliasTarget::Alternatives(alternatives) => alternatives.clone(),
+            };
+            let mapping =
+                ImportMapping::primary_alternatives(alternatives, Some(project_path)).cell();
+            match request.split_once('*') {
+                Some((prefix, suffix)) => {
+                    import_map.insert_wildcard_alias_with_suffix(prefix, suffix, mapping)
+                }
+                None => import_map.insert_exact_alias(request, mapping),
+            }
+        }
+    }
+
+    pub fn defines(&self) -> impl Iterator<Item = (Vec<String>, CompileTimeDefineValue)> + '_ {
+        self.define.iter().map(|(name, value)| {
+            let value = match value {
+                serde_json::Value::Bool(value) => CompileTimeDefineValue::Bool(*value),
+                serde_json::Value::String(value) => CompileTimeDefineValue::String(value.clone()),
+                value => value.clone().into(),
+            };
+            (name.split('.').map(str::to_string).collect(), value)
+        })
+    }
+
+    pub fn postcss_transform_options(
+        &self,
+        project_path: Vc<FileSystemPath>,
+    ) -> PostCssTransformOptions {
+        PostCssTransformOptions {
+            postcss_package: self.postcss.package.as_ref().map(|package| {
+                ImportMapping::PrimaryAlternative(package.clone(), Some(project_path)).cell()
+            }),
+            ..Default::default()
+        }
+    }
+
+    pub fn webpack_loaders_options(&self) -> Option<Vc<WebpackLoadersOptions>> {
+        if self.webpack_loaders.is_empty() {
+            return None;
+        }
+        let rules = self
+            .webpack_loaders
+            .iter()
+            .map(|(glob, rule)| {
+                let loaders = rule
+                    .loaders
+                    .iter()
+                    .map(|loader| match loader {
+                        LoaderConfig::Name(loader) => WebpackLoaderItem {
+                            loader: loader.clone(),
+                            options: Default::default(),
+                        },
+                        LoaderConfig::WithOptions { loader, options } => WebpackLoaderItem {
+                            loader: loader.clone(),
+                            options: options.clone(),
+                        },
+                    })
+                    .collect();
+                let item = LoaderRuleItem {
+                    loaders: Vc::cell(loaders),
+                    rename_as: rule.rename_as.clone(),
+                };
+                (glob.clone(), item)
+            })
+            .collect();
+        Some(
+            WebpackLoadersOptions {
+                rules: Vc::cell(rules),
+                loader_runner_package: None,
+            }
+            .cell(),
+        )
+    }
+
+    /// Creates the module rules for `rules`. An invalid glob is reported as
+    /// an issue and its rule is skipped.
+    pub async fn module_rules(&self, project_path: Vc<FileSystemPath>) -> Result<Vec<ModuleRule>> {
+        let mut rules = Vec::new();
+        for rule in &self.rules {
+            let glob = match Glob::new(rule.test.clone()).await {
+                Ok(glob) => glob,
+                Err(err) => {
+                    NxpkgpackConfigIssue {
+                        path: project_path.join(CONFIG_FILE_NAME.to_string()),
+                        error_message: format!(
+                            "Invalid glob `{}` in `rules`: {}",
+                            rule.test,
+                            PrettyPrintError(&err)
+                        ),
+                    }
+                    .cell()
+                    .emit();
+                    continue;
+                }
+            };
+            let condition = if !rule.test.contains('/') {
+                ModuleRuleCondition::ResourceBasePathGlob(glob)
+            } else {
+                ModuleRuleCondition::ResourcePathGlob {
+                    base: project_path.await?,
+                    glob,
+                }
+            };
+            // The CSS module types reference the file itself again with an
+            // internal reference, which needs to match a plain CSS rule.
+            let internal_css = match rule.ty {
+                RuleModuleType::Css => Some(CssModuleAssetType::Default),
+                RuleModuleType::CssModule => Some(CssModuleAssetType::Module),
+                RuleModuleType::Json | RuleModuleType::Raw | RuleModuleType::Static => None,
+            };
+            rules.push(ModuleRule::new(
+                condition.clone(),
+                vec![ModuleRuleEffect::ModuleType(rule.ty.into())],
+            ));
+            if let Some(ty) = internal_css {
+                rules.push(ModuleRule::new_internal(
+                    condition,
+                    vec![ModuleRuleEffect::ModuleType(ModuleType::Css {
+                        ty,
+                        transforms: Vc::cell(vec![CssInputTransform::Nested]),
+                    })],
+                ));
+            }
+        }
+        Ok(rules)
+    }
+}
+
+/// Reads `nxpkgpack.config.json` from the project directory. A missing file
+/// is the default config, an invalid one is reported as an issue.
+#[nxpkg_tasks::function]
+pub async fn load_nxpkgpack_config(
+    project_path: Vc<FileSystemPath>,
+) -> Result<Vc<NxpkgpackConfig>> {
+    let path = project_path.join(CONFIG_FILE_NAME.to_string());
+    let FileContent::Content(file) = &*path.read().await? else {
+        return Ok(NxpkgpackConfig::default().cell());
+    };
+    match parse_json_rope_with_source_context::<NxpkgpackConfig>(file.content()) {
+        Ok(config) => Ok(config.cell()),
+        Err(err) => {
+            NxpkgpackConfigIssue {
+                path,
+                error_message: err.to_string(),
+            }
+            .cell()
+            .emit();
+            Ok(NxpkgpackConfig::default().cell())
+        }
+    }
+}
+
+#[nxpkg_tasks::value(shared)]
+struct NxpkgpackConfigIssue {
+    path: Vc<FileSystemPath>,
+    error_message: String,
+}
+
+#[nxpkg_tasks::value_impl]
+impl Issue for NxpkgpackConfigIssue {
+    #[nxpkg_tasks::function]
+    fn title(&self) -> Vc<String> {
+        Vc::cell(format!("Invalid {CONFIG_FILE_NAME}"))
+    }
+
+    #[nxpkg_tasks::function]
+    fn category(&self) -> Vc<String> {
+        Vc::cell("config".to_string())
+    }
+
+    #[nxpkg_tasks::function]
+    fn file_path(&self) -> Vc<FileSystemPath> {
+        self.path
+    }
+
+    #[nxpkg_tasks::function]
+    fn description(&self) -> Vc<StyledString> {
+        StyledString::Text(self.error_message.clone()).cell()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nxpkg_tasks_fs::{FileSystem, VirtualFileSystem};
+    use nxpkgpack_core::resolve::{options::ImportMapResult, parse::Request};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config: NxpkgpackConfig = serde_json::from_str(
+            r#"{
+                "resolve": {
+                    "alias": { "lodash": "lodash-es", "@/*": ["./src/*", "./lib/*"] },
+                    "conditions": ["browser"]
+                },
+                "define": { "process.env.API_URL": "https://example.com" },
+                "browserslist": "last 1 chrome version",
+                "webpackLoaders": {
+                    "*.svg": { "loaders": ["@svgr/webpack"], "as": "*.js" },
+                    "*.md": { "loaders": [{ "loader": "md-loader", "options": { "a": 1 } }] }
+                },
+                "rules": [{ "test": "*.txt", "type": "raw" }, { "test": "src/*.css", "type": "css-module" }]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.resolve.alias["lodash"],
+            AliasTarget::Single("lodash-es".to_string())
+        );
+        assert_eq!(
+            config.resolve.alias["@/*"],
+            AliasTarget::Alternatives(vec!["./src/*".to_string(), "./lib/*".to_string()])
+        );
+        assert_eq!(config.resolve.conditions, vec!["browser".to_string()]);
+        assert_eq!(
+            config.browserslist.as_deref(),
+            Some("last 1 chrome version")
+        );
+        assert_eq!(
+            config.webpack_loaders["*.svg"],
+            LoaderRuleConfig {
+                loaders: vec![LoaderConfig::Name("@svgr/webpack".to_string())],
+                rename_as: Some("*.js".to_string()),
+            }
+        );
+        let LoaderConfig::WithOptions { loader, options } =
+            &config.webpack_loaders["*.md"].loaders[0]
+        else {
+            panic!("expected a loader with options");
+        };
+        assert_eq!(loader, "md-loader");
+        assert_eq!(options["a"], 1);
+        assert_eq!(
+            config.rules,
+            vec![
+                RuleConfig {
+                    test: "*.txt".to_string(),
+                    ty: RuleModuleType::Raw,
+                },
+                RuleConfig {
+                    test: "src/*.css".to_string(),
+                    ty: RuleModuleType::CssModule,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_config_unknown_field() {
+        assert!(serde_json::from_str::<NxpkgpackConfig>(r#"{ "alias": {} }"#).is_err());
+        assert!(
+            serde_json::from_str::<NxpkgpackConfig>(r#"{ "rules": [{ "test": "*.txt" }] }"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_defines() {
+        let config: NxpkgpackConfig = serde_json::from_str(
+            r#"{
+                "define": {
+                    "DEBUG": false,
+                    "process.env.API_URL": "https://example.com",
+                    "process.env.RETRIES": 3
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let defines = config.defines().collect::<Vec<_>>();
+        assert_eq!(
+            defines,
+            vec![
+                (
+                    vec!["DEBUG".to_string()],
+                    CompileTimeDefineValue::Bool(false)
+                ),
+                (
+                    vec![
+                        "process".to_string(),
+                        "env".to_string(),
+                        "API_URL".to_string()
+                    ],
+                    CompileTimeDefineValue::String("https://example.com".to_string())
+                ),
+                (
+                    vec![
+                        "process".to_string(),
+                        "env".to_string(),
+                        "RETRIES".to_string()
+                    ],
+                    serde_json::json!(3).into()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insert_aliases() {
+        crate::register();
+
+        nxpkg_tasks_testing::VcStorage::with(async {
+            let config: NxpkgpackConfig = serde_json::from_str(
+                r#"{
+                    "resolve": {
+                        "alias": {
+                            "lodash": "lodash-es",
+                            "@/*": "./src/*",
+                            "icons/*.svg": "./assets/icons/*.svg"
+                        }
+                    }
+                }"#,
+            )
+            .unwrap();
+            let project_path = Vc::upcast::<Box<dyn FileSystem>>(VirtualFileSystem::new()).root();
+            let mut import_map = ImportMap::empty();
+            config.insert_aliases(&mut import_map, project_path);
+
+            let lookup = |request: &str| {
+                let import_map = &import_map;
+                let request = Request::parse_string(request.to_string());
+                async move {
+                    match import_map.lookup(project_path, request).await? {
+                        ImportMapResult::Alias(request, _) => anyhow::Ok(request.await?.request()),
+                        ImportMapResult::NoEntry => Ok(None),
+                        result => panic!("unexpected import map result {result:?}"),
+                    }
+                }
+            };
+
+            assert_eq!(lookup("lodash").await?.as_deref(), Some("lodash-es"));
+            assert_eq!(lookup("lodash/fp").await?, None);
+            assert_eq!(
+                lookup("@/components/button").await?.as_deref(),
+                Some("./src/components/button")
+            );
+            assert_eq!(
+                lookup("icons/arrow.svg").await?.as_deref(),
+                Some("./assets/icons/arrow.svg")
+            );
+            assert_eq!(lookup("icons/arrow.png").await?, None);
+
+            anyhow::Ok(())
+        })
+        .await
+        .unwrap()
+    }
+}
@@ -24,6 +24,8 @@ use nxpkgpack_ecmascript_plugins::transform::{
 };
 use nxpkgpack_node::execution_context::ExecutionContext;
 
+use crate::config::{load_nxpkgpack_config, NxpkgpackConfig};
+
 #[nxpkg_tasks::value(shared)]
 pub enum NodeEnv {
     Development,
@@ -45,6 +47,7 @@ async fn foreign_code_context_condition() -> Result<ContextCondition> {
 
 #[nxpkg_tasks::function]
 pub async fn get_client_import_map(project_path: Vc<FileSystemPath>) -> Result<Vc<ImportMap>> {
+    let config = load_nxpkgpack_config(project_path).await?;
     let mut import_map = ImportMap::empty();
 
     import_map.insert_singleton_alias("@swc/helpers", project_path);
@@ -61,6 +64,9 @@ pub async fn get_client_import_map(project_path: Vc<FileSystemPath>) -> Result<V
         .cell(),
     );
 
+    // Inserted last, so that they take precedence over the singleton aliases
+    config.insert_aliases(&mut import_map, project_path);
+
     Ok(import_map.cell())
 }
 
@@ -68,10 +74,13 @@ pub async fn get_client_import_map(project_path: Vc<FileSystemPath>) -> Result<V
 pub async fn get_client_resolve_options_context(
     project_path: Vc<FileSystemPath>,
 ) -> Result<Vc<ResolveOptionsContext>> {
+    let config = load_nxpkgpack_config(project_path).await?;
     let next_client_import_map = get_client_import_map(project_path);
+    let mut custom_conditions = vec!["development".to_string()];
+    custom_conditions.extend(config.resolve.conditions.iter().cloned());
     let module_options_context = ResolveOptionsContext {
         enable_node_modules: Some(project_path.root().resolve().await?),
-        custom_conditions: vec!["development".to_string()],
+        custom_conditions,
         import_map: Some(next_client_import_map),
         browser: true,
         module: true,
@@ -96,9 +105,11 @@ async fn get_client_module_options_context(
     env: Vc<Environment>,
     node_env: Vc<NodeEnv>,
 ) -> Result<Vc<ModuleOptionsContext>> {
+    let config = load_nxpkgpack_config(project_path).await?;
     let module_options_context = ModuleOptionsContext {
         preset_env_versions: Some(env),
         execution_context: Some(execution_context),
+        custom_rules: config.module_rules(project_path).await?,
         ..Default::default()
     };
 
@@ -135,7 +146,8 @@ async fn get_client_module_options_context(
 
     let module_options_context = ModuleOptionsContext {
         enable_jsx,
-        enable_postcss_transform: Some(Default::default()),
+        enable_postcss_transform: Some(config.postcss_transform_options(project_path)),
+        enable_webpack_loaders: config.webpack_loaders_options(),
         enable_typescript_transform: Some(Default::default()),
         rules: vec![(
             foreign_code_context_condition().await?,
@@ -175,20 +187,24 @@ pub fn get_client_asset_context(
     asset_context
 }
 
-fn client_defines(node_env: &NodeEnv) -> Vc<CompileTimeDefines> {
-    compile_time_defines!(
+fn client_defines(node_env: &NodeEnv, config: &NxpkgpackConfig) -> Vc<CompileTimeDefines> {
+    let mut defines = compile_time_defines!(
         process.nxpkgpack = true,
         process.env.NXPKGPACK = true,
         process.env.NODE_ENV = node_env.to_string()
-    )
-    .cell()
+    );
+    defines.0.extend(config.defines());
+    defines.cell()
 }
 
 #[nxpkg_tasks::function]
 pub async fn get_client_compile_time_info(
+    project_path: Vc<FileSystemPath>,
     browserslist_query: String,
     node_env: Vc<NodeEnv>,
 ) -> Result<Vc<CompileTimeInfo>> {
+    let config = load_nxpkgpack_config(project_path).await?;
+    let browserslist_query = config.browserslist.clone().unwrap_or(browserslist_query);
     Ok(
         CompileTimeInfo::builder(Environment::new(Value::new(ExecutionEnvironment::Browser(
             BrowserEnvironment {
@@ -199,7 +215,7 @@ pub async fn get_client_compile_time_info(
             }
             .into(),
         ))))
-        .defines(client_defines(&*node_env.await?))
+        .defines(client_defines(&*node_env.await?, &config))
         .cell(),
     )
 }
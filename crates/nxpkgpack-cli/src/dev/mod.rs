@@ -3,7 +3,7 @@ use std::{
     env::current_dir,
     future::{join, Future},
     io::{stdout, Write},
-    net::{IpAddr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
     path::{PathBuf, MAIN_SEPARATOR},
     sync::Arc,
     time::{Duration, Instant},
@@ -33,6 +33,7 @@ use nxpkgpack_dev_server::{
         combined::CombinedContentSource, router::PrefixedRouterContentSource,
         static_assets::StaticAssetsContentSource, ContentSource,
     },
+    tls::{is_local_host, LocalCertificateAuthority, TlsCertificate, TlsConfig},
     DevServer, DevServerBuilder,
 };
 use nxpkgpack_env::dotenv::load_env;
@@ -64,6 +65,8 @@ pub struct NxpkgpackDevServerBuilder {
     show_all: bool,
     log_detail: bool,
     allow_retry: bool,
+    https: bool,
+    https_certificate: Option<(PathBuf, PathBuf)>,
 }
 
 impl NxpkgpackDevServerBuilder {
@@ -88,6 +91,8 @@ impl NxpkgpackDevServerBuilder {
             show_all: false,
             log_detail: false,
             allow_retry: false,
+            https: false,
+            https_certificate: None,
         }
     }
 
@@ -136,6 +141,20 @@ impl NxpkgpackDevServerBuilder {
         self
     }
 
+    /// Serves HTTPS, with a generated certificate unless one is set with
+    /// [Self::https_certificate].
+    pub fn https(mut self, https: bool) -> NxpkgpackDevServerBuilder {
+        self.https = https;
+        self
+    }
+
+    /// Serves HTTPS with the certificate chain and private key in PEM files.
+    pub fn https_certificate(mut self, cert: PathBuf, key: PathBuf) -> NxpkgpackDevServerBuilder {
+        self.https = true;
+        self.https_certificate = Some((cert, key));
+        self
+    }
+
     pub fn issue_reporter(
         mut self,
         issue_reporter: Box<dyn IssueReporterProvider>,
@@ -188,7 +207,24 @@ impl NxpkgpackDevServerBuilder {
         let port = self.port.context("port must be set")?;
         let host = self.hostname.context("hostname must be set")?;
 
-        let server = self.find_port(host, port, 10)?;
+        let mut server = self.find_port(host, port, 10)?;
+        if self.https {
+            let certificate = match self.https_certificate {
+                Some((cert, key)) => TlsCertificate::Files { cert, key },
+                None => TlsCertificate::SelfSigned {
+                    ca_dir: dirs_next::data_local_dir()
+                        .context("unable to find the local data directory")?
+                        .join("nxpkgpack")
+                        .join("certificates"),
+                    hosts: certificate_hosts(host),
+                },
+            };
+            let (tls, ca) = TlsConfig::new(&certificate).context("unable to set up HTTPS")?;
+            if let Some(ca) = ca {
+                check_certificate_authority(&ca);
+            }
+            server = server.tls(tls);
+        }
 
         let nxpkg_tasks = self.nxpkg_tasks;
         let project_dir = self.project_dir;
@@ -227,6 +263,70 @@ impl NxpkgpackDevServerBuilder {
     }
 }
 
+/// Trusts a newly created certificate authority. Browsers warn about the
+/// certificate as long as it isn't trusted, so that's repeated on every start.
+fn check_certificate_authority(ca: &LocalCertificateAuthority) {
+    let path = ca.certificate_path.display();
+    if ca.created {
+        println!(
+            "{} - created a local certificate authority at {path}",
+            "event".purple(),
+        );
+        if let Err(err) = ca.trust() {
+            println!(
+                "{} - the certificate authority is not trusted, browsers will warn about the \
+                 certificate: {err:#}",
+                "warn ".yellow(),
+            );
+        }
+        return;
+    }
+    match ca.is_trusted() {
+        Some(true) => {}
+        Some(false) => println!(
+            "{} - the certificate authority at {path} is not trusted, browsers will warn about \
+             the certificate. Remove it to create and trust a new one",
+            "warn ".yellow(),
+        ),
+        None => println!(
+            "{} - using the local certificate authority at {path}. Browsers will warn about the \
+             certificate unless it's added to the certificates of your system or browser",
+            "warn ".yellow(),
+        ),
+    }
+}
+
+/// The hosts the generated HTTPS certificate is valid for. When the server
+/// listens on all interfaces, the address in the local network is included,
+/// so the server can be opened from other devices. Public addresses are left
+/// out, as the local certificate authority can't sign certificates for them.
+fn certificate_hosts(host: IpAddr) -> Vec<String> {
+    let mut hosts = vec![
+        "localhost".to_string(),
+        Ipv4Addr::LOCALHOST.to_string(),
+        Ipv6Addr::LOCALHOST.to_string(),
+    ];
+    let local_network_ip = if host.is_unspecified() {
+        // Connecting a UDP socket doesn't send anything, but selects the
+        // interface that routes to the address
+        UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+            .and_then(|socket| {
+                socket.connect((Ipv4Addr::new(192, 0, 2, 1), 80))?;
+                socket.local_addr()
+            })
+            .ok()
+            .map(|addr| addr.ip())
+    } else {
+        Some(host)
+    };
+    if let Some(ip) =
+        local_network_ip.filter(|ip| !ip.is_loopback() && is_local_host(&ip.to_string()))
+    {
+        hosts.push(ip.to_string());
+    }
+    hosts
+}
+
 #[nxpkg_tasks::function]
 async fn source(
     root_dir: String,
@@ -363,10 +463,17 @@ pub async fn start_server(args: &DevArguments) -> Result<()> {
         server = server.allow_retry(args.allow_retry);
     }
 
+    server = match (&args.https_cert, &args.https_key) {
+        (Some(cert), Some(key)) => server.https_certificate(cert.clone(), key.clone()),
+        _ => server.https(args.https),
+    };
+
     let server = server.build().await?;
 
     {
-        let index_uri = ServerAddr::new(server.addr).to_string()?;
+        let index_uri = ServerAddr::new(server.addr)
+            .with_protocol(server.protocol)
+            .to_string()?;
         println!(
             "{} - started server on {}, url: {}",
             "ready".green(),
@@ -505,3 +612,48 @@ where
         self()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use super::certificate_hosts;
+
+    #[test]
+    fn test_certificate_hosts_loopback() {
+        assert_eq!(
+            certificate_hosts(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            vec!["localhost", "127.0.0.1", "::1"]
+        );
+        assert_eq!(
+            certificate_hosts(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+            vec!["localhost", "127.0.0.1", "::1"]
+        );
+    }
+
+    #[test]
+    fn test_certificate_hosts_specific_address() {
+        let host = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20));
+        assert_eq!(
+            certificate_hosts(host),
+            vec!["localhost", "127.0.0.1", "::1", "192.168.1.20"]
+        );
+        assert_eq!(
+            certificate_hosts(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7))),
+            vec!["localhost", "127.0.0.1", "::1"]
+        );
+    }
+
+    #[test]
+    fn test_certificate_hosts_all_interfaces() {
+        let hosts = certificate_hosts(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        assert_eq!(&hosts[..3], ["localhost", "127.0.0.1", "::1"]);
+        // The address in the local network depends on the machine, if there
+        // is one at all
+        assert!(hosts.len() <= 4);
+        for host in &hosts[3..] {
+            let ip = host.parse::<IpAddr>().unwrap();
+            assert!(!ip.is_loopback() && !ip.is_unspecified());
+        }
+    }
+}
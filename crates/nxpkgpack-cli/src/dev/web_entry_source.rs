@@ -89,7 +89,8 @@ pub async fn create_web_entry_source(
     node_env: Vc<NodeEnv>,
     browserslist_query: String,
 ) -> Result<Vc<Box<dyn ContentSource>>> {
-    let compile_time_info = get_client_compile_time_info(browserslist_query, node_env);
+    let compile_time_info =
+        get_client_compile_time_info(project_path, browserslist_query, node_env);
     let asset_context =
         get_client_asset_context(project_path, execution_context, compile_time_info, node_env);
     let chunking_context =
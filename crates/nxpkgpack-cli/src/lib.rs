@@ -4,6 +4,7 @@
 
 pub mod arguments;
 pub mod build;
+pub(crate) mod config;
 pub(crate) mod contexts;
 pub mod dev;
 pub(crate) mod embed_js;
@@ -17,11 +17,20 @@ static DEFAULT_NODEJS_VERSION: &str = "16.0.0";
 
 #[nxpkg_tasks::value(shared)]
 #[derive(Default)]
-pub struct ServerAddr(#[nxpkg_tasks(trace_ignore)] Option<SocketAddr>);
+pub struct ServerAddr(
+    #[nxpkg_tasks(trace_ignore)] Option<SocketAddr>,
+    #[nxpkg_tasks(trace_ignore)] Option<Protocol>,
+);
 
 impl ServerAddr {
     pub fn new(addr: SocketAddr) -> Self {
-        Self(Some(addr))
+        Self(Some(addr), None)
+    }
+
+    /// Sets the protocol the server is serving, instead of inferring it from
+    /// the port.
+    pub fn with_protocol(self, protocol: Protocol) -> Self {
+        Self(self.0, Some(protocol))
     }
 
     /// The hostname portion of the address, without the port. Prefers
@@ -48,15 +57,21 @@ impl ServerAddr {
         self.0.map(|addr| addr.port())
     }
 
+    /// The protocol of the server. Unless it was set, HTTPS is assumed for
+    /// port 443 and HTTP otherwise.
+    pub fn protocol(&self) -> Option<Protocol> {
+        self.1.or_else(|| self.port().map(Protocol::from))
+    }
+
     /// Constructs a URL out of the address.
     pub fn to_string(&self) -> Result<String> {
         let (hostname, port) = self
             .hostname()
             .zip(self.port())
             .context("expected some server address")?;
-        let protocol = Protocol::from(port);
-        Ok(match port {
-            80 | 443 => format!("{protocol}://{hostname}"),
+        let protocol = self.protocol().context("expected some server address")?;
+        Ok(match (protocol, port) {
+            (Protocol::HTTP, 80) | (Protocol::HTTPS, 443) => format!("{protocol}://{hostname}"),
             _ => format!("{protocol}://{hostname}:{port}"),
         })
     }
@@ -66,7 +81,7 @@ impl ServerAddr {
 impl ServerAddr {
     #[nxpkg_tasks::function]
     pub fn empty() -> Vc<Self> {
-        ServerAddr(None).cell()
+        ServerAddr(None, None).cell()
     }
 }
 
@@ -84,7 +99,7 @@ pub struct ServerInfo {
     pub hostname: String,
 }
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Protocol {
     HTTP,
@@ -121,7 +136,7 @@ impl TryFrom<&ServerAddr> for ServerInfo {
             ip: addr.ip().unwrap(),
             hostname: addr.hostname().unwrap(),
             port,
-            protocol: Protocol::from(port),
+            protocol: addr.protocol().unwrap(),
         })
     }
 }
@@ -1,6 +1,7 @@
 pub mod module;
 pub mod output_asset;
 pub mod source;
+pub mod stats;
 pub mod utils;
 
 use indexmap::IndexSet;
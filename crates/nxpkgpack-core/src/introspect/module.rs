@@ -18,6 +18,11 @@ impl IntrospectableModule {
             .await?
             .unwrap_or_else(|| Vc::upcast(IntrospectableModule(asset).cell())))
     }
+
+    #[nxpkg_tasks::function]
+    pub fn module(&self) -> Vc<Box<dyn Module>> {
+        self.0
+    }
 }
 
 #[nxpkg_tasks::function]
@@ -0,0 +1,410 @@
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::Result;
+use indexmap::{IndexMap, IndexSet};
+use serde::{Deserialize, Serialize};
+use nxpkg_tasks::{trace::TraceRawVcs, ValueToString, Vc};
+use nxpkg_tasks_fs::{FileContent, FileSystemPath};
+
+use super::{
+    module::IntrospectableModule, output_asset::IntrospectableOutputAsset, Introspectable,
+};
+use crate::{
+    asset::{Asset, AssetContent},
+    module::{Module, Modules},
+    output::{OutputAsset, OutputAssets},
+    package_json::read_package_json,
+    source_map::{GenerateSourceMap, Token},
+    SOURCE_MAP_ROOT_NAME,
+};
+
+/// A description of the output of a build, collected by walking the
+/// introspection graph of the output assets and the modules in them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[nxpkg_tasks::value(serialization = "custom")]
+pub struct Stats {
+    pub output_assets: Vec<OutputAssetStats>,
+    pub modules: Vec<ModuleStats>,
+    /// Packages that are bundled from more than one directory.
+    pub duplicate_packages: Vec<DuplicatePackage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputAssetStats {
+    /// The path relative to the output root.
+    pub path: String,
+    /// The introspection type, e. g. `css chunk`.
+    pub ty: String,
+    pub size: u64,
+    /// The idents of the modules in the asset, if it is a chunk.
+    pub modules: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleStats {
+    pub ident: String,
+    pub entry: bool,
+    /// The size of the module's source.
+    pub original_size: u64,
+    /// The size of the module's code in all output assets, after minification
+    /// if it is enabled. It's attributed through the source maps of the
+    /// output assets, so it's an estimate.
+    pub output_size: u64,
+    /// The paths of the output assets that contain the module.
+    pub chunks: Vec<String>,
+    /// The modules that reference this module.
+    pub reasons: Vec<ModuleReason>,
+    /// The shortest chain of modules from an entry to this module, starting
+    /// with the entry and ending with the module that references this one.
+    pub reference_chain: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleReason {
+    pub module: String,
+    /// The introspection type of the reference, e. g. `async reference`.
+    pub ty: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicatePackage {
+    pub name: String,
+    pub copies: Vec<PackageCopy>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageCopy {
+    pub path: String,
+    pub version: Option<String>,
+}
+
+#[derive(Default)]
+struct ModuleNode {
+    entry: bool,
+    issuer: Option<Vc<Box<dyn Module>>>,
+    reasons: Vec<ModuleReason>,
+    chunks: Vec<String>,
+    output_size: u64,
+}
+
+/// Collects [Stats] for the `output_assets` of a build. `entries` are the
+/// entry modules, the module graph is walked from them to find out why each
+/// module was included.
+#[nxpkg_tasks::function]
+pub async fn collect_stats(
+    output_root: Vc<FileSystemPath>,
+    entries: Vc<Modules>,
+    output_assets: Vc<OutputAssets>,
+) -> Result<Vc<Stats>> {
+    let entries = entries.await?;
+    let mut graph: IndexMap<Vc<Box<dyn Module>>, ModuleNode> = IndexMap::new();
+    let mut queue = VecDeque::new();
+    for &entry in entries.iter() {
+        let entry = entry.resolve().await?;
+        graph.insert(
+            entry,
+            ModuleNode {
+                entry: true,
+                ..Default::default()
+            },
+        );
+        queue.push_back(entry);
+    }
+    while let Some(module) = queue.pop_front() {
+        let ident = module.ident().to_string().await?;
+        for &(ty, child) in IntrospectableModule::new(module).children().await?.iter() {
+            let Some(child) = as_module(child).await? else {
+                continue;
+            };
+            let node = graph.entry(child).or_insert_with(|| {
+                queue.push_back(child);
+                ModuleNode {
+                    issuer: Some(module),
+                    ..Default::default()
+                }
+            });
+            node.reasons.push(ModuleReason {
+                module: ident.clone_value(),
+                ty: ty.await?.clone_value(),
+            });
+        }
+    }
+
+    // Sorted by path, so that the stats don't depend on the order of the
+    // output assets
+    let output_root = output_root.await?;
+    let mut assets = Vec::new();
+    for &asset in output_assets.await?.iter() {
+        let path = asset.ident().path();
+        let path = match output_root.get_relative_path_to(&*path.await?) {
+            Some(path) => path,
+            None => path.to_string().await?.clone_value(),
+        };
+        assets.push((path, asset));
+    }
+    assets.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut output_asset_stats = Vec::new();
+    for (path, asset) in assets {
+        let introspectable = IntrospectableOutputAsset::new(asset);
+        let content = file_content(asset.content()).await?;
+        let source_sizes = match &content {
+            Some(content) => source_sizes(asset, content).await?,
+            None => HashMap::new(),
+        };
+
+        let mut modules = Vec::new();
+        for module in chunk_modules(introspectable).await? {
+            let node = graph.entry(module).or_default();
+            node.chunks.push(path.clone());
+            let source = format!(
+                "/{SOURCE_MAP_ROOT_NAME}/{}",
+                module.ident().path().to_string().await?
+            );
+            node.output_size += source_sizes.get(&source).copied().unwrap_or_default();
+            modules.push(module.ident().to_string().await?.clone_value());
+        }
+
+        output_asset_stats.push(OutputAssetStats {
+            path,
+            ty: introspectable.ty().await?.clone_value(),
+            size: content.map_or(0, |content| content.len() as u64),
+            modules,
+        });
+    }
+
+    let mut module_stats = Vec::new();
+    let mut packages: IndexMap<String, IndexMap<String, Option<String>>> = IndexMap::new();
+    for (&module, node) in &graph {
+        let mut reference_chain = Vec::new();
+        let mut issuer = node.issuer;
+        while let Some(module) = issuer {
+            reference_chain.push(module.ident().to_string().await?.clone_value());
+            issuer = graph.get(&module).and_then(|node| node.issuer);
+        }
+        reference_chain.reverse();
+
+        let path = module.ident().path();
+        if let Some((name, dir)) = package_of(&path.await?.path) {
+            if let indexmap::map::Entry::Vacant(entry) =
+                packages.entry(name).or_default().entry(dir.clone())
+            {
+                let package_json_path = path.root().join(format!("{dir}/package.json"));
+                let version = match &*read_package_json(package_json_path).await? {
+                    Some(package_json) => package_json["version"].as_str().map(str::to_string),
+                    None => None,
+                };
+                entry.insert(version);
+            }
+        }
+
+        module_stats.push(ModuleStats {
+            ident: module.ident().to_string().await?.clone_value(),
+            entry: node.entry,
+            original_size: file_content(module.content())
+                .await?
+                .map_or(0, |content| content.len() as u64),
+            output_size: node.output_size,
+            chunks: node.chunks.clone(),
+            reasons: node.reasons.clone(),
+            reference_chain,
+        });
+    }
+
+    let duplicate_packages = packages
+        .into_iter()
+        .filter(|(_, copies)| copies.len() > 1)
+        .map(|(name, copies)| DuplicatePackage {
+            name,
+            copies: copies
+                .into_iter()
+                .map(|(path, version)| PackageCopy { path, version })
+                .collect(),
+        })
+        .collect();
+
+    Ok(Stats {
+        output_assets: output_asset_stats,
+        modules: module_stats,
+        duplicate_packages,
+    }
+    .cell())
+}
+
+async fn as_module(
+    introspectable: Vc<Box<dyn Introspectable>>,
+) -> Result<Option<Vc<Box<dyn Module>>>> {
+    Ok(
+        match Vc::try_resolve_downcast_type::<IntrospectableModule>(introspectable).await? {
+            Some(module) => Some(module.module().resolve().await?),
+            None => None,
+        },
+    )
+}
+
+/// Returns the modules in a chunk. Chunks list their modules as `module` or
+/// `entry module` children, and output assets that wrap a chunk list it as a
+/// `chunk` child.
+async fn chunk_modules(
+    introspectable: Vc<Box<dyn Introspectable>>,
+) -> Result<IndexSet<Vc<Box<dyn Module>>>> {
+    let mut modules = IndexSet::new();
+    let mut stack = vec![introspectable];
+    while let Some(introspectable) = stack.pop() {
+        for &(key, child) in introspectable.children().await?.iter() {
+            match key.await?.as_str() {
+                "chunk" => stack.push(child),
+                "module" | "entry module" => {
+                    if let Some(module) = as_module(child).await? {
+                        modules.insert(module);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(modules)
+}
+
+async fn file_content(content: Vc<AssetContent>) -> Result<Option<Vec<u8>>> {
+    Ok(match &*content.await? {
+        AssetContent::File(file_content) => match &*file_content.await? {
+            FileContent::Content(file) => Some(file.content().to_bytes()?.into_owned()),
+            FileContent::NotFound => None,
+        },
+        AssetContent::Redirect { .. } => None,
+    })
+}
+
+/// Attributes the bytes of an output asset to the original sources in its
+/// source map.
+async fn source_sizes(
+    asset: Vc<Box<dyn OutputAsset>>,
+    content: &[u8],
+) -> Result<HashMap<String, u64>> {
+    let Some(generate_source_map) =
+        Vc::try_resolve_sidecast::<Box<dyn GenerateSourceMap>>(asset).await?
+    else {
+        return Ok(HashMap::new());
+    };
+    let Some(source_map) = *generate_source_map.generate_source_map().await? else {
+        return Ok(HashMap::new());
+    };
+    let tokens = source_map.await?.tokens().await?;
+    Ok(token_sizes(tokens, content))
+}
+
+/// Attributes the bytes of `content` to the original sources of the source
+/// map `tokens`. Every mapping is counted up to the next mapping on the same
+/// line, or the end of the line.
+fn token_sizes(mut tokens: Vec<Token>, content: &[u8]) -> HashMap<String, u64> {
+    let mut sizes = HashMap::new();
+    tokens.sort_by_key(|token| (token.generated_line(), token.generated_column()));
+
+    let line_lengths: Vec<usize> = content.split(|&b| b == b'\n').map(<[u8]>::len).collect();
+    for (i, token) in tokens.iter().enumerate() {
+        let Token::Original(original) = token else {
+            continue;
+        };
+        let Some(&line_length) = line_lengths.get(original.generated_line) else {
+            continue;
+        };
+        let end = match tokens.get(i + 1) {
+            Some(next) if next.generated_line() == original.generated_line => {
+                next.generated_column()
+            }
+            _ => line_length,
+        };
+        *sizes.entry(original.original_file.clone()).or_default() +=
+            end.min(line_length)
+                .saturating_sub(original.generated_column) as u64;
+    }
+    sizes
+}
+
+/// Returns the name of the package a path is in and the package's directory,
+/// if the path is inside of a `node_modules` directory.
+fn package_of(path: &str) -> Option<(String, String)> {
+    let (prefix, rest) = path.rsplit_once("node_modules/")?;
+    let mut segments = rest.splitn(3, '/');
+    let name = match segments.next()? {
+        scope if scope.starts_with('@') => format!("{scope}/{}", segments.next()?),
+        name => name.to_string(),
+    };
+    // Only files inside of the package directory belong to the package
+    segments.next()?;
+    let dir = format!("{prefix}node_modules/{name}");
+    Some((name, dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{package_of, token_sizes};
+    use crate::source_map::{OriginalToken, SyntheticToken, Token};
+
+    fn original(line: usize, column: usize, file: &str) -> Token {
+        Token::Original(OriginalToken {
+            generated_line: line,
+            generated_column: column,
+            original_file: file.to_string(),
+            original_line: 0,
+            original_column: 0,
+            name: None,
+        })
+    }
+
+    #[test]
+    fn test_package_of() {
+        assert_eq!(
+            package_of("node_modules/react/index.js"),
+            Some(("react".to_string(), "node_modules/react".to_string()))
+        );
+        assert_eq!(
+            package_of("app/node_modules/@scope/pkg/dist/index.js"),
+            Some((
+                "@scope/pkg".to_string(),
+                "app/node_modules/@scope/pkg".to_string()
+            ))
+        );
+        // The innermost node_modules directory decides the package
+        assert_eq!(
+            package_of("node_modules/a/node_modules/b/index.js"),
+            Some(("b".to_string(), "node_modules/a/node_modules/b".to_string()))
+        );
+        assert_eq!(package_of("src/index.js"), None);
+        assert_eq!(package_of("node_modules/react"), None);
+        assert_eq!(package_of("node_modules/@scope/pkg"), None);
+    }
+
+    #[test]
+    fn test_token_sizes() {
+        let content = b"aaaabbbbbb\ncccc\nddd";
+        let tokens = vec![
+            // Tokens are sorted by their position first
+            original(0, 4, "b.js"),
+            original(0, 0, "a.js"),
+            Token::Synthetic(SyntheticToken {
+                generated_line: 1,
+                generated_column: 2,
+            }),
+            original(1, 0, "a.js"),
+            // Columns past the end of the line are clamped
+            original(2, 1, "c.js"),
+            original(2, 10, "b.js"),
+            // Lines past the end of the content are ignored
+            original(5, 0, "c.js"),
+        ];
+
+        let sizes = token_sizes(tokens, content);
+        assert_eq!(sizes.len(), 3);
+        assert_eq!(sizes["a.js"], 4 + 2);
+        assert_eq!(sizes["b.js"], 6);
+        assert_eq!(sizes["c.js"], 2);
+    }
+}
@@ -13,6 +13,7 @@ mod module_asset;
 pub(crate) mod parse;
 mod path_visitor;
 pub(crate) mod references;
+mod sass;
 pub(crate) mod transform;
 pub(crate) mod util;
 
@@ -20,6 +21,7 @@ pub use asset::CssModuleAsset;
 pub use global_asset::GlobalCssAsset;
 pub use module_asset::ModuleCssAsset;
 pub use parse::{ParseCss, ParseCssResult};
+pub use sass::SassSource;
 use serde::{Deserialize, Serialize};
 pub use transform::{CssInputTransform, CssInputTransforms};
 use nxpkg_tasks::{trace::TraceRawVcs, TaskInput};
@@ -0,0 +1,611 @@
+//! Compiles Sass and SCSS stylesheets to CSS in-process with `grass`.
+//!
+//! Every stylesheet loaded with `@use`, `@forward` or `@import` is resolved
+//! through the nxpkgpack resolver before compiling, so aliases and packages
+//! work the same way they do for CSS, and every loaded file is a tracked
+//! dependency of the compilation. The rules are then pointed at the resolved
+//! files, and the compiler only sees those, served from memory.
+//!
+//! grass doesn't generate source maps, so none are generated for the compiled
+//! CSS either.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use grass::{Fs, Options, OutputStyle};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use nxpkg_tasks::{Value, Vc};
+use nxpkg_tasks_fs::{File, FileContent, FileSystemPath};
+use nxpkgpack_core::{
+    asset::{Asset, AssetContent},
+    context::AssetContext,
+    ident::AssetIdent,
+    issue::{Issue, IssueExt, StyledString},
+    reference_type::{CssReferenceSubType, ReferenceType},
+    resolve::{parse::Request, resolve, ResolveResult},
+    source::Source,
+};
+
+// Matches a rule loading other stylesheets along with its quoted URLs, or
+// the rest of the line if the first URL isn't quoted. `@import` can list
+// several URLs separated by commas.
+static IMPORT_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"@(use|forward|import)\s+(?:((?:(?:"[^"]*"|'[^']*')\s*,\s*)*(?:"[^"]*"|'[^']*'))|([^"'\s;][^\n;]*))"#,
+    )
+    .unwrap()
+});
+static URL_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r#""([^"]*)"|'([^']*)'"#).unwrap());
+// Matches the start of what follows the URL of a `@use` rule that sets its
+// own namespace
+static NAMESPACE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s+as\s").unwrap());
+
+const SASS_EXTENSIONS: [&str; 3] = ["scss", "sass", "css"];
+
+#[nxpkg_tasks::function]
+fn modifier() -> Vc<String> {
+    Vc::cell("sass".to_string())
+}
+
+#[nxpkg_tasks::value(transparent)]
+struct CompiledCss(Option<String>);
+
+/// The CSS compiled from a Sass or SCSS source.
+#[nxpkg_tasks::value]
+pub struct SassSource {
+    source: Vc<Box<dyn Source>>,
+    asset_context: Vc<Box<dyn AssetContext>>,
+}
+
+#[nxpkg_tasks::value_impl]
+impl SassSource {
+    /// Creates a source compiling `source`. Stylesheets it loads are resolved
+    /// with `asset_context`.
+    #[nxpkg_tasks::function]
+    pub fn new(source: Vc<Box<dyn Source>>, asset_context: Vc<Box<dyn AssetContext>>) -> Vc<Self> {
+        Self::cell(SassSource {
+            source,
+            asset_context,
+        })
+    }
+
+    /// Compiles the stylesheet. Compile errors are reported as issues and
+    /// yield no CSS.
+    #[nxpkg_tasks::function]
+    async fn compile(self: Vc<Self>) -> Result<Vc<CompiledCss>> {
+        let this = self.await?;
+        let Some((entry, stylesheets)) = load_stylesheets(this.source, this.asset_context).await?
+        else {
+            return Ok(Vc::cell(None));
+        };
+
+        let options = Options::default()
+            .fs(&stylesheets)
+            .style(OutputStyle::Expanded)
+            .quiet(true);
+        match grass::from_path(&entry, &options) {
+            Ok(css) => Ok(Vc::cell(Some(css))),
+            Err(err) => {
+                SassCompileIssue {
+                    file_path: this.source.ident().path(),
+                    message: err.to_string(),
+                }
+                .cell()
+                .emit();
+                Ok(Vc::cell(None))
+            }
+        }
+    }
+}
+
+#[nxpkg_tasks::value_impl]
+impl Source for SassSource {
+    #[nxpkg_tasks::function]
+    fn ident(&self) -> Vc<AssetIdent> {
+        self.source.ident().with_modifier(modifier())
+    }
+}
+
+#[nxpkg_tasks::value_impl]
+impl Asset for SassSource {
+    #[nxpkg_tasks::function]
+    async fn content(self: Vc<Self>) -> Result<Vc<AssetContent>> {
+        Ok(match &*self.compile().await? {
+            Some(css) => AssetContent::file(File::from(css.clone()).into()),
+            None => AssetContent::file(FileContent::NotFound.cell()),
+        })
+    }
+}
+
+/// The stylesheets a compilation can load, keyed by their resolved path.
+#[derive(Debug, Default)]
+struct Stylesheets {
+    files: HashMap<PathBuf, Vec<u8>>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl Stylesheets {
+    fn new(files: HashMap<PathBuf, Vec<u8>>) -> Self {
+        let dirs = files
+            .keys()
+            .flat_map(|path| path.ancestors().skip(1))
+            .map(Path::to_path_buf)
+            .collect();
+        Self { files, dirs }
+    }
+}
+
+impl Fs for Stylesheets {
+    fn is_dir(&self, path: &Path) -> bool {
+        self.dirs.contains(path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_path_buf())
+    }
+}
+
+async fn read_to_string(source: Vc<Box<dyn Source>>) -> Result<Option<String>> {
+    let AssetContent::File(file) = &*source.content().await? else {
+        return Ok(None);
+    };
+    let FileContent::Content(file) = &*file.await? else {
+        return Ok(None);
+    };
+    Ok(file.content().to_str().ok().map(|s| s.into_owned()))
+}
+
+/// Loads `source` and every stylesheet it loads, directly or through other
+/// stylesheets, with their rules pointed at the resolved stylesheets. Returns
+/// the path to compile and the loaded stylesheets, or `None` if `source`
+/// can't be read.
+async fn load_stylesheets(
+    source: Vc<Box<dyn Source>>,
+    asset_context: Vc<Box<dyn AssetContext>>,
+) -> Result<Option<(PathBuf, Stylesheets)>> {
+    let origin = source.ident().path();
+    let entry = stylesheet_path(origin).await?;
+    let mut stylesheets = HashMap::new();
+    let mut queue = vec![(entry.clone(), origin, source)];
+    while let Some((path, origin, source)) = queue.pop() {
+        if stylesheets.contains_key(&path) {
+            continue;
+        }
+        let Some(contents) = read_to_string(source).await? else {
+            if path == entry {
+                return Ok(None);
+            }
+            // The compiler reports the missing stylesheet
+            continue;
+        };
+
+        let indented = path.extension().is_some_and(|ext| ext == "sass");
+        let mut imports = Vec::new();
+        for url in import_urls(&contents, indented) {
+            let Some(import) =
+                resolve_import(origin, &contents[url.range.clone()], asset_context).await?
+            else {
+                // Left as is, so the compiler reports the missing stylesheet
+                continue;
+            };
+            let import_origin = import.ident().path();
+            let import_path = stylesheet_path(import_origin).await?;
+            queue.push((import_path.clone(), import_origin, import));
+            imports.push((url, import_path));
+        }
+        let contents = rewrite_imports(&contents, &imports);
+        stylesheets.insert(path, contents.into_bytes());
+    }
+
+    Ok(Some((entry, Stylesheets::new(stylesheets))))
+}
+
+/// The path the compiler sees a stylesheet under.
+async fn stylesheet_path(path: Vc<FileSystemPath>) -> Result<PathBuf> {
+    Ok(Path::new("/").join(&path.await?.path))
+}
+
+async fn resolve_import(
+    origin: Vc<FileSystemPath>,
+    url: &str,
+    asset_context: Vc<Box<dyn AssetContext>>,
+) -> Result<Option<Vc<Box<dyn Source>>>> {
+    let options = asset_context.resolve_options(
+        origin,
+        Value::new(ReferenceType::Css(CssReferenceSubType::AtImport)),
+    );
+    let lookup_path = origin.parent();
+    let results = import_candidates(url)
+        .into_iter()
+        .map(|request| resolve(lookup_path, Request::parse_string(request), options))
+        .collect();
+    Ok(*ResolveResult::select_first(results).first_source().await?)
+}
+
+/// A URL in a rule loading another stylesheet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ImportUrl {
+    /// Where the URL is in the stylesheet, without quotes
+    range: Range<usize>,
+    quoted: bool,
+    is_import: bool,
+    /// The namespace a `@use` rule without an explicit one gets from its URL
+    default_namespace: Option<String>,
+}
+
+/// Returns the URLs of the stylesheets `contents` loads. Built-in modules and
+/// imports Sass leaves as plain CSS `@import`s are skipped. Unquoted URLs are
+/// only allowed in the `indented` syntax.
+fn import_urls(contents: &str, indented: bool) -> Vec<ImportUrl> {
+    let code = mask_comments_and_strings(contents);
+    let mut urls = Vec::new();
+    for rule in IMPORT_RE.captures_iter(&code) {
+        let is_import = &rule[1] == "import";
+        if let Some(quoted) = rule.get(2) {
+            let has_namespace = NAMESPACE_RE.is_match(&code[quoted.end()..]);
+            // The quotes are kept in place when masking, so the URLs are read
+            // from the same range of the contents
+            urls.extend(
+                URL_RE
+                    .captures_iter(&contents[quoted.range()])
+                    .filter_map(|url| url.get(1).or_else(|| url.get(2)))
+                    .filter(|url| is_loaded(url.as_str(), is_import))
+                    .map(|url| ImportUrl {
+                        range: quoted.start() + url.start()..quoted.start() + url.end(),
+                        quoted: true,
+                        is_import,
+                        default_namespace: (&rule[1] == "use" && !has_namespace)
+                            .then(|| default_namespace(url.as_str()).to_string()),
+                    }),
+            );
+        } else if let Some(unquoted) = rule.get(3).filter(|_| indented && is_import) {
+            let mut start = unquoted.start();
+            for url in unquoted.as_str().split(',') {
+                let url_start = start + url.len() - url.trim_start().len();
+                let url_end = url_start + url.trim().len();
+                start += url.len() + 1;
+                let url = &contents[url_start..url_end];
+                if !url.is_empty() && !url.starts_with("url(") && is_loaded(url, is_import) {
+                    urls.push(ImportUrl {
+                        range: url_start..url_end,
+                        quoted: false,
+                        is_import,
+                        default_namespace: None,
+                    });
+                }
+            }
+        }
+    }
+    urls
+}
+
+/// Like Sass, the namespace of a module is its file name up to the first dot.
+fn default_namespace(url: &str) -> &str {
+    let name = url.rsplit_once('/').map_or(url, |(_, name)| name);
+    name.split_once('.').map_or(name, |(name, _)| name)
+}
+
+/// Points each of `imports` at the path of the stylesheet it was resolved to.
+/// `@use` rules keep the namespace they had from the original URL.
+fn rewrite_imports(contents: &str, imports: &[(ImportUrl, PathBuf)]) -> String {
+    let mut rewritten = String::with_capacity(contents.len());
+    let mut end = 0;
+    for (url, path) in imports {
+        let range = match url.quoted {
+            true => url.range.start - 1..url.range.end + 1,
+            false => url.range.clone(),
+        };
+        rewritten.push_str(&contents[end..range.start]);
+        // `@import` leaves URLs ending in `.css` as plain CSS imports, so CSS
+        // files are imported without their extension
+        let path = match url.is_import && path.extension().is_some_and(|ext| ext == "css") {
+            true => path.with_extension(""),
+            false => path.clone(),
+        };
+        let path = path.to_string_lossy();
+        rewritten.push('"');
+        rewritten.push_str(&path.replace('\\', "\\\\").replace('"', "\\\""));
+        rewritten.push('"');
+        if let Some(namespace) = &url.default_namespace {
+            rewritten.push_str(" as ");
+            rewritten.push_str(namespace);
+        }
+        end = range.end;
+    }
+    rewritten.push_str(&contents[end..]);
+    rewritten
+}
+
+/// Whether a URL loads a stylesheet for the compiler, rather than a built-in
+/// module or a plain CSS `@import`.
+fn is_loaded(url: &str, is_import: bool) -> bool {
+    !(url.starts_with("sass:")
+        || url.starts_with("//")
+        || url.contains("://")
+        || (is_import && url.ends_with(".css")))
+}
+
+/// Replaces comments and the contents of strings with spaces, so rules can be
+/// matched without finding them in either. Offsets and line breaks stay the
+/// same.
+fn mask_comments_and_strings(contents: &str) -> String {
+    let bytes = contents.as_bytes();
+    let mut masked = bytes.to_vec();
+    let mut mask = |range: std::ops::Range<usize>| {
+        for byte in &mut masked[range] {
+            if *byte != b'\n' {
+                *byte = b' ';
+            }
+        }
+    };
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1)) {
+            (quote @ (b'"' | b'\''), _) => {
+                let start = i + 1;
+                i = start;
+                while i < bytes.len() && bytes[i] != quote && bytes[i] != b'\n' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                let end = i.min(bytes.len());
+                mask(start..end);
+                i = end + 1;
+            }
+            (b'/', Some(b'/')) => {
+                let start = i;
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                mask(start..i);
+            }
+            (b'/', Some(b'*')) => {
+                let start = i;
+                i = contents[i + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |end| i + 2 + end + 2);
+                mask(start..i);
+            }
+            _ => i += 1,
+        }
+    }
+    // Only whole characters are masked, as every range starts and ends at an
+    // ASCII character
+    String::from_utf8(masked).expect("masking keeps the contents valid UTF-8")
+}
+
+fn has_sass_extension(url: &str) -> bool {
+    Path::new(url)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| SASS_EXTENSIONS.contains(&ext))
+}
+
+/// Returns the requests an import URL is resolved as, in order of
+/// preference. Like Sass, this looks for partials (`_name.scss`) and index
+/// files, first next to the importing stylesheet and then in packages.
+fn import_candidates(url: &str) -> Vec<String> {
+    let (dir, name) = match url.rsplit_once('/') {
+        Some((dir, name)) => (format!("{dir}/"), name),
+        None => (String::new(), url),
+    };
+    let has_extension = has_sass_extension(url);
+    let files = if has_extension {
+        vec![format!("{dir}_{name}"), url.to_string()]
+    } else {
+        let files = SASS_EXTENSIONS
+            .iter()
+            .flat_map(|ext| [format!("{dir}_{name}.{ext}"), format!("{url}.{ext}")]);
+        let index_files = SASS_EXTENSIONS
+            .iter()
+            .flat_map(|ext| [format!("{url}/_index.{ext}"), format!("{url}/index.{ext}")]);
+        files.chain(index_files).collect()
+    };
+
+    if url.starts_with("./") || url.starts_with("../") || url.starts_with('/') {
+        return files;
+    }
+    let mut candidates = files
+        .iter()
+        .map(|file| format!("./{file}"))
+        .collect::<Vec<_>>();
+    candidates.extend(files);
+    if !has_extension {
+        // Lets a package point at its stylesheet from its `package.json`
+        candidates.push(url.to_string());
+    }
+    candidates
+}
+
+#[nxpkg_tasks::value(shared)]
+struct SassCompileIssue {
+    file_path: Vc<FileSystemPath>,
+    message: String,
+}
+
+#[nxpkg_tasks::value_impl]
+impl Issue for SassCompileIssue {
+    #[nxpkg_tasks::function]
+    fn category(&self) -> Vc<String> {
+        Vc::cell("css".to_string())
+    }
+
+    #[nxpkg_tasks::function]
+    fn file_path(&self) -> Vc<FileSystemPath> {
+        self.file_path
+    }
+
+    #[nxpkg_tasks::function]
+    fn title(&self) -> Vc<String> {
+        Vc::cell("Compiling Sass failed".to_string())
+    }
+
+    #[nxpkg_tasks::function]
+    fn description(&self) -> Vc<StyledString> {
+        StyledString::Text(self.message.clone()).cell()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use grass::{Options, OutputStyle};
+
+    use crate::sass::{import_candidates, import_urls, rewrite_imports, Stylesheets};
+
+    fn urls(contents: &str, indented: bool) -> Vec<&str> {
+        import_urls(contents, indented)
+            .into_iter()
+            .map(|url| &contents[url.range])
+            .collect()
+    }
+
+    #[test]
+    fn finds_import_urls() {
+        let contents = r#"
+            @use "sass:math";
+            @use 'tokens' as t;
+            @forward "./mixins" show rounded;
+            @import "reset", 'theme.css', "https://example.com/font.css";
+        "#;
+        assert_eq!(urls(contents, false), vec!["tokens", "./mixins", "reset"]);
+    }
+
+    #[test]
+    fn skips_comments_and_strings() {
+        let contents = r#"
+            // @use "commented";
+            /* @import "block",
+               "comment"; */
+            .icon::before { content: "@use 'in-string'"; }
+            .quote::after { content: '\'@import "escaped"'; }
+            @use "tokens"; // @use "trailing";
+            @import "reset" /* , "inline" */;
+        "#;
+        assert_eq!(urls(contents, false), vec!["tokens", "reset"]);
+    }
+
+    #[test]
+    fn finds_unquoted_imports_in_indented_syntax() {
+        let contents = "
+@use \"tokens\"
+@import reset, base/typography // @import commented
+@import theme.css, url(font.css)
+.icon::before
+  content: \"@import in-string\"
+";
+        assert_eq!(
+            urls(contents, true),
+            vec!["tokens", "reset", "base/typography"]
+        );
+        assert_eq!(urls(contents, false), vec!["tokens"]);
+    }
+
+    #[test]
+    fn tries_partials_and_index_files() {
+        let candidates = import_candidates("./base/colors");
+        assert_eq!(
+            &candidates[..2],
+            ["./base/_colors.scss", "./base/colors.scss"]
+        );
+        assert!(candidates.contains(&"./base/colors/_index.scss".to_string()));
+        assert!(!candidates.contains(&"./base/colors".to_string()));
+    }
+
+    #[test]
+    fn tries_packages_after_relative_files() {
+        let candidates = import_candidates("@design/tokens.scss");
+        assert_eq!(
+            candidates,
+            [
+                "./@design/_tokens.scss",
+                "./@design/tokens.scss",
+                "@design/_tokens.scss",
+                "@design/tokens.scss",
+            ]
+        );
+    }
+
+    #[test]
+    fn rewrites_imports_to_resolved_paths() {
+        let contents =
+            "@use \"@design/tokens.scss\";\n@use './mixins' as m;\n@import 'reset', \"theme\";\n";
+        let resolved = [
+            "/node_modules/@design/tokens.scss",
+            "/app/_mixins.scss",
+            "/app/reset.sass",
+            "/app/theme.css",
+        ];
+        let imports = import_urls(contents, false)
+            .into_iter()
+            .zip(resolved.map(PathBuf::from))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rewrite_imports(contents, &imports),
+            "@use \"/node_modules/@design/tokens.scss\" as tokens;\n@use \"/app/_mixins.scss\" as \
+             m;\n@import \"/app/reset.sass\", \"/app/theme\";\n"
+        );
+    }
+
+    #[test]
+    fn compiles_resolved_stylesheets() {
+        let sources = [
+            (
+                "/app/main.scss",
+                "@use \"@design/tokens\";\n@import \"./base\";\n.button { color: tokens.$primary; \
+                 }\n",
+            ),
+            (
+                "/node_modules/@design/tokens/_index.scss",
+                "$primary: #0070f3;\n",
+            ),
+            ("/app/_base.scss", "body { margin: 0; }\n"),
+        ];
+        let resolved: HashMap<&str, &str> = [
+            ("@design/tokens", "/node_modules/@design/tokens/_index.scss"),
+            ("./base", "/app/_base.scss"),
+        ]
+        .into();
+        let files = sources
+            .into_iter()
+            .map(|(path, contents)| {
+                let imports = import_urls(contents, false)
+                    .into_iter()
+                    .map(|url| {
+                        let path = PathBuf::from(resolved[&contents[url.range.clone()]]);
+                        (url, path)
+                    })
+                    .collect::<Vec<_>>();
+                let contents = rewrite_imports(contents, &imports);
+                (PathBuf::from(path), contents.into_bytes())
+            })
+            .collect();
+        let stylesheets = Stylesheets::new(files);
+
+        let options = Options::default()
+            .fs(&stylesheets)
+            .style(OutputStyle::Compressed)
+            .quiet(true);
+        assert_eq!(
+            grass::from_path("/app/main.scss", &options).unwrap(),
+            "body{margin:0}.button{color:#0070f3}\n"
+        );
+    }
+}
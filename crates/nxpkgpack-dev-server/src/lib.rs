@@ -10,6 +10,7 @@ mod http;
 pub mod introspect;
 mod invalidation;
 pub mod source;
+pub mod tls;
 pub mod update;
 
 use std::{
@@ -23,7 +24,7 @@ use std::{
 
 use anyhow::{Context, Result};
 use hyper::{
-    server::{conn::AddrIncoming, Builder},
+    server::conn::AddrIncoming,
     service::{make_service_fn, service_fn},
     Request, Response, Server,
 };
@@ -35,6 +36,7 @@ use nxpkg_tasks::{
     run_once_with_reason, trace::TraceRawVcs, util::FormatDuration, NxpkgTasksApi, Vc,
 };
 use nxpkgpack_core::{
+    environment::Protocol as ServerProtocol,
     error::PrettyPrintError,
     issue::{handle_issues, IssueReporter, IssueSeverity},
 };
@@ -43,6 +45,7 @@ use self::{source::ContentSource, update::UpdateServer};
 use crate::{
     invalidation::{ServerRequest, ServerRequestSideEffects},
     source::ContentSourceSideEffect,
+    tls::{DevServerIncoming, TlsConfig},
 };
 
 pub trait SourceProvider: Send + Clone + 'static {
@@ -64,7 +67,9 @@ pub struct DevServerBuilder {
     #[nxpkg_tasks(trace_ignore)]
     pub addr: SocketAddr,
     #[nxpkg_tasks(trace_ignore)]
-    server: Builder<AddrIncoming>,
+    incoming: AddrIncoming,
+    #[nxpkg_tasks(trace_ignore)]
+    tls: Option<TlsConfig>,
 }
 
 #[derive(TraceRawVcs)]
@@ -72,6 +77,8 @@ pub struct DevServer {
     #[nxpkg_tasks(trace_ignore)]
     pub addr: SocketAddr,
     #[nxpkg_tasks(trace_ignore)]
+    pub protocol: ServerProtocol,
+    #[nxpkg_tasks(trace_ignore)]
     pub future: Pin<Box<dyn Future<Output = Result<()>> + Send + 'static>>,
 }
 
@@ -105,12 +112,28 @@ impl DevServer {
         let addr = listener
             .local_addr()
             .context("not able to get bound address")?;
-        let server = Server::from_tcp(listener).context("Not able to start server")?;
-        Ok(DevServerBuilder { addr, server })
+        listener
+            .set_nonblocking(true)
+            .context("Not able to start server")?;
+        let listener =
+            tokio::net::TcpListener::from_std(listener).context("Not able to start server")?;
+        let incoming = AddrIncoming::from_listener(listener).context("Not able to start server")?;
+        Ok(DevServerBuilder {
+            addr,
+            incoming,
+            tls: None,
+        })
     }
 }
 
 impl DevServerBuilder {
+    /// Serves HTTPS instead of HTTP. HTTP/2 is negotiated with clients that
+    /// support it.
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
     pub fn serve(
         self,
         nxpkg_tasks: Arc<dyn NxpkgTasksApi>,
@@ -277,10 +300,19 @@ impl DevServerBuilder {
                 anyhow::Ok(service_fn(handler))
             }
         });
-        let server = self.server.serve(make_svc);
+        let protocol = if self.tls.is_some() {
+            ServerProtocol::HTTPS
+        } else {
+            ServerProtocol::HTTP
+        };
+        // Connections that don't use TLS can use HTTP/2 as well, hyper detects
+        // it from the connection preface
+        let server =
+            Server::builder(DevServerIncoming::new(self.incoming, self.tls)).serve(make_svc);
 
         DevServer {
             addr: self.addr,
+            protocol,
             future: Box::pin(async move {
                 server.await?;
                 Ok(())
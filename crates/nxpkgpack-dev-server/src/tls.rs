@@ -0,0 +1,728 @@
+//! HTTPS for the dev server. Connections are accepted by `DevServerIncoming`,
+//! which performs the TLS handshake when the server has a [TlsConfig]. ALPN
+//! offers HTTP/2 and HTTP/1.1. WebSocket upgrades, e. g. for HMR, are made
+//! over HTTP/1.1 connections, as browsers open those for WebSockets when the
+//! server doesn't support WebSockets over HTTP/2.
+
+use std::{
+    fmt, fs,
+    io::{self, BufReader},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::{Path, PathBuf},
+    pin::Pin,
+    process::{Command, Stdio},
+    sync::Arc,
+    task::{Context as TaskContext, Poll},
+    time::Duration as StdDuration,
+};
+
+use anyhow::{bail, Context, Result};
+use futures::{stream::FuturesUnordered, StreamExt};
+use hyper::server::{
+    accept::Accept,
+    conn::{AddrIncoming, AddrStream},
+};
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, CidrSubnet, DistinguishedName, DnType,
+    ExtendedKeyUsagePurpose, GeneralSubtree, IsCa, KeyPair, KeyUsagePurpose, NameConstraints,
+    SanType,
+};
+use time::{Duration, OffsetDateTime};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{timeout, Timeout},
+};
+use tokio_rustls::{
+    rustls::{self, ServerConfig},
+    server::TlsStream,
+    TlsAcceptor,
+};
+
+const CA_CERTIFICATE_FILE: &str = "nxpkgpack-ca.pem";
+const CA_KEY_FILE: &str = "nxpkgpack-ca-key.pem";
+// Connections that don't complete the handshake in time are dropped, so
+// clients that never send anything don't hold on to their connection
+const HANDSHAKE_TIMEOUT: StdDuration = StdDuration::from_secs(10);
+// The networks the local certificate authority can sign certificates for:
+// loopback, link-local and private addresses. Its certificate is trusted like
+// any other, so a leaked key must not be usable for public hosts.
+const LOCAL_NETWORKS: [(IpAddr, u8); 8] = [
+    (IpAddr::V4(Ipv4Addr::new(127, 0, 0, 0)), 8),
+    (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 0)), 8),
+    (IpAddr::V4(Ipv4Addr::new(172, 16, 0, 0)), 12),
+    (IpAddr::V4(Ipv4Addr::new(192, 168, 0, 0)), 16),
+    (IpAddr::V4(Ipv4Addr::new(169, 254, 0, 0)), 16),
+    (IpAddr::V6(Ipv6Addr::LOCALHOST), 128),
+    (IpAddr::V6(Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 0)), 7),
+    (IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0)), 10),
+];
+const LOCAL_DOMAIN: &str = "localhost";
+
+/// The certificate to serve HTTPS with.
+#[derive(Debug, Clone)]
+pub enum TlsCertificate {
+    /// A certificate chain and its private key, read from PEM files.
+    Files { cert: PathBuf, key: PathBuf },
+    /// A certificate for `hosts`, generated on every start. It's signed by a
+    /// local certificate authority in `ca_dir`, which is created on first use
+    /// and only needs to be trusted once. The certificate authority is
+    /// constrained to local hosts, see [is_local_host].
+    SelfSigned { ca_dir: PathBuf, hosts: Vec<String> },
+}
+
+/// The rustls configuration of a dev server.
+#[derive(Clone)]
+pub struct TlsConfig(Arc<ServerConfig>);
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("alpn_protocols", &self.0.alpn_protocols)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TlsConfig {
+    /// Creates the configuration for a certificate. For a
+    /// [TlsCertificate::SelfSigned] certificate, the local certificate
+    /// authority that signed it is returned as well.
+    pub fn new(
+        certificate: &TlsCertificate,
+    ) -> Result<(TlsConfig, Option<LocalCertificateAuthority>)> {
+        match certificate {
+            TlsCertificate::Files { cert, key } => {
+                let certs = read_pem_certs(cert)?;
+                let key = read_pem_key(key)?;
+                Ok((Self::from_der(certs, key)?, None))
+            }
+            TlsCertificate::SelfSigned { ca_dir, hosts } => {
+                let ca = LocalCertificateAuthority::load_or_create(ca_dir)?;
+                let (cert, key) = ca.sign(hosts)?;
+                Ok((Self::from_der(vec![cert], key)?, Some(ca)))
+            }
+        }
+    }
+
+    fn from_der(certs: Vec<Vec<u8>>, key: Vec<u8>) -> Result<Self> {
+        let mut config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                certs.into_iter().map(rustls::Certificate).collect(),
+                rustls::PrivateKey(key),
+            )
+            .context("invalid certificate or private key")?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(TlsConfig(Arc::new(config)))
+    }
+}
+
+fn read_pem_certs(path: &Path) -> Result<Vec<Vec<u8>>> {
+    let file = fs::File::open(path)
+        .with_context(|| format!("unable to read certificate {}", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .with_context(|| format!("unable to parse certificate {}", path.display()))?;
+    if certs.is_empty() {
+        bail!("no certificate found in {}", path.display());
+    }
+    Ok(certs)
+}
+
+fn read_pem_key(path: &Path) -> Result<Vec<u8>> {
+    let file = fs::File::open(path)
+        .with_context(|| format!("unable to read private key {}", path.display()))?;
+    let items = rustls_pemfile::read_all(&mut BufReader::new(file))
+        .with_context(|| format!("unable to parse private key {}", path.display()))?;
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(key),
+            _ => None,
+        })
+        .with_context(|| format!("no private key found in {}", path.display()))
+}
+
+/// Whether `host` is a hostname or IP address the local certificate authority
+/// can sign certificates for: `localhost` and its subdomains, and loopback,
+/// link-local and private IP addresses.
+pub fn is_local_host(host: &str) -> bool {
+    match host.parse::<IpAddr>() {
+        Ok(ip) => LOCAL_NETWORKS
+            .iter()
+            .any(|&(network, prefix)| network_contains(network, prefix, ip)),
+        Err(_) => {
+            let host = host.trim_end_matches('.').to_ascii_lowercase();
+            host == LOCAL_DOMAIN || host.ends_with(&format!(".{LOCAL_DOMAIN}"))
+        }
+    }
+}
+
+fn network_contains(network: IpAddr, prefix: u8, ip: IpAddr) -> bool {
+    match (network, ip) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+/// A certificate authority that signs the generated certificates. Browsers
+/// accept the certificates once it's trusted. Its name constraints limit it
+/// to local hosts, so trusting it doesn't allow intercepting traffic to other
+/// hosts.
+pub struct LocalCertificateAuthority {
+    /// The PEM file of the certificate authority's certificate, which needs
+    /// to be trusted, e. g. on devices in the local network.
+    pub certificate_path: PathBuf,
+    /// Whether the certificate authority was created by this start, so it's
+    /// not trusted yet.
+    pub created: bool,
+    certificate: Certificate,
+}
+
+impl LocalCertificateAuthority {
+    fn load_or_create(dir: &Path) -> Result<Self> {
+        let certificate_path = dir.join(CA_CERTIFICATE_FILE);
+        let key_path = dir.join(CA_KEY_FILE);
+        if certificate_path.exists() && key_path.exists() {
+            let certificate_pem = fs::read_to_string(&certificate_path)?;
+            let key = KeyPair::from_pem(&fs::read_to_string(&key_path)?)?;
+            let params = CertificateParams::from_ca_cert_pem(&certificate_pem, key)
+                .with_context(|| format!("invalid certificate {}", certificate_path.display()))?;
+            if params.name_constraints.is_none() {
+                bail!(
+                    "the certificate authority {} isn't constrained to local hosts. Remove it \
+                     from the trusted certificates and delete it to create a new one",
+                    certificate_path.display()
+                );
+            }
+            return Ok(LocalCertificateAuthority {
+                certificate_path,
+                created: false,
+                certificate: Certificate::from_params(params)?,
+            });
+        }
+
+        let mut params = CertificateParams::default();
+        let mut name = DistinguishedName::new();
+        name.push(DnType::OrganizationName, "nxpkgpack");
+        name.push(DnType::CommonName, "nxpkgpack development CA");
+        params.distinguished_name = name;
+        params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+        let mut permitted_subtrees = vec![GeneralSubtree::DnsName(LOCAL_DOMAIN.to_string())];
+        permitted_subtrees.extend(LOCAL_NETWORKS.iter().map(|&(network, prefix)| {
+            GeneralSubtree::IpAddress(CidrSubnet::from_addr_prefix(network, prefix))
+        }));
+        params.name_constraints = Some(NameConstraints {
+            permitted_subtrees,
+            excluded_subtrees: Vec::new(),
+        });
+        params.key_usages = vec![
+            KeyUsagePurpose::KeyCertSign,
+            KeyUsagePurpose::CrlSign,
+            KeyUsagePurpose::DigitalSignature,
+        ];
+        let now = OffsetDateTime::now_utc();
+        params.not_before = now - Duration::days(1);
+        params.not_after = now + Duration::days(10 * 365);
+        let certificate = Certificate::from_params(params)?;
+
+        fs::create_dir_all(dir).with_context(|| format!("unable to create {}", dir.display()))?;
+        fs::write(&certificate_path, certificate.serialize_pem()?)?;
+        write_private_file(&key_path, &certificate.serialize_private_key_pem())?;
+        Ok(LocalCertificateAuthority {
+            certificate_path,
+            created: true,
+            certificate,
+        })
+    }
+
+    /// Generates a certificate and its private key for `hosts`, which are
+    /// hostnames or IP addresses. They must be local hosts, as clients reject
+    /// certificates for other hosts because of the name constraints.
+    fn sign(&self, hosts: &[String]) -> Result<(Vec<u8>, Vec<u8>)> {
+        if let Some(host) = hosts.iter().find(|host| !is_local_host(host)) {
+            bail!(
+                "the local certificate authority can only sign certificates for localhost and \
+                 local network addresses, not {host}. Use a certificate and key from files instead"
+            );
+        }
+        let mut params = CertificateParams::default();
+        let mut name = DistinguishedName::new();
+        name.push(DnType::CommonName, "nxpkgpack development server");
+        params.distinguished_name = name;
+        params.subject_alt_names = hosts
+            .iter()
+            .map(|host| match host.parse::<IpAddr>() {
+                Ok(ip) => SanType::IpAddress(ip),
+                Err(_) => SanType::DnsName(host.clone()),
+            })
+            .collect();
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        // macOS rejects server certificates that are valid for longer than
+        // 825 days, even when they are signed by a trusted certificate
+        // authority
+        let now = OffsetDateTime::now_utc();
+        params.not_before = now - Duration::days(1);
+        params.not_after = now + Duration::days(365);
+        let certificate = Certificate::from_params(params)?;
+        Ok((
+            certificate.serialize_der_with_signer(&self.certificate)?,
+            certificate.serialize_private_key_der(),
+        ))
+    }
+
+    /// Whether the certificate authority is trusted by the current user, or
+    /// `None` if that can't be checked on this platform.
+    pub fn is_trusted(&self) -> Option<bool> {
+        if !cfg!(target_os = "macos") {
+            return None;
+        }
+        Command::new("security")
+            .args(["verify-cert", "-q", "-c"])
+            .arg(&self.certificate_path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .ok()
+            .map(|status| status.success())
+    }
+
+    /// Adds the certificate authority to the trusted certificates of the
+    /// current user. The operating system asks for confirmation. On Linux
+    /// this needs root permissions, so it returns an error that explains how
+    /// to do it instead.
+    pub fn trust(&self) -> Result<()> {
+        let path = &self.certificate_path;
+        let mut command = if cfg!(target_os = "macos") {
+            let mut command = Command::new("security");
+            command
+                .args(["add-trusted-cert", "-r", "trustRoot", "-k"])
+                .arg(
+                    home_dir()?
+                        .join("Library")
+                        .join("Keychains")
+                        .join("login.keychain-db"),
+                )
+                .arg(path);
+            command
+        } else if cfg!(windows) {
+            let mut command = Command::new("certutil");
+            command.args(["-user", "-addstore", "Root"]).arg(path);
+            command
+        } else {
+            bail!(
+                "adding the certificate authority to the trusted certificates is not supported on \
+                 this platform. To trust it, add {} to the certificates of your system or \
+                 browser, e. g. by copying it to /usr/local/share/ca-certificates/nxpkgpack.crt \
+                 and running `sudo update-ca-certificates`",
+                path.display()
+            );
+        };
+        let status = command
+            .status()
+            .with_context(|| format!("unable to run {:?}", command.get_program()))?;
+        if !status.success() {
+            bail!(
+                "{:?} exited with {status} while adding {} to the trusted certificates",
+                command.get_program(),
+                path.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+fn home_dir() -> Result<PathBuf> {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .context("HOME is not set")
+}
+
+// The private key of the certificate authority can sign certificates for any
+// host, so only the current user can read it
+fn write_private_file(path: &Path, contents: &str) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("unable to write {}", path.display()))?;
+    io::Write::write_all(&mut file, contents.as_bytes())?;
+    Ok(())
+}
+
+/// Accepts the connections of the dev server, with a TLS handshake when the
+/// server is configured for HTTPS. Handshakes run concurrently, so a slow
+/// client doesn't block others, and time out after [HANDSHAKE_TIMEOUT].
+pub(crate) struct DevServerIncoming {
+    incoming: AddrIncoming,
+    acceptor: Option<TlsAcceptor>,
+    handshakes: FuturesUnordered<Timeout<tokio_rustls::Accept<AddrStream>>>,
+}
+
+impl DevServerIncoming {
+    pub fn new(incoming: AddrIncoming, tls: Option<TlsConfig>) -> Self {
+        DevServerIncoming {
+            incoming,
+            acceptor: tls.map(|TlsConfig(config)| TlsAcceptor::from(config)),
+            handshakes: FuturesUnordered::new(),
+        }
+    }
+}
+
+impl Accept for DevServerIncoming {
+    type Conn = DevServerConnection;
+    type Error = io::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let this = self.get_mut();
+        loop {
+            match Pin::new(&mut this.incoming).poll_accept(cx) {
+                Poll::Ready(Some(Ok(stream))) => match &this.acceptor {
+                    Some(acceptor) => this
+                        .handshakes
+                        .push(timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream))),
+                    None => return Poll::Ready(Some(Ok(DevServerConnection::Plain(stream)))),
+                },
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => break,
+            }
+        }
+        loop {
+            match this.handshakes.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(Ok(stream)))) => {
+                    return Poll::Ready(Some(Ok(DevServerConnection::Tls(Box::new(stream)))));
+                }
+                // A failed or timed out handshake only affects its connection,
+                // e. g. when the browser doesn't trust the certificate
+                Poll::Ready(Some(Ok(Err(_)) | Err(_))) => continue,
+                Poll::Ready(None) | Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// A connection to the dev server, over TLS when the server is configured
+/// for HTTPS.
+pub(crate) enum DevServerConnection {
+    Plain(AddrStream),
+    Tls(Box<TlsStream<AddrStream>>),
+}
+
+impl AsyncRead for DevServerConnection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            DevServerConnection::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            DevServerConnection::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for DevServerConnection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            DevServerConnection::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            DevServerConnection::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            DevServerConnection::Plain(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+            DevServerConnection::Tls(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            DevServerConnection::Plain(stream) => stream.is_write_vectored(),
+            DevServerConnection::Tls(stream) => stream.is_write_vectored(),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            DevServerConnection::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            DevServerConnection::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            DevServerConnection::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            DevServerConnection::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use futures::SinkExt;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Request, Response, Server,
+    };
+    use hyper_tungstenite::tungstenite::{error::ProtocolError, Message};
+    use tokio::net::TcpStream;
+    use tokio_rustls::TlsConnector;
+
+    use super::*;
+
+    #[test]
+    fn test_load_or_create_certificate_authority() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca_dir = dir.path().join("certificates");
+
+        let ca = LocalCertificateAuthority::load_or_create(&ca_dir).unwrap();
+        assert!(ca.created);
+        assert_eq!(ca.certificate_path, ca_dir.join(CA_CERTIFICATE_FILE));
+        let certificate_pem = fs::read_to_string(&ca.certificate_path).unwrap();
+
+        let ca = LocalCertificateAuthority::load_or_create(&ca_dir).unwrap();
+        assert!(!ca.created);
+        assert_eq!(
+            fs::read_to_string(&ca.certificate_path).unwrap(),
+            certificate_pem
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_certificate_authority_key_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        LocalCertificateAuthority::load_or_create(dir.path()).unwrap();
+        let metadata = fs::metadata(dir.path().join(CA_KEY_FILE)).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_sign() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca = LocalCertificateAuthority::load_or_create(dir.path()).unwrap();
+
+        let hosts = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+        let (cert, key) = ca.sign(&hosts).unwrap();
+        let params =
+            CertificateParams::from_ca_cert_der(&cert, KeyPair::from_der(&key).unwrap()).unwrap();
+        assert_eq!(
+            params.subject_alt_names,
+            vec![
+                SanType::DnsName("localhost".to_string()),
+                SanType::IpAddress(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            ]
+        );
+        // rustls checks that the private key belongs to the certificate
+        TlsConfig::from_der(vec![cert], key).unwrap();
+    }
+
+    #[test]
+    fn test_certificate_authority_is_constrained_to_local_hosts() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca = LocalCertificateAuthority::load_or_create(dir.path()).unwrap();
+        let params = CertificateParams::from_ca_cert_pem(
+            &fs::read_to_string(&ca.certificate_path).unwrap(),
+            KeyPair::from_pem(&fs::read_to_string(dir.path().join(CA_KEY_FILE)).unwrap()).unwrap(),
+        )
+        .unwrap();
+        let constraints = params.name_constraints.unwrap();
+        assert_eq!(
+            constraints.permitted_subtrees.len(),
+            LOCAL_NETWORKS.len() + 1
+        );
+        assert!(constraints.excluded_subtrees.is_empty());
+
+        let err = ca
+            .sign(&["localhost".to_string(), "example.com".to_string()])
+            .unwrap_err();
+        assert!(err.to_string().contains("not example.com"));
+        assert!(ca.sign(&["8.8.8.8".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_unconstrained_certificate_authority_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = CertificateParams::default();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let certificate = Certificate::from_params(params).unwrap();
+        fs::write(
+            dir.path().join(CA_CERTIFICATE_FILE),
+            certificate.serialize_pem().unwrap(),
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join(CA_KEY_FILE),
+            certificate.serialize_private_key_pem(),
+        )
+        .unwrap();
+
+        let err = LocalCertificateAuthority::load_or_create(dir.path())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("isn't constrained to local hosts"));
+    }
+
+    #[test]
+    fn test_is_local_host() {
+        for host in [
+            "localhost",
+            "LOCALHOST.",
+            "app.localhost",
+            "127.0.0.1",
+            "127.1.2.3",
+            "10.0.0.8",
+            "172.31.255.1",
+            "192.168.1.20",
+            "169.254.0.1",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+        ] {
+            assert!(is_local_host(host), "{host} should be local");
+        }
+        for host in [
+            "example.com",
+            "localhost.example.com",
+            "notlocalhost",
+            "8.8.8.8",
+            "172.32.0.1",
+            "192.169.0.1",
+            "2001:db8::1",
+        ] {
+            assert!(!is_local_host(host), "{host} shouldn't be local");
+        }
+    }
+
+    // Browsers open HTTP/1.1 connections for WebSockets, like the HMR client,
+    // so the upgrade has to work over TLS with the constrained certificate
+    #[tokio::test]
+    async fn test_websocket_over_tls() {
+        let dir = tempfile::tempdir().unwrap();
+        let (config, ca) = TlsConfig::new(&TlsCertificate::SelfSigned {
+            ca_dir: dir.path().to_path_buf(),
+            hosts: vec!["localhost".to_string(), "127.0.0.1".to_string()],
+        })
+        .unwrap();
+
+        let incoming = AddrIncoming::bind(&(Ipv4Addr::LOCALHOST, 0).into()).unwrap();
+        let addr = incoming.local_addr();
+        let make_svc = make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|request: Request<Body>| async move {
+                if request.uri().path() != "/nxpkgpack-hmr"
+                    || !hyper_tungstenite::is_upgrade_request(&request)
+                {
+                    return Ok(Response::builder().status(404).body(Body::empty()).unwrap());
+                }
+                let (response, websocket) = hyper_tungstenite::upgrade(request, None)?;
+                tokio::spawn(async move {
+                    let Ok(mut websocket) = websocket.await else {
+                        return;
+                    };
+                    while let Some(Ok(message)) = websocket.next().await {
+                        if websocket.send(message).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+                Ok::<_, ProtocolError>(response)
+            }))
+        });
+        tokio::spawn(
+            Server::builder(DevServerIncoming::new(incoming, Some(config))).serve(make_svc),
+        );
+
+        // The client only trusts the local certificate authority
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in read_pem_certs(&ca.unwrap().certificate_path).unwrap() {
+            roots.add(&rustls::Certificate(cert)).unwrap();
+        }
+        let mut client_config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        client_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let stream = TlsConnector::from(Arc::new(client_config))
+            .connect(rustls::ServerName::try_from("localhost").unwrap(), stream)
+            .await
+            .unwrap();
+
+        let (mut websocket, response) = tokio_tungstenite::client_async(
+            format!("wss://localhost:{}/nxpkgpack-hmr", addr.port()),
+            stream,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), 101);
+        let message = Message::Text(r#"{"type":"subscribe"}"#.to_string());
+        websocket.send(message.clone()).await.unwrap();
+        assert_eq!(websocket.next().await.unwrap().unwrap(), message);
+    }
+
+    #[test]
+    fn test_read_pem_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca = LocalCertificateAuthority::load_or_create(dir.path()).unwrap();
+        let key_path = dir.path().join(CA_KEY_FILE);
+
+        let certs = read_pem_certs(&ca.certificate_path).unwrap();
+        assert_eq!(certs.len(), 1);
+        let key = read_pem_key(&key_path).unwrap();
+        assert_eq!(key, ca.certificate.serialize_private_key_der());
+
+        let (config, ca) = TlsConfig::new(&TlsCertificate::Files {
+            cert: ca.certificate_path,
+            key: key_path,
+        })
+        .unwrap();
+        assert!(ca.is_none());
+        assert_eq!(
+            config.0.alpn_protocols,
+            vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_read_pem_files_without_items() {
+        let dir = tempfile::tempdir().unwrap();
+        let empty = dir.path().join("empty.pem");
+        fs::write(&empty, "").unwrap();
+
+        let err = read_pem_certs(&empty).unwrap_err();
+        assert!(err.to_string().starts_with("no certificate found"));
+        let err = read_pem_key(&empty).unwrap_err();
+        assert!(err.to_string().starts_with("no private key found"));
+        assert!(read_pem_certs(&dir.path().join("missing.pem")).is_err());
+    }
+}
@@ -280,6 +280,10 @@ impl EvalContext {
         self.imports.is_esm()
     }
 
+    pub fn unresolved_mark(&self) -> Mark {
+        self.unresolved_mark
+    }
+
     fn eval_prop_name(&self, prop: &PropName) -> JsValue {
         match prop {
             PropName::Ident(ident) => ident.sym.clone().into(),
@@ -15,7 +15,6 @@ pub mod rebase;
 pub mod resolve;
 pub mod resolve_options_context;
 pub mod transition;
-pub(crate) mod unsupported_sass;
 
 use std::{
     collections::{HashMap, HashSet},
@@ -23,7 +22,7 @@ use std::{
 };
 
 use anyhow::Result;
-use css::{CssModuleAsset, GlobalCssAsset, ModuleCssAsset};
+use css::{CssModuleAsset, GlobalCssAsset, ModuleCssAsset, SassSource};
 use ecmascript::{
     typescript::resolve::TypescriptTypesAssetReference, EcmascriptModuleAsset,
     EcmascriptModuleAssetType,
@@ -176,6 +175,12 @@ async fn apply_module_type(
             *transforms,
             *ty,
         )),
+        ModuleType::Sass { ty, transforms } => Vc::upcast(CssModuleAsset::new(
+            Vc::upcast(SassSource::new(source, Vc::upcast(module_asset_context))),
+            Vc::upcast(module_asset_context),
+            *transforms,
+            *ty,
+        )),
         ModuleType::Static => Vc::upcast(StaticModuleAsset::new(
             source,
             Vc::upcast(module_asset_context),
@@ -498,6 +498,59 @@ impl ModuleOptions {
             ]);
         }
 
+        // `ModuleType::Sass` compiles to CSS, so the Sass rules mirror the CSS ones
+        let sass = || {
+            ModuleRuleCondition::any(vec![
+                ModuleRuleCondition::ResourcePathEndsWith(".scss".to_string()),
+                ModuleRuleCondition::ResourcePathEndsWith(".sass".to_string()),
+            ])
+        };
+        let sass_module = || {
+            ModuleRuleCondition::any(vec![
+                ModuleRuleCondition::ResourcePathEndsWith(".module.scss".to_string()),
+                ModuleRuleCondition::ResourcePathEndsWith(".module.sass".to_string()),
+            ])
+        };
+        let at_import = || {
+            ModuleRuleCondition::ReferenceType(ReferenceType::Css(CssReferenceSubType::AtImport))
+        };
+        let sass_type = |ty| {
+            vec![ModuleRuleEffect::ModuleType(ModuleType::Sass {
+                ty,
+                transforms: css_transforms,
+            })]
+        };
+        if enable_raw_css {
+            rules.extend([
+                ModuleRule::new(sass(), sass_type(CssModuleAssetType::Default)),
+                ModuleRule::new(sass_module(), sass_type(CssModuleAssetType::Module)),
+            ]);
+        } else {
+            rules.extend([
+                ModuleRule::new(
+                    ModuleRuleCondition::all(vec![sass(), ModuleRuleCondition::not(at_import())]),
+                    vec![ModuleRuleEffect::ModuleType(ModuleType::CssGlobal)],
+                ),
+                ModuleRule::new(
+                    ModuleRuleCondition::all(vec![
+                        sass_module(),
+                        ModuleRuleCondition::not(at_import()),
+                    ]),
+                    vec![ModuleRuleEffect::ModuleType(ModuleType::CssModule)],
+                ),
+                ModuleRule::new(
+                    ModuleRuleCondition::all(vec![sass(), at_import()]),
+                    sass_type(CssModuleAssetType::Default),
+                ),
+                ModuleRule::new(
+                    ModuleRuleCondition::all(vec![sass_module(), at_import()]),
+                    sass_type(CssModuleAssetType::Module),
+                ),
+                ModuleRule::new_internal(sass(), sass_type(CssModuleAssetType::Default)),
+                ModuleRule::new_internal(sass_module(), sass_type(CssModuleAssetType::Module)),
+            ]);
+        }
+
         if enable_mdx || enable_mdx_rs.is_some() {
             let (jsx_runtime, jsx_import_source) = if let Some(enable_jsx) = enable_jsx {
                 let jsx = enable_jsx.await?;
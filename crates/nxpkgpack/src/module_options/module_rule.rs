@@ -124,6 +124,11 @@ pub enum ModuleType {
         ty: CssModuleAssetType,
         transforms: Vc<CssInputTransforms>,
     },
+    /// Sass or SCSS, compiled to CSS and then handled like [ModuleType::Css].
+    Sass {
+        ty: CssModuleAssetType,
+        transforms: Vc<CssInputTransforms>,
+    },
     Static,
     WebAssembly {
         source_ty: WebAssemblySourceType,
@@ -49,6 +49,8 @@ pub trait Client {
         duration: u64,
         tag: Option<&str>,
         token: &str,
+        team_id: Option<&str>,
+        team_slug: Option<&str>,
     ) -> Result<()>;
     async fn handle_403(response: Response) -> Error;
     async fn fetch_artifact(
@@ -223,6 +225,8 @@ impl Client for APIClient {
         duration: u64,
         tag: Option<&str>,
         token: &str,
+        team_id: Option<&str>,
+        team_slug: Option<&str>,
     ) -> Result<()> {
         let mut request_url = self.make_url(&format!("/v8/artifacts/{}", hash));
         let mut allow_auth = true;
@@ -254,6 +258,7 @@ impl Client for APIClient {
         }
 
         request_builder = Self::add_ci_header(request_builder);
+        request_builder = Self::add_team_params(request_builder, team_id, team_slug);
 
         if let Some(tag) = tag {
             request_builder = request_builder.header("x-artifact-tag", tag);
@@ -0,0 +1,555 @@
+#![deny(clippy::all)]
+//! A self-hostable remote cache server.
+//!
+//! Implements the `/v8/artifacts` endpoints used by `nxpkgrepo-cache`'s HTTP
+//! cache and stores artifacts on local disk. Requests are authenticated with
+//! bearer tokens, each of which can be restricted to a set of teams. Every
+//! team gets its own artifact namespace.
+
+mod storage;
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    path::PathBuf,
+    pin::Pin,
+    sync::Arc,
+};
+
+use axum::{
+    body::StreamBody,
+    extract::{BodyStream, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use bytes::Bytes;
+use futures_util::{stream, Stream, StreamExt, TryStreamExt};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use thiserror::Error;
+use tokio_util::io::ReaderStream;
+use tracing::{error, info};
+use nxpkgrepo_vercel_api::{APIError, CachingStatus, CachingStatusResponse};
+
+pub use crate::storage::{ArtifactKey, ArtifactMetadata, Storage};
+
+const ARTIFACT_DURATION_HEADER: &str = "x-artifact-duration";
+const ARTIFACT_TAG_HEADER: &str = "x-artifact-tag";
+
+type HmacSha256 = Hmac<Sha256>;
+type ArtifactBody = Pin<Box<dyn Stream<Item = Result<Bytes, Error>> + Send>>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("missing or invalid authorization token")]
+    Unauthorized,
+    #[error("team is not allowed to access this cache")]
+    ForbiddenTeam,
+    #[error("invalid artifact hash: {0}")]
+    InvalidHash(String),
+    #[error("invalid team: {0}")]
+    InvalidTeam(String),
+    #[error("artifact is missing the {ARTIFACT_TAG_HEADER} header")]
+    MissingTag,
+    #[error("artifact signature doesn't match its contents")]
+    InvalidSignature,
+    #[error("artifact exceeds the cache size limit of {limit} bytes")]
+    TooLarge { limit: u64 },
+    #[error("failed to read request body: {0}")]
+    Body(#[from] axum::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+impl Error {
+    fn status(&self) -> StatusCode {
+        match self {
+            Error::Unauthorized => StatusCode::UNAUTHORIZED,
+            Error::ForbiddenTeam => StatusCode::FORBIDDEN,
+            Error::InvalidHash(_)
+            | Error::InvalidTeam(_)
+            | Error::MissingTag
+            | Error::InvalidSignature
+            | Error::Body(_) => StatusCode::BAD_REQUEST,
+            Error::TooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            Error::Io(_) | Error::Json(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Error::Unauthorized => "unauthorized",
+            Error::ForbiddenTeam => "forbidden",
+            Error::InvalidHash(_) | Error::InvalidTeam(_) => "bad_request",
+            Error::MissingTag => "missing_signature",
+            Error::InvalidSignature => "invalid_signature",
+            Error::TooLarge { .. } => "payload_too_large",
+            Error::Body(_) => "bad_request",
+            Error::Io(_) | Error::Json(_) => "internal_error",
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status.is_server_error() {
+            error!("{self}");
+        }
+        let body = APIError {
+            code: self.code().to_string(),
+            message: self.to_string(),
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+pub struct Config {
+    /// Directory artifacts are stored in
+    pub storage_dir: PathBuf,
+    /// Bearer tokens accepted by the server, mapped to the teams each of them
+    /// may access. A token without any teams may access every team.
+    pub tokens: HashMap<String, Vec<String>>,
+    /// Total size in bytes the stored artifacts may take up before the
+    /// least recently used ones are evicted
+    pub max_size: Option<u64>,
+    /// Key that clients sign artifacts with. If set, uploads must carry an
+    /// `x-artifact-tag` signature made with it.
+    pub signature_key: Option<Vec<u8>>,
+}
+
+struct AppState {
+    storage: Storage,
+    tokens: Vec<(String, HashSet<String>)>,
+    signature_key: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TeamParams {
+    #[serde(rename = "teamId")]
+    team_id: Option<String>,
+    slug: Option<String>,
+}
+
+impl AppState {
+    /// Checks the request's credentials and returns the team it was made
+    /// for.
+    fn authorize<'a>(
+        &self,
+        headers: &HeaderMap,
+        params: &'a TeamParams,
+    ) -> Result<Option<&'a str>, Error> {
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(Error::Unauthorized)?;
+        // Check every token so the response time doesn't reveal which one
+        // came closest to matching
+        let teams = self
+            .tokens
+            .iter()
+            .fold(None, |teams, (expected, allowed)| {
+                match constant_time_eq(expected.as_bytes(), token.as_bytes()) {
+                    true => Some(allowed),
+                    false => teams,
+                }
+            })
+            .ok_or(Error::Unauthorized)?;
+
+        let team = params.team_id.as_deref().or(params.slug.as_deref());
+        if !teams.is_empty() && !team.map_or(false, |team| teams.contains(team)) {
+            return Err(Error::ForbiddenTeam);
+        }
+
+        Ok(team)
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// Passes the body through, failing at its end if it doesn't match the tag.
+// Storage discards an artifact whose body fails, so unsigned contents are
+// never stored.
+fn verify_signature(body: ArtifactBody, mac: HmacSha256, tag: Vec<u8>) -> ArtifactBody {
+    Box::pin(stream::unfold(
+        (body, Some((mac, tag))),
+        |(mut body, verifier)| async move {
+            let (mut mac, tag) = verifier?;
+            match body.next().await {
+                Some(Ok(chunk)) => {
+                    mac.update(&chunk);
+                    Some((Ok(chunk), (body, Some((mac, tag)))))
+                }
+                Some(Err(e)) => Some((Err(e), (body, None))),
+                None => match mac.verify_slice(&tag) {
+                    Ok(()) => None,
+                    Err(_) => Some((Err(Error::InvalidSignature), (body, None))),
+                },
+            }
+        },
+    ))
+}
+
+fn metadata_headers(metadata: &ArtifactMetadata) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/octet-stream"),
+    );
+    headers.insert(
+        ARTIFACT_DURATION_HEADER,
+        HeaderValue::from(metadata.duration),
+    );
+    if let Some(tag) = metadata
+        .tag
+        .as_deref()
+        .and_then(|tag| HeaderValue::from_str(tag).ok())
+    {
+        headers.insert(ARTIFACT_TAG_HEADER, tag);
+    }
+    headers
+}
+
+async fn caching_status() -> Json<CachingStatusResponse> {
+    Json(CachingStatusResponse {
+        status: CachingStatus::Enabled,
+    })
+}
+
+async fn artifact_exists(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+    Query(params): Query<TeamParams>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let key = ArtifactKey::new(state.authorize(&headers, &params)?, &hash)?;
+    match state.storage.metadata(&key).await? {
+        Some((_, metadata)) => Ok((metadata_headers(&metadata), ()).into_response()),
+        None => Ok(StatusCode::NOT_FOUND.into_response()),
+    }
+}
+
+async fn fetch_artifact(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+    Query(params): Query<TeamParams>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let key = ArtifactKey::new(state.authorize(&headers, &params)?, &hash)?;
+    match state.storage.get(&key).await? {
+        Some((file, size, metadata)) => {
+            let mut headers = metadata_headers(&metadata);
+            headers.insert(header::CONTENT_LENGTH, HeaderValue::from(size));
+            Ok((headers, StreamBody::new(ReaderStream::new(file))).into_response())
+        }
+        None => Ok(StatusCode::NOT_FOUND.into_response()),
+    }
+}
+
+async fn put_artifact(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+    Query(params): Query<TeamParams>,
+    headers: HeaderMap,
+    body: BodyStream,
+) -> Result<Response, Error> {
+    let key = ArtifactKey::new(state.authorize(&headers, &params)?, &hash)?;
+
+    let duration = headers
+        .get(ARTIFACT_DURATION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0);
+    let tag = headers
+        .get(ARTIFACT_TAG_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|tag| tag.to_string());
+
+    let mut body: ArtifactBody = Box::pin(body.map_err(Error::Body));
+    if let Some(signature_key) = &state.signature_key {
+        let tag = tag.as_deref().ok_or(Error::MissingTag)?;
+        let tag = BASE64_STANDARD
+            .decode(tag)
+            .map_err(|_| Error::InvalidSignature)?;
+        // Clients sign the artifact hash and their team id, followed by the
+        // artifact itself
+        let mut mac =
+            HmacSha256::new_from_slice(signature_key).expect("HMAC can take keys of any size");
+        mac.update(hash.as_bytes());
+        mac.update(params.team_id.as_deref().unwrap_or_default().as_bytes());
+        body = verify_signature(body, mac, tag);
+    }
+
+    state
+        .storage
+        .put(&key, &ArtifactMetadata { duration, tag }, body)
+        .await?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({ "urls": [] })),
+    )
+        .into_response())
+}
+
+// Analytics are accepted so clients don't log errors, but aren't recorded
+async fn record_events(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<TeamParams>,
+    headers: HeaderMap,
+) -> Result<StatusCode, Error> {
+    state.authorize(&headers, &params)?;
+    Ok(StatusCode::OK)
+}
+
+/// Builds the cache server's router, opening the storage directory.
+pub fn router(config: Config) -> Result<Router, Error> {
+    // Teams are used as directory names, so reject invalid ones upfront
+    // rather than on every request
+    if let Some(team) = config
+        .tokens
+        .values()
+        .flatten()
+        .find(|team| !storage::is_valid_team(team))
+    {
+        return Err(Error::InvalidTeam(team.clone()));
+    }
+    let state = Arc::new(AppState {
+        storage: Storage::open(config.storage_dir, config.max_size)?,
+        tokens: config
+            .tokens
+            .into_iter()
+            .map(|(token, teams)| (token, teams.into_iter().collect()))
+            .collect(),
+        signature_key: config.signature_key,
+    });
+
+    Ok(Router::new()
+        .route("/v8/artifacts/status", get(caching_status))
+        .route("/v8/artifacts/events", post(record_events))
+        .route(
+            "/v8/artifacts/:hash",
+            get(fetch_artifact).head(artifact_exists).put(put_artifact),
+        )
+        .with_state(state))
+}
+
+/// Serves the cache on `addr` until `shutdown` resolves.
+pub async fn serve(
+    config: Config,
+    addr: SocketAddr,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    let app = router(config)?;
+    let handle = axum_server::Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        shutdown.await;
+        shutdown_handle.graceful_shutdown(None);
+    });
+
+    info!("listening on {addr}");
+    axum_server::bind(addr)
+        .handle(handle)
+        .serve(app.into_make_service())
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use nxpkgrepo_api_client::{APIClient, Client};
+
+    use super::*;
+
+    const TOKEN: &str = "secret";
+    const TEAM: &str = "team_allowed";
+    const SIGNATURE_KEY: &[u8] = b"signature key";
+
+    async fn start(config: Config) -> Result<(String, tokio::sync::oneshot::Sender<()>)> {
+        let port = port_scanner::request_open_port().unwrap();
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(serve(config, addr, async move {
+            rx.await.ok();
+        }));
+        // Wait for the listener to come up
+        let base_url = format!("http://localhost:{port}");
+        let client = reqwest::Client::new();
+        for _ in 0..50 {
+            if client
+                .get(format!("{base_url}/v8/artifacts/status"))
+                .send()
+                .await
+                .is_ok()
+            {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        Ok((base_url, tx))
+    }
+
+    fn config(dir: &tempfile::TempDir) -> Config {
+        Config {
+            storage_dir: dir.path().to_path_buf(),
+            tokens: HashMap::from([(TOKEN.to_string(), vec![TEAM.to_string()])]),
+            max_size: None,
+            signature_key: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (base_url, _shutdown) = start(config(&dir)).await?;
+        let client = APIClient::new(&base_url, 10, "2.0.0", false)?;
+        let hash = "abcdef0123";
+        let body = b"artifact contents";
+
+        assert!(client
+            .artifact_exists(hash, TOKEN, Some(TEAM), None)
+            .await?
+            .is_none());
+
+        client
+            .put_artifact(hash, body, 123, Some("signature"), TOKEN, Some(TEAM), None)
+            .await?;
+
+        let response = client
+            .artifact_exists(hash, TOKEN, Some(TEAM), None)
+            .await?
+            .expect("artifact should exist");
+        assert_eq!(response.headers()[ARTIFACT_DURATION_HEADER], "123");
+
+        let response = client
+            .fetch_artifact(hash, TOKEN, Some(TEAM), None)
+            .await?
+            .expect("artifact should exist");
+        assert_eq!(response.headers()[ARTIFACT_TAG_HEADER], "signature");
+        assert_eq!(response.bytes().await?.as_ref(), body);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejects_invalid_credentials() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (base_url, _shutdown) = start(config(&dir)).await?;
+        let client = APIClient::new(&base_url, 10, "2.0.0", false)?;
+
+        assert!(client
+            .put_artifact("abc", b"", 0, None, "wrong", Some(TEAM), None)
+            .await
+            .is_err());
+        // Forbidden teams surface as a 403 with a structured error body
+        let err = client
+            .fetch_artifact("abc", TOKEN, Some("team_other"), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            nxpkgrepo_api_client::Error::UnknownStatus { ref code, .. } if code == "forbidden"
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_token_teams() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (base_url, _shutdown) = start(Config {
+            tokens: HashMap::from([
+                (TOKEN.to_string(), vec![TEAM.to_string()]),
+                ("other".to_string(), vec!["team_other".to_string()]),
+                ("admin".to_string(), Vec::new()),
+            ]),
+            ..config(&dir)
+        })
+        .await?;
+        let client = APIClient::new(&base_url, 10, "2.0.0", false)?;
+        let hash = "abcdef0123";
+
+        client
+            .put_artifact(hash, b"other", 0, None, "other", Some("team_other"), None)
+            .await?;
+        // Tokens can only reach the teams they were given
+        assert!(client
+            .fetch_artifact(hash, TOKEN, Some("team_other"), None)
+            .await
+            .is_err());
+        assert!(client
+            .put_artifact(hash, b"data", 0, None, "other", Some(TEAM), None)
+            .await
+            .is_err());
+        // A token without teams can reach all of them
+        let response = client
+            .fetch_artifact(hash, "admin", Some("team_other"), None)
+            .await?
+            .expect("artifact should exist");
+        assert_eq!(response.bytes().await?.as_ref(), b"other");
+
+        Ok(())
+    }
+
+    fn sign(hash: &str, team: &str, body: &[u8]) -> String {
+        let mut mac = HmacSha256::new_from_slice(SIGNATURE_KEY).unwrap();
+        mac.update(hash.as_bytes());
+        mac.update(team.as_bytes());
+        mac.update(body);
+        BASE64_STANDARD.encode(mac.finalize().into_bytes())
+    }
+
+    #[tokio::test]
+    async fn test_signature() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (base_url, _shutdown) = start(Config {
+            signature_key: Some(SIGNATURE_KEY.to_vec()),
+            ..config(&dir)
+        })
+        .await?;
+        let client = APIClient::new(&base_url, 10, "2.0.0", false)?;
+        let hash = "abcdef0123";
+
+        assert!(client
+            .put_artifact(hash, b"data", 0, None, TOKEN, Some(TEAM), None)
+            .await
+            .is_err());
+        // Signed for other contents
+        let forged = sign(hash, TEAM, b"other data");
+        assert!(client
+            .put_artifact(hash, b"data", 0, Some(&forged), TOKEN, Some(TEAM), None)
+            .await
+            .is_err());
+        assert!(client
+            .artifact_exists(hash, TOKEN, Some(TEAM), None)
+            .await?
+            .is_none());
+
+        let tag = sign(hash, TEAM, b"data");
+        client
+            .put_artifact(hash, b"data", 0, Some(&tag), TOKEN, Some(TEAM), None)
+            .await?;
+        let response = client
+            .fetch_artifact(hash, TOKEN, Some(TEAM), None)
+            .await?
+            .expect("artifact should exist");
+        assert_eq!(response.headers()[ARTIFACT_TAG_HEADER], tag.as_str());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    path::PathBuf,
+};
+
+use anyhow::Result;
+use clap::Parser;
+use nxpkgrepo_cache_server::{serve, Config};
+use tracing_subscriber::EnvFilter;
+
+/// Self-hosted remote cache server for nxpkg
+#[derive(Parser, Debug)]
+#[command(name = "nxpkg-cache-server", version)]
+struct Args {
+    /// Address to listen on
+    #[arg(
+        long,
+        env = "NXPKG_CACHE_SERVER_ADDR",
+        default_value = "127.0.0.1:3000"
+    )]
+    addr: SocketAddr,
+    /// Directory artifacts are stored in
+    #[arg(long, env = "NXPKG_CACHE_SERVER_STORAGE_DIR")]
+    storage_dir: PathBuf,
+    /// Bearer token clients must authenticate with, as `TOKEN` to allow every
+    /// team or `TOKEN=TEAM` to only allow the given team. Can be given
+    /// multiple times, or as a comma separated list.
+    #[arg(
+        long = "token",
+        env = "NXPKG_CACHE_SERVER_TOKENS",
+        value_delimiter = ',',
+        required = true
+    )]
+    tokens: Vec<String>,
+    /// Maximum total size of stored artifacts in bytes. The least recently
+    /// used artifacts are evicted once it is exceeded.
+    #[arg(long, env = "NXPKG_CACHE_SERVER_MAX_SIZE")]
+    max_size: Option<u64>,
+    /// Key that clients sign artifacts with, set with
+    /// NXPKG_REMOTE_CACHE_SIGNATURE_KEY. Uploads that aren't signed with it
+    /// are rejected.
+    #[arg(long, env = "NXPKG_CACHE_SERVER_SIGNATURE_KEY")]
+    signature_key: Option<String>,
+}
+
+// Groups `TOKEN=TEAM` arguments by token. A token that is also given on its
+// own may access every team.
+fn team_tokens(args: Vec<String>) -> HashMap<String, Vec<String>> {
+    let mut tokens: HashMap<String, Vec<String>> = HashMap::new();
+    let mut unrestricted = HashSet::new();
+    for arg in args {
+        match arg.split_once('=') {
+            Some((token, team)) => tokens
+                .entry(token.to_string())
+                .or_default()
+                .push(team.to_string()),
+            None => {
+                unrestricted.insert(arg);
+            }
+        }
+    }
+    for token in unrestricted {
+        tokens.insert(token, Vec::new());
+    }
+    tokens
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
+        .init();
+
+    let args = Args::parse();
+    let config = Config {
+        storage_dir: args.storage_dir,
+        tokens: team_tokens(args.tokens),
+        max_size: args.max_size,
+        signature_key: args.signature_key.map(String::into_bytes),
+    };
+
+    serve(config, args.addr, async {
+        tokio::signal::ctrl_c().await.ok();
+    })
+    .await
+}
@@ -0,0 +1,536 @@
+//! On-disk artifact storage with size-bounded LRU eviction.
+//!
+//! Artifacts live at `<root>/<namespace>/<hash>` next to a `<hash>.json`
+//! file holding the metadata the client sent along with the upload. The
+//! namespace is `default` for requests without a team and `teams/<team>`
+//! otherwise.
+//!
+//! An in-memory index tracks the size and recency of every artifact. It is
+//! rebuilt from the file system on startup, using modification times as the
+//! initial access order.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::SystemTime,
+};
+
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::{fs::File, io::AsyncWriteExt};
+use tracing::{debug, warn};
+
+use crate::Error;
+
+const DEFAULT_NAMESPACE: &str = "default";
+const TEAMS_DIR: &str = "teams";
+const METADATA_EXTENSION: &str = "json";
+const TEMP_EXTENSION: &str = "tmp";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ArtifactKey {
+    namespace: String,
+    hash: String,
+}
+
+impl ArtifactKey {
+    /// Builds a key for `hash`, scoped to `team` if one is given.
+    ///
+    /// Both values end up as path components, so they're restricted to a
+    /// safe character set.
+    pub fn new(team: Option<&str>, hash: &str) -> Result<Self, Error> {
+        if !is_valid_hash(hash) {
+            return Err(Error::InvalidHash(hash.to_string()));
+        }
+        let namespace = match team {
+            Some(team) if is_valid_team(team) => format!("{TEAMS_DIR}/{team}"),
+            Some(team) => return Err(Error::InvalidTeam(team.to_string())),
+            None => DEFAULT_NAMESPACE.to_string(),
+        };
+        Ok(Self {
+            namespace,
+            hash: hash.to_string(),
+        })
+    }
+}
+
+fn is_valid_hash(hash: &str) -> bool {
+    !hash.is_empty() && hash.len() <= 128 && hash.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+pub(crate) fn is_valid_team(team: &str) -> bool {
+    !team.is_empty()
+        && team.len() <= 128
+        && team
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Metadata sent by the client when uploading an artifact and returned as
+/// headers when it is requested.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactMetadata {
+    /// Time in milliseconds it took to produce the artifact
+    pub duration: u64,
+    /// Signature of the artifact, if the client signs its uploads
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    size: u64,
+    last_access: u64,
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    entries: HashMap<ArtifactKey, Entry>,
+    // Ordered by last access, oldest first
+    lru: BTreeMap<u64, ArtifactKey>,
+    clock: u64,
+    total_size: u64,
+}
+
+impl Index {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn insert(&mut self, key: ArtifactKey, size: u64) {
+        self.remove(&key);
+        let last_access = self.tick();
+        self.lru.insert(last_access, key.clone());
+        self.entries.insert(key, Entry { size, last_access });
+        self.total_size += size;
+    }
+
+    fn touch(&mut self, key: &ArtifactKey) -> Option<Entry> {
+        let last_access = self.tick();
+        let entry = self.entries.get_mut(key)?;
+        self.lru.remove(&entry.last_access);
+        entry.last_access = last_access;
+        self.lru.insert(last_access, key.clone());
+        Some(*entry)
+    }
+
+    fn remove(&mut self, key: &ArtifactKey) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.lru.remove(&entry.last_access);
+        self.total_size -= entry.size;
+        Some(entry)
+    }
+
+    fn pop_lru(&mut self) -> Option<ArtifactKey> {
+        let (_, key) = self.lru.pop_first()?;
+        let entry = self
+            .entries
+            .remove(&key)
+            .expect("lru and entries must be in sync");
+        self.total_size -= entry.size;
+        Some(key)
+    }
+}
+
+pub struct Storage {
+    root: PathBuf,
+    max_size: Option<u64>,
+    index: Mutex<Index>,
+    temp_counter: AtomicU64,
+}
+
+impl Storage {
+    /// Opens the storage at `root`, creating it if needed and indexing any
+    /// artifacts left by a previous run.
+    pub fn open(root: impl Into<PathBuf>, max_size: Option<u64>) -> Result<Self, Error> {
+        let root = root.into();
+        std::fs::create_dir_all(root.join(DEFAULT_NAMESPACE))?;
+        std::fs::create_dir_all(root.join(TEAMS_DIR))?;
+
+        let mut found = Vec::new();
+        scan_namespace(&root, DEFAULT_NAMESPACE, &mut found)?;
+        for team_dir in std::fs::read_dir(root.join(TEAMS_DIR))? {
+            let team_dir = team_dir?;
+            let name = team_dir.file_name();
+            match name.to_str() {
+                Some(team) if is_valid_team(team) && team_dir.file_type()?.is_dir() => {
+                    scan_namespace(&root, &format!("{TEAMS_DIR}/{team}"), &mut found)?;
+                }
+                _ => warn!("ignoring unexpected entry {}", team_dir.path().display()),
+            }
+        }
+        found.sort_by_key(|(_, _, modified)| *modified);
+
+        let mut index = Index::default();
+        for (key, size, _) in found {
+            index.insert(key, size);
+        }
+        debug!(
+            "indexed {} artifacts ({} bytes)",
+            index.entries.len(),
+            index.total_size
+        );
+
+        let storage = Self {
+            root,
+            max_size,
+            index: Mutex::new(index),
+            temp_counter: AtomicU64::new(0),
+        };
+        // The limit might have been lowered since the last run
+        for key in storage.collect_evictions() {
+            remove_artifact_files(&storage.artifact_path(&key), &storage.metadata_path(&key));
+        }
+
+        Ok(storage)
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.index.lock().expect("index lock poisoned").total_size
+    }
+
+    pub fn contains(&self, key: &ArtifactKey) -> bool {
+        self.index
+            .lock()
+            .expect("index lock poisoned")
+            .entries
+            .contains_key(key)
+    }
+
+    fn artifact_path(&self, key: &ArtifactKey) -> PathBuf {
+        self.root.join(&key.namespace).join(&key.hash)
+    }
+
+    fn metadata_path(&self, key: &ArtifactKey) -> PathBuf {
+        self.artifact_path(key).with_extension(METADATA_EXTENSION)
+    }
+
+    fn temp_path(&self, key: &ArtifactKey, suffix: &str) -> PathBuf {
+        let n = self.temp_counter.fetch_add(1, Ordering::Relaxed);
+        self.root.join(&key.namespace).join(format!(
+            ".{}.{}.{n}{suffix}.{TEMP_EXTENSION}",
+            key.hash,
+            std::process::id()
+        ))
+    }
+
+    /// Looks up an artifact and marks it as recently used.
+    ///
+    /// Returns the size of the artifact along with its metadata.
+    pub async fn metadata(
+        &self,
+        key: &ArtifactKey,
+    ) -> Result<Option<(u64, ArtifactMetadata)>, Error> {
+        let Some(entry) = self.index.lock().expect("index lock poisoned").touch(key) else {
+            return Ok(None);
+        };
+        let metadata = match tokio::fs::read(self.metadata_path(key)).await {
+            Ok(contents) => serde_json::from_slice(&contents).unwrap_or_else(|e| {
+                warn!("invalid metadata for {}: {e}", key.hash);
+                ArtifactMetadata::default()
+            }),
+            Err(e) if e.kind() == ErrorKind::NotFound => ArtifactMetadata::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some((entry.size, metadata)))
+    }
+
+    /// Opens an artifact for reading and marks it as recently used.
+    pub async fn get(
+        &self,
+        key: &ArtifactKey,
+    ) -> Result<Option<(File, u64, ArtifactMetadata)>, Error> {
+        let Some((size, metadata)) = self.metadata(key).await? else {
+            return Ok(None);
+        };
+        match File::open(self.artifact_path(key)).await {
+            Ok(file) => Ok(Some((file, size, metadata))),
+            // Evicted between the index lookup and opening the file
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                self.index.lock().expect("index lock poisoned").remove(key);
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Stores an artifact, replacing any existing one with the same key, and
+    /// evicts the least recently used artifacts if the storage is over its
+    /// size limit.
+    ///
+    /// The body is streamed to a temporary file first so readers never
+    /// observe a partially written artifact.
+    pub async fn put(
+        &self,
+        key: &ArtifactKey,
+        metadata: &ArtifactMetadata,
+        body: impl Stream<Item = Result<Bytes, Error>> + Unpin,
+    ) -> Result<(), Error> {
+        let temp_path = self.temp_path(key, "");
+        let temp_metadata_path = self.temp_path(key, ".meta");
+        let result = self
+            .write_artifact(key, metadata, body, &temp_path, &temp_metadata_path)
+            .await;
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            let _ = tokio::fs::remove_file(&temp_metadata_path).await;
+        }
+        let size = result?;
+
+        self.index
+            .lock()
+            .expect("index lock poisoned")
+            .insert(key.clone(), size);
+
+        for key in self.collect_evictions() {
+            debug!("evicting {}/{}", key.namespace, key.hash);
+            let artifact_path = self.artifact_path(&key);
+            let metadata_path = self.metadata_path(&key);
+            tokio::task::spawn_blocking(move || {
+                remove_artifact_files(&artifact_path, &metadata_path)
+            })
+            .await
+            .ok();
+        }
+
+        Ok(())
+    }
+
+    async fn write_artifact(
+        &self,
+        key: &ArtifactKey,
+        metadata: &ArtifactMetadata,
+        mut body: impl Stream<Item = Result<Bytes, Error>> + Unpin,
+        temp_path: &Path,
+        temp_metadata_path: &Path,
+    ) -> Result<u64, Error> {
+        // Team namespaces are only created by their first upload
+        tokio::fs::create_dir_all(self.root.join(&key.namespace)).await?;
+        let mut file = File::create(temp_path).await?;
+        let mut size = 0u64;
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk?;
+            size += chunk.len() as u64;
+            if let Some(limit) = self.max_size {
+                if size > limit {
+                    return Err(Error::TooLarge { limit });
+                }
+            }
+            file.write_all(&chunk).await?;
+        }
+        file.sync_all().await?;
+        drop(file);
+
+        tokio::fs::write(temp_metadata_path, serde_json::to_vec(metadata)?).await?;
+        tokio::fs::rename(temp_metadata_path, self.metadata_path(key)).await?;
+        tokio::fs::rename(temp_path, self.artifact_path(key)).await?;
+
+        Ok(size)
+    }
+
+    fn collect_evictions(&self) -> Vec<ArtifactKey> {
+        let Some(max_size) = self.max_size else {
+            return Vec::new();
+        };
+        let mut index = self.index.lock().expect("index lock poisoned");
+        let mut evicted = Vec::new();
+        while index.total_size > max_size {
+            let Some(key) = index.pop_lru() else {
+                break;
+            };
+            evicted.push(key);
+        }
+        evicted
+    }
+}
+
+fn remove_artifact_files(artifact_path: &Path, metadata_path: &Path) {
+    for path in [artifact_path, metadata_path] {
+        if let Err(e) = std::fs::remove_file(path) {
+            if e.kind() != ErrorKind::NotFound {
+                warn!("failed to remove {}: {e}", path.display());
+            }
+        }
+    }
+}
+
+fn scan_namespace(
+    root: &Path,
+    namespace: &str,
+    found: &mut Vec<(ArtifactKey, u64, SystemTime)>,
+) -> Result<(), Error> {
+    let dir = root.join(namespace);
+    std::fs::create_dir_all(&dir)?;
+    for entry in std::fs::read_dir(&dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if name.ends_with(TEMP_EXTENSION) && name.starts_with('.') {
+            // Leftover from an interrupted upload
+            let _ = std::fs::remove_file(&path);
+            continue;
+        }
+        if !is_valid_hash(name) {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        found.push((
+            ArtifactKey {
+                namespace: namespace.to_string(),
+                hash: name.to_string(),
+            },
+            metadata.len(),
+            metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use futures_util::stream;
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    fn body(contents: &'static [u8]) -> impl Stream<Item = Result<Bytes, Error>> + Unpin {
+        stream::iter([Ok(Bytes::from_static(contents))])
+    }
+
+    async fn read(storage: &Storage, key: &ArtifactKey) -> Option<Vec<u8>> {
+        let (mut file, _, _) = storage.get(key).await.unwrap()?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).await.unwrap();
+        Some(contents)
+    }
+
+    #[test]
+    fn test_key_validation() {
+        assert!(ArtifactKey::new(None, "abc123").is_ok());
+        assert!(ArtifactKey::new(Some("team_my-team"), "abc123").is_ok());
+        assert!(matches!(
+            ArtifactKey::new(None, "../etc/passwd"),
+            Err(Error::InvalidHash(_))
+        ));
+        assert!(matches!(
+            ArtifactKey::new(Some("../other"), "abc123"),
+            Err(Error::InvalidTeam(_))
+        ));
+        assert!(matches!(
+            ArtifactKey::new(None, ""),
+            Err(Error::InvalidHash(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_put_get() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::open(dir.path(), None).unwrap();
+        let key = ArtifactKey::new(Some("team_a"), "abc").unwrap();
+        let metadata = ArtifactMetadata {
+            duration: 42,
+            tag: Some("tag".to_string()),
+        };
+
+        storage.put(&key, &metadata, body(b"hello")).await.unwrap();
+
+        let (size, stored) = storage.metadata(&key).await.unwrap().unwrap();
+        assert_eq!(size, 5);
+        assert_eq!(stored, metadata);
+        assert_eq!(read(&storage, &key).await.unwrap(), b"hello");
+
+        // Teams don't share artifacts
+        let other = ArtifactKey::new(Some("team_b"), "abc").unwrap();
+        assert!(storage.metadata(&other).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_put_creates_team_namespace() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::open(dir.path(), None).unwrap();
+        let key = ArtifactKey::new(Some("team_new"), "abc").unwrap();
+        assert!(!dir.path().join("teams/team_new").exists());
+
+        storage
+            .put(&key, &ArtifactMetadata::default(), body(b"hello"))
+            .await
+            .unwrap();
+
+        assert!(dir.path().join("teams/team_new/abc").is_file());
+        assert_eq!(read(&storage, &key).await.unwrap(), b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::open(dir.path(), Some(10)).unwrap();
+        let metadata = ArtifactMetadata::default();
+        let a = ArtifactKey::new(None, "a").unwrap();
+        let b = ArtifactKey::new(None, "b").unwrap();
+        let c = ArtifactKey::new(None, "c").unwrap();
+
+        storage.put(&a, &metadata, body(b"aaaa")).await.unwrap();
+        storage.put(&b, &metadata, body(b"bbbb")).await.unwrap();
+        // Reading `a` makes `b` the oldest entry
+        assert!(read(&storage, &a).await.is_some());
+        storage.put(&c, &metadata, body(b"cccc")).await.unwrap();
+
+        assert!(storage.contains(&a));
+        assert!(!storage.contains(&b));
+        assert!(storage.contains(&c));
+        assert_eq!(storage.total_size(), 8);
+        assert!(!dir.path().join("default/b").exists());
+        assert!(!dir.path().join("default/b.json").exists());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_artifacts_over_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::open(dir.path(), Some(4)).unwrap();
+        let key = ArtifactKey::new(None, "big").unwrap();
+
+        let result = storage
+            .put(&key, &ArtifactMetadata::default(), body(b"too big"))
+            .await;
+
+        assert!(matches!(result, Err(Error::TooLarge { limit: 4 })));
+        assert!(!storage.contains(&key));
+        assert_eq!(
+            std::fs::read_dir(dir.path().join(DEFAULT_NAMESPACE))
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reopen_restores_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = ArtifactKey::new(Some("team_a"), "abc").unwrap();
+        {
+            let storage = Storage::open(dir.path(), None).unwrap();
+            storage
+                .put(&key, &ArtifactMetadata::default(), body(b"hello"))
+                .await
+                .unwrap();
+        }
+        std::fs::write(dir.path().join("teams/team_a/.abc.1.0.tmp"), b"partial").unwrap();
+
+        let storage = Storage::open(dir.path(), None).unwrap();
+
+        assert!(storage.contains(&key));
+        assert_eq!(storage.total_size(), 5);
+        assert!(!dir.path().join("teams/team_a/.abc.1.0.tmp").exists());
+    }
+}
@@ -5,6 +5,7 @@ use tokio::{
     sync::{mpsc, Semaphore},
     task::JoinHandle,
 };
+use tracing::{debug, warn};
 use nxpkgpath::{AbsoluteSystemPath, AbsoluteSystemPathBuf, AnchoredSystemPathBuf};
 use nxpkgrepo_analytics::AnalyticsSender;
 use nxpkgrepo_api_client::{APIAuth, APIClient};
@@ -15,6 +16,7 @@ pub struct AsyncCache {
     real_cache: Arc<CacheMultiplexer>,
     writer_sender: mpsc::Sender<WorkerRequest>,
     writer_thread: JoinHandle<()>,
+    eviction_thread: Option<JoinHandle<()>>,
 }
 
 enum WorkerRequest {
@@ -46,6 +48,22 @@ impl AsyncCache {
         )?);
         let (writer_sender, mut write_consumer) = mpsc::channel(1);
 
+        // Evict old entries from the filesystem cache while the run goes on.
+        // Entries written by this run are the most recently used ones, so
+        // they're only pruned once a later run finds the cache over its limit.
+        let eviction_policy = opts.fs_eviction;
+        let eviction_thread = eviction_policy.is_enabled().then(|| {
+            let real_cache = real_cache.clone();
+            tokio::task::spawn_blocking(move || match real_cache.prune_fs(&eviction_policy) {
+                Ok(Some(summary)) => debug!(
+                    "evicted {} entries ({} bytes) from the filesystem cache",
+                    summary.evicted_entries, summary.evicted_bytes
+                ),
+                Ok(None) => {}
+                Err(e) => warn!("failed to prune filesystem cache: {e}"),
+            })
+        });
+
         // start a task to manage workers
         let worker_real_cache = real_cache.clone();
         let writer_thread = tokio::spawn(async move {
@@ -90,6 +108,7 @@ impl AsyncCache {
             real_cache,
             writer_sender,
             writer_thread,
+            eviction_thread,
         })
     }
 
@@ -143,8 +162,15 @@ impl AsyncCache {
     }
 
     pub async fn shutdown(self) {
-        let Self { writer_thread, .. } = self;
+        let Self {
+            writer_thread,
+            eviction_thread,
+            ..
+        } = self;
         writer_thread.await.unwrap();
+        if let Some(eviction_thread) = eviction_thread {
+            eviction_thread.await.unwrap();
+        }
     }
 }
 
@@ -195,7 +221,10 @@ mod tests {
             remote_cache_opts: Some(RemoteCacheOpts {
                 team_id: "my-team".to_string(),
                 signature: false,
+                content_addressed: false,
             }),
+            fs_eviction: Default::default(),
+            backends: Vec::new(),
         };
 
         let api_client = APIClient::new(format!("http://localhost:{}", port), 200, "2.0.0", true)?;
@@ -233,7 +262,7 @@ mod tests {
             "node_modules",
             ".cache",
             "nxpkg",
-            &format!("{}.tar.zst", hash),
+            &format!("{}.nxpkgca", hash),
         ]);
 
         // Confirm that fs cache file does *not* exist
@@ -268,7 +297,10 @@ mod tests {
             remote_cache_opts: Some(RemoteCacheOpts {
                 team_id: "my-team".to_string(),
                 signature: false,
+                content_addressed: false,
             }),
+            fs_eviction: Default::default(),
+            backends: Vec::new(),
         };
 
         // Initialize client with invalid API url to ensure that we don't hit the
@@ -308,7 +340,7 @@ mod tests {
             "node_modules",
             ".cache",
             "nxpkg",
-            &format!("{}.tar.zst", hash),
+            &format!("{}.nxpkgca", hash),
         ]);
 
         // Confirm that fs cache file exists
@@ -351,7 +383,10 @@ mod tests {
             remote_cache_opts: Some(RemoteCacheOpts {
                 team_id: "my-team".to_string(),
                 signature: false,
+                content_addressed: false,
             }),
+            fs_eviction: Default::default(),
+            backends: Vec::new(),
         };
 
         let api_client = APIClient::new(format!("http://localhost:{}", port), 200, "2.0.0", true)?;
@@ -389,7 +424,7 @@ mod tests {
             "node_modules",
             ".cache",
             "nxpkg",
-            &format!("{}.tar.zst", hash),
+            &format!("{}.nxpkgca", hash),
         ]);
 
         // Confirm that fs cache file exists
@@ -0,0 +1,202 @@
+//! Stores the cache multiplexer can read artifacts from and write them to.
+//!
+//! Besides the filesystem and Vercel caches, artifacts can be kept in any
+//! object store configured under `remoteCache.backends` in `nxpkg.json`.
+mod object_store;
+mod s3;
+#[cfg(test)]
+pub(crate) mod test_server;
+mod webdav;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use nxpkgpath::{AbsoluteSystemPath, AnchoredSystemPathBuf};
+
+pub use self::{
+    object_store::{ObjectStore, ObjectStoreCache},
+    s3::{S3Credentials, S3Store},
+    webdav::WebDavStore,
+};
+use crate::{fs::FSCache, http::HTTPCache, CacheError, CacheHitMetadata, CacheOpts};
+
+#[async_trait]
+pub trait CacheBackend: Send + Sync {
+    /// Name of the backend, used when logging
+    fn name(&self) -> &str;
+
+    async fn put(
+        &self,
+        anchor: &AbsoluteSystemPath,
+        hash: &str,
+        files: &[AnchoredSystemPathBuf],
+        duration: u64,
+    ) -> Result<(), CacheError>;
+
+    async fn fetch(
+        &self,
+        anchor: &AbsoluteSystemPath,
+        hash: &str,
+    ) -> Result<Option<(CacheHitMetadata, Vec<AnchoredSystemPathBuf>)>, CacheError>;
+
+    async fn exists(&self, hash: &str) -> Result<Option<CacheHitMetadata>, CacheError>;
+}
+
+/// A backend as configured in `nxpkg.json`.
+///
+/// Credentials are never part of the configuration, they're read from the
+/// environment instead.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum BackendConfig {
+    /// An S3-compatible object store, authenticated with the
+    /// `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and optional
+    /// `AWS_SESSION_TOKEN` environment variables
+    S3(S3Config),
+    /// A WebDAV server, or any other HTTP server accepting `PUT`s
+    Http(HttpStoreConfig),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct S3Config {
+    pub bucket: String,
+    /// Defaults to `AWS_REGION`, then `us-east-1`
+    #[serde(default)]
+    pub region: Option<String>,
+    /// Endpoint of an S3-compatible store, defaults to AWS
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// Prefix prepended to every object key
+    #[serde(default)]
+    pub prefix: String,
+    /// Address the bucket as part of the path rather than the host name.
+    /// Always used with a custom `endpoint`.
+    #[serde(default)]
+    pub force_path_style: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpStoreConfig {
+    /// Base URL objects are stored under
+    pub url: String,
+    /// Environment variable holding a bearer token to authenticate with
+    #[serde(default)]
+    pub token_env: Option<String>,
+}
+
+impl BackendConfig {
+    /// Builds the backend. Like the Vercel cache, it signs and
+    /// content-addresses artifacts as `opts.remote_cache_opts` asks it to.
+    pub fn build(&self, opts: &CacheOpts) -> Result<Box<dyn CacheBackend>, CacheError> {
+        Ok(match self {
+            BackendConfig::S3(config) => Box::new(ObjectStoreCache::new(
+                format!("s3://{}", config.bucket),
+                S3Store::from_env(config)?,
+                opts,
+            )),
+            BackendConfig::Http(config) => Box::new(ObjectStoreCache::new(
+                config.url.clone(),
+                WebDavStore::from_env(config)?,
+                opts,
+            )),
+        })
+    }
+}
+
+#[async_trait]
+impl CacheBackend for FSCache {
+    fn name(&self) -> &str {
+        "filesystem"
+    }
+
+    async fn put(
+        &self,
+        anchor: &AbsoluteSystemPath,
+        hash: &str,
+        files: &[AnchoredSystemPathBuf],
+        duration: u64,
+    ) -> Result<(), CacheError> {
+        FSCache::put(self, anchor, hash, files, duration)
+    }
+
+    async fn fetch(
+        &self,
+        anchor: &AbsoluteSystemPath,
+        hash: &str,
+    ) -> Result<Option<(CacheHitMetadata, Vec<AnchoredSystemPathBuf>)>, CacheError> {
+        FSCache::fetch(self, anchor, hash)
+    }
+
+    async fn exists(&self, hash: &str) -> Result<Option<CacheHitMetadata>, CacheError> {
+        FSCache::exists(self, hash)
+    }
+}
+
+#[async_trait]
+impl CacheBackend for HTTPCache {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    async fn put(
+        &self,
+        anchor: &AbsoluteSystemPath,
+        hash: &str,
+        files: &[AnchoredSystemPathBuf],
+        duration: u64,
+    ) -> Result<(), CacheError> {
+        HTTPCache::put(self, anchor, hash, files, duration).await
+    }
+
+    // The HTTP cache always restores into the repository root
+    async fn fetch(
+        &self,
+        _anchor: &AbsoluteSystemPath,
+        hash: &str,
+    ) -> Result<Option<(CacheHitMetadata, Vec<AnchoredSystemPathBuf>)>, CacheError> {
+        HTTPCache::fetch(self, hash).await
+    }
+
+    async fn exists(&self, hash: &str) -> Result<Option<CacheHitMetadata>, CacheError> {
+        HTTPCache::exists(self, hash).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_backends() {
+        let backends: Vec<BackendConfig> = serde_json::from_str(
+            r#"[
+                {
+                    "type": "s3",
+                    "bucket": "artifacts",
+                    "endpoint": "http://localhost:9000",
+                    "forcePathStyle": true
+                },
+                { "type": "http", "url": "https://cache.example.com/nxpkg", "tokenEnv": "CACHE_TOKEN" }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            backends,
+            vec![
+                BackendConfig::S3(S3Config {
+                    bucket: "artifacts".to_string(),
+                    region: None,
+                    endpoint: Some("http://localhost:9000".to_string()),
+                    prefix: String::new(),
+                    force_path_style: true,
+                }),
+                BackendConfig::Http(HttpStoreConfig {
+                    url: "https://cache.example.com/nxpkg".to_string(),
+                    token_env: Some("CACHE_TOKEN".to_string()),
+                }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,387 @@
+use std::{backtrace::Backtrace, collections::HashSet};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use nxpkgpath::{AbsoluteSystemPath, AnchoredSystemPathBuf};
+
+use crate::{
+    backend::CacheBackend,
+    cache_archive::{CacheReader, CacheWriter, MemoryBlobStore},
+    signature_authentication::ArtifactSignatureAuthenticator,
+    CacheError, CacheHitMetadata, CacheOpts, CacheSource,
+};
+
+/// A store of opaque objects, addressed by key
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    /// Returns `None` if there is no object with the key
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, CacheError>;
+
+    async fn exists(&self, key: &str) -> Result<bool, CacheError>;
+
+    async fn put(&self, key: &str, body: Bytes) -> Result<(), CacheError>;
+}
+
+// Turns unsuccessful responses into errors
+pub(crate) fn check_status(response: reqwest::Response) -> Result<reqwest::Response, CacheError> {
+    let status = response.status();
+    if status.is_success() {
+        Ok(response)
+    } else {
+        Err(CacheError::BackendStatus {
+            status: status.as_u16(),
+            url: response.url().to_string(),
+            backtrace: Backtrace::capture(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ObjectMetadata {
+    hash: String,
+    duration: u64,
+    // Signature of the archive, if artifacts are signed. Objects can't carry
+    // headers in every store, so unlike the Vercel cache it's kept here.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+}
+
+/// Stores artifacts in an `ObjectStore`.
+///
+/// Every artifact is made up of:
+/// - `<hash>.tar.zst`, the archive
+/// - `blob-<digest>.zst` for each file in the archive, shared between
+///   artifacts, if it's content-addressed
+/// - `<hash>-meta.json`, written last, so an artifact is only visible once all
+///   of its objects are
+///
+/// Like the Vercel cache, archives are only content-addressed and signed if
+/// `remoteCache` opts into it. Blobs aren't signed, as the signed archive
+/// references them by digest.
+///
+/// Keys are kept flat so that stores without directories, like plain WebDAV
+/// collections, don't need to create any.
+pub struct ObjectStoreCache<S> {
+    name: String,
+    store: S,
+    signer_verifier: Option<ArtifactSignatureAuthenticator>,
+    content_addressed: bool,
+}
+
+impl<S: ObjectStore> ObjectStoreCache<S> {
+    pub fn new(name: String, store: S, opts: &CacheOpts) -> Self {
+        let remote_cache_opts = opts.remote_cache_opts.as_ref();
+        let signer_verifier = remote_cache_opts
+            .filter(|remote_cache_opts| remote_cache_opts.signature)
+            .map(|remote_cache_opts| {
+                ArtifactSignatureAuthenticator::new(
+                    remote_cache_opts.team_id.as_bytes().to_vec(),
+                    None,
+                )
+            });
+        let content_addressed = remote_cache_opts.map_or(false, |remote_cache_opts| {
+            remote_cache_opts.content_addressed
+        });
+
+        Self {
+            name,
+            store,
+            signer_verifier,
+            content_addressed,
+        }
+    }
+
+    fn archive_key(hash: &str) -> String {
+        format!("{hash}.tar.zst")
+    }
+
+    fn metadata_key(hash: &str) -> String {
+        format!("{hash}-meta.json")
+    }
+
+    fn blob_key(digest: &str) -> String {
+        format!("blob-{digest}.zst")
+    }
+
+    async fn read_metadata(&self, hash: &str) -> Result<Option<ObjectMetadata>, CacheError> {
+        let Some(body) = self.store.get(&Self::metadata_key(hash)).await? else {
+            return Ok(None);
+        };
+        let metadata = serde_json::from_slice(&body)
+            .map_err(|e| CacheError::InvalidMetadata(e, Backtrace::capture()))?;
+
+        Ok(Some(metadata))
+    }
+
+    async fn put_blob(&self, digest: &str, compressed: &[u8]) -> Result<(), CacheError> {
+        let key = Self::blob_key(digest);
+        if self.store.exists(&key).await? {
+            return Ok(());
+        }
+
+        self.store
+            .put(&key, Bytes::copy_from_slice(compressed))
+            .await
+    }
+
+    // Returns `None` if any of the blobs is missing
+    async fn fetch_blobs(
+        &self,
+        digests: HashSet<String>,
+    ) -> Result<Option<MemoryBlobStore>, CacheError> {
+        let digests: Vec<_> = digests.into_iter().collect();
+        let keys: Vec<_> = digests
+            .iter()
+            .map(|digest| Self::blob_key(digest))
+            .collect();
+        let bodies = try_join_all(keys.iter().map(|key| self.store.get(key))).await?;
+
+        let mut blobs = MemoryBlobStore::new();
+        for (digest, body) in digests.iter().zip(bodies) {
+            let Some(body) = body else {
+                debug!("{} is missing blob {digest}", self.name);
+                return Ok(None);
+            };
+            blobs.insert_compressed(digest, body.to_vec())?;
+        }
+
+        Ok(Some(blobs))
+    }
+}
+
+#[async_trait]
+impl<S: ObjectStore> CacheBackend for ObjectStoreCache<S> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn put(
+        &self,
+        anchor: &AbsoluteSystemPath,
+        hash: &str,
+        files: &[AnchoredSystemPathBuf],
+        duration: u64,
+    ) -> Result<(), CacheError> {
+        let mut blobs = MemoryBlobStore::new();
+        let mut archive = Vec::new();
+        {
+            let mut cache_writer = if self.content_addressed {
+                CacheWriter::from_writer_content_addressed(&mut archive, &mut blobs)?
+            } else {
+                CacheWriter::from_writer(&mut archive, true)?
+            };
+            for file in files {
+                cache_writer.add_file(anchor, file)?;
+            }
+            cache_writer.finish()?;
+        }
+        let tag = self
+            .signer_verifier
+            .as_ref()
+            .map(|signer| signer.generate_tag(hash.as_bytes(), &archive))
+            .transpose()?;
+
+        try_join_all(
+            blobs
+                .iter()
+                .map(|(digest, compressed)| self.put_blob(digest, compressed)),
+        )
+        .await?;
+        self.store
+            .put(&Self::archive_key(hash), Bytes::from(archive))
+            .await?;
+
+        let metadata = ObjectMetadata {
+            hash: hash.to_string(),
+            duration,
+            tag,
+        };
+        let metadata = serde_json::to_vec(&metadata)
+            .map_err(|e| CacheError::MetadataWriteFailure(e, Backtrace::capture()))?;
+        self.store
+            .put(&Self::metadata_key(hash), Bytes::from(metadata))
+            .await
+    }
+
+    async fn fetch(
+        &self,
+        anchor: &AbsoluteSystemPath,
+        hash: &str,
+    ) -> Result<Option<(CacheHitMetadata, Vec<AnchoredSystemPathBuf>)>, CacheError> {
+        let Some(metadata) = self.read_metadata(hash).await? else {
+            return Ok(None);
+        };
+        let Some(archive) = self.store.get(&Self::archive_key(hash)).await? else {
+            return Ok(None);
+        };
+
+        if let Some(signer_verifier) = &self.signer_verifier {
+            let expected_tag = metadata
+                .tag
+                .as_deref()
+                .ok_or(CacheError::ArtifactTagMissing(Backtrace::capture()))?;
+            if !signer_verifier.validate(hash.as_bytes(), &archive, expected_tag)? {
+                return Err(CacheError::InvalidTag(Backtrace::capture()));
+            }
+        }
+
+        let blob_references = {
+            let cache_reader = CacheReader::from_reader(&archive[..])?;
+            if cache_reader.is_content_addressed() {
+                Some(cache_reader.blob_references()?)
+            } else {
+                None
+            }
+        };
+        let blobs = match blob_references {
+            Some(digests) => {
+                let Some(blobs) = self.fetch_blobs(digests).await? else {
+                    return Ok(None);
+                };
+                Some(blobs)
+            }
+            None => None,
+        };
+
+        let mut cache_reader = CacheReader::from_reader(&archive[..])?;
+        if let Some(blobs) = &blobs {
+            cache_reader = cache_reader.with_blob_store(blobs);
+        }
+        let files = cache_reader.restore(anchor)?;
+
+        Ok(Some((
+            CacheHitMetadata {
+                source: CacheSource::Remote,
+                time_saved: metadata.duration,
+            },
+            files,
+        )))
+    }
+
+    async fn exists(&self, hash: &str) -> Result<Option<CacheHitMetadata>, CacheError> {
+        Ok(self
+            .read_metadata(hash)
+            .await?
+            .map(|metadata| CacheHitMetadata {
+                source: CacheSource::Remote,
+                time_saved: metadata.duration,
+            }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use anyhow::Result;
+    use tempfile::{tempdir, TempDir};
+    use nxpkgpath::{AbsoluteSystemPath, AnchoredSystemPathBuf};
+
+    use super::*;
+    use crate::backend::{test_server::TestServer, WebDavStore};
+
+    fn cache(
+        server: &TestServer,
+        signed: bool,
+        content_addressed: bool,
+    ) -> Result<ObjectStoreCache<WebDavStore>> {
+        Ok(ObjectStoreCache {
+            name: server.url(),
+            store: WebDavStore::new(&server.url(), None)?,
+            signer_verifier: signed.then(|| {
+                ArtifactSignatureAuthenticator::new(b"team_1".to_vec(), Some(b"secret".to_vec()))
+            }),
+            content_addressed,
+        })
+    }
+
+    fn repo_with_file() -> Result<(TempDir, AnchoredSystemPathBuf)> {
+        let repo_root = tempdir()?;
+        let file = AnchoredSystemPathBuf::from_raw("out.txt")?;
+        AbsoluteSystemPath::from_std_path(repo_root.path())?
+            .resolve(&file)
+            .create_with_contents("output")?;
+        Ok((repo_root, file))
+    }
+
+    #[tokio::test]
+    async fn test_signed_round_trip() -> Result<()> {
+        let server = TestServer::start(Arc::new(|_| true)).await?;
+        let (repo_root, file) = repo_with_file()?;
+        let repo_root_path = AbsoluteSystemPath::from_std_path(repo_root.path())?;
+
+        for content_addressed in [false, true] {
+            let hash = format!("hash-{content_addressed}");
+            let cache = cache(&server, true, content_addressed)?;
+            cache.put(repo_root_path, &hash, &[file.clone()], 5).await?;
+
+            let output = tempdir()?;
+            let output_path = AbsoluteSystemPath::from_std_path(output.path())?;
+            let (hit, files) = cache.fetch(output_path, &hash).await?.unwrap();
+            assert_eq!(hit.time_saved, 5);
+            assert_eq!(files, vec![file.clone()]);
+            assert_eq!(output_path.resolve(&file).read_to_string()?, "output");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_content_addressed_is_opt_in() -> Result<()> {
+        let server = TestServer::start(Arc::new(|_| true)).await?;
+        let (repo_root, file) = repo_with_file()?;
+        let repo_root_path = AbsoluteSystemPath::from_std_path(repo_root.path())?;
+
+        cache(&server, false, false)?
+            .put(repo_root_path, "the-hash", &[file.clone()], 0)
+            .await?;
+        let archive = server.get("/the-hash.tar.zst").unwrap();
+        assert!(!CacheReader::from_reader(&archive[..])?.is_content_addressed());
+
+        cache(&server, false, true)?
+            .put(repo_root_path, "the-hash", &[file], 0)
+            .await?;
+        let archive = server.get("/the-hash.tar.zst").unwrap();
+        assert!(CacheReader::from_reader(&archive[..])?.is_content_addressed());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejects_unsigned_and_tampered_artifacts() -> Result<()> {
+        let server = TestServer::start(Arc::new(|_| true)).await?;
+        let (repo_root, file) = repo_with_file()?;
+        let repo_root_path = AbsoluteSystemPath::from_std_path(repo_root.path())?;
+        let signed = cache(&server, true, false)?;
+
+        cache(&server, false, false)?
+            .put(repo_root_path, "unsigned", &[file.clone()], 0)
+            .await?;
+        assert!(matches!(
+            signed.fetch(repo_root_path, "unsigned").await,
+            Err(CacheError::ArtifactTagMissing(_))
+        ));
+
+        signed
+            .put(repo_root_path, "tampered", &[file.clone()], 0)
+            .await?;
+        let other = cache(&server, false, false)?;
+        let (other_root, _) = repo_with_file()?;
+        let other_root_path = AbsoluteSystemPath::from_std_path(other_root.path())?;
+        other_root_path
+            .resolve(&file)
+            .create_with_contents("tampered")?;
+        other.put(other_root_path, "other", &[file], 0).await?;
+        let tampered = server.get("/other.tar.zst").unwrap();
+        other.store.put("tampered.tar.zst", tampered).await?;
+        assert!(matches!(
+            signed.fetch(repo_root_path, "tampered").await,
+            Err(CacheError::InvalidTag(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, TcpListener},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, Method, StatusCode, Uri},
+    Router,
+};
+
+pub(crate) struct TestRequest {
+    pub method: Method,
+    pub path: String,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl TestRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+}
+
+type Authorize = Arc<dyn Fn(&TestRequest) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+struct ServerState {
+    objects: Arc<Mutex<HashMap<String, Bytes>>>,
+    authorize: Authorize,
+}
+
+/// In-memory object store that accepts `GET`, `HEAD` and `PUT` requests
+/// for any path, and rejects requests `authorize` returns false for
+pub(crate) struct TestServer {
+    addr: SocketAddr,
+    objects: Arc<Mutex<HashMap<String, Bytes>>>,
+}
+
+impl TestServer {
+    pub async fn start(authorize: Authorize) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let objects = Arc::new(Mutex::new(HashMap::new()));
+
+        let app = Router::new().fallback(handle).with_state(ServerState {
+            objects: objects.clone(),
+            authorize,
+        });
+        let server = axum::Server::from_tcp(listener)?.serve(app.into_make_service());
+        tokio::spawn(server);
+
+        Ok(Self { addr, objects })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.objects.lock().unwrap().contains_key(path)
+    }
+
+    pub fn get(&self, path: &str) -> Option<Bytes> {
+        self.objects.lock().unwrap().get(path).cloned()
+    }
+
+    pub fn remove(&self, path: &str) {
+        self.objects.lock().unwrap().remove(path);
+    }
+}
+
+async fn handle(
+    State(state): State<ServerState>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, Bytes) {
+    let request = TestRequest {
+        method,
+        path: uri.path().to_string(),
+        headers,
+        body,
+    };
+    if !(state.authorize)(&request) {
+        return (StatusCode::FORBIDDEN, Bytes::new());
+    }
+
+    let mut objects = state.objects.lock().unwrap();
+    match request.method {
+        Method::GET | Method::HEAD => match objects.get(&request.path) {
+            Some(body) => (StatusCode::OK, body.clone()),
+            None => (StatusCode::NOT_FOUND, Bytes::new()),
+        },
+        Method::PUT => {
+            objects.insert(request.path, request.body);
+            (StatusCode::CREATED, Bytes::new())
+        }
+        _ => (StatusCode::METHOD_NOT_ALLOWED, Bytes::new()),
+    }
+}
@@ -0,0 +1,156 @@
+use std::{backtrace::Backtrace, env};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use reqwest::{Method, RequestBuilder, StatusCode, Url};
+
+use crate::{
+    backend::{object_store::check_status, HttpStoreConfig, ObjectStore},
+    CacheError,
+};
+
+/// Stores objects on a WebDAV server, or any HTTP server that serves `GET`
+/// and `HEAD` for paths it accepted a `PUT` for.
+pub struct WebDavStore {
+    client: reqwest::Client,
+    base_url: Url,
+    token: Option<String>,
+}
+
+impl WebDavStore {
+    pub fn from_env(config: &HttpStoreConfig) -> Result<Self, CacheError> {
+        let token = config
+            .token_env
+            .as_deref()
+            .map(|name| {
+                env::var(name).map_err(|_| {
+                    CacheError::MissingBackendCredentials(name.to_string(), Backtrace::capture())
+                })
+            })
+            .transpose()?;
+
+        Self::new(&config.url, token)
+    }
+
+    pub fn new(url: &str, token: Option<String>) -> Result<Self, CacheError> {
+        // Without a trailing slash the last segment of the URL would be
+        // replaced when joining keys onto it
+        let url = if url.ends_with('/') {
+            url.to_string()
+        } else {
+            format!("{url}/")
+        };
+        let base_url = Url::parse(&url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or_else(|| CacheError::InvalidBackendEndpoint(url, Backtrace::capture()))?;
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            base_url,
+            token,
+        })
+    }
+
+    fn request(&self, method: Method, key: &str) -> Result<RequestBuilder, CacheError> {
+        let url = self.base_url.join(key).map_err(|_| {
+            CacheError::InvalidBackendEndpoint(key.to_string(), Backtrace::capture())
+        })?;
+        let request = self.client.request(method, url);
+
+        Ok(match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        })
+    }
+}
+
+#[async_trait]
+impl ObjectStore for WebDavStore {
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, CacheError> {
+        let response = self.request(Method::GET, key)?.send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(check_status(response)?.bytes().await?))
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, CacheError> {
+        let response = self.request(Method::HEAD, key)?.send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+
+        check_status(response)?;
+        Ok(true)
+    }
+
+    async fn put(&self, key: &str, body: Bytes) -> Result<(), CacheError> {
+        let response = self.request(Method::PUT, key)?.body(body).send().await?;
+        check_status(response)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use anyhow::Result;
+    use tempfile::tempdir;
+    use nxpkgpath::{AbsoluteSystemPath, AnchoredSystemPathBuf};
+
+    use super::*;
+    use crate::{
+        backend::{test_server::TestServer, CacheBackend, ObjectStoreCache},
+        CacheOpts,
+    };
+
+    #[tokio::test]
+    async fn test_round_trip() -> Result<()> {
+        let server = TestServer::start(Arc::new(|request| {
+            request.header("authorization") == Some("Bearer secret")
+        }))
+        .await?;
+        let store = WebDavStore::new(&format!("{}/cache", server.url()), Some("secret".into()))?;
+        let cache = ObjectStoreCache::new(server.url(), store, &CacheOpts::default());
+
+        let repo_root = tempdir()?;
+        let repo_root_path = AbsoluteSystemPath::from_std_path(repo_root.path())?;
+        let files =
+            ["one.txt", "two.txt"].map(|file| AnchoredSystemPathBuf::from_raw(file).unwrap());
+        for file in &files {
+            repo_root_path.resolve(file).create_with_contents("same")?;
+        }
+
+        cache.put(repo_root_path, "the-hash", &files, 7).await?;
+        assert!(server.contains("/cache/the-hash.tar.zst"));
+        assert!(server.contains("/cache/the-hash-meta.json"));
+
+        let output = tempdir()?;
+        let output_path = AbsoluteSystemPath::from_std_path(output.path())?;
+        let (hit, restored) = cache.fetch(output_path, "the-hash").await?.unwrap();
+        assert_eq!(hit.time_saved, 7);
+        assert_eq!(restored, files.to_vec());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized() -> Result<()> {
+        let server = TestServer::start(Arc::new(|request| {
+            request.header("authorization") == Some("Bearer secret")
+        }))
+        .await?;
+        let store = WebDavStore::new(&server.url(), Some("wrong".into()))?;
+
+        let result = store.put("key", Bytes::from_static(b"body")).await;
+        assert!(matches!(
+            result,
+            Err(CacheError::BackendStatus { status: 403, .. })
+        ));
+
+        Ok(())
+    }
+}
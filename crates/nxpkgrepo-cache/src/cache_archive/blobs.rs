@@ -0,0 +1,238 @@
+use std::{
+    backtrace::Backtrace,
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io,
+    io::{BufWriter, Read, Write},
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+use sha2::{Digest, Sha256};
+use nxpkgpath::{AbsoluteSystemPath, AbsoluteSystemPathBuf};
+
+use crate::CacheError;
+
+/// Storage for the file contents referenced by content-addressed cache
+/// archives.
+///
+/// Blobs are keyed by the hex encoded SHA-256 digest of their uncompressed
+/// contents and are stored zstd compressed.
+pub trait BlobStore {
+    fn contains(&self, digest: &str) -> Result<bool, CacheError>;
+    /// Stores `contents` under `digest`. Callers are responsible for
+    /// `digest` matching `contents`.
+    fn insert(&mut self, digest: &str, contents: &mut dyn Read) -> Result<(), CacheError>;
+    /// Opens a reader over the uncompressed contents of the blob
+    fn open(&self, digest: &str) -> Result<Box<dyn Read + '_>, CacheError>;
+}
+
+pub fn is_valid_digest(digest: &str) -> bool {
+    digest.len() == 64 && digest.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+}
+
+fn validate_digest(digest: &str) -> Result<(), CacheError> {
+    if is_valid_digest(digest) {
+        Ok(())
+    } else {
+        Err(CacheError::InvalidBlobDigest(
+            digest.to_string(),
+            Backtrace::capture(),
+        ))
+    }
+}
+
+// Computes the digest of a reader's contents
+pub(crate) fn digest_reader(reader: &mut impl Read) -> Result<String, CacheError> {
+    let mut hasher = Sha256::new();
+    io::copy(reader, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn compress(contents: &mut dyn Read, writer: impl Write) -> Result<(), CacheError> {
+    let mut encoder = zstd::Encoder::new(writer, 0)?;
+    io::copy(contents, &mut encoder)?;
+    encoder.finish()?.flush()?;
+    Ok(())
+}
+
+/// Reads a blob while hashing it, so that a corrupted blob is caught once
+/// it has been fully read.
+pub(crate) struct VerifyingReader<R> {
+    reader: R,
+    hasher: Sha256,
+    digest: String,
+}
+
+impl<R: Read> VerifyingReader<R> {
+    pub fn new(reader: R, digest: &str) -> Self {
+        Self {
+            reader,
+            hasher: Sha256::new(),
+            digest: digest.to_string(),
+        }
+    }
+
+    pub fn verify(self) -> Result<(), CacheError> {
+        if hex::encode(self.hasher.finalize()) == self.digest {
+            Ok(())
+        } else {
+            Err(CacheError::BlobDigestMismatch(
+                self.digest,
+                Backtrace::capture(),
+            ))
+        }
+    }
+}
+
+impl<R: Read> Read for VerifyingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Keeps blobs in memory. Used to gather the blobs of an artifact before
+/// uploading them, or after downloading them.
+#[derive(Debug, Default)]
+pub struct MemoryBlobStore {
+    blobs: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryBlobStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an already compressed blob, as returned by `iter`
+    pub fn insert_compressed(
+        &mut self,
+        digest: &str,
+        compressed: Vec<u8>,
+    ) -> Result<(), CacheError> {
+        validate_digest(digest)?;
+        self.blobs.insert(digest.to_string(), compressed);
+        Ok(())
+    }
+
+    /// Iterates over the digests and compressed contents of the blobs
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.blobs
+            .iter()
+            .map(|(digest, compressed)| (digest.as_str(), compressed.as_slice()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.blobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blobs.is_empty()
+    }
+}
+
+impl BlobStore for MemoryBlobStore {
+    fn contains(&self, digest: &str) -> Result<bool, CacheError> {
+        Ok(self.blobs.contains_key(digest))
+    }
+
+    fn insert(&mut self, digest: &str, contents: &mut dyn Read) -> Result<(), CacheError> {
+        validate_digest(digest)?;
+        let mut compressed = Vec::new();
+        compress(contents, &mut compressed)?;
+        self.blobs.insert(digest.to_string(), compressed);
+        Ok(())
+    }
+
+    fn open(&self, digest: &str) -> Result<Box<dyn Read + '_>, CacheError> {
+        let compressed = self
+            .blobs
+            .get(digest)
+            .ok_or_else(|| CacheError::BlobNotFound(digest.to_string(), Backtrace::capture()))?;
+        Ok(Box::new(zstd::Decoder::new(compressed.as_slice())?))
+    }
+}
+
+/// Keeps blobs as individual files in a directory, shared by every artifact
+/// in the filesystem cache.
+pub struct FsBlobStore {
+    root: AbsoluteSystemPathBuf,
+}
+
+impl FsBlobStore {
+    pub fn new(root: AbsoluteSystemPathBuf) -> Self {
+        Self { root }
+    }
+
+    pub fn root(&self) -> &AbsoluteSystemPath {
+        &self.root
+    }
+
+    fn blob_path(&self, digest: &str) -> Result<AbsoluteSystemPathBuf, CacheError> {
+        validate_digest(digest)?;
+        Ok(self.root.join_component(&format!("{digest}.zst")))
+    }
+
+    /// Returns the digest of a blob file in the store's directory, if the
+    /// file name is one the store would have written.
+    pub fn digest_from_file_name(file_name: &str) -> Option<&str> {
+        file_name
+            .strip_suffix(".zst")
+            .filter(|digest| is_valid_digest(digest))
+    }
+}
+
+impl BlobStore for FsBlobStore {
+    fn contains(&self, digest: &str) -> Result<bool, CacheError> {
+        let path = self.blob_path(digest)?;
+        // Bump the modification time so that a concurrent prune doesn't
+        // sweep a blob an in-flight write is about to reference
+        match OpenOptions::new().write(true).open(path.as_std_path()) {
+            Ok(file) => {
+                file.set_modified(SystemTime::now())?;
+                Ok(true)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn insert(&mut self, digest: &str, contents: &mut dyn Read) -> Result<(), CacheError> {
+        let path = self.blob_path(digest)?;
+        self.root.create_dir_all()?;
+
+        // Write to a temporary file first so that readers never observe a
+        // partially written blob. Workers in the same process can insert the
+        // same blob at once, so the name is unique per call.
+        static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+        let temp_path = self.root.join_component(&format!(
+            "{digest}.{}.{}.tmp",
+            std::process::id(),
+            TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = File::create(temp_path.as_std_path())
+            .map_err(CacheError::from)
+            .and_then(|file| compress(contents, BufWriter::new(file)))
+            .and_then(|_| Ok(temp_path.rename(&path)?));
+        if result.is_err() {
+            let _ = temp_path.remove_file();
+        }
+        result
+    }
+
+    fn open(&self, digest: &str) -> Result<Box<dyn Read + '_>, CacheError> {
+        let path = self.blob_path(digest)?;
+        let file = match path.open() {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(CacheError::BlobNotFound(
+                    digest.to_string(),
+                    Backtrace::capture(),
+                ))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Box::new(zstd::Decoder::new(file)?))
+    }
+}
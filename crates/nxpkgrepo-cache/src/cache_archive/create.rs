@@ -9,10 +9,19 @@ use std::{
 use tar::{EntryType, Header};
 use nxpkgpath::{AbsoluteSystemPath, AnchoredSystemPath};
 
-use crate::CacheError;
+use crate::{
+    cache_archive::{
+        blobs::{digest_reader, BlobStore},
+        BLOB_PAX_KEY, CONTENT_ADDRESSED_MAGIC,
+    },
+    CacheError,
+};
 
 pub struct CacheWriter<'a> {
     builder: tar::Builder<Box<dyn Write + 'a>>,
+    // Set for content-addressed archives, where the contents of regular files
+    // are stored here rather than in the tar
+    blobs: Option<&'a mut dyn BlobStore>,
 }
 
 impl<'a> CacheWriter<'a> {
@@ -35,14 +44,34 @@ impl<'a> CacheWriter<'a> {
             let zw = zstd::Encoder::new(writer, 0)?.auto_finish();
             Ok(CacheWriter {
                 builder: tar::Builder::new(Box::new(zw)),
+                blobs: None,
             })
         } else {
             Ok(CacheWriter {
                 builder: tar::Builder::new(Box::new(writer)),
+                blobs: None,
             })
         }
     }
 
+    // Makes a content-addressed archive. The archive is always zstd
+    // compressed and starts with `CONTENT_ADDRESSED_MAGIC` so that readers
+    // that don't know about the format fail to read it instead of restoring
+    // empty files.
+    // The contents of regular files are put into `blobs`, and only inserted
+    // if the store doesn't already have them.
+    pub fn from_writer_content_addressed(
+        writer: impl Write + 'a,
+        blobs: &'a mut dyn BlobStore,
+    ) -> Result<Self, CacheError> {
+        let mut zw = zstd::Encoder::new(writer, 0)?.auto_finish();
+        zw.write_all(CONTENT_ADDRESSED_MAGIC)?;
+        Ok(CacheWriter {
+            builder: tar::Builder::new(Box::new(zw)),
+            blobs: Some(blobs),
+        })
+    }
+
     // Makes a new CacheArchive at the specified path
     // Wires up the chain of writers:
     // tar::Builder -> zstd::Encoder (optional) -> BufWriter -> File
@@ -57,17 +86,21 @@ impl<'a> CacheWriter<'a> {
 
         let is_compressed = path.extension() == Some("zst");
 
-        if is_compressed {
-            let zw = zstd::Encoder::new(file_buffer, 0)?.auto_finish();
+        Self::from_writer(file_buffer, is_compressed)
+    }
 
-            Ok(CacheWriter {
-                builder: tar::Builder::new(Box::new(zw)),
-            })
-        } else {
-            Ok(CacheWriter {
-                builder: tar::Builder::new(Box::new(file_buffer)),
-            })
-        }
+    // Makes a new content-addressed CacheArchive at the specified path
+    pub fn create_content_addressed(
+        path: &AbsoluteSystemPath,
+        blobs: &'a mut dyn BlobStore,
+    ) -> Result<Self, CacheError> {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+
+        let file = path.open_with_options(options)?;
+        let file_buffer = BufWriter::with_capacity(2usize.pow(20), file);
+
+        Self::from_writer_content_addressed(file_buffer, blobs)
     }
 
     // Adds a user-cached item to the tar
@@ -89,8 +122,18 @@ impl<'a> CacheWriter<'a> {
         let mut header = Self::create_header(&source_path, &file_info)?;
 
         if matches!(header.entry_type(), EntryType::Regular) && file_info.len() > 0 {
-            let file = source_path.open()?;
-            self.append_data(&mut header, file_path.as_str(), file)?;
+            if let Some(blobs) = self.blobs.as_mut() {
+                let digest = digest_reader(&mut source_path.open()?)?;
+                if !blobs.contains(&digest)? {
+                    blobs.insert(&digest, &mut source_path.open()?)?;
+                }
+                self.append_blob_reference(&digest)?;
+                header.set_size(0);
+                self.append_data(&mut header, file_path.as_str(), &mut std::io::empty())?;
+            } else {
+                let file = source_path.open()?;
+                self.append_data(&mut header, file_path.as_str(), file)?;
+            }
         } else {
             self.append_data(&mut header, file_path.as_str(), &mut std::io::empty())?;
         }
@@ -98,6 +141,21 @@ impl<'a> CacheWriter<'a> {
         Ok(())
     }
 
+    // Writes a pax extended header pointing the next entry at its blob.
+    fn append_blob_reference(&mut self, digest: &str) -> Result<(), CacheError> {
+        let record = pax_record(BLOB_PAX_KEY, digest);
+
+        let mut header = Header::new_ustar();
+        header.set_path("PaxHeader")?;
+        header.set_entry_type(EntryType::XHeader);
+        header.set_mode(0o644);
+        header.set_mtime(0);
+        header.set_size(record.len() as u64);
+        header.set_cksum();
+
+        Ok(self.builder.append(&header, record.as_bytes())?)
+    }
+
     fn create_header(
         source_path: &AbsoluteSystemPath,
         file_info: &fs::Metadata,
@@ -146,9 +204,20 @@ impl<'a> CacheWriter<'a> {
     }
 }
 
+// Formats a pax record, `<length> <key>=<value>\n`, where the length
+// includes itself.
+fn pax_record(key: &str, value: &str) -> String {
+    let rest = format!(" {key}={value}\n");
+    let mut len = rest.len();
+    while (len.to_string().len() + rest.len()) != len {
+        len = len.to_string().len() + rest.len();
+    }
+    format!("{len}{rest}")
+}
+
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::{collections::HashSet, path::PathBuf};
 
     use anyhow::Result;
     use tempfile::tempdir;
@@ -156,7 +225,7 @@ mod tests {
     use nxpkgpath::{AbsoluteSystemPath, AbsoluteSystemPathBuf, AnchoredSystemPathBuf};
 
     use super::*;
-    use crate::cache_archive::restore::CacheReader;
+    use crate::cache_archive::{restore::CacheReader, MemoryBlobStore};
 
     #[derive(Debug)]
     enum FileType {
@@ -439,4 +508,89 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_content_addressed_round_trip() -> Result<()> {
+        let input_dir = tempdir()?;
+        let anchor = AbsoluteSystemPath::new(input_dir.path().to_str().unwrap())?;
+        let files = ["one.txt", "two.txt", "empty.txt"]
+            .map(|file| AnchoredSystemPathBuf::from_raw(file).unwrap());
+        anchor
+            .resolve(&files[0])
+            .create_with_contents("same contents")?;
+        anchor
+            .resolve(&files[1])
+            .create_with_contents("same contents")?;
+        anchor.resolve(&files[2]).create_with_contents("")?;
+
+        let mut blobs = MemoryBlobStore::new();
+        let mut archive = Vec::new();
+        let mut cache_writer =
+            CacheWriter::from_writer_content_addressed(&mut archive, &mut blobs)?;
+        for file in &files {
+            cache_writer.add_file(anchor, file)?;
+        }
+        cache_writer.finish()?;
+
+        // Identical files share a blob, empty files are stored inline
+        assert_eq!(blobs.len(), 1);
+
+        let output_dir = tempdir()?;
+        let output = AbsoluteSystemPath::new(output_dir.path().to_str().unwrap())?;
+        let cache_reader = CacheReader::from_reader(archive.as_slice())?;
+        assert!(cache_reader.is_content_addressed());
+        let restored = cache_reader.with_blob_store(&blobs).restore(output)?;
+
+        assert_eq!(restored, files.to_vec());
+        assert_eq!(output.resolve(&files[1]).read_to_string()?, "same contents");
+        assert_eq!(output.resolve(&files[2]).read_to_string()?, "");
+
+        let references = CacheReader::from_reader(archive.as_slice())?.blob_references()?;
+        assert_eq!(
+            references,
+            blobs
+                .iter()
+                .map(|(digest, _)| digest.to_string())
+                .collect::<HashSet<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_addressed_bad_blobs() -> Result<()> {
+        let input_dir = tempdir()?;
+        let anchor = AbsoluteSystemPath::new(input_dir.path().to_str().unwrap())?;
+        let file = AnchoredSystemPathBuf::from_raw("file.txt")?;
+        anchor.resolve(&file).create_with_contents("contents")?;
+
+        let mut blobs = MemoryBlobStore::new();
+        let mut archive = Vec::new();
+        let mut cache_writer =
+            CacheWriter::from_writer_content_addressed(&mut archive, &mut blobs)?;
+        cache_writer.add_file(anchor, &file)?;
+        cache_writer.finish()?;
+
+        let output_dir = tempdir()?;
+        let output = AbsoluteSystemPath::new(output_dir.path().to_str().unwrap())?;
+
+        let result = CacheReader::from_reader(archive.as_slice())?.restore(output);
+        assert!(matches!(result, Err(CacheError::MissingBlobStore(_))));
+
+        let result = CacheReader::from_reader(archive.as_slice())?
+            .with_blob_store(&MemoryBlobStore::new())
+            .restore(output);
+        assert!(matches!(result, Err(CacheError::BlobNotFound(_, _))));
+
+        let mut corrupted = MemoryBlobStore::new();
+        for (digest, _) in blobs.iter() {
+            corrupted.insert(digest, &mut "other contents".as_bytes())?;
+        }
+        let result = CacheReader::from_reader(archive.as_slice())?
+            .with_blob_store(&corrupted)
+            .restore(output);
+        assert!(matches!(result, Err(CacheError::BlobDigestMismatch(_, _))));
+
+        Ok(())
+    }
 }
@@ -1,9 +1,18 @@
 #![allow(dead_code)]
+mod blobs;
 mod create;
 mod restore;
 mod restore_directory;
 mod restore_regular;
 mod restore_symlink;
 
+pub use blobs::{BlobStore, FsBlobStore, MemoryBlobStore};
 pub use create::CacheWriter;
 pub use restore::CacheReader;
+
+// Marks the start of a content-addressed archive, ahead of the tar stream
+// inside the zstd frame.
+const CONTENT_ADDRESSED_MAGIC: &[u8; 8] = b"nxpkgca2";
+
+// Pax extension key holding the digest of a regular file's blob
+const BLOB_PAX_KEY: &str = "NXPKG.blob";
@@ -1,4 +1,8 @@
-use std::{backtrace::Backtrace, collections::HashMap, io::Read};
+use std::{
+    backtrace::Backtrace,
+    collections::{HashMap, HashSet},
+    io::{Cursor, Read},
+};
 
 use petgraph::graph::DiGraph;
 use sha2::{Digest, Sha512};
@@ -7,41 +11,83 @@ use nxpkgpath::{AbsoluteSystemPath, AbsoluteSystemPathBuf, AnchoredSystemPathBuf
 
 use crate::{
     cache_archive::{
+        blobs::{is_valid_digest, BlobStore, VerifyingReader},
         restore_directory::{restore_directory, CachedDirTree},
         restore_regular::restore_regular,
         restore_symlink::{
             canonicalize_linkname, restore_symlink, restore_symlink_allow_missing_target,
         },
+        BLOB_PAX_KEY, CONTENT_ADDRESSED_MAGIC,
     },
     CacheError,
 };
 
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
 pub struct CacheReader<'a> {
     reader: Box<dyn Read + 'a>,
+    content_addressed: bool,
+    blobs: Option<&'a dyn BlobStore>,
 }
 
 impl<'a> CacheReader<'a> {
-    pub fn from_reader(reader: impl Read + 'a, is_compressed: bool) -> Result<Self, CacheError> {
-        let reader: Box<dyn Read> = if is_compressed {
+    // Detects the compression of the archive from its contents, so that
+    // zstd, gzip and uncompressed archives can all be restored, regardless
+    // of which version of nxpkg wrote them.
+    pub fn from_reader(mut reader: impl Read + 'a) -> Result<Self, CacheError> {
+        let prefix = read_prefix(&mut reader, ZSTD_MAGIC.len())?;
+        let reader = Cursor::new(prefix.clone()).chain(reader);
+        let mut reader: Box<dyn Read + 'a> = if prefix.starts_with(ZSTD_MAGIC) {
             Box::new(zstd::Decoder::new(reader)?)
+        } else if prefix.starts_with(GZIP_MAGIC) {
+            Box::new(flate2::read::GzDecoder::new(reader))
         } else {
             Box::new(reader)
         };
 
-        Ok(CacheReader { reader })
+        let prefix = read_prefix(&mut reader, CONTENT_ADDRESSED_MAGIC.len())?;
+        let content_addressed = prefix == CONTENT_ADDRESSED_MAGIC;
+        let reader: Box<dyn Read + 'a> = if content_addressed {
+            reader
+        } else {
+            Box::new(Cursor::new(prefix).chain(reader))
+        };
+
+        Ok(CacheReader {
+            reader,
+            content_addressed,
+            blobs: None,
+        })
     }
 
     pub fn open(path: &AbsoluteSystemPathBuf) -> Result<Self, CacheError> {
-        let file = path.open()?;
-        let is_compressed = path.extension() == Some("zst");
+        Self::from_reader(path.open()?)
+    }
 
-        let reader: Box<dyn Read> = if is_compressed {
-            Box::new(zstd::Decoder::new(file)?)
-        } else {
-            Box::new(file)
-        };
+    // Sets the store that the file contents of a content-addressed archive
+    // are restored from.
+    pub fn with_blob_store(mut self, blobs: &'a dyn BlobStore) -> Self {
+        self.blobs = Some(blobs);
+        self
+    }
+
+    pub fn is_content_addressed(&self) -> bool {
+        self.content_addressed
+    }
+
+    // Lists the digests of the blobs referenced by the archive
+    pub fn blob_references(mut self) -> Result<HashSet<String>, CacheError> {
+        let mut references = HashSet::new();
+        let mut tr = tar::Archive::new(&mut self.reader);
+        for entry in tr.entries()? {
+            let mut entry = entry?;
+            if let Some(digest) = blob_reference(&mut entry)? {
+                references.insert(digest);
+            }
+        }
 
-        Ok(CacheReader { reader })
+        Ok(references)
     }
 
     pub fn get_sha(mut self) -> Result<Vec<u8>, CacheError> {
@@ -62,6 +108,10 @@ impl<'a> CacheReader<'a> {
         &mut self,
         anchor: &AbsoluteSystemPath,
     ) -> Result<Vec<AnchoredSystemPathBuf>, CacheError> {
+        if self.content_addressed && self.blobs.is_none() {
+            return Err(CacheError::MissingBlobStore(Backtrace::capture()));
+        }
+
         let mut restored = Vec::new();
         anchor.create_dir_all()?;
 
@@ -80,9 +130,10 @@ impl<'a> CacheReader<'a> {
         // not apply for your path, it will clobber and re-start from the common
         // shared prefix.
         let dir_cache = CachedDirTree::new(anchor.to_owned());
+        let blobs = self.blobs;
         let mut tr = tar::Archive::new(&mut self.reader);
 
-        Self::restore_entries(&mut tr, &mut restored, dir_cache, anchor)?;
+        Self::restore_entries(&mut tr, &mut restored, dir_cache, anchor, blobs)?;
         Ok(restored)
     }
 
@@ -91,6 +142,7 @@ impl<'a> CacheReader<'a> {
         restored: &mut Vec<AnchoredSystemPathBuf>,
         mut dir_cache: CachedDirTree,
         anchor: &AbsoluteSystemPath,
+        blobs: Option<&dyn BlobStore>,
     ) -> Result<(), CacheError> {
         // On first attempt to restore it's possible that a link target doesn't exist.
         // Save them and topologically sort them.
@@ -98,7 +150,7 @@ impl<'a> CacheReader<'a> {
 
         for entry in tr.entries()? {
             let mut entry = entry?;
-            match restore_entry(&mut dir_cache, anchor, &mut entry) {
+            match restore_entry(&mut dir_cache, anchor, &mut entry, blobs) {
                 Err(CacheError::LinkTargetDoesNotExist(_, _)) => {
                     symlinks.push(entry);
                 }
@@ -168,13 +220,24 @@ fn restore_entry<T: Read>(
     dir_cache: &mut CachedDirTree,
     anchor: &AbsoluteSystemPath,
     entry: &mut Entry<T>,
+    blobs: Option<&dyn BlobStore>,
 ) -> Result<AnchoredSystemPathBuf, CacheError> {
-    let header = entry.header();
+    let header = entry.header().clone();
 
     match header.entry_type() {
-        tar::EntryType::Directory => restore_directory(dir_cache, anchor, entry.header()),
-        tar::EntryType::Regular => restore_regular(dir_cache, anchor, entry),
-        tar::EntryType::Symlink => restore_symlink(dir_cache, anchor, entry.header()),
+        tar::EntryType::Directory => restore_directory(dir_cache, anchor, &header),
+        tar::EntryType::Regular => match blob_reference(entry)? {
+            Some(digest) => {
+                let blobs =
+                    blobs.ok_or_else(|| CacheError::MissingBlobStore(Backtrace::capture()))?;
+                let mut blob = VerifyingReader::new(blobs.open(&digest)?, &digest);
+                let restored = restore_regular(dir_cache, anchor, &header, &mut blob)?;
+                blob.verify()?;
+                Ok(restored)
+            }
+            None => restore_regular(dir_cache, anchor, &header, entry),
+        },
+        tar::EntryType::Symlink => restore_symlink(dir_cache, anchor, &header),
         ty => Err(CacheError::RestoreUnsupportedFileType(
             ty,
             Backtrace::capture(),
@@ -182,6 +245,33 @@ fn restore_entry<T: Read>(
     }
 }
 
+// Returns the digest of the blob holding the entry's contents, if it has
+// one.
+fn blob_reference<T: Read>(entry: &mut Entry<T>) -> Result<Option<String>, CacheError> {
+    let Some(extensions) = entry.pax_extensions()? else {
+        return Ok(None);
+    };
+    for extension in extensions {
+        let extension = extension?;
+        if extension.key() != Ok(BLOB_PAX_KEY) {
+            continue;
+        }
+        let digest = String::from_utf8_lossy(extension.value_bytes()).into_owned();
+        if !is_valid_digest(&digest) {
+            return Err(CacheError::InvalidBlobDigest(digest, Backtrace::capture()));
+        }
+        return Ok(Some(digest));
+    }
+
+    Ok(None)
+}
+
+fn read_prefix(reader: &mut impl Read, len: usize) -> Result<Vec<u8>, CacheError> {
+    let mut prefix = Vec::with_capacity(len);
+    reader.take(len as u64).read_to_end(&mut prefix)?;
+    Ok(prefix)
+}
+
 #[cfg(test)]
 mod tests {
     use std::{fs, fs::File, io::empty, path::Path};
@@ -338,10 +428,8 @@ mod tests {
     fn test_name_traversal() -> Result<()> {
         let uncompressed_tar = include_bytes!("../../fixtures/name-traversal.tar");
         let compressed_tar = include_bytes!("../../fixtures/name-traversal.tar.zst");
-        for (tar_bytes, is_compressed) in
-            [(&uncompressed_tar[..], false), (&compressed_tar[..], true)]
-        {
-            let mut cache_reader = CacheReader::from_reader(tar_bytes, is_compressed)?;
+        for tar_bytes in [&uncompressed_tar[..], &compressed_tar[..]] {
+            let mut cache_reader = CacheReader::from_reader(tar_bytes)?;
             let output_dir = tempdir()?;
             let output_dir_path = output_dir.path().to_string_lossy();
             let anchor = AbsoluteSystemPath::new(&output_dir_path)?;
@@ -356,15 +444,39 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_restore_gzip() -> Result<()> {
+        let input_dir = tempdir()?;
+        let file = AnchoredSystemPathBuf::from_raw("hello.txt")?;
+        let archive_path = generate_tar(
+            &input_dir,
+            &[TarFile::File {
+                body: b"hello world".to_vec(),
+                path: file.clone(),
+            }],
+        )?;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::copy(&mut archive_path.open()?, &mut encoder)?;
+        let compressed = encoder.finish()?;
+
+        let output_dir = tempdir()?;
+        let anchor = AbsoluteSystemPath::from_std_path(output_dir.path())?;
+        let restored = CacheReader::from_reader(compressed.as_slice())?.restore(anchor)?;
+
+        assert_eq!(restored, vec![file.clone()]);
+        assert_eq!(fs::read(anchor.resolve(&file))?, b"hello world");
+
+        Ok(())
+    }
+
     #[test]
     fn test_windows_unsafe() -> Result<()> {
         let uncompressed_tar = include_bytes!("../../fixtures/windows-unsafe.tar");
         let compressed_tar = include_bytes!("../../fixtures/windows-unsafe.tar.zst");
 
-        for (tar_bytes, is_compressed) in
-            [(&uncompressed_tar[..], false), (&compressed_tar[..], true)]
-        {
-            let mut cache_reader = CacheReader::from_reader(tar_bytes, is_compressed)?;
+        for tar_bytes in [&uncompressed_tar[..], &compressed_tar[..]] {
+            let mut cache_reader = CacheReader::from_reader(tar_bytes)?;
             let output_dir = tempdir()?;
             let output_dir_path = output_dir.path().to_string_lossy();
             let anchor = AbsoluteSystemPath::new(&output_dir_path)?;
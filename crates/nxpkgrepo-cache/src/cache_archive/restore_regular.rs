@@ -1,16 +1,18 @@
 use std::{fs::OpenOptions, io, io::Read, path::Path};
 
-use tar::Entry;
+use tar::Header;
 use nxpkgpath::{AbsoluteSystemPath, AnchoredSystemPath, AnchoredSystemPathBuf};
 
 use crate::{cache_archive::restore_directory::CachedDirTree, CacheError};
 
+// Restores the file described by `header` with the contents of `reader`,
+// which is either the tar entry itself or the blob it references.
 pub fn restore_regular(
     dir_cache: &mut CachedDirTree,
     anchor: &AbsoluteSystemPath,
-    entry: &mut Entry<impl Read>,
+    header: &Header,
+    reader: &mut impl Read,
 ) -> Result<AnchoredSystemPathBuf, CacheError> {
-    let header = entry.header();
     // Assuming this was a `nxpkg`-created input, we currently have an
     // AnchoredUnixPath. Assuming this is malicious input we don't really care
     // if we do the wrong thing.
@@ -32,7 +34,7 @@ pub fn restore_regular(
     }
 
     let mut file = open_options.open(resolved_path.as_path())?;
-    io::copy(entry, &mut file)?;
+    io::copy(reader, &mut file)?;
 
     Ok(processed_name)
 }
@@ -1,16 +1,33 @@
-use std::{backtrace::Backtrace, fs::OpenOptions};
+use std::{
+    backtrace::Backtrace,
+    collections::{HashMap, HashSet},
+    fs::OpenOptions,
+    time::{Duration, SystemTime},
+};
 
 use camino::Utf8Path;
 use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
 use nxpkgpath::{AbsoluteSystemPath, AbsoluteSystemPathBuf, AnchoredSystemPathBuf};
 use nxpkgrepo_analytics::AnalyticsSender;
 use nxpkgrepo_api_client::{analytics, analytics::AnalyticsEvent};
 
 use crate::{
-    cache_archive::{CacheReader, CacheWriter},
+    cache_archive::{CacheReader, CacheWriter, FsBlobStore},
     CacheError, CacheHitMetadata, CacheSource,
 };
 
+// Archives in the content-addressed format get their own extension, so
+// versions of nxpkg that only know the tarball format miss instead of failing
+// to read them
+const CONTENT_ADDRESSED_EXTENSION: &str = ".nxpkgca";
+// Checked in order when looking up an entry's archive
+const ARCHIVE_EXTENSIONS: &[&str] = &[CONTENT_ADDRESSED_EXTENSION, ".tar", ".tar.zst"];
+
+// Unreferenced blobs younger than this are kept by `FSCache::prune`, as they
+// may belong to an artifact that is still being written
+const BLOB_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
 pub struct FSCache {
     cache_directory: AbsoluteSystemPathBuf,
     analytics_recorder: Option<AnalyticsSender>,
@@ -29,6 +46,77 @@ impl CacheMetadata {
     }
 }
 
+/// Limits on the contents of the filesystem cache.
+///
+/// Entries are evicted oldest first, where an entry's age is the last time it
+/// was written or restored, as recorded by the modification time of its
+/// `-meta.json` file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EvictionPolicy {
+    /// Total size in bytes of the cache entries to keep
+    pub max_size: Option<u64>,
+    /// Entries unused for longer than this are evicted
+    pub max_age: Option<Duration>,
+}
+
+impl EvictionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_size.is_some() || self.max_age.is_some()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PruneSummary {
+    pub evicted_entries: usize,
+    pub evicted_bytes: u64,
+    pub remaining_entries: usize,
+    pub remaining_bytes: u64,
+}
+
+// All of the files on disk making up a single cache entry
+#[derive(Debug, Default)]
+struct CacheEntryFiles {
+    files: Vec<AbsoluteSystemPathBuf>,
+    size: u64,
+    archive_modified: Option<SystemTime>,
+    metadata_modified: Option<SystemTime>,
+    // Digests of the blobs the archive references
+    blobs: HashSet<String>,
+}
+
+// A file in the blob directory
+#[derive(Debug)]
+struct BlobFile {
+    path: AbsoluteSystemPathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+impl CacheEntryFiles {
+    fn has_archive(&self) -> bool {
+        self.archive_modified.is_some()
+    }
+
+    // The metadata file is touched on every hit, so it's the best record
+    // of when the entry was last used
+    fn last_used(&self) -> Option<SystemTime> {
+        self.metadata_modified.or(self.archive_modified)
+    }
+
+    fn remove(&self, hash: &str, summary: &mut PruneSummary) {
+        debug!("evicting {hash} from the filesystem cache");
+        // Archives are sorted first so a concurrent fetch can't find an
+        // archive without its metadata
+        for file in &self.files {
+            if let Err(e) = file.remove_file() {
+                warn!("unable to remove {file}: {e}");
+            }
+        }
+        summary.evicted_entries += 1;
+        summary.evicted_bytes += self.size;
+    }
+}
+
 impl FSCache {
     fn resolve_cache_dir(
         repo_root: &AbsoluteSystemPath,
@@ -55,6 +143,11 @@ impl FSCache {
         })
     }
 
+    // Blobs are shared between every entry in the cache
+    fn blob_store(&self) -> FsBlobStore {
+        FsBlobStore::new(self.cache_directory.join_component("blobs"))
+    }
+
     fn log_fetch(&self, event: analytics::CacheEvent, hash: &str, duration: u64) {
         // If analytics fails to record, it's not worth failing the cache
         if let Some(analytics_recorder) = &self.analytics_recorder {
@@ -75,23 +168,13 @@ impl FSCache {
         anchor: &AbsoluteSystemPath,
         hash: &str,
     ) -> Result<Option<(CacheHitMetadata, Vec<AnchoredSystemPathBuf>)>, CacheError> {
-        let uncompressed_cache_path = self
-            .cache_directory
-            .join_component(&format!("{}.tar", hash));
-        let compressed_cache_path = self
-            .cache_directory
-            .join_component(&format!("{}.tar.zst", hash));
-
-        let cache_path = if uncompressed_cache_path.exists() {
-            uncompressed_cache_path
-        } else if compressed_cache_path.exists() {
-            compressed_cache_path
-        } else {
+        let Some(cache_path) = self.archive_path(hash) else {
             self.log_fetch(analytics::CacheEvent::Miss, hash, 0);
             return Ok(None);
         };
 
-        let mut cache_reader = CacheReader::open(&cache_path)?;
+        let blobs = self.blob_store();
+        let mut cache_reader = CacheReader::open(&cache_path)?.with_blob_store(&blobs);
 
         let restored_files = cache_reader.restore(anchor)?;
 
@@ -102,6 +185,7 @@ impl FSCache {
         )?;
 
         self.log_fetch(analytics::CacheEvent::Hit, hash, meta.duration);
+        self.mark_used(hash);
 
         Ok(Some((
             CacheHitMetadata {
@@ -112,15 +196,33 @@ impl FSCache {
         )))
     }
 
-    pub(crate) fn exists(&self, hash: &str) -> Result<Option<CacheHitMetadata>, CacheError> {
-        let uncompressed_cache_path = self
-            .cache_directory
-            .join_component(&format!("{}.tar", hash));
-        let compressed_cache_path = self
+    fn archive_path(&self, hash: &str) -> Option<AbsoluteSystemPathBuf> {
+        ARCHIVE_EXTENSIONS
+            .iter()
+            .map(|extension| {
+                self.cache_directory
+                    .join_component(&format!("{hash}{extension}"))
+            })
+            .find(|path| path.exists())
+    }
+
+    // Bumps the modification time of the entry's metadata file so that
+    // eviction treats the entry as recently used.
+    fn mark_used(&self, hash: &str) {
+        let metadata_path = self
             .cache_directory
-            .join_component(&format!("{}.tar.zst", hash));
+            .join_component(&format!("{}-meta.json", hash));
+        let result = OpenOptions::new()
+            .write(true)
+            .open(metadata_path.as_std_path())
+            .and_then(|file| file.set_modified(SystemTime::now()));
+        if let Err(e) = result {
+            debug!("unable to mark {hash} as used: {e}");
+        }
+    }
 
-        if !uncompressed_cache_path.exists() && !compressed_cache_path.exists() {
+    pub(crate) fn exists(&self, hash: &str) -> Result<Option<CacheHitMetadata>, CacheError> {
+        if self.archive_path(hash).is_none() {
             return Ok(None);
         }
 
@@ -147,14 +249,17 @@ impl FSCache {
     ) -> Result<(), CacheError> {
         let cache_path = self
             .cache_directory
-            .join_component(&format!("{}.tar.zst", hash));
+            .join_component(&format!("{hash}{CONTENT_ADDRESSED_EXTENSION}"));
 
-        let mut cache_item = CacheWriter::create(&cache_path)?;
+        let mut blobs = self.blob_store();
+        let mut cache_item = CacheWriter::create_content_addressed(&cache_path, &mut blobs)?;
 
         for file in files {
             cache_item.add_file(anchor, file)?;
         }
 
+        cache_item.finish()?;
+
         let metadata_path = self
             .cache_directory
             .join_component(&format!("{}-meta.json", hash));
@@ -174,6 +279,180 @@ impl FSCache {
 
         Ok(())
     }
+
+    /// Evicts cache entries that violate the given policy, least recently
+    /// used first.
+    ///
+    /// Entries that are missing their archive are always removed, as are
+    /// blobs that are no longer referenced by any entry. The size of an entry
+    /// includes the blobs it references, shared blobs are only counted once.
+    pub fn prune(&self, policy: &EvictionPolicy) -> Result<PruneSummary, CacheError> {
+        let mut entries = self.read_entries()?;
+        let blob_files = self.read_blob_files()?;
+        let now = SystemTime::now();
+        let mut summary = PruneSummary::default();
+
+        let mut kept = Vec::with_capacity(entries.len());
+        for (hash, entry) in entries.drain() {
+            let expired = match (policy.max_age, entry.last_used()) {
+                (Some(max_age), Some(last_used)) => now
+                    .duration_since(last_used)
+                    .map_or(false, |age| age > max_age),
+                _ => false,
+            };
+            if !entry.has_archive() || expired {
+                entry.remove(&hash, &mut summary);
+            } else {
+                kept.push((hash, entry));
+            }
+        }
+
+        let mut references: HashMap<String, usize> = HashMap::new();
+        for (hash, entry) in &mut kept {
+            entry.blobs = self.read_blob_references(hash, &entry.files[0]);
+            for digest in &entry.blobs {
+                *references.entry(digest.clone()).or_default() += 1;
+            }
+        }
+        let blob_size = |digest: &str| blob_files.get(digest).map_or(0, |blob| blob.size);
+
+        // Newest first, so we can pop the least recently used entries
+        kept.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.last_used()));
+        let mut total_size: u64 = kept.iter().map(|(_, entry)| entry.size).sum::<u64>()
+            + references
+                .keys()
+                .map(|digest| blob_size(digest))
+                .sum::<u64>();
+        if let Some(max_size) = policy.max_size {
+            while total_size > max_size {
+                let Some((hash, entry)) = kept.pop() else {
+                    break;
+                };
+                total_size -= entry.size;
+                for digest in &entry.blobs {
+                    if let Some(count) = references.get_mut(digest) {
+                        *count -= 1;
+                        if *count == 0 {
+                            references.remove(digest);
+                            total_size -= blob_size(digest);
+                        }
+                    }
+                }
+                entry.remove(&hash, &mut summary);
+            }
+        }
+
+        summary.remaining_entries = kept.len();
+        summary.remaining_bytes = kept.iter().map(|(_, entry)| entry.size).sum();
+        for (digest, blob) in &blob_files {
+            let in_grace_period = now
+                .duration_since(blob.modified)
+                .map_or(true, |age| age < BLOB_GRACE_PERIOD);
+            if references.contains_key(digest) || in_grace_period {
+                summary.remaining_bytes += blob.size;
+                continue;
+            }
+            debug!("evicting blob {digest} from the filesystem cache");
+            match blob.path.remove_file() {
+                Ok(()) => summary.evicted_bytes += blob.size,
+                Err(e) => warn!("unable to remove {}: {e}", blob.path),
+            }
+        }
+
+        Ok(summary)
+    }
+
+    // An archive we can't read can't be restored either, so it's treated as
+    // referencing nothing
+    fn read_blob_references(&self, hash: &str, archive: &AbsoluteSystemPathBuf) -> HashSet<String> {
+        match CacheReader::open(archive).and_then(|reader| reader.blob_references()) {
+            Ok(references) => references,
+            Err(e) => {
+                debug!("unable to read blob references of {hash}: {e}");
+                HashSet::new()
+            }
+        }
+    }
+
+    // Keyed by digest. Leftover temporary files are keyed by their file name
+    // so they're never referenced.
+    fn read_blob_files(&self) -> Result<HashMap<String, BlobFile>, CacheError> {
+        let blob_store = self.blob_store();
+        let read_dir = match blob_store.root().as_std_path().read_dir() {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut blob_files = HashMap::new();
+        for dir_entry in read_dir {
+            let dir_entry = dir_entry?;
+            let Some(file_name) = dir_entry.file_name().to_str().map(|s| s.to_string()) else {
+                continue;
+            };
+            let metadata = dir_entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let key = FsBlobStore::digest_from_file_name(&file_name)
+                .unwrap_or(&file_name)
+                .to_string();
+            blob_files.insert(
+                key,
+                BlobFile {
+                    path: blob_store.root().join_component(&file_name),
+                    size: metadata.len(),
+                    modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                },
+            );
+        }
+        Ok(blob_files)
+    }
+
+    fn read_entries(&self) -> Result<HashMap<String, CacheEntryFiles>, CacheError> {
+        let mut entries: HashMap<String, CacheEntryFiles> = HashMap::new();
+        for dir_entry in self.cache_directory.as_std_path().read_dir()? {
+            let dir_entry = dir_entry?;
+            let Some(file_name) = dir_entry.file_name().to_str().map(|s| s.to_string()) else {
+                continue;
+            };
+            let archive_hash = ARCHIVE_EXTENSIONS
+                .iter()
+                .find_map(|extension| file_name.strip_suffix(extension));
+            let (hash, is_archive) = if let Some(hash) = archive_hash {
+                (hash, true)
+            } else if let Some(hash) = file_name.strip_suffix("-meta.json") {
+                (hash, false)
+            } else {
+                continue;
+            };
+            let metadata = dir_entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+
+            let entry = entries.entry(hash.to_string()).or_default();
+            entry.size += metadata.len();
+            entry
+                .files
+                .push(self.cache_directory.join_component(&file_name));
+            // Fall back to the epoch so entries with unknown times are
+            // evicted first
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            if is_archive {
+                entry.archive_modified = entry.archive_modified.max(Some(modified));
+            } else {
+                entry.metadata_modified = Some(modified);
+            }
+        }
+        for entry in entries.values_mut() {
+            // Archives before metadata, see `CacheEntryFiles::remove`
+            entry
+                .files
+                .sort_by_key(|file| file.as_str().ends_with("-meta.json"));
+        }
+        Ok(entries)
+    }
 }
 
 #[cfg(test)]
@@ -207,6 +486,173 @@ mod test {
         Ok(())
     }
 
+    fn write_entry(
+        cache_dir: &AbsoluteSystemPath,
+        hash: &str,
+        size: usize,
+        age: Duration,
+    ) -> Result<()> {
+        let modified = SystemTime::now() - age;
+        for (file_name, contents) in [
+            (format!("{hash}.tar.zst"), vec![0u8; size]),
+            (format!("{hash}-meta.json"), Vec::new()),
+        ] {
+            let path = cache_dir.join_component(&file_name);
+            path.create_with_contents(contents)?;
+            set_modified(&path, modified)?;
+        }
+        Ok(())
+    }
+
+    fn set_modified(path: &AbsoluteSystemPath, modified: SystemTime) -> Result<()> {
+        OpenOptions::new()
+            .write(true)
+            .open(path.as_std_path())?
+            .set_modified(modified)?;
+        Ok(())
+    }
+
+    fn entry_exists(cache_dir: &AbsoluteSystemPath, hash: &str) -> bool {
+        cache_dir
+            .join_component(&format!("{hash}.tar.zst"))
+            .exists()
+    }
+
+    #[test]
+    fn test_prune_max_size() -> Result<()> {
+        let repo_root = tempdir()?;
+        let repo_root_path = AbsoluteSystemPath::from_std_path(repo_root.path())?;
+        let cache = FSCache::new(None, repo_root_path, None)?;
+        let cache_dir = &cache.cache_directory;
+
+        write_entry(cache_dir, "oldest", 10, Duration::from_secs(300))?;
+        write_entry(cache_dir, "older", 10, Duration::from_secs(200))?;
+        write_entry(cache_dir, "newest", 10, Duration::from_secs(100))?;
+        // Restoring an entry marks it as used
+        cache.mark_used("oldest");
+
+        let summary = cache.prune(&EvictionPolicy {
+            max_size: Some(25),
+            max_age: None,
+        })?;
+
+        assert_eq!(
+            summary,
+            PruneSummary {
+                evicted_entries: 1,
+                evicted_bytes: 10,
+                remaining_entries: 2,
+                remaining_bytes: 20,
+            }
+        );
+        assert!(entry_exists(cache_dir, "oldest"));
+        assert!(!entry_exists(cache_dir, "older"));
+        assert!(!cache_dir.join_component("older-meta.json").exists());
+        assert!(entry_exists(cache_dir, "newest"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_max_age() -> Result<()> {
+        let repo_root = tempdir()?;
+        let repo_root_path = AbsoluteSystemPath::from_std_path(repo_root.path())?;
+        let cache = FSCache::new(None, repo_root_path, None)?;
+        let cache_dir = &cache.cache_directory;
+
+        write_entry(
+            cache_dir,
+            "stale",
+            10,
+            Duration::from_secs(60 * 60 * 24 * 8),
+        )?;
+        write_entry(cache_dir, "fresh", 10, Duration::from_secs(60))?;
+        // Metadata without an archive is never useful
+        cache_dir
+            .join_component("orphan-meta.json")
+            .create_with_contents("{}")?;
+
+        let summary = cache.prune(&EvictionPolicy {
+            max_size: None,
+            max_age: Some(Duration::from_secs(60 * 60 * 24 * 7)),
+        })?;
+
+        assert_eq!(summary.evicted_entries, 2);
+        assert_eq!(summary.remaining_entries, 1);
+        assert!(!entry_exists(cache_dir, "stale"));
+        assert!(entry_exists(cache_dir, "fresh"));
+        assert!(!cache_dir.join_component("orphan-meta.json").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_blobs() -> Result<()> {
+        let repo_root = tempdir()?;
+        let repo_root_path = AbsoluteSystemPath::from_std_path(repo_root.path())?;
+        let cache = FSCache::new(None, repo_root_path, None)?;
+        let shared = AnchoredSystemPathBuf::from_raw("shared.txt")?;
+        let unique = AnchoredSystemPathBuf::from_raw("unique.txt")?;
+        repo_root_path
+            .resolve(&shared)
+            .create_with_contents("shared")?;
+        repo_root_path
+            .resolve(&unique)
+            .create_with_contents("unique")?;
+
+        cache.put(repo_root_path, "first", &[shared.clone(), unique], 10)?;
+        cache.put(repo_root_path, "second", &[shared.clone()], 10)?;
+
+        // Age everything past the blob grace period, then make "first" stale
+        let old = SystemTime::now() - BLOB_GRACE_PERIOD * 2;
+        let blob_store = cache.blob_store();
+        for dir_entry in blob_store.root().as_std_path().read_dir()? {
+            let path = AbsoluteSystemPathBuf::try_from(dir_entry?.path())?;
+            set_modified(&path, old)?;
+        }
+        for file_name in ["first.nxpkgca", "first-meta.json"] {
+            set_modified(&cache.cache_directory.join_component(file_name), old)?;
+        }
+
+        let summary = cache.prune(&EvictionPolicy {
+            max_size: None,
+            max_age: Some(BLOB_GRACE_PERIOD),
+        })?;
+
+        assert_eq!(summary.evicted_entries, 1);
+        // Only the blob of "unique.txt" is unreferenced
+        assert_eq!(blob_store.root().as_std_path().read_dir()?.count(), 1);
+        let (_, restored) = cache.fetch(repo_root_path, "second")?.unwrap();
+        assert_eq!(restored, vec![shared]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_put_archive_name() -> Result<()> {
+        let repo_root = tempdir()?;
+        let repo_root_path = AbsoluteSystemPath::from_std_path(repo_root.path())?;
+        let cache = FSCache::new(None, repo_root_path, None)?;
+        let file = AnchoredSystemPathBuf::from_raw("file.txt")?;
+        repo_root_path.resolve(&file).create_with_contents("file")?;
+
+        cache.put(repo_root_path, "hash", &[file.clone()], 10)?;
+
+        // Older versions of nxpkg only look for tarballs and must miss
+        let cache_dir = &cache.cache_directory;
+        assert!(cache_dir.join_component("hash.nxpkgca").exists());
+        assert!(!cache_dir.join_component("hash.tar.zst").exists());
+        assert!(!cache_dir.join_component("hash.tar").exists());
+
+        // Tarballs written by older versions can still be restored
+        let mut legacy = CacheWriter::create(&cache_dir.join_component("legacy.tar.zst"))?;
+        legacy.add_file(repo_root_path, &file)?;
+        legacy.finish()?;
+        cache_dir
+            .join_component("legacy-meta.json")
+            .create_with_contents(r#"{"hash":"legacy","duration":10}"#)?;
+        let (_, restored) = cache.fetch(repo_root_path, "legacy")?.unwrap();
+        assert_eq!(restored, vec![file]);
+        Ok(())
+    }
+
     async fn round_trip_test(test_case: &TestCase, port: u16) -> Result<()> {
         let repo_root = tempdir()?;
         let repo_root_path = AbsoluteSystemPath::from_std_path(repo_root.path())?;
@@ -1,5 +1,6 @@
-use std::{backtrace::Backtrace, io::Write};
+use std::{backtrace::Backtrace, collections::HashSet, io::Write};
 
+use futures::future::try_join_all;
 use nxpkgpath::{AbsoluteSystemPath, AbsoluteSystemPathBuf, AnchoredSystemPathBuf};
 use nxpkgrepo_analytics::AnalyticsSender;
 use nxpkgrepo_api_client::{
@@ -7,7 +8,7 @@ use nxpkgrepo_api_client::{
 };
 
 use crate::{
-    cache_archive::{CacheReader, CacheWriter},
+    cache_archive::{CacheReader, CacheWriter, MemoryBlobStore},
     signature_authentication::ArtifactSignatureAuthenticator,
     CacheError, CacheHitMetadata, CacheOpts, CacheSource,
 };
@@ -18,6 +19,10 @@ pub struct HTTPCache {
     repo_root: AbsoluteSystemPathBuf,
     api_auth: APIAuth,
     analytics_recorder: Option<AnalyticsSender>,
+    // Whether artifacts are uploaded in the content-addressed format, which
+    // other versions of nxpkg sharing the remote cache might not be able to
+    // read
+    content_addressed: bool,
 }
 
 impl HTTPCache {
@@ -45,6 +50,12 @@ impl HTTPCache {
         } else {
             None
         };
+        let content_addressed = opts
+            .remote_cache_opts
+            .as_ref()
+            .map_or(false, |remote_cache_opts| {
+                remote_cache_opts.content_addressed
+            });
 
         HTTPCache {
             client,
@@ -52,6 +63,7 @@ impl HTTPCache {
             repo_root,
             api_auth,
             analytics_recorder,
+            content_addressed,
         }
     }
 
@@ -62,8 +74,23 @@ impl HTTPCache {
         files: &[AnchoredSystemPathBuf],
         duration: u64,
     ) -> Result<(), CacheError> {
+        let mut blobs = MemoryBlobStore::new();
         let mut artifact_body = Vec::new();
-        self.write(&mut artifact_body, anchor, files).await?;
+        self.write(
+            &mut artifact_body,
+            self.content_addressed.then_some(&mut blobs),
+            anchor,
+            files,
+        )
+        .await?;
+
+        // Blobs go first, so that the artifact is never visible without them
+        try_join_all(
+            blobs
+                .iter()
+                .map(|(digest, compressed)| self.put_blob(digest, compressed)),
+        )
+        .await?;
 
         let tag = self
             .signer_verifier
@@ -78,6 +105,8 @@ impl HTTPCache {
                 duration,
                 tag.as_deref(),
                 &self.api_auth.token,
+                self.api_auth.team_id.as_deref(),
+                self.api_auth.team_slug.as_deref(),
             )
             .await?;
 
@@ -87,17 +116,86 @@ impl HTTPCache {
     async fn write(
         &self,
         writer: impl Write,
+        blobs: Option<&mut MemoryBlobStore>,
         anchor: &AbsoluteSystemPath,
         files: &[AnchoredSystemPathBuf],
     ) -> Result<(), CacheError> {
-        let mut cache_archive = CacheWriter::from_writer(writer, true)?;
+        let mut cache_archive = match blobs {
+            Some(blobs) => CacheWriter::from_writer_content_addressed(writer, blobs)?,
+            None => CacheWriter::from_writer(writer, true)?,
+        };
         for file in files {
             cache_archive.add_file(anchor, file)?;
         }
+        cache_archive.finish()?;
 
         Ok(())
     }
 
+    // Blobs are stored as artifacts keyed by their digest. As the key is
+    // derived from the contents, a blob that already exists doesn't need to
+    // be uploaded again.
+    async fn put_blob(&self, digest: &str, compressed: &[u8]) -> Result<(), CacheError> {
+        if self.exists(digest).await?.is_some() {
+            return Ok(());
+        }
+
+        let tag = self
+            .signer_verifier
+            .as_ref()
+            .map(|signer| signer.generate_tag(digest.as_bytes(), compressed))
+            .transpose()?;
+
+        self.client
+            .put_artifact(
+                digest,
+                compressed,
+                0,
+                tag.as_deref(),
+                &self.api_auth.token,
+                self.api_auth.team_id.as_deref(),
+                self.api_auth.team_slug.as_deref(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    // Returns `None` if any of the blobs is missing, e.g. because the remote
+    // cache evicted it. Blob contents are checked against their digest when
+    // they are restored, so they don't need to be verified here.
+    async fn fetch_blobs(
+        &self,
+        digests: HashSet<String>,
+    ) -> Result<Option<MemoryBlobStore>, CacheError> {
+        let digests: Vec<_> = digests.into_iter().collect();
+        let responses = try_join_all(digests.iter().map(|digest| {
+            self.client.fetch_artifact(
+                digest,
+                &self.api_auth.token,
+                self.api_auth.team_id.as_deref(),
+                self.api_auth.team_slug.as_deref(),
+            )
+        }))
+        .await?;
+
+        let mut blobs = MemoryBlobStore::new();
+        for (digest, response) in digests.iter().zip(responses) {
+            let Some(response) = response else {
+                return Ok(None);
+            };
+            let compressed = response.bytes().await.map_err(|e| {
+                CacheError::ApiClientError(
+                    Box::new(nxpkgrepo_api_client::Error::ReqwestError(e)),
+                    Backtrace::capture(),
+                )
+            })?;
+            blobs.insert_compressed(digest, compressed.to_vec())?;
+        }
+
+        Ok(Some(blobs))
+    }
+
     pub async fn exists(&self, hash: &str) -> Result<Option<CacheHitMetadata>, CacheError> {
         let Some(response) = self
             .client
@@ -201,7 +299,26 @@ impl HTTPCache {
             })?
         };
 
-        let files = Self::restore_tar(&self.repo_root, &body)?;
+        let blob_references = {
+            let cache_reader = CacheReader::from_reader(&body[..])?;
+            if cache_reader.is_content_addressed() {
+                Some(cache_reader.blob_references()?)
+            } else {
+                None
+            }
+        };
+        let blobs = match blob_references {
+            Some(digests) => {
+                let Some(blobs) = self.fetch_blobs(digests).await? else {
+                    self.log_fetch(analytics::CacheEvent::Miss, hash, 0);
+                    return Ok(None);
+                };
+                Some(blobs)
+            }
+            None => None,
+        };
+
+        let files = Self::restore_tar(&self.repo_root, &body, blobs.as_ref())?;
 
         self.log_fetch(analytics::CacheEvent::Hit, hash, duration);
         Ok(Some((
@@ -216,8 +333,12 @@ impl HTTPCache {
     pub(crate) fn restore_tar(
         root: &AbsoluteSystemPath,
         body: &[u8],
+        blobs: Option<&MemoryBlobStore>,
     ) -> Result<Vec<AnchoredSystemPathBuf>, CacheError> {
-        let mut cache_reader = CacheReader::from_reader(body, true)?;
+        let mut cache_reader = CacheReader::from_reader(body)?;
+        if let Some(blobs) = blobs {
+            cache_reader = cache_reader.with_blob_store(blobs);
+        }
         cache_reader.restore(root)
     }
 }
@@ -235,7 +356,7 @@ mod test {
     use crate::{
         http::{APIAuth, HTTPCache},
         test_cases::{get_test_cases, validate_analytics, TestCase},
-        CacheOpts, CacheSource,
+        CacheOpts, CacheSource, RemoteCacheOpts,
     };
 
     #[tokio::test]
@@ -247,7 +368,7 @@ mod test {
         try_join_all(
             test_cases
                 .iter()
-                .map(|test_case| round_trip_test(test_case, port)),
+                .map(|test_case| round_trip_test(test_case, port, false)),
         )
         .await?;
 
@@ -256,7 +377,28 @@ mod test {
         Ok(())
     }
 
-    async fn round_trip_test(test_case: &TestCase, port: u16) -> Result<()> {
+    #[tokio::test]
+    async fn test_http_cache_content_addressed() -> Result<()> {
+        let port = port_scanner::request_open_port().unwrap();
+        let handle = tokio::spawn(start_test_server(port));
+        let test_cases = get_test_cases();
+
+        try_join_all(
+            test_cases
+                .iter()
+                .map(|test_case| round_trip_test(test_case, port, true)),
+        )
+        .await?;
+
+        handle.abort();
+        Ok(())
+    }
+
+    async fn round_trip_test(
+        test_case: &TestCase,
+        port: u16,
+        content_addressed: bool,
+    ) -> Result<()> {
         let repo_root = tempdir()?;
         let repo_root_path = AbsoluteSystemPathBuf::try_from(repo_root.path())?;
         test_case.initialize(&repo_root_path)?;
@@ -266,7 +408,14 @@ mod test {
         let duration = test_case.duration;
 
         let api_client = APIClient::new(format!("http://localhost:{}", port), 200, "2.0.0", true)?;
-        let opts = CacheOpts::default();
+        let opts = CacheOpts {
+            remote_cache_opts: Some(RemoteCacheOpts::new(
+                "my-team".to_string(),
+                false,
+                content_addressed,
+            )),
+            ..CacheOpts::default()
+        };
         let api_auth = APIAuth {
             team_id: Some("my-team".to_string()),
             token: "my-token".to_string(),
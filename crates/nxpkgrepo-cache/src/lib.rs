@@ -4,6 +4,7 @@
 #![deny(clippy::all)]
 
 mod async_cache;
+pub mod backend;
 pub mod cache_archive;
 pub mod fs;
 pub mod http;
@@ -19,7 +20,7 @@ use camino::Utf8Path;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::signature_authentication::SignatureError;
+use crate::{backend::BackendConfig, fs::EvictionPolicy, signature_authentication::SignatureError};
 
 #[derive(Debug, Error)]
 pub enum CacheError {
@@ -64,6 +65,27 @@ pub enum CacheError {
     InvalidMetadata(serde_json::Error, #[backtrace] Backtrace),
     #[error("Failed to write cache metadata file")]
     MetadataWriteFailure(serde_json::Error, #[backtrace] Backtrace),
+    #[error("content-addressed cache archive cannot be restored without a blob store")]
+    MissingBlobStore(#[backtrace] Backtrace),
+    #[error("cache blob not found: {0}")]
+    BlobNotFound(String, #[backtrace] Backtrace),
+    #[error("invalid cache blob digest: {0}")]
+    InvalidBlobDigest(String, #[backtrace] Backtrace),
+    #[error("cache blob contents do not match digest: {0}")]
+    BlobDigestMismatch(String, #[backtrace] Backtrace),
+    #[error("cache backend request failed: {0}")]
+    BackendRequest(#[from] reqwest::Error, #[backtrace] Backtrace),
+    #[error("cache backend responded with status {status} for {url}")]
+    BackendStatus {
+        status: u16,
+        url: String,
+        #[backtrace]
+        backtrace: Backtrace,
+    },
+    #[error("cache backend is missing credentials: {0} is not set")]
+    MissingBackendCredentials(String, #[backtrace] Backtrace),
+    #[error("invalid cache backend url: {0}")]
+    InvalidBackendEndpoint(String, #[backtrace] Backtrace),
     #[error("Unable to perform write as cache is shutting down")]
     CacheShuttingDown,
 }
@@ -93,16 +115,28 @@ pub struct CacheOpts<'a> {
     pub skip_filesystem: bool,
     pub workers: u32,
     pub remote_cache_opts: Option<RemoteCacheOpts>,
+    pub fs_eviction: EvictionPolicy,
+    /// Caches checked after the filesystem cache, and before the Vercel
+    /// remote cache, in order
+    pub backends: Vec<BackendConfig>,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct RemoteCacheOpts {
     team_id: String,
     signature: bool,
+    /// Whether to upload artifacts in the content-addressed format. Versions
+    /// of nxpkg that don't know the format can't restore these artifacts, so
+    /// it's opt-in for remote caches.
+    content_addressed: bool,
 }
 
 impl RemoteCacheOpts {
-    pub fn new(team_id: String, signature: bool) -> Self {
-        Self { team_id, signature }
+    pub fn new(team_id: String, signature: bool, content_addressed: bool) -> Self {
+        Self {
+            team_id,
+            signature,
+            content_addressed,
+        }
     }
 }
@@ -1,20 +1,49 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 
+use futures::future::join_all;
 use tracing::{debug, warn};
 use nxpkgpath::{AbsoluteSystemPath, AnchoredSystemPathBuf};
 use nxpkgrepo_analytics::AnalyticsSender;
 use nxpkgrepo_api_client::{APIAuth, APIClient};
 
-use crate::{fs::FSCache, http::HTTPCache, CacheError, CacheHitMetadata, CacheOpts};
+use crate::{
+    backend::CacheBackend,
+    fs::{EvictionPolicy, FSCache, PruneSummary},
+    http::HTTPCache,
+    CacheError, CacheHitMetadata, CacheOpts,
+};
 
-pub struct CacheMultiplexer {
+// A cache that's checked after the filesystem cache
+struct RemoteTier {
+    backend: Box<dyn CacheBackend>,
     // We use an `AtomicBool` instead of removing the cache because that would require
     // wrapping the cache in a `Mutex` which would cause a lot of contention.
     // This does create a mild race condition where we might use the cache
     // even though another thread might be removing it, but that's fine.
-    should_use_http_cache: AtomicBool,
+    enabled: AtomicBool,
+}
+
+impl RemoteTier {
+    fn new(backend: Box<dyn CacheBackend>) -> Self {
+        Self {
+            backend,
+            enabled: AtomicBool::new(true),
+        }
+    }
+
+    // This is technically a TOCTOU bug, but at worst it'll cause
+    // a few extra cache requests.
+    fn get(&self) -> Option<&dyn CacheBackend> {
+        self.enabled
+            .load(Ordering::Relaxed)
+            .then_some(self.backend.as_ref())
+    }
+}
+
+pub struct CacheMultiplexer {
     fs: Option<FSCache>,
-    http: Option<HTTPCache>,
+    // Ordered from the fastest to the slowest cache
+    remotes: Vec<RemoteTier>,
 }
 
 impl CacheMultiplexer {
@@ -31,7 +60,7 @@ impl CacheMultiplexer {
         // Since the above two flags are not mutually exclusive it is possible to
         // configure yourself out of having a cache. We should tell you about it
         // but we shouldn't fail your build for that reason.
-        if !use_fs_cache && !use_http_cache {
+        if !use_fs_cache && !use_http_cache && opts.backends.is_empty() {
             warn!("no caches are enabled");
         }
 
@@ -39,6 +68,19 @@ impl CacheMultiplexer {
             .then(|| FSCache::new(opts.override_dir, repo_root, analytics_recorder.clone()))
             .transpose()?;
 
+        // A misconfigured backend shouldn't fail your build either
+        let mut remotes: Vec<_> = opts
+            .backends
+            .iter()
+            .filter_map(|config| match config.build(opts) {
+                Ok(backend) => Some(RemoteTier::new(backend)),
+                Err(err) => {
+                    warn!("failed to set up cache backend: {err}");
+                    None
+                }
+            })
+            .collect();
+
         let http_cache = use_http_cache
             .then_some(api_auth)
             .flatten()
@@ -51,24 +93,14 @@ impl CacheMultiplexer {
                     analytics_recorder.clone(),
                 )
             });
+        remotes.extend(http_cache.map(|http_cache| RemoteTier::new(Box::new(http_cache))));
 
         Ok(CacheMultiplexer {
-            should_use_http_cache: AtomicBool::new(http_cache.is_some()),
             fs: fs_cache,
-            http: http_cache,
+            remotes,
         })
     }
 
-    // This is technically a TOCTOU bug, but at worst it'll cause
-    // a few extra cache requests.
-    fn get_http_cache(&self) -> Option<&HTTPCache> {
-        if self.should_use_http_cache.load(Ordering::Relaxed) {
-            self.http.as_ref()
-        } else {
-            None
-        }
-    }
-
     pub async fn put(
         &self,
         anchor: &AbsoluteSystemPath,
@@ -81,27 +113,31 @@ impl CacheMultiplexer {
             .map(|fs| fs.put(anchor, key, files, duration))
             .transpose()?;
 
-        let http_result = match self.get_http_cache() {
-            Some(http) => {
-                let http_result = http.put(anchor, key, files, duration).await;
-
-                Some(http_result)
+        join_all(self.remotes.iter().map(|tier| async move {
+            let Some(backend) = tier.get() else {
+                return;
+            };
+            match backend.put(anchor, key, files, duration).await {
+                Ok(()) => {}
+                Err(CacheError::ApiClientError(
+                    box nxpkgrepo_api_client::Error::CacheDisabled { .. },
+                    ..,
+                )) => {
+                    warn!("failed to put to {} cache: cache disabled", backend.name());
+                    tier.enabled.store(false, Ordering::Relaxed);
+                }
+                Err(err) => debug!("failed to put to {} cache: {:?}", backend.name(), err),
             }
-            _ => None,
-        };
-
-        if let Some(Err(CacheError::ApiClientError(
-            box nxpkgrepo_api_client::Error::CacheDisabled { .. },
-            ..,
-        ))) = http_result
-        {
-            warn!("failed to put to http cache: cache disabled");
-            self.should_use_http_cache.store(false, Ordering::Relaxed);
-        }
+        }))
+        .await;
 
         Ok(())
     }
 
+    pub fn prune_fs(&self, policy: &EvictionPolicy) -> Result<Option<PruneSummary>, CacheError> {
+        self.fs.as_ref().map(|fs| fs.prune(policy)).transpose()
+    }
+
     pub async fn fetch(
         &self,
         anchor: &AbsoluteSystemPath,
@@ -113,25 +149,52 @@ impl CacheMultiplexer {
             }
         }
 
-        if let Some(http) = self.get_http_cache() {
-            if let Ok(Some((CacheHitMetadata { source, time_saved }, files))) =
-                http.fetch(key).await
-            {
-                // Store this into fs cache. We can ignore errors here because we know
-                // we have previously successfully stored in HTTP cache, and so the overall
-                // result is a success at fetching. Storing in lower-priority caches is an
-                // optimization.
-                if let Some(fs) = &self.fs {
-                    let _ = fs.put(anchor, key, &files, time_saved);
+        for (i, tier) in self.remotes.iter().enumerate() {
+            let Some(backend) = tier.get() else {
+                continue;
+            };
+            match backend.fetch(anchor, key).await {
+                Ok(Some((CacheHitMetadata { source, time_saved }, files))) => {
+                    self.backfill(&self.remotes[..i], anchor, key, &files, time_saved)
+                        .await;
+                    return Ok(Some((CacheHitMetadata { source, time_saved }, files)));
                 }
-
-                return Ok(Some((CacheHitMetadata { source, time_saved }, files)));
+                Ok(None) => {}
+                Err(err) => debug!("failed to fetch from {} cache: {:?}", backend.name(), err),
             }
         }
 
         Ok(None)
     }
 
+    // Stores an artifact fetched from a slower cache into the faster ones.
+    // We can ignore errors here because the overall result is a success at
+    // fetching. Storing in faster caches is an optimization.
+    async fn backfill(
+        &self,
+        faster_remotes: &[RemoteTier],
+        anchor: &AbsoluteSystemPath,
+        key: &str,
+        files: &[AnchoredSystemPathBuf],
+        time_saved: u64,
+    ) {
+        if let Some(fs) = &self.fs {
+            let _ = fs.put(anchor, key, files, time_saved);
+        }
+
+        join_all(
+            faster_remotes
+                .iter()
+                .filter_map(|tier| tier.get())
+                .map(|backend| async move {
+                    if let Err(err) = backend.put(anchor, key, files, time_saved).await {
+                        debug!("failed to backfill {} cache: {:?}", backend.name(), err);
+                    }
+                }),
+        )
+        .await;
+    }
+
     pub async fn exists(&self, key: &str) -> Result<Option<CacheHitMetadata>, CacheError> {
         if let Some(fs) = &self.fs {
             match fs.exists(key) {
@@ -143,16 +206,95 @@ impl CacheMultiplexer {
             }
         }
 
-        if let Some(http) = self.get_http_cache() {
-            match http.exists(key).await {
+        for backend in self.remotes.iter().filter_map(|tier| tier.get()) {
+            match backend.exists(key).await {
                 cache_hit @ Ok(Some(_)) => {
                     return cache_hit;
                 }
                 Ok(None) => {}
-                Err(err) => debug!("failed to check http cache: {:?}", err),
+                Err(err) => debug!("failed to check {} cache: {:?}", backend.name(), err),
             }
         }
 
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use anyhow::Result;
+    use tempfile::tempdir;
+    use nxpkgpath::{AbsoluteSystemPath, AnchoredSystemPathBuf};
+    use nxpkgrepo_api_client::APIClient;
+
+    use super::*;
+    use crate::{
+        backend::{test_server::TestServer, BackendConfig, HttpStoreConfig},
+        CacheSource,
+    };
+
+    #[tokio::test]
+    async fn test_backfill_faster_tiers() -> Result<()> {
+        let fast = TestServer::start(Arc::new(|_| true)).await?;
+        let slow = TestServer::start(Arc::new(|_| true)).await?;
+        let backends = [&fast, &slow]
+            .map(|server| {
+                BackendConfig::Http(HttpStoreConfig {
+                    url: server.url(),
+                    token_env: None,
+                })
+            })
+            .to_vec();
+
+        // Populate only the slow tier
+        let other_root = tempdir()?;
+        let other_root_path = AbsoluteSystemPath::from_std_path(other_root.path())?;
+        let file = AnchoredSystemPathBuf::from_raw("out.txt")?;
+        other_root_path
+            .resolve(&file)
+            .create_with_contents("output")?;
+        backends[1]
+            .build(&CacheOpts::default())?
+            .put(other_root_path, "the-hash", &[file.clone()], 12)
+            .await?;
+
+        let repo_root = tempdir()?;
+        let repo_root_path = AbsoluteSystemPath::from_std_path(repo_root.path())?;
+        let opts = CacheOpts {
+            skip_remote: true,
+            backends,
+            ..Default::default()
+        };
+        let api_client = APIClient::new("http://example.com", 200, "2.0.0", true)?;
+        let cache = CacheMultiplexer::new(&opts, repo_root_path, api_client, None, None)?;
+
+        let (hit, files) = cache.fetch(repo_root_path, "the-hash").await?.unwrap();
+        assert_eq!(hit.source, CacheSource::Remote);
+        assert_eq!(hit.time_saved, 12);
+        assert_eq!(files, vec![file.clone()]);
+        assert_eq!(repo_root_path.resolve(&file).read_to_string()?, "output");
+
+        // Both the filesystem cache and the faster remote tier were backfilled
+        assert!(fast.contains("/the-hash-meta.json"));
+        let fs_hit = cache.fs.as_ref().unwrap().exists("the-hash")?.unwrap();
+        assert_eq!(fs_hit.source, CacheSource::Local);
+
+        // Once the slow tier loses the artifact it's still served
+        slow.remove("/the-hash-meta.json");
+        let remote_only = CacheMultiplexer::new(
+            &CacheOpts {
+                skip_filesystem: true,
+                ..opts
+            },
+            repo_root_path,
+            APIClient::new("http://example.com", 200, "2.0.0", true)?,
+            None,
+            None,
+        )?;
+        assert!(remote_only.exists("the-hash").await?.is_some());
+
+        Ok(())
+    }
+}
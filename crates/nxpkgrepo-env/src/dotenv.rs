@@ -0,0 +1,334 @@
+use std::mem;
+
+use crate::Error;
+
+// A piece of a value before variable references are resolved
+enum Part {
+    Text(String),
+    Var {
+        name: String,
+        default: Option<String>,
+    },
+}
+
+/// Parses the contents of a `.env` file into its variables, in the order they
+/// are defined.
+///
+/// Unquoted and double-quoted values expand `$NAME`, `${NAME}` and
+/// `${NAME:-default}`. References are resolved against `env` first, as the
+/// environment takes precedence over `.env` files, then against variables
+/// defined earlier in the same file and last against `inherited`, e.g. the
+/// variables of files loaded before this one. Single-quoted values are taken
+/// literally.
+pub fn parse_dot_env(
+    contents: &str,
+    env: impl Fn(&str) -> Option<String>,
+    inherited: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<(String, String)>, Error> {
+    let mut parser = Parser {
+        rest: contents,
+        line: 1,
+    };
+    let mut vars: Vec<(String, String)> = Vec::new();
+    while let Some((name, parts)) = parser.entry()? {
+        let resolve = |name: &str| {
+            env(name)
+                .or_else(|| {
+                    vars.iter()
+                        .rev()
+                        .find(|(var, _)| var == name)
+                        .map(|(_, value)| value.clone())
+                })
+                .or_else(|| inherited(name))
+        };
+        let value = parts
+            .into_iter()
+            .map(|part| match part {
+                Part::Text(text) => text,
+                Part::Var { name, default } => match (resolve(&name), default) {
+                    (Some(value), Some(default)) if value.is_empty() => default,
+                    (Some(value), _) => value,
+                    (None, default) => default.unwrap_or_default(),
+                },
+            })
+            .collect();
+        vars.push((name, value));
+    }
+    Ok(vars)
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.rest.chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.rest = &self.rest[c.len_utf8()..];
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, line: usize, reason: &'static str) -> Error {
+        Error::DotEnv { line, reason }
+    }
+
+    fn skip_inline_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    fn skip_line(&mut self) {
+        while !matches!(self.bump(), Some('\n') | None) {}
+    }
+
+    // Skips blank lines and comments up to the start of the next entry
+    fn skip_to_entry(&mut self) {
+        loop {
+            while matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+                self.bump();
+            }
+            if self.peek() != Some('#') {
+                return;
+            }
+            self.skip_line();
+        }
+    }
+
+    fn name(&mut self, is_name_char: impl Fn(char) -> bool) -> String {
+        let len = self
+            .rest
+            .find(|c: char| !is_name_char(c))
+            .unwrap_or(self.rest.len());
+        let name = self.rest[..len].to_string();
+        self.rest = &self.rest[len..];
+        name
+    }
+
+    fn entry(&mut self) -> Result<Option<(String, Vec<Part>)>, Error> {
+        self.skip_to_entry();
+        if self.rest.is_empty() {
+            return Ok(None);
+        }
+
+        if let Some(rest) = self.rest.strip_prefix("export") {
+            if rest.starts_with([' ', '\t']) {
+                self.rest = rest;
+                self.skip_inline_whitespace();
+            }
+        }
+
+        let name = self.name(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+        if name.is_empty() {
+            return Err(self.error(self.line, "expected a variable name"));
+        }
+        self.skip_inline_whitespace();
+        if self.bump() != Some('=') {
+            return Err(self.error(self.line, "expected `=` after the variable name"));
+        }
+        self.skip_inline_whitespace();
+
+        let value = match self.peek() {
+            Some('\'') => {
+                self.bump();
+                vec![Part::Text(self.single_quoted()?)]
+            }
+            Some('"') => {
+                self.bump();
+                self.double_quoted()?
+            }
+            _ => self.unquoted()?,
+        };
+
+        self.skip_inline_whitespace();
+        match self.peek() {
+            None | Some('\r' | '\n' | '#') => self.skip_line(),
+            Some(_) => {
+                return Err(self.error(self.line, "unexpected characters after the value"));
+            }
+        }
+
+        Ok(Some((name, value)))
+    }
+
+    // Parses a reference following a `$`. Returns `None` if the `$` doesn't
+    // start a reference and should be kept as is.
+    fn variable(&mut self) -> Result<Option<Part>, Error> {
+        if self.peek() != Some('{') {
+            if !self
+                .peek()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            {
+                return Ok(None);
+            }
+            let name = self.name(|c| c.is_ascii_alphanumeric() || c == '_');
+            return Ok(Some(Part::Var {
+                name,
+                default: None,
+            }));
+        }
+
+        let line = self.line;
+        self.bump();
+        let name = self.name(|c| c.is_ascii_alphanumeric() || c == '_');
+        if name.is_empty() {
+            return Err(self.error(line, "expected a variable name after `${`"));
+        }
+        let default = if let Some(rest) = self.rest.strip_prefix(":-") {
+            self.rest = rest;
+            let mut default = String::new();
+            loop {
+                match self.peek() {
+                    Some('}') => break Some(default),
+                    Some('\n') | None => break None,
+                    Some(c) => {
+                        self.bump();
+                        default.push(c);
+                    }
+                }
+            }
+        } else {
+            None
+        };
+        if self.bump() != Some('}') {
+            return Err(self.error(line, "unterminated variable reference"));
+        }
+
+        Ok(Some(Part::Var { name, default }))
+    }
+
+    fn unquoted(&mut self) -> Result<Vec<Part>, Error> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            // `#` only starts a comment at the start of the value or after
+            // whitespace
+            let at_start = text.is_empty() && parts.is_empty();
+            if c == '\n' || (c == '#' && (at_start || text.ends_with([' ', '\t']))) {
+                break;
+            }
+            self.bump();
+            if c != '$' {
+                text.push(c);
+                continue;
+            }
+            match self.variable()? {
+                Some(var) => {
+                    parts.push(Part::Text(mem::take(&mut text)));
+                    parts.push(var);
+                }
+                None => text.push('$'),
+            }
+        }
+        // Drops trailing whitespace, including the `\r` of CRLF line endings
+        text.truncate(text.trim_end().len());
+        parts.push(Part::Text(text));
+        Ok(parts)
+    }
+
+    fn single_quoted(&mut self) -> Result<String, Error> {
+        let line = self.line;
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some('\'') => return Ok(text),
+                Some(c) => text.push(c),
+                None => return Err(self.error(line, "unterminated single-quoted value")),
+            }
+        }
+    }
+
+    fn double_quoted(&mut self) -> Result<Vec<Part>, Error> {
+        let line = self.line;
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => break,
+                Some('\\') => match self.bump() {
+                    Some('n') => text.push('\n'),
+                    Some('r') => text.push('\r'),
+                    Some('t') => text.push('\t'),
+                    Some(c @ ('"' | '\\' | '$')) => text.push(c),
+                    Some(c) => {
+                        text.push('\\');
+                        text.push(c);
+                    }
+                    None => return Err(self.error(line, "unterminated double-quoted value")),
+                },
+                Some('$') => match self.variable()? {
+                    Some(var) => {
+                        parts.push(Part::Text(mem::take(&mut text)));
+                        parts.push(var);
+                    }
+                    None => text.push('$'),
+                },
+                Some(c) => text.push(c),
+                None => return Err(self.error(line, "unterminated double-quoted value")),
+            }
+        }
+        parts.push(Part::Text(text));
+        Ok(parts)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn parse(contents: &str) -> Vec<(String, String)> {
+        parse_dot_env(
+            contents,
+            |name| (name == "HOME").then(|| "/home/nxpkg".to_string()),
+            |name| (name == "INHERITED").then(|| "earlier file".to_string()),
+        )
+        .unwrap()
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test_case("A=1\nB=2", &[("A", "1"), ("B", "2")] ; "simple")]
+    #[test_case("# comment\n\nexport A = 1 # trailing\r\n", &[("A", "1")] ; "comments and export")]
+    #[test_case("A=a#b", &[("A", "a#b")] ; "hash inside value")]
+    #[test_case("A='$HOME \\n'", &[("A", "$HOME \\n")] ; "single quotes are literal")]
+    #[test_case("A=\"line\\nbreak \\$HOME\"", &[("A", "line\nbreak $HOME")] ; "double quote escapes")]
+    #[test_case("A=\"multi\nline\"", &[("A", "multi\nline")] ; "multiline double quotes")]
+    #[test_case("A=$HOME/bin", &[("A", "/home/nxpkg/bin")] ; "expands lookup")]
+    #[test_case("A=1\nB=\"${A}2\"", &[("A", "1"), ("B", "12")] ; "expands earlier entries")]
+    #[test_case("A=${MISSING:-fallback} $MISSING.", &[("A", "fallback .")] ; "default and unset")]
+    #[test_case("HOME=/tmp\nA=$HOME", &[("HOME", "/tmp"), ("A", "/home/nxpkg")] ; "env takes precedence")]
+    #[test_case("A=$INHERITED", &[("A", "earlier file")] ; "expands inherited")]
+    #[test_case("INHERITED=1\nA=$INHERITED", &[("INHERITED", "1"), ("A", "1")] ; "entries take precedence over inherited")]
+    #[test_case("A=cost $5", &[("A", "cost $5")] ; "lone dollar")]
+    fn test_parse_dot_env(contents: &str, expected: &[(&str, &str)]) {
+        assert_eq!(parse(contents), vars(expected));
+    }
+
+    #[test_case("A", 1 ; "missing equals")]
+    #[test_case("A=1\n=2", 2 ; "missing name")]
+    #[test_case("A=\"open\nB=2", 1 ; "unterminated quote")]
+    #[test_case("A='x' y", 1 ; "trailing characters")]
+    #[test_case("A=${B", 1 ; "unterminated reference")]
+    fn test_parse_dot_env_errors(contents: &str, expected_line: usize) {
+        let error = parse_dot_env(contents, |_| None, |_| None).unwrap_err();
+        assert!(
+            matches!(error, Error::DotEnv { line, .. } if line == expected_line),
+            "{error}"
+        );
+    }
+}
@@ -12,6 +12,10 @@ use serde::Serialize;
 use sha2::{Digest, Sha256};
 use thiserror::Error;
 
+mod dotenv;
+
+pub use dotenv::parse_dot_env;
+
 const DEFAULT_ENV_VARS: [&str; 1] = ["VERCEL_ANALYTICS_ID"];
 
 /// Environment mode after we've resolved the `Infer` variant
@@ -25,6 +29,8 @@ pub enum ResolvedEnvMode {
 pub enum Error {
     #[error("Failed to parse regex: {0}")]
     Regex(#[from] regex::Error),
+    #[error("invalid .env syntax on line {line}: {reason}")]
+    DotEnv { line: usize, reason: &'static str },
 }
 
 // TODO: Consider using immutable data structures here
@@ -148,6 +154,14 @@ impl EnvironmentVariableMap {
         }
     }
 
+    // Takes another EnvironmentVariableMap and adds the keys that don't
+    // exist in `self` yet. Existing values are kept.
+    pub fn union_missing(&mut self, another: &EnvironmentVariableMap) {
+        for (key, value) in &another.0 {
+            self.0.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+
     // Takes another EnvironmentVariableMap and removes matching keys
     // from `self`
     pub fn difference(&mut self, another: &EnvironmentVariableMap) {
@@ -308,8 +322,31 @@ pub fn get_global_hashable_env_vars(
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use test_case::test_case;
 
+    use super::EnvironmentVariableMap;
+
+    #[test]
+    fn test_union_missing_keeps_existing_values() {
+        let mut env = EnvironmentVariableMap(HashMap::from([(
+            "API_URL".to_string(),
+            "from-process".to_string(),
+        )]));
+        env.union_missing(&EnvironmentVariableMap(HashMap::from([
+            ("API_URL".to_string(), "from-dot-env".to_string()),
+            ("API_KEY".to_string(), "from-dot-env".to_string()),
+        ])));
+        assert_eq!(
+            env.into_inner(),
+            HashMap::from([
+                ("API_URL".to_string(), "from-process".to_string()),
+                ("API_KEY".to_string(), "from-dot-env".to_string()),
+            ])
+        );
+    }
+
     #[test_case("LITERAL_\\*", "LITERAL_\\*" ; "literal star")]
     #[test_case("\\*LEADING", "\\*LEADING" ; "leading literal star")]
     #[test_case("\\!LEADING", "\\\\!LEADING" ; "leading literal bang")]
@@ -5,8 +5,7 @@ use std::{
 
 use thiserror::Error;
 use nxpkgrepo_lockfiles::{
-    self, BerryLockfile, BunLockfile, Lockfile, LockfileData, NpmLockfile, Package, PnpmLockfile,
-    Yarn1Lockfile,
+    self, BerryLockfile, Lockfile, LockfileData, NpmLockfile, Package, PnpmLockfile, Yarn1Lockfile,
 };
 
 use super::{proto, Buffer};
@@ -136,10 +135,9 @@ fn bun_transitive_closure_inner(
         workspaces,
         ..
     } = request;
-    let lockfile =
-        BunLockfile::from_bytes(contents.as_slice()).map_err(nxpkgrepo_lockfiles::Error::from)?;
+    let lockfile = nxpkgrepo_lockfiles::bun_lockfile_from_bytes(contents.as_slice())?;
     let dependencies = nxpkgrepo_lockfiles::all_transitive_closures(
-        &lockfile,
+        lockfile.as_ref(),
         workspaces.into_iter().map(|(k, v)| (k, v.into())).collect(),
     )?;
     Ok(dependencies.into())
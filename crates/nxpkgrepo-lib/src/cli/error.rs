@@ -4,8 +4,9 @@ use thiserror::Error;
 use nxpkgrepo_repository::package_graph;
 
 use crate::{
-    commands::{bin, generate, prune},
+    commands::{bin, compare_runs, generate, prune},
     daemon::DaemonError,
+    query,
     rewrite_json::RewriteError,
     run,
 };
@@ -21,6 +22,8 @@ pub enum Error {
     #[error("at least one task must be specified")]
     NoTasks(#[backtrace] backtrace::Backtrace),
     #[error(transparent)]
+    CompareRuns(#[from] compare_runs::Error),
+    #[error(transparent)]
     Config(#[from] crate::config::Error),
     #[error(transparent)]
     ChromeTracing(#[from] crate::tracing::Error),
@@ -41,7 +44,11 @@ pub enum Error {
     #[error(transparent)]
     PackageManager(#[from] nxpkgrepo_repository::package_manager::Error),
     #[error(transparent)]
+    Query(#[from] query::Error),
+    #[error(transparent)]
     Run(#[from] run::Error),
     #[error(transparent)]
     SerdeJson(#[from] serde_json::Error),
+    #[error(transparent)]
+    Cache(#[from] nxpkgrepo_cache::CacheError),
 }
@@ -11,7 +11,10 @@ use nxpkgrepo_repository::inference::{RepoMode, RepoState};
 use nxpkgrepo_ui::UI;
 
 use crate::{
-    commands::{bin, daemon, generate, info, link, login, logout, prune, unlink, CommandBase},
+    commands::{
+        bin, cache, compare_runs, daemon, generate, info, link, login, logout, prune, query,
+        unlink, watch, CommandBase,
+    },
     get_version,
     tracing::NxpkgSubscriber,
     Payload,
@@ -197,6 +200,26 @@ pub enum DaemonCommand {
     Clean,
 }
 
+#[derive(Subcommand, Clone, Debug, Serialize, PartialEq)]
+#[serde(tag = "command")]
+pub enum CacheCommand {
+    /// Evicts the least recently used entries from the local filesystem
+    /// cache until it is within its configured limits
+    Prune {
+        /// Override the filesystem cache directory.
+        #[clap(long)]
+        cache_dir: Option<Utf8PathBuf>,
+        /// Maximum total size of the cache in bytes. Overrides
+        /// localCache.maxSize in nxpkg.json.
+        #[clap(long)]
+        max_size: Option<u64>,
+        /// Maximum time in seconds an entry can go unused before it is
+        /// evicted. Overrides localCache.maxAge in nxpkg.json.
+        #[clap(long)]
+        max_age: Option<u64>,
+    },
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, ValueEnum)]
 pub enum LinkTarget {
     RemoteCache,
@@ -240,7 +263,17 @@ impl Args {
                     run_args.single_package = is_single_package
                 }
 
-                if let Some(Command::Run(ref mut run_args)) = args.command {
+                if let Some(
+                    Command::Run(ref mut run_args)
+                    | Command::Watch(ref mut run_args)
+                    | Command::Query {
+                        ref mut run_args, ..
+                    }
+                    | Command::CompareRuns {
+                        ref mut run_args, ..
+                    },
+                ) = args.command
+                {
                     run_args.single_package = is_single_package;
                 }
 
@@ -289,7 +322,18 @@ impl Args {
 
     pub fn get_tasks(&self) -> &[String] {
         match &self.command {
-            Some(Command::Run(box RunArgs { tasks, .. })) => tasks,
+            Some(
+                Command::Run(box RunArgs { tasks, .. })
+                | Command::Watch(box RunArgs { tasks, .. })
+                | Command::Query {
+                    run_args: box RunArgs { tasks, .. },
+                    ..
+                }
+                | Command::CompareRuns {
+                    run_args: box RunArgs { tasks, .. },
+                    ..
+                },
+            ) => tasks,
             _ => self
                 .run_args
                 .as_ref()
@@ -308,6 +352,35 @@ pub enum Command {
     // them as `{ "Bin": {} }` instead of as `"Bin"`.
     /// Get the path to the Nxpkg binary
     Bin {},
+    /// Manage the local filesystem cache
+    Cache {
+        #[clap(subcommand)]
+        #[serde(flatten)]
+        command: CacheCommand,
+    },
+    /// Explain why task hashes changed between two runs
+    ///
+    /// Compares the hash inputs recorded in run summaries and reports which
+    /// files, environment variables, dependencies, lockfile entries and
+    /// pass-through args differ. Without `--after` the current state of the
+    /// repo is compared, using the tasks and flags given after the options,
+    /// e.g. `nxpkg compare-runs --before <hash> build --filter web`.
+    #[serde(skip)]
+    CompareRuns {
+        /// A run summary file from `.nxpkg/runs`, or a task hash to look up
+        /// in the saved run summaries
+        #[clap(long)]
+        before: String,
+        /// A run summary file to compare against. Defaults to a dry run of
+        /// the current state of the repo
+        #[clap(long)]
+        after: Option<String>,
+        /// Only explain this task, e.g. `web#build`
+        #[clap(long)]
+        task: Option<String>,
+        #[clap(flatten)]
+        run_args: Box<RunArgs>,
+    },
     /// Generate the autocompletion script for the specified shell
     #[serde(skip)]
     Completion { shell: Shell },
@@ -384,6 +457,18 @@ pub enum Command {
         #[clap(long = "out-dir", default_value_t = String::from("out"), value_parser)]
         output_dir: String,
     },
+    /// Query the package graph and task graph
+    ///
+    /// Evaluates a query like `dependents(ui) & changed("main")` and prints the
+    /// matching packages or tasks as JSON. Tasks can only be queried when they
+    /// are passed after the query, e.g. `nxpkg query 'tasks(all())' build`.
+    #[serde(skip)]
+    Query {
+        /// The query to evaluate
+        query: String,
+        #[clap(flatten)]
+        run_args: Box<RunArgs>,
+    },
 
     /// Run tasks across projects in your monorepo
     ///
@@ -402,6 +487,13 @@ pub enum Command {
         #[clap(long, value_enum, default_value_t = LinkTarget::RemoteCache)]
         target: LinkTarget,
     },
+    /// Run tasks and re-run them as files in your monorepo change
+    ///
+    /// Uses the nxpkg daemon to watch for file changes. Only the tasks whose
+    /// inputs changed and the tasks that depend on them are re-run.
+    /// Persistent tasks are restarted when they are affected by a change.
+    #[serde(skip)]
+    Watch(Box<RunArgs>),
 }
 
 #[derive(Parser, Clone, Debug, Default, Serialize, PartialEq)]
@@ -500,8 +592,9 @@ pub struct RunArgs {
     #[clap(long = "global-deps", action = ArgAction::Append)]
     pub global_deps: Vec<String>,
     /// Generate a graph of the task execution and output to a file when a
-    /// filename is specified (.svg, .png, .jpg, .pdf, .json,
-    /// .html). Outputs dot graph to stdout when if no filename is provided
+    /// filename is specified (.html for an interactive viewer, .mmd or
+    /// .mermaid for Mermaid, .dot or .gv for Graphviz). Outputs dot graph to
+    /// stdout if no filename is provided
     #[clap(long, num_args = 0..=1, default_missing_value = "")]
     pub graph: Option<String>,
     /// Environment variable mode.
@@ -559,6 +652,11 @@ pub struct RunArgs {
     /// allow reading and caching artifacts using the remote cache.
     #[clap(long, env = "NXPKG_REMOTE_ONLY", value_name = "BOOL", action = ArgAction::Set, default_value = "false", default_missing_value = "true", num_args = 0..=1)]
     pub remote_only: bool,
+    /// Fetch more history when a shallow clone doesn't reach back to the
+    /// merge base of a --filter or --since range, instead of treating every
+    /// package as changed.
+    #[clap(long, env = "NXPKG_SCM_DEEPEN", value_name = "BOOL", action = ArgAction::Set, default_value = "false", default_missing_value = "true", num_args = 0..=1)]
+    pub scm_deepen: bool,
     /// Specify package(s) to act as entry points for task execution.
     /// Supports globs.
     #[clap(long)]
@@ -652,7 +750,11 @@ pub async fn run(
     };
 
     // Set some run flags if we have the data and are executing a Run
-    if let Command::Run(run_args) = &mut command {
+    if let Command::Run(run_args)
+    | Command::Watch(run_args)
+    | Command::Query { run_args, .. }
+    | Command::CompareRuns { run_args, .. } = &mut command
+    {
         // Don't overwrite the flag if it's already been set for whatever reason
         run_args.single_package = run_args.single_package
             || repo_state
@@ -710,6 +812,20 @@ pub async fn run(
 
             Ok(Payload::Rust(Ok(0)))
         }
+        Command::Cache { command } => {
+            let command = command.clone();
+            let base = CommandBase::new(cli_args, repo_root, version, ui);
+
+            match command {
+                CacheCommand::Prune {
+                    cache_dir,
+                    max_size,
+                    max_age,
+                } => cache::prune(&base, cache_dir.as_deref(), max_size, max_age)?,
+            }
+
+            Ok(Payload::Rust(Ok(0)))
+        }
         #[allow(unused_variables)]
         Command::Daemon { command, idle_time } => {
             let base = CommandBase::new(cli_args.clone(), repo_root, version, ui);
@@ -829,6 +945,35 @@ pub async fn run(
                 Ok(Payload::Go(Box::new(base)))
             }
         }
+        Command::Watch(args) => {
+            if args.tasks.is_empty() {
+                return Err(Error::NoTasks(backtrace::Backtrace::capture()));
+            }
+            let base = CommandBase::new(cli_args.clone(), repo_root, version, ui);
+            let exit_code = watch::run(base).await?;
+            Ok(Payload::Rust(Ok(exit_code)))
+        }
+        Command::Query { query, .. } => {
+            let query = query.clone();
+            let base = CommandBase::new(cli_args, repo_root, version, ui);
+            query::run(base, &query).await?;
+            Ok(Payload::Rust(Ok(0)))
+        }
+        Command::CompareRuns {
+            before,
+            after,
+            task,
+            run_args,
+        } => {
+            // Comparing against the current state needs a task graph to hash
+            if after.is_none() && run_args.tasks.is_empty() {
+                return Err(Error::NoTasks(backtrace::Backtrace::capture()));
+            }
+            let (before, after, task) = (before.clone(), after.clone(), task.clone());
+            let base = CommandBase::new(cli_args, repo_root, version, ui);
+            compare_runs::run(base, &before, after.as_deref(), task.as_deref()).await?;
+            Ok(Payload::Rust(Ok(0)))
+        }
         Command::Prune {
             scope,
             scope_arg,
@@ -913,7 +1058,8 @@ mod test {
     use anyhow::Result;
 
     use crate::cli::{
-        Args, Command, DryRunMode, EnvMode, LogOrder, LogPrefix, OutputLogsMode, RunArgs, Verbosity,
+        Args, CacheCommand, Command, DryRunMode, EnvMode, LogOrder, LogPrefix, OutputLogsMode,
+        RunArgs, Verbosity,
     };
 
     #[test]
@@ -1662,6 +1808,23 @@ mod test {
         .test();
     }
 
+    #[test]
+    fn test_parse_cache_prune() {
+        assert_eq!(
+            Args::try_parse_from(["nxpkg", "cache", "prune", "--max-size", "1024"]).unwrap(),
+            Args {
+                command: Some(Command::Cache {
+                    command: CacheCommand::Prune {
+                        cache_dir: None,
+                        max_size: Some(1024),
+                        max_age: None,
+                    }
+                }),
+                ..Args::default()
+            }
+        );
+    }
+
     #[test]
     fn test_parse_unlink() {
         assert_eq!(
@@ -1866,6 +2029,63 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_query() {
+        assert_eq!(
+            Args::try_parse_from([
+                "nxpkg",
+                "query",
+                "dependents(ui) & changed('main')",
+                "build",
+                "--filter",
+                "web"
+            ])
+            .unwrap(),
+            Args {
+                command: Some(Command::Query {
+                    query: "dependents(ui) & changed('main')".to_string(),
+                    run_args: Box::new(RunArgs {
+                        tasks: vec!["build".to_string()],
+                        filter: vec!["web".to_string()],
+                        ..get_default_run_args()
+                    }),
+                }),
+                ..Args::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_compare_runs() {
+        assert_eq!(
+            Args::try_parse_from([
+                "nxpkg",
+                "compare-runs",
+                "--before",
+                "abc123",
+                "--task",
+                "web#build",
+                "build",
+                "--filter",
+                "web"
+            ])
+            .unwrap(),
+            Args {
+                command: Some(Command::CompareRuns {
+                    before: "abc123".to_string(),
+                    after: None,
+                    task: Some("web#build".to_string()),
+                    run_args: Box::new(RunArgs {
+                        tasks: vec!["build".to_string()],
+                        filter: vec!["web".to_string()],
+                        ..get_default_run_args()
+                    }),
+                }),
+                ..Args::default()
+            }
+        );
+    }
+
     #[test]
     fn test_parse_prune_no_mixed_arg_and_flag() {
         assert!(Args::try_parse_from(["nxpkg", "prune", "foo", "--scope", "bar"]).is_err(),);
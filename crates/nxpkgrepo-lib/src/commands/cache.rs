@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use camino::Utf8Path;
+use nxpkgrepo_cache::fs::{EvictionPolicy, FSCache};
+use nxpkgrepo_ui::GREY;
+
+use crate::{cli, commands::CommandBase};
+
+/// Evicts entries from the local filesystem cache.
+///
+/// Limits passed on the command line take precedence over the configured
+/// ones.
+pub fn prune(
+    base: &CommandBase,
+    cache_dir: Option<&Utf8Path>,
+    max_size: Option<u64>,
+    max_age: Option<u64>,
+) -> Result<(), cli::Error> {
+    let configured = base.config()?.cache_eviction_policy();
+    let policy = EvictionPolicy {
+        max_size: max_size.or(configured.max_size),
+        max_age: max_age.map(Duration::from_secs).or(configured.max_age),
+    };
+
+    if !policy.is_enabled() {
+        println!(
+            "{}",
+            base.ui.apply(GREY.apply_to(
+                "> No cache limits configured. Set localCache.maxSize or localCache.maxAge in \
+                 nxpkg.json, or pass --max-size or --max-age."
+            ))
+        );
+        return Ok(());
+    }
+
+    let cache = FSCache::new(cache_dir, &base.repo_root, None)?;
+    let summary = cache.prune(&policy)?;
+
+    println!(
+        "{}",
+        base.ui.apply(GREY.apply_to(format!(
+            "> Evicted {} entries ({} bytes), {} entries ({} bytes) remaining",
+            summary.evicted_entries,
+            summary.evicted_bytes,
+            summary.remaining_entries,
+            summary.remaining_bytes
+        )))
+    );
+
+    Ok(())
+}
@@ -0,0 +1,228 @@
+//! Explains why tasks missed the cache by comparing the hash inputs of two
+//! runs. Runs are given as run summaries, and a task hash can be used to look
+//! up the saved summary it came from.
+use std::collections::HashMap;
+
+use tracing::warn;
+use nxpkgpath::{AbsoluteSystemPath, AbsoluteSystemPathBuf};
+use nxpkgrepo_lockfiles::{Lockfile, PackageChanges};
+use nxpkgrepo_repository::{
+    package_graph::{self, ChangedPackagesError, PackageGraph},
+    package_json::{self, PackageJson},
+    package_manager,
+};
+use nxpkgrepo_scm::SCM;
+use nxpkgrepo_ui::{cprintln, BOLD, BOLD_GREEN, BOLD_RED, GREY, UI, YELLOW};
+
+use super::CommandBase;
+use crate::run::{
+    self,
+    summary::compare::{compare, RunComparison, SavedRunSummary, TaskComparison},
+    Run,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to read run summary {1}: {0}")]
+    Read(#[source] std::io::Error, AbsoluteSystemPathBuf),
+    #[error("failed to parse run summary {1}: {0}")]
+    Parse(#[source] serde_json::Error, AbsoluteSystemPathBuf),
+    #[error("failed to parse the run summary of the current state: {0}")]
+    ParseCurrent(#[source] serde_json::Error),
+    #[error(
+        "{0} is not a run summary file and no summary in .nxpkg/runs contains a task with that \
+         hash. Run summaries are only saved when running with --summarize."
+    )]
+    UnknownHash(String),
+    #[error("task {0} is not in either run")]
+    UnknownTask(String),
+    #[error("the run summary doesn't record the commit it was made at")]
+    MissingSha,
+    #[error(transparent)]
+    PackageJson(#[from] package_json::Error),
+    #[error(transparent)]
+    PackageGraph(#[from] package_graph::Error),
+    #[error(transparent)]
+    PackageManager(#[from] package_manager::Error),
+    #[error(transparent)]
+    Scm(#[from] nxpkgrepo_scm::Error),
+    #[error(transparent)]
+    Lockfile(#[from] ChangedPackagesError),
+    #[error(transparent)]
+    Run(#[from] run::Error),
+}
+
+pub async fn run(
+    base: CommandBase,
+    before: &str,
+    after: Option<&str>,
+    task: Option<&str>,
+) -> Result<(), Error> {
+    let before_path = AbsoluteSystemPathBuf::from_unknown(base.cwd(), before);
+    let (before, hash_task) = if before_path.as_path().is_file() {
+        (load_summary(&before_path)?, None)
+    } else {
+        let (summary, task_id) = find_summary_with_hash(&base.repo_root, before)?
+            .ok_or_else(|| Error::UnknownHash(before.to_string()))?;
+        (summary, Some(task_id))
+    };
+    let task = task.map(str::to_string).or(hash_task);
+
+    let after_is_current = after.is_none();
+    let after = match after {
+        Some(after) => load_summary(&AbsoluteSystemPathBuf::from_unknown(base.cwd(), after))?,
+        None => {
+            let summary = Run::new(&base).dry_run_summary().await?;
+            serde_json::from_str(&summary).map_err(Error::ParseCurrent)?
+        }
+    };
+
+    // Listing the changed packages is best effort, the hashes still tell
+    // which tasks are affected
+    let lockfile_changes = if before.external_dependencies_changed(&after) {
+        let after = (!after_is_current).then_some(&after);
+        lockfile_changes(&base, &before, after).unwrap_or_else(|e| {
+            warn!("unable to list the changed external packages: {e}");
+            HashMap::new()
+        })
+    } else {
+        HashMap::new()
+    };
+
+    let comparison = compare(&before, &after, task.as_deref(), &lockfile_changes);
+    if let Some(task) = task {
+        if comparison.tasks.is_empty() {
+            return Err(Error::UnknownTask(task));
+        }
+    }
+
+    print_comparison(base.ui, &comparison);
+    Ok(())
+}
+
+fn load_summary(path: &AbsoluteSystemPath) -> Result<SavedRunSummary, Error> {
+    let contents = path
+        .read_to_string()
+        .map_err(|e| Error::Read(e, path.to_owned()))?;
+    serde_json::from_str(&contents).map_err(|e| Error::Parse(e, path.to_owned()))
+}
+
+// The lockfile of each run is read from the commit it was made at. The current
+// state uses the lockfile on disk instead, which may not be committed yet.
+// Workspaces are keyed by the name used in their task ids.
+fn lockfile_changes(
+    base: &CommandBase,
+    before: &SavedRunSummary,
+    after: Option<&SavedRunSummary>,
+) -> Result<HashMap<String, PackageChanges>, Error> {
+    let root_package_json = PackageJson::load(&base.repo_root.join_component("package.json"))?;
+    let pkg_graph = PackageGraph::builder(&base.repo_root, root_package_json.clone()).build()?;
+    let package_manager = pkg_graph.package_manager();
+    let lockfile_path = package_manager.lockfile_path(&base.repo_root);
+    let scm = SCM::new(&base.repo_root);
+
+    let read_lockfile = |summary: &SavedRunSummary| -> Result<Box<dyn Lockfile>, Error> {
+        let sha = summary.sha().ok_or(Error::MissingSha)?;
+        let contents = scm.previous_content(sha, &lockfile_path)?;
+        Ok(package_manager.parse_lockfile(&root_package_json, &contents)?)
+    };
+
+    let previous = read_lockfile(before)?;
+    let changes = match after {
+        Some(after) => {
+            let current = read_lockfile(after)?;
+            pkg_graph.lockfile_changes_between(previous.as_ref(), current.as_ref())?
+        }
+        None => pkg_graph.lockfile_changes(previous.as_ref())?,
+    };
+
+    Ok(changes
+        .into_iter()
+        .map(|(workspace, changes)| (workspace.to_string(), changes))
+        .collect())
+}
+
+// Summary file names are KSUIDs, which sort by creation time, so the most
+// recent run with the hash is used
+fn find_summary_with_hash(
+    repo_root: &AbsoluteSystemPath,
+    hash: &str,
+) -> Result<Option<(SavedRunSummary, String)>, Error> {
+    let runs_dir = repo_root.join_components(&[".nxpkg", "runs"]);
+    let Ok(entries) = runs_dir.as_path().read_dir_utf8() else {
+        return Ok(None);
+    };
+    let mut paths = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string())
+        .filter(|name| name.ends_with(".json"))
+        .collect::<Vec<_>>();
+    paths.sort_unstable_by(|a, b| b.cmp(a));
+
+    for name in paths {
+        // Skip summaries we can't read rather than failing the lookup
+        let Ok(summary) = load_summary(&runs_dir.join_component(&name)) else {
+            continue;
+        };
+        if let Some(task_id) = summary.task_with_hash(hash).map(|t| t.task_id.clone()) {
+            return Ok(Some((summary, task_id)));
+        }
+    }
+
+    Ok(None)
+}
+
+fn print_comparison(ui: UI, comparison: &RunComparison) {
+    if !comparison.global.is_empty() {
+        cprintln!(
+            ui,
+            BOLD,
+            "Global hash inputs changed, affecting every task:"
+        );
+        for change in &comparison.global {
+            println!("  {change}");
+        }
+        println!();
+    }
+
+    let mut unchanged = 0;
+    for (task_id, task) in &comparison.tasks {
+        match task {
+            TaskComparison::Unchanged { .. } if comparison.tasks.len() > 1 => unchanged += 1,
+            TaskComparison::Unchanged { hash } => {
+                cprintln!(ui, BOLD_GREEN, "{task_id}: hash unchanged ({hash})");
+            }
+            TaskComparison::Added { hash } => {
+                cprintln!(ui, YELLOW, "{task_id}: only in the second run ({hash})");
+            }
+            TaskComparison::Removed { hash } => {
+                cprintln!(ui, YELLOW, "{task_id}: only in the first run ({hash})");
+            }
+            TaskComparison::Changed {
+                before,
+                after,
+                changes,
+            } => {
+                cprintln!(ui, BOLD_RED, "{task_id}: {before} -> {after}");
+                for change in changes {
+                    println!("  {change}");
+                }
+                if changes.is_empty() && comparison.global.is_empty() {
+                    cprintln!(
+                        ui,
+                        GREY,
+                        "  no recorded input changed, the task's package directory may have moved"
+                    );
+                }
+            }
+        }
+    }
+
+    if unchanged > 0 {
+        cprintln!(
+            ui,
+            GREY,
+            "{unchanged} tasks have the same hash in both runs"
+        );
+    }
+}
@@ -12,6 +12,8 @@ use crate::{
 };
 
 pub(crate) mod bin;
+pub(crate) mod cache;
+pub(crate) mod compare_runs;
 pub(crate) mod daemon;
 pub(crate) mod generate;
 pub(crate) mod info;
@@ -19,8 +21,10 @@ pub(crate) mod link;
 pub(crate) mod login;
 pub(crate) mod logout;
 pub(crate) mod prune;
+pub(crate) mod query;
 pub(crate) mod run;
 pub(crate) mod unlink;
+pub(crate) mod watch;
 
 #[derive(Debug)]
 pub struct CommandBase {
@@ -40,8 +40,11 @@ pub enum Error {
     MissingWorkspace(WorkspaceName),
     #[error("Cannot prune without parsed lockfile")]
     MissingLockfile,
-    #[error("Prune is not supported for Bun")]
-    BunUnsupported,
+    #[error(
+        "Cannot prune a binary bun.lockb, run `bun install --save-text-lockfile` to switch to a \
+         text bun.lock"
+    )]
+    BinaryBunLockfile,
 }
 
 // Files that should be copied from root and if they're required for install
@@ -87,11 +90,12 @@ pub fn prune(
 ) -> Result<(), Error> {
     let prune = Prune::new(base, scope, docker, output_dir)?;
 
-    if matches!(
-        prune.package_graph.package_manager(),
-        nxpkgrepo_repository::package_manager::PackageManager::Bun
-    ) {
-        return Err(Error::BunUnsupported);
+    // Only a text bun.lock can be pruned, we can't write the binary format
+    let package_manager = prune.package_graph.package_manager();
+    if package_manager.lockfile_path(&prune.root).file_name()
+        != Some(package_manager.lockfile_name())
+    {
+        return Err(Error::BinaryBunLockfile);
     }
 
     println!(
@@ -0,0 +1,144 @@
+//! Evaluates a query over the package graph and task graph and outputs the
+//! matching packages or tasks as JSON. See `crate::query` for the syntax.
+use std::collections::HashSet;
+
+use itertools::Itertools;
+use serde::Serialize;
+use nxpkgpath::AnchoredSystemPath;
+use nxpkgrepo_repository::package_graph::{PackageGraph, WorkspaceName, WorkspaceNode};
+use nxpkgrepo_scm::SCM;
+
+use crate::{
+    cli,
+    commands::CommandBase,
+    engine::{Engine, TaskNode},
+    opts::Opts,
+    query::{Query, Value},
+    run::{
+        self, scope::SCMChangeDetector, summary::TaskSummaryTaskDefinition, task_id::TaskId, Run,
+    },
+    task_hash::TaskHashTrackerState,
+};
+
+#[derive(Serialize)]
+#[serde(tag = "kind", content = "results", rename_all = "camelCase")]
+enum QueryResult<'a> {
+    Packages(Vec<PackageDetails<'a>>),
+    Tasks(Vec<TaskDetails<'a>>),
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PackageDetails<'a> {
+    name: &'a WorkspaceName,
+    path: &'a AnchoredSystemPath,
+    dependencies: Vec<&'a WorkspaceName>,
+    dependents: Vec<&'a WorkspaceName>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TaskDetails<'a> {
+    task_id: &'a TaskId<'static>,
+    package: &'a str,
+    task: &'a str,
+    hash: Option<&'a str>,
+    dependencies: Vec<&'a TaskId<'static>>,
+    dependents: Vec<&'a TaskId<'static>>,
+    resolved_task_definition: Option<TaskSummaryTaskDefinition>,
+}
+
+pub async fn run(base: CommandBase, query: &str) -> Result<(), cli::Error> {
+    let graphs = Run::new(&base).graphs().await?;
+    let opts = Opts::try_from(base.args()).map_err(run::Error::from)?;
+    // Without any tasks the task graph is empty, so rather than have task
+    // queries silently match nothing we report that there's no task graph
+    let engine = (!base.args().get_tasks().is_empty()).then_some(graphs.engine.as_ref());
+
+    let scm = SCM::new(&base.repo_root);
+    let change_detector = SCMChangeDetector::new(
+        &base.repo_root,
+        &scm,
+        &graphs.pkg_dep_graph,
+        opts.scope_opts.global_deps.clone(),
+        opts.scope_opts.ignore_patterns.clone(),
+        opts.scope_opts.scm_deepen,
+    );
+    let value = Query::new(&graphs.pkg_dep_graph, engine, change_detector).evaluate(query)?;
+
+    let result = match &value {
+        Value::Packages(packages) => QueryResult::Packages(
+            packages
+                .iter()
+                .filter_map(|name| PackageDetails::new(&graphs.pkg_dep_graph, name))
+                .collect(),
+        ),
+        Value::Tasks(tasks) => QueryResult::Tasks(
+            tasks
+                .iter()
+                .map(|task_id| TaskDetails::new(&graphs.engine, &graphs.task_hashes, task_id))
+                .collect(),
+        ),
+    };
+    println!("{}", serde_json::to_string_pretty(&result)?);
+
+    Ok(())
+}
+
+impl<'a> PackageDetails<'a> {
+    fn new(pkg_graph: &'a PackageGraph, name: &'a WorkspaceName) -> Option<Self> {
+        let info = pkg_graph.workspace_info(name)?;
+        let node = WorkspaceNode::Workspace(name.clone());
+        Some(Self {
+            name,
+            path: info.package_path(),
+            dependencies: workspace_names(pkg_graph.immediate_dependencies(&node)),
+            dependents: workspace_names(pkg_graph.immediate_ancestors(&node)),
+        })
+    }
+}
+
+impl<'a> TaskDetails<'a> {
+    fn new(
+        engine: &'a Engine,
+        task_hashes: &'a TaskHashTrackerState,
+        task_id: &'a TaskId<'static>,
+    ) -> Self {
+        Self {
+            task_id,
+            package: task_id.package(),
+            task: task_id.task(),
+            hash: task_hashes.hash(task_id),
+            dependencies: task_ids(engine.dependencies(task_id)),
+            dependents: task_ids(engine.dependents(task_id)),
+            resolved_task_definition: engine
+                .task_definition(task_id)
+                .cloned()
+                .map(TaskSummaryTaskDefinition::from),
+        }
+    }
+}
+
+fn workspace_names(nodes: Option<HashSet<&WorkspaceNode>>) -> Vec<&WorkspaceName> {
+    nodes
+        .into_iter()
+        .flatten()
+        .filter_map(|node| match node {
+            WorkspaceNode::Root => None,
+            WorkspaceNode::Workspace(name) => Some(name),
+        })
+        .sorted()
+        .collect()
+}
+
+fn task_ids(nodes: Option<HashSet<&TaskNode>>) -> Vec<&TaskId<'static>> {
+    nodes
+        .into_iter()
+        .flatten()
+        .filter_map(|node| match node {
+            TaskNode::Root => None,
+            TaskNode::Task(task_id) => Some(task_id),
+        })
+        .sorted()
+        .collect()
+}
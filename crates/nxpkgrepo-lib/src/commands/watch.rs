@@ -0,0 +1,27 @@
+use tracing::error;
+
+use crate::{commands::CommandBase, run, run::WatchClient, signal::SignalHandler};
+
+pub async fn run(base: CommandBase) -> Result<i32, run::Error> {
+    let handler = SignalHandler::new(tokio::signal::ctrl_c());
+    let watch_subscriber = handler
+        .subscribe()
+        .expect("handler shouldn't close immediately after opening");
+
+    let client = WatchClient::new(&base);
+    let watch_fut = client.start(watch_subscriber);
+    let handler_fut = handler.done();
+    tokio::select! {
+        biased;
+        // Watching only stops because of a signal, the client stops all running
+        // tasks before releasing its subscription
+        _ = handler_fut => Ok(0),
+        result = watch_fut => {
+            handler.close().await;
+            result.map_err(|err| {
+                error!("watch failed: {}", err);
+                err
+            })
+        },
+    }
+}
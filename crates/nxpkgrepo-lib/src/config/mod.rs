@@ -74,4 +74,14 @@ pub enum Error {
     InvalidRemoteCacheTimeout(#[source] std::num::ParseIntError),
     #[error("NXPKG_PREFLIGHT should be either 1 or 0.")]
     InvalidPreflight,
+    #[error("NXPKG_CACHE_MAX_SIZE: error parsing size.")]
+    InvalidCacheMaxSize(#[source] std::num::ParseIntError),
+    #[error("NXPKG_CACHE_MAX_AGE: error parsing age.")]
+    InvalidCacheMaxAge(#[source] std::num::ParseIntError),
+    #[error("\"timeout\" must be at least 1 second.")]
+    InvalidTaskTimeout,
+    #[error("\"timeout\" can't be set for persistent tasks, which aren't expected to exit.")]
+    PersistentTaskTimeout,
+    #[error("\"weight\" must be at least 1.")]
+    InvalidTaskWeight,
 }
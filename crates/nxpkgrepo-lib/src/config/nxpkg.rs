@@ -2,6 +2,7 @@ use std::{
     collections::{BTreeMap, HashMap, HashSet},
     io::Write,
     path::Path,
+    time::Duration,
 };
 
 use camino::Utf8Path;
@@ -65,6 +66,20 @@ pub struct RawNxpkgJSON {
     // Configuration options when interfacing with the remote cache
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) remote_cache: Option<ConfigurationOptions>,
+    // Limits on the size of the local filesystem cache
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) local_cache: Option<LocalCacheOptions>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalCacheOptions {
+    // Maximum size of the cache in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) max_size: Option<u64>,
+    // Maximum time in seconds an entry can go unused before it is evicted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) max_age: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Clone)]
@@ -92,6 +107,23 @@ struct RawTaskDefinition {
     outputs: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     output_mode: Option<OutputLogsMode>,
+    // Number of times a failing task is retried before it is considered failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retries: Option<u32>,
+    // Seconds a task can run before it is killed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout: Option<u64>,
+    // Number of concurrency slots the task occupies while it runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weight: Option<u32>,
+    // Names of locks the task holds while it runs, tasks sharing a mutex
+    // never run at the same time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mutexes: Option<Vec<String>>,
+    // Whether line endings and path separators are normalized when hashing
+    // the task, so that it hashes the same on every platform
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cross_platform: Option<bool>,
 }
 
 const CONFIG_FILE: &str = "nxpkg.json";
@@ -264,6 +296,50 @@ impl TryFrom<RawTaskDefinition> for BookkeepingTaskDefinition {
         if raw_task.persistent.is_some() {
             defined_fields.insert("Persistent".to_string());
         }
+        if raw_task.retries.is_some() {
+            defined_fields.insert("Retries".to_string());
+        }
+        if raw_task.cross_platform.is_some() {
+            defined_fields.insert("CrossPlatform".to_string());
+        }
+
+        let timeout = raw_task
+            .timeout
+            .map(|timeout| {
+                defined_fields.insert("Timeout".to_string());
+                // A timeout of 0 would kill every task immediately
+                if timeout == 0 {
+                    return Err(Error::InvalidTaskTimeout);
+                }
+                Ok(Duration::from_secs(timeout))
+            })
+            .transpose()?;
+        if timeout.is_some() && raw_task.persistent == Some(true) {
+            return Err(Error::PersistentTaskTimeout);
+        }
+
+        let weight = raw_task
+            .weight
+            .map(|weight| {
+                defined_fields.insert("Weight".to_string());
+                // A task without a weight would never hold the scheduler back
+                if weight == 0 {
+                    return Err(Error::InvalidTaskWeight);
+                }
+                Ok(weight)
+            })
+            .transpose()?
+            .unwrap_or(1);
+
+        let mutexes = raw_task
+            .mutexes
+            .map(|mut mutexes| {
+                defined_fields.insert("Mutexes".to_string());
+                mutexes.sort();
+                mutexes.dedup();
+                mutexes
+            })
+            .unwrap_or_default();
 
         Ok(BookkeepingTaskDefinition {
             defined_fields,
@@ -280,6 +356,11 @@ impl TryFrom<RawTaskDefinition> for BookkeepingTaskDefinition {
                 dot_env,
                 output_mode: raw_task.output_mode.unwrap_or_default(),
                 persistent: raw_task.persistent.unwrap_or_default(),
+                retries: raw_task.retries.unwrap_or_default(),
+                timeout,
+                weight,
+                mutexes,
+                cross_platform: raw_task.cross_platform.unwrap_or_default(),
             },
         })
     }
@@ -561,7 +642,7 @@ fn gather_env_vars(vars: Vec<String>, key: &str, into: &mut HashSet<String>) ->
 
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashSet, fs};
+    use std::{collections::HashSet, fs, time::Duration};
 
     use anyhow::Result;
     use pretty_assertions::assert_eq;
@@ -573,7 +654,7 @@ mod tests {
     use super::RawNxpkgJSON;
     use crate::{
         cli::OutputLogsMode,
-        config::{nxpkg::RawTaskDefinition, NxpkgJson},
+        config::{nxpkg::RawTaskDefinition, Error, NxpkgJson},
         run::task_id::TaskName,
         task_graph::{
             BookkeepingTaskDefinition, TaskDefinitionExperiments, TaskDefinitionStable, TaskOutputs,
@@ -741,7 +822,12 @@ mod tests {
           "cache": false,
           "inputs": ["package/a/src/**"],
           "outputMode": "full",
-          "persistent": true
+          "persistent": false,
+          "retries": 2,
+          "timeout": 600,
+          "weight": 2,
+          "mutexes": ["port-3000", "database"],
+          "crossPlatform": true
         }"#,
         RawTaskDefinition {
             depends_on: Some(vec!["cli#build".to_string()]),
@@ -752,7 +838,12 @@ mod tests {
             cache: Some(false),
             inputs: Some(vec!["package/a/src/**".to_string()]),
             output_mode: Some(OutputLogsMode::Full),
-            persistent: Some(true),
+            persistent: Some(false),
+            retries: Some(2),
+            timeout: Some(600),
+            weight: Some(2),
+            mutexes: Some(vec!["port-3000".to_string(), "database".to_string()]),
+            cross_platform: Some(true),
         },
         BookkeepingTaskDefinition {
             defined_fields: [
@@ -763,6 +854,11 @@ mod tests {
                 "PassThroughEnv".to_string(),
                 "Cache".to_string(),
                 "Persistent".to_string(),
+                "Retries".to_string(),
+                "Timeout".to_string(),
+                "Weight".to_string(),
+                "Mutexes".to_string(),
+                "CrossPlatform".to_string(),
                 "Inputs".to_string(),
                 "DependsOn".to_string()
             ].into_iter().collect(),
@@ -781,7 +877,12 @@ mod tests {
                 pass_through_env: Some(vec!["AWS_SECRET_KEY".to_string()]),
                 task_dependencies: vec!["cli#build".into()],
                 topological_dependencies: vec![],
-                persistent: true,
+                persistent: false,
+                retries: 2,
+                timeout: Some(Duration::from_secs(600)),
+                weight: 2,
+                mutexes: vec!["database".to_string(), "port-3000".to_string()],
+                cross_platform: true,
             }
         }
     )]
@@ -799,6 +900,30 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_zero_timeout() {
+        let raw_task_definition: RawTaskDefinition =
+            serde_json::from_str(r#"{ "timeout": 0 }"#).unwrap();
+        let task_definition = BookkeepingTaskDefinition::try_from(raw_task_definition);
+        assert!(matches!(task_definition, Err(Error::InvalidTaskTimeout)));
+    }
+
+    #[test]
+    fn test_persistent_timeout() {
+        let raw_task_definition: RawTaskDefinition =
+            serde_json::from_str(r#"{ "persistent": true, "timeout": 600 }"#).unwrap();
+        let task_definition = BookkeepingTaskDefinition::try_from(raw_task_definition);
+        assert!(matches!(task_definition, Err(Error::PersistentTaskTimeout)));
+    }
+
+    #[test]
+    fn test_zero_weight() {
+        let raw_task_definition: RawTaskDefinition =
+            serde_json::from_str(r#"{ "weight": 0 }"#).unwrap();
+        let task_definition = BookkeepingTaskDefinition::try_from(raw_task_definition);
+        assert!(matches!(task_definition, Err(Error::InvalidTaskWeight)));
+    }
+
     #[test_case("[]", TaskOutputs::default())]
     #[test_case(r#"["target/**"]"#, TaskOutputs { inclusions: vec!["target/**".to_string()], exclusions: vec![] })]
     #[test_case(
@@ -1,8 +1,9 @@
-use std::{collections::HashMap, ffi::OsString};
+use std::{collections::HashMap, ffi::OsString, time::Duration};
 
 use dirs_next::config_dir;
 use serde::{Deserialize, Serialize};
 use nxpkgpath::AbsoluteSystemPathBuf;
+use nxpkgrepo_cache::{backend::BackendConfig, fs::EvictionPolicy};
 use nxpkgrepo_repository::package_json::{Error as PackageJsonError, PackageJson};
 
 use crate::{
@@ -44,9 +45,19 @@ pub struct ConfigurationOptions {
     pub(crate) team_id: Option<String>,
     pub(crate) token: Option<String>,
     pub(crate) signature: Option<bool>,
+    /// Whether artifacts are uploaded to the remote cache in the
+    /// content-addressed format
+    pub(crate) content_addressed: Option<bool>,
     pub(crate) preflight: Option<bool>,
     pub(crate) timeout: Option<u64>,
     pub(crate) enabled: Option<bool>,
+    /// Maximum size of the local filesystem cache in bytes
+    pub(crate) cache_max_size: Option<u64>,
+    /// Maximum time in seconds an entry can go unused before it is evicted
+    /// from the local filesystem cache
+    pub(crate) cache_max_age: Option<u64>,
+    /// Caches to use in addition to the Vercel remote cache
+    pub(crate) backends: Option<Vec<BackendConfig>>,
 }
 
 #[derive(Default)]
@@ -86,6 +97,10 @@ impl ConfigurationOptions {
         self.signature.unwrap_or_default()
     }
 
+    pub fn content_addressed(&self) -> bool {
+        self.content_addressed.unwrap_or_default()
+    }
+
     pub fn enabled(&self) -> bool {
         self.enabled.unwrap_or(true)
     }
@@ -97,6 +112,17 @@ impl ConfigurationOptions {
     pub fn timeout(&self) -> u64 {
         self.timeout.unwrap_or(DEFAULT_TIMEOUT)
     }
+
+    pub fn cache_eviction_policy(&self) -> EvictionPolicy {
+        EvictionPolicy {
+            max_size: self.cache_max_size,
+            max_age: self.cache_max_age.map(Duration::from_secs),
+        }
+    }
+
+    pub fn cache_backends(&self) -> &[BackendConfig] {
+        self.backends.as_deref().unwrap_or_default()
+    }
 }
 
 trait ResolvedConfigurationOptions {
@@ -118,7 +144,7 @@ impl ResolvedConfigurationOptions for PackageJson {
 
 impl ResolvedConfigurationOptions for RawNxpkgJSON {
     fn get_configuration_options(self) -> Result<ConfigurationOptions, ConfigError> {
-        match &self.remote_cache {
+        let mut configuration_options = match &self.remote_cache {
             Some(configuration_options) => {
                 configuration_options
                     .clone()
@@ -127,10 +153,15 @@ impl ResolvedConfigurationOptions for RawNxpkgJSON {
                     .map(|mut configuration_options| {
                         configuration_options.token = None;
                         configuration_options
-                    })
+                    })?
             }
-            None => Ok(ConfigurationOptions::default()),
+            None => ConfigurationOptions::default(),
+        };
+        if let Some(local_cache) = &self.local_cache {
+            configuration_options.cache_max_size = local_cache.max_size;
+            configuration_options.cache_max_age = local_cache.max_age;
         }
+        Ok(configuration_options)
     }
 }
 
@@ -157,6 +188,8 @@ fn get_env_var_config(
     nxpkg_mapping.insert(OsString::from("nxpkg_teamid"), "team_id");
     nxpkg_mapping.insert(OsString::from("nxpkg_token"), "token");
     nxpkg_mapping.insert(OsString::from("nxpkg_remote_cache_timeout"), "timeout");
+    nxpkg_mapping.insert(OsString::from("nxpkg_cache_max_size"), "cache_max_size");
+    nxpkg_mapping.insert(OsString::from("nxpkg_cache_max_age"), "cache_max_age");
 
     // We do not enable new config sources:
     // nxpkg_mapping.insert(String::from("nxpkg_signature"), "signature"); // new
@@ -226,6 +259,17 @@ fn get_env_var_config(
         None
     };
 
+    let cache_max_size = output_map
+        .get("cache_max_size")
+        .map(|size| size.parse::<u64>())
+        .transpose()
+        .map_err(ConfigError::InvalidCacheMaxSize)?;
+    let cache_max_age = output_map
+        .get("cache_max_age")
+        .map(|age| age.parse::<u64>())
+        .transpose()
+        .map_err(ConfigError::InvalidCacheMaxAge)?;
+
     let output = ConfigurationOptions {
         api_url: output_map.get("api_url").cloned(),
         login_url: output_map.get("login_url").cloned(),
@@ -235,11 +279,16 @@ fn get_env_var_config(
 
         // Processed booleans
         signature,
+        content_addressed: None,
         preflight,
         enabled,
 
         // Processed numbers
         timeout,
+        cache_max_size,
+        cache_max_age,
+
+        backends: None,
     };
 
     Ok(output)
@@ -280,9 +329,13 @@ fn get_override_env_var_config(
         token: output_map.get("token").cloned(),
 
         signature: None,
+        content_addressed: None,
         preflight: None,
         enabled: None,
         timeout: None,
+        cache_max_size: None,
+        cache_max_age: None,
+        backends: None,
     };
 
     Ok(output)
@@ -441,6 +494,9 @@ impl NxpkgrepoConfigBuilder {
                     if let Some(signature) = current_source_config.signature {
                         acc.signature = Some(signature);
                     }
+                    if let Some(content_addressed) = current_source_config.content_addressed {
+                        acc.content_addressed = Some(content_addressed);
+                    }
                     if let Some(enabled) = current_source_config.enabled {
                         acc.enabled = Some(enabled);
                     }
@@ -450,6 +506,15 @@ impl NxpkgrepoConfigBuilder {
                     if let Some(timeout) = current_source_config.timeout {
                         acc.timeout = Some(timeout);
                     }
+                    if let Some(cache_max_size) = current_source_config.cache_max_size {
+                        acc.cache_max_size = Some(cache_max_size);
+                    }
+                    if let Some(cache_max_age) = current_source_config.cache_max_age {
+                        acc.cache_max_age = Some(cache_max_age);
+                    }
+                    if let Some(backends) = current_source_config.backends.clone() {
+                        acc.backends = Some(backends);
+                    }
 
                     acc
                 })
@@ -476,6 +541,7 @@ mod test {
         assert!(defaults.enabled());
         assert!(!defaults.preflight());
         assert_eq!(defaults.timeout(), DEFAULT_TIMEOUT);
+        assert!(!defaults.cache_eviction_policy().is_enabled());
     }
 
     #[test]
@@ -488,6 +554,8 @@ mod test {
         let nxpkg_teamid = "team_nLlpyC6REAqxydlFKbrMDlud";
         let nxpkg_token = "abcdef1234567890abcdef";
         let nxpkg_remote_cache_timeout = 200;
+        let nxpkg_cache_max_size = 1024 * 1024;
+        let nxpkg_cache_max_age = 60 * 60;
 
         env.insert("nxpkg_api".into(), nxpkg_api.into());
         env.insert("nxpkg_login".into(), nxpkg_login.into());
@@ -498,6 +566,14 @@ mod test {
             "nxpkg_remote_cache_timeout".into(),
             nxpkg_remote_cache_timeout.to_string().into(),
         );
+        env.insert(
+            "nxpkg_cache_max_size".into(),
+            nxpkg_cache_max_size.to_string().into(),
+        );
+        env.insert(
+            "nxpkg_cache_max_age".into(),
+            nxpkg_cache_max_age.to_string().into(),
+        );
 
         let config = get_env_var_config(&env).unwrap();
         assert_eq!(nxpkg_api, config.api_url.unwrap());
@@ -506,6 +582,13 @@ mod test {
         assert_eq!(nxpkg_teamid, config.team_id.unwrap());
         assert_eq!(nxpkg_token, config.token.unwrap());
         assert_eq!(nxpkg_remote_cache_timeout, config.timeout.unwrap());
+        assert_eq!(
+            config.cache_eviction_policy(),
+            EvictionPolicy {
+                max_size: Some(nxpkg_cache_max_size),
+                max_age: Some(Duration::from_secs(nxpkg_cache_max_age)),
+            }
+        );
     }
 
     #[test]
@@ -589,4 +672,45 @@ mod test {
             None
         );
     }
+
+    #[test]
+    fn test_shared_local_cache() {
+        let nxpkg_json: RawNxpkgJSON = serde_json::from_str(
+            r#"{"remoteCache": {"signature": true}, "localCache": {"maxSize": 1000, "maxAge": 60}}"#,
+        )
+        .unwrap();
+
+        let configuration_options = nxpkg_json.get_configuration_options().unwrap();
+        assert!(configuration_options.signature());
+        assert_eq!(
+            configuration_options.cache_eviction_policy(),
+            EvictionPolicy {
+                max_size: Some(1000),
+                max_age: Some(Duration::from_secs(60)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_shared_content_addressed() {
+        let nxpkg_json: RawNxpkgJSON =
+            serde_json::from_str(r#"{"remoteCache": {"contentAddressed": true}}"#).unwrap();
+        assert!(nxpkg_json
+            .get_configuration_options()
+            .unwrap()
+            .content_addressed());
+        assert!(!ConfigurationOptions::default().content_addressed());
+    }
+
+    #[test]
+    fn test_shared_cache_backends() {
+        let nxpkg_json: RawNxpkgJSON = serde_json::from_str(
+            r#"{"remoteCache": {"backends": [{"type": "http", "url": "https://cache.example.com"}]}}"#,
+        )
+        .unwrap();
+
+        let configuration_options = nxpkg_json.get_configuration_options().unwrap();
+        assert_eq!(configuration_options.cache_backends().len(), 1);
+        assert!(ConfigurationOptions::default().cache_backends().is_empty());
+    }
 }
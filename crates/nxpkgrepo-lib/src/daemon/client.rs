@@ -1,5 +1,6 @@
 use std::io;
 
+use futures::{Stream, StreamExt};
 use thiserror::Error;
 use tonic::{Code, Status};
 use tracing::info;
@@ -118,6 +119,20 @@ impl DaemonClient<DaemonConnector> {
         Ok(())
     }
 
+    /// Subscribe to file changes in the repository. The stream ends when the
+    /// daemon shuts down.
+    pub async fn file_changes(
+        &mut self,
+    ) -> Result<impl Stream<Item = Result<proto::FileChangesResponse, DaemonError>>, DaemonError>
+    {
+        let stream = self
+            .client
+            .file_changes(proto::FileChangesRequest {})
+            .await?
+            .into_inner();
+        Ok(stream.map(|response| response.map_err(DaemonError::from)))
+    }
+
     /// Get the status of the daemon.
     pub async fn status(&mut self) -> Result<proto::DaemonStatus, DaemonError> {
         self.client
@@ -14,6 +14,7 @@
 
 use std::{
     collections::{HashMap, HashSet},
+    pin::Pin,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc, Mutex,
@@ -21,11 +22,11 @@ use std::{
     time::{Duration, Instant},
 };
 
-use futures::Future;
+use futures::{Future, Stream};
 use thiserror::Error;
 use tokio::{
     select,
-    sync::{mpsc, oneshot, watch},
+    sync::{broadcast, mpsc, oneshot, watch},
 };
 use tonic::transport::{NamedService, Server};
 use tower::ServiceBuilder;
@@ -59,7 +60,7 @@ pub enum CloseReason {
 }
 
 struct FileWatching {
-    _watcher: FileSystemWatcher,
+    watcher: FileSystemWatcher,
     glob_watcher: GlobWatcher,
 }
 
@@ -102,7 +103,7 @@ async fn start_filewatching(
     // We can ignore failures here, it means the server is shutting down and
     // receivers have gone out of scope.
     let _ = watcher_tx.send(Some(Arc::new(FileWatching {
-        _watcher: watcher,
+        watcher,
         glob_watcher,
     })));
     Ok(())
@@ -167,12 +168,16 @@ where
 
     // when one of these futures complete, let the server gracefully shutdown
     let (grpc_shutdown_tx, shutdown_reason) = oneshot::channel();
+    // Streaming RPCs would keep the server from shutting down gracefully, so
+    // they listen for this signal and close their streams.
+    let (close_streams_tx, close_streams_rx) = watch::channel(false);
     let shutdown_fut = async move {
         select! {
             _ = shutdown_signal.recv() => grpc_shutdown_tx.send(CloseReason::Shutdown).ok(),
             _ = timeout_fut => grpc_shutdown_tx.send(CloseReason::Timeout).ok(),
             reason = external_shutdown => grpc_shutdown_tx.send(reason).ok(),
         };
+        let _ = close_streams_tx.send(true);
     };
 
     // Run the actual service. It takes ownership of the struct given to it,
@@ -184,6 +189,8 @@ where
         times_saved: Arc::new(Mutex::new(HashMap::new())),
         start_time: Instant::now(),
         log_file,
+        repo_root: repo_root.to_owned(),
+        close_streams_rx,
     };
     let server_fut = {
         let service = ServiceBuilder::new()
@@ -227,6 +234,8 @@ struct NxpkgGrpcService {
     times_saved: Arc<Mutex<HashMap<String, u64>>>,
     start_time: Instant,
     log_file: AbsoluteSystemPathBuf,
+    repo_root: AbsoluteSystemPathBuf,
+    close_streams_rx: watch::Receiver<bool>,
 }
 
 impl NxpkgGrpcService {
@@ -270,8 +279,57 @@ impl NxpkgGrpcService {
         let changed_globs = fw.glob_watcher.get_changed_globs(hash, candidates).await?;
         Ok((changed_globs, time_saved))
     }
+
+    async fn file_changes(&self) -> Result<FileChangesStream, RpcError> {
+        let mut recv_events = self.wait_for_filewatching().await?.watcher.subscribe();
+        let repo_root = self.repo_root.clone();
+        let mut close_streams = self.close_streams_rx.clone();
+        let stream = async_stream::stream! {
+            loop {
+                let event = select! {
+                    _ = close_streams.changed() => break,
+                    event = recv_events.recv() => event,
+                };
+                match event {
+                    Ok(Ok(event)) => {
+                        let paths = event
+                            .paths
+                            .iter()
+                            .filter_map(|path| {
+                                let path = AbsoluteSystemPath::from_std_path(path).ok()?;
+                                let relative = repo_root.anchor(path).ok()?;
+                                Some(relative.to_unix().to_string())
+                            })
+                            .collect::<Vec<_>>();
+                        if !paths.is_empty() {
+                            yield Ok(proto::FileChangesResponse {
+                                paths,
+                                lost_events: false,
+                            });
+                        }
+                    }
+                    Ok(Err(err)) => {
+                        yield Err(tonic::Status::unavailable(err.to_string()));
+                        break;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(count)) => {
+                        warn!("file changes stream lagged by {} events", count);
+                        yield Ok(proto::FileChangesResponse {
+                            paths: Vec::new(),
+                            lost_events: true,
+                        });
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        };
+        Ok(Box::pin(stream))
+    }
 }
 
+type FileChangesStream =
+    Pin<Box<dyn Stream<Item = Result<proto::FileChangesResponse, tonic::Status>> + Send>>;
+
 async fn wait_for_filewatching(
     mut rx: watch::Receiver<Option<Arc<FileWatching>>>,
     timeout: Duration,
@@ -305,7 +363,7 @@ async fn watch_root(
             return Ok(());
         };
 
-        fw._watcher.subscribe()
+        fw.watcher.subscribe()
     };
 
     loop {
@@ -407,6 +465,15 @@ impl proto::nxpkgd_server::Nxpkgd for NxpkgGrpcService {
             time_saved,
         }))
     }
+
+    type FileChangesStream = FileChangesStream;
+
+    async fn file_changes(
+        &self,
+        _request: tonic::Request<proto::FileChangesRequest>,
+    ) -> Result<tonic::Response<Self::FileChangesStream>, tonic::Status> {
+        Ok(tonic::Response::new(self.file_changes().await?))
+    }
 }
 
 impl NamedService for NxpkgGrpcService {
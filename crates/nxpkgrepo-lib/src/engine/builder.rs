@@ -1,7 +1,7 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 
 use itertools::Itertools;
-use nxpkgpath::AbsoluteSystemPath;
+use nxpkgpath::{AbsoluteSystemPath, RelativeUnixPathBuf};
 use nxpkgrepo_graph_utils as graph;
 use nxpkgrepo_repository::package_graph::{
     PackageGraph, WorkspaceName, WorkspaceNode, ROOT_PKG_NAME,
@@ -176,11 +176,19 @@ impl<'a> EngineBuilder<'a> {
                     task_id: task_id.to_string(),
                 });
             }
-            let task_definition = TaskDefinition::from_iter(self.task_definition_chain(
+            let mut task_definition = TaskDefinition::from_iter(self.task_definition_chain(
                 &mut nxpkg_jsons,
                 &task_id,
                 &task_id.as_non_workspace_task_name(),
             )?);
+            let workspace_dot_env = self.workspace_dot_env(&mut nxpkg_jsons, &task_id)?;
+            if !workspace_dot_env.is_empty() {
+                // Listed after the task's own files so those take precedence
+                task_definition
+                    .dot_env
+                    .get_or_insert_with(Vec::new)
+                    .extend(workspace_dot_env);
+            }
 
             // Skip this iteration of the loop if we've already seen this taskID
             if visited.contains(&task_id) {
@@ -356,6 +364,25 @@ impl<'a> EngineBuilder<'a> {
         Ok(task_definitions)
     }
 
+    // A workspace's `globalDotEnv` applies to every task in that workspace. The
+    // root workspace's `globalDotEnv` is part of the global hash instead.
+    fn workspace_dot_env(
+        &self,
+        nxpkg_jsons: &mut HashMap<WorkspaceName, NxpkgJson>,
+        task_id: &TaskId,
+    ) -> Result<Vec<RelativeUnixPathBuf>, Error> {
+        if self.is_single || task_id.package() == ROOT_PKG_NAME {
+            return Ok(Vec::new());
+        }
+        match self.nxpkg_json(nxpkg_jsons, &WorkspaceName::from(task_id.package())) {
+            Ok(workspace_json) => Ok(workspace_json
+                .and_then(|json| json.global_dot_env.clone())
+                .unwrap_or_default()),
+            Err(e) if e.is_missing_nxpkg_json() => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
     fn nxpkg_json<'b>(
         &self,
         nxpkg_jsons: &'b mut HashMap<WorkspaceName, NxpkgJson>,
@@ -669,6 +696,61 @@ mod test {
         assert_eq!(all_dependencies(&engine), expected);
     }
 
+    #[test]
+    fn test_workspace_dot_env() {
+        let repo_root_dir = TempDir::new("repo").unwrap();
+        let repo_root = AbsoluteSystemPathBuf::new(repo_root_dir.path().to_str().unwrap()).unwrap();
+        let package_graph = mock_package_graph(
+            &repo_root,
+            package_jsons! {
+                repo_root,
+                "a" => [],
+                "b" => []
+            },
+        );
+        let nxpkg_jsons = vec![
+            (
+                WorkspaceName::Root,
+                nxpkg_json(json!({
+                    "globalDotEnv": [".env"],
+                    "pipeline": {
+                        "build": { "dotEnv": [".env.build"] },
+                    }
+                })),
+            ),
+            (
+                WorkspaceName::from("a"),
+                nxpkg_json(json!({
+                    "extends": ["//"],
+                    "globalDotEnv": [".env.local", ".env"],
+                    "pipeline": {}
+                })),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let engine = EngineBuilder::new(&repo_root, &package_graph, false)
+            .with_nxpkg_jsons(Some(nxpkg_jsons))
+            .with_tasks(Some(TaskName::from("build")))
+            .with_workspaces(vec![WorkspaceName::from("a"), WorkspaceName::from("b")])
+            .build()
+            .unwrap();
+
+        let dot_env = |task_id: &'static str| {
+            engine
+                .task_definition(&TaskId::try_from(task_id).unwrap())
+                .unwrap()
+                .dot_env
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|path| path.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(dot_env("a#build"), [".env.build", ".env.local", ".env"]);
+        assert_eq!(dot_env("b#build"), [".env.build"]);
+    }
+
     #[test]
     fn test_dependencies_on_unspecified_packages() {
         let repo_root_dir = TempDir::new("repo").unwrap();
@@ -1,22 +1,46 @@
 use std::io;
 
-use petgraph::{visit::EdgeRef, Graph};
+use petgraph::{graph::NodeIndex, visit::EdgeRef, Graph};
 
 use super::{Built, Engine, TaskNode};
 
 impl Engine<Built> {
     pub fn dot_graph<W: io::Write>(&self, writer: W, is_single: bool) -> Result<(), io::Error> {
-        let display_node = match is_single {
-            true => |node: &TaskNode| match node {
-                TaskNode::Root => node.to_string(),
-                TaskNode::Task(task) => task.task().to_string(),
-            },
-            false => |node: &TaskNode| node.to_string(),
-        };
-        render_graph(&self.task_graph, display_node, writer)
+        render_graph(&self.task_graph, display_node(is_single), writer)
     }
 }
 
+/// Picks how task nodes are labelled in graph output. Single package repos
+/// only have one workspace so the package prefix is dropped.
+pub(super) fn display_node(is_single: bool) -> fn(&TaskNode) -> String {
+    match is_single {
+        true => |node: &TaskNode| match node {
+            TaskNode::Root => node.to_string(),
+            TaskNode::Task(task) => task.task().to_string(),
+        },
+        false => |node: &TaskNode| node.to_string(),
+    }
+}
+
+/// Returns the nodes that `display_node` labels, sorted by label so output
+/// is stable between runs.
+pub(super) fn labelled_nodes<N>(
+    graph: &Graph<N, ()>,
+    mut display_node: impl FnMut(&N) -> Option<String>,
+) -> Vec<(String, NodeIndex)> {
+    let mut labels = graph
+        .node_indices()
+        .filter_map(|index| {
+            let node = graph
+                .node_weight(index)
+                .expect("node index should exist in graph");
+            Some((display_node(node)?, index))
+        })
+        .collect::<Vec<_>>();
+    labels.sort();
+    labels
+}
+
 const GRAPH_PRELUDE: &str = "\ndigraph {\n\tcompound = \"true\"
 \tnewrank = \"true\"
 \tsubgraph \"root\" {
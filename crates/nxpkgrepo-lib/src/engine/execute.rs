@@ -1,4 +1,7 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
 use futures::{stream::FuturesUnordered, StreamExt};
 use tokio::sync::{mpsc, oneshot, Semaphore};
@@ -55,6 +58,8 @@ pub struct StopExecution;
 impl Engine {
     /// Execute a task graph by sending task ids to the visitor
     /// while respecting concurrency limits.
+    /// Each task takes up as many concurrency slots as its weight, and holds
+    /// its named mutexes for as long as it runs.
     /// The visitor is expected to handle any error handling on it's end.
     /// We enforce this by only allowing the returning of a sentinel error
     /// type which will stop any further execution of tasks.
@@ -74,6 +79,14 @@ impl Engine {
             concurrency,
         } = options;
         let sema = Arc::new(Semaphore::new(concurrency));
+        let slots = u32::try_from(concurrency).unwrap_or(u32::MAX);
+        let mutexes: Arc<HashMap<String, Arc<tokio::sync::Mutex<()>>>> = Arc::new(
+            self.task_definitions
+                .values()
+                .flat_map(|definition| definition.mutexes.iter())
+                .map(|name| (name.clone(), Arc::default()))
+                .collect(),
+        );
         let mut tasks: FuturesUnordered<tokio::task::JoinHandle<Result<(), ExecuteError>>> =
             FuturesUnordered::new();
 
@@ -83,6 +96,7 @@ impl Engine {
         while let Some((node_id, done)) = nodes.recv().await {
             let visitor = visitor.clone();
             let sema = sema.clone();
+            let mutexes = mutexes.clone();
             let walker = walker.clone();
             let this = self.clone();
 
@@ -102,9 +116,25 @@ impl Engine {
                     return Ok(());
                 };
 
+                let task_definition = this.task_definitions.get(task_id);
+
+                // Take the task's mutexes before any permits, so that a task waiting on
+                // another one doesn't hold back unrelated tasks. Mutexes are sorted,
+                // so tasks sharing several of them can't deadlock.
+                let mut _guards = Vec::new();
+                for name in task_definition.map_or(&[][..], |definition| &definition.mutexes) {
+                    let mutex = mutexes
+                        .get(name)
+                        .expect("mutexes are collected from all task definitions")
+                        .clone();
+                    _guards.push(mutex.lock_owned().await);
+                }
+
                 // Acquire the semaphore unless parallel
+                let weight =
+                    task_definition.map_or(1, |definition| definition.effective_weight(slots));
                 let _permit = match parallel {
-                    false => Some(sema.acquire().await.expect(
+                    false => Some(sema.acquire_many(weight).await.expect(
                         "Graph concurrency semaphore closed while tasks are still attempting to \
                          acquire permits",
                     )),
@@ -0,0 +1,106 @@
+use std::{collections::HashMap, io};
+
+use petgraph::{visit::EdgeRef, Graph};
+use serde::Serialize;
+
+use super::{
+    dot::{display_node, labelled_nodes},
+    Built, Engine, TaskNode,
+};
+
+const TEMPLATE: &str = include_str!("graph.html");
+const DATA_PLACEHOLDER: &str = "/*NXPKG_GRAPH_DATA*/ null";
+
+/// Graph data embedded into the viewer. Edges point from a task to one of its
+/// dependencies and refer to tasks by their position in `tasks`.
+#[derive(Debug, Serialize)]
+struct GraphData {
+    tasks: Vec<String>,
+    edges: Vec<(usize, usize)>,
+}
+
+impl Engine<Built> {
+    /// Writes a self-contained HTML page for exploring the task graph. The
+    /// page can load a run summary from `.nxpkg/runs` to colour tasks by
+    /// their cache status.
+    pub fn html_graph<W: io::Write>(&self, writer: W, is_single: bool) -> Result<(), io::Error> {
+        let display = display_node(is_single);
+        render_html(
+            &self.task_graph,
+            |node| matches!(node, TaskNode::Task(_)).then(|| display(node)),
+            writer,
+        )
+    }
+}
+
+fn render_html<N>(
+    graph: &Graph<N, ()>,
+    display_node: impl FnMut(&N) -> Option<String>,
+    mut writer: impl io::Write,
+) -> Result<(), io::Error> {
+    let nodes = labelled_nodes(graph, display_node);
+    let positions = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, (_, index))| (*index, i))
+        .collect::<HashMap<_, _>>();
+
+    let mut edges = graph
+        .edge_references()
+        .filter_map(|edge| {
+            Some((
+                *positions.get(&edge.source())?,
+                *positions.get(&edge.target())?,
+            ))
+        })
+        .collect::<Vec<_>>();
+    edges.sort();
+    edges.dedup();
+
+    let data = GraphData {
+        tasks: nodes.into_iter().map(|(label, _)| label).collect(),
+        edges,
+    };
+    // Task names end up inside a <script> tag so `<` gets escaped to keep a
+    // name like `</script>` from closing it early.
+    let data = serde_json::to_string(&data)?.replace('<', "\\u003c");
+
+    writer.write_all(TEMPLATE.replacen(DATA_PLACEHOLDER, &data, 1).as_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_embeds_graph_data() {
+        let mut bytes = Vec::new();
+        let mut graph = Graph::new();
+        let root = graph.add_node("___ROOT___");
+        let web = graph.add_node("web#build");
+        let ui = graph.add_node("ui#build");
+        graph.add_edge(web, ui, ());
+        graph.add_edge(ui, root, ());
+        render_html(
+            &graph,
+            |n| (*n != "___ROOT___").then(|| n.to_string()),
+            &mut bytes,
+        )
+        .unwrap();
+        let html = String::from_utf8(bytes).unwrap();
+        assert!(!html.contains(DATA_PLACEHOLDER));
+        assert!(
+            html.contains(r#"const GRAPH = {"tasks":["ui#build","web#build"],"edges":[[1,0]]};"#)
+        );
+    }
+
+    #[test]
+    fn test_escapes_script_tags() {
+        let mut bytes = Vec::new();
+        let mut graph = Graph::<&str, ()>::new();
+        graph.add_node("</script>#build");
+        render_html(&graph, |n| Some(n.to_string()), &mut bytes).unwrap();
+        let html = String::from_utf8(bytes).unwrap();
+        assert!(html.contains(r#""\u003c/script>#build""#));
+    }
+}
@@ -0,0 +1,97 @@
+use std::{collections::HashMap, io};
+
+use petgraph::{visit::EdgeRef, Graph};
+
+use super::{
+    dot::{display_node, labelled_nodes},
+    Built, Engine, TaskNode,
+};
+
+impl Engine<Built> {
+    /// Writes the task graph as a Mermaid flowchart. The synthetic root node
+    /// is left out so the output can be pasted into Markdown as is.
+    pub fn mermaid_graph<W: io::Write>(&self, writer: W, is_single: bool) -> Result<(), io::Error> {
+        let display = display_node(is_single);
+        render_mermaid(
+            &self.task_graph,
+            |node| matches!(node, TaskNode::Task(_)).then(|| display(node)),
+            writer,
+        )
+    }
+}
+
+fn render_mermaid<N>(
+    graph: &Graph<N, ()>,
+    display_node: impl FnMut(&N) -> Option<String>,
+    mut writer: impl io::Write,
+) -> Result<(), io::Error> {
+    let nodes = labelled_nodes(graph, display_node);
+
+    // Labels can contain characters Mermaid doesn't allow in identifiers
+    // (`#`, `@`, `/`) so nodes get positional ids instead.
+    writer.write_all(b"graph TD\n")?;
+    for (i, (label, _)) in nodes.iter().enumerate() {
+        writeln!(writer, "\tT{i}(\"{}\")", escape_label(label))?;
+    }
+
+    let ids = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, (_, index))| (*index, format!("T{i}")))
+        .collect::<HashMap<_, _>>();
+
+    let mut edges = graph
+        .edge_references()
+        .filter_map(|edge| {
+            let source = ids.get(&edge.source())?;
+            let target = ids.get(&edge.target())?;
+            Some(format!("\t{source} --> {target}"))
+        })
+        .collect::<Vec<_>>();
+    edges.sort();
+    edges.dedup();
+    for edge in edges {
+        writeln!(writer, "{edge}")?;
+    }
+
+    Ok(())
+}
+
+fn escape_label(label: &str) -> String {
+    label.replace('"', "#quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_simple_graph_output() {
+        let mut bytes = Vec::new();
+        let mut graph = Graph::new();
+        let root = graph.add_node("___ROOT___");
+        let web = graph.add_node("web#build");
+        let ui = graph.add_node("@repo/ui#build");
+        let docs = graph.add_node("docs#lint");
+        graph.add_edge(web, ui, ());
+        graph.add_edge(ui, root, ());
+        graph.add_edge(docs, root, ());
+        render_mermaid(
+            &graph,
+            |n| (*n != "___ROOT___").then(|| n.to_string()),
+            &mut bytes,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(bytes).unwrap(),
+            "graph TD
+\tT0(\"@repo/ui#build\")
+\tT1(\"docs#lint\")
+\tT2(\"web#build\")
+\tT2 --> T0
+"
+        );
+    }
+}
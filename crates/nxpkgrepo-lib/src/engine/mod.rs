@@ -2,9 +2,11 @@ mod builder;
 mod execute;
 
 mod dot;
+mod html;
+mod mermaid;
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fmt,
 };
 
@@ -67,6 +69,17 @@ impl Engine<Building> {
         self.task_graph.add_edge(source, self.root_index, ());
     }
 
+    #[cfg(test)]
+    pub(crate) fn add_dependency(
+        &mut self,
+        task_id: &TaskId<'static>,
+        dependency: &TaskId<'static>,
+    ) {
+        let source = self.get_index(task_id);
+        let target = self.get_index(dependency);
+        self.task_graph.add_edge(source, target, ());
+    }
+
     pub fn add_definition(
         &mut self,
         task_id: TaskId<'static>,
@@ -141,6 +154,60 @@ impl Engine<Built> {
         &self.task_definitions
     }
 
+    /// Returns the given tasks along with all tasks that transitively depend
+    /// on them.
+    pub fn tasks_with_dependents<'b>(
+        &self,
+        tasks: impl IntoIterator<Item = &'b TaskId<'static>>,
+    ) -> HashSet<TaskId<'static>> {
+        let mut visited = HashSet::new();
+        let mut stack = tasks.into_iter().cloned().collect::<Vec<_>>();
+        while let Some(task_id) = stack.pop() {
+            if !self.task_lookup.contains_key(&task_id) || visited.contains(&task_id) {
+                continue;
+            }
+            for dependent in self.dependents(&task_id).into_iter().flatten() {
+                if let TaskNode::Task(dependent) = dependent {
+                    stack.push(dependent.clone());
+                }
+            }
+            visited.insert(task_id);
+        }
+        visited
+    }
+
+    /// Creates a new engine containing only the given tasks. Dependencies
+    /// between the given tasks are kept and any task without a dependency in
+    /// the subgraph is connected to the root.
+    pub fn create_engine_for_subgraph(&self, tasks: &HashSet<TaskId<'static>>) -> Engine<Built> {
+        let mut engine = Engine::<Building>::new();
+        for task_id in tasks {
+            if !self.task_lookup.contains_key(task_id) {
+                continue;
+            }
+            if let Some(definition) = self.task_definitions.get(task_id) {
+                engine.add_definition(task_id.clone(), definition.clone());
+            }
+
+            let index = engine.get_index(task_id);
+            let mut has_dependency = false;
+            for dependency in self.dependencies(task_id).into_iter().flatten() {
+                let TaskNode::Task(dependency) = dependency else {
+                    continue;
+                };
+                if tasks.contains(dependency) {
+                    let dependency_index = engine.get_index(dependency);
+                    engine.task_graph.add_edge(index, dependency_index, ());
+                    has_dependency = true;
+                }
+            }
+            if !has_dependency {
+                engine.connect_to_root(task_id);
+            }
+        }
+        engine.seal()
+    }
+
     pub fn validate(
         &self,
         package_graph: &PackageGraph,
@@ -148,7 +215,7 @@ impl Engine<Built> {
     ) -> Result<(), Vec<ValidateError>> {
         // TODO(olszewski) once this is hooked up to a real run, we should
         // see if using rayon to parallelize would provide a speedup
-        let (persistent_count, mut validation_errors) = self
+        let (persistent_slots, mut validation_errors) = self
             .task_graph
             .node_indices()
             .map(|node_index| {
@@ -158,12 +225,14 @@ impl Engine<Built> {
                     .expect("graph should contain weight for node index")
                 else {
                     // No need to check the root node if that's where we are.
-                    return Ok(false);
+                    return Ok(0);
                 };
-                let is_persistent = self
+                // Persistent tasks never release their concurrency slots
+                let persistent_slots = self
                     .task_definitions
                     .get(task_id)
-                    .map_or(false, |task_def| task_def.persistent);
+                    .filter(|task_def| task_def.persistent)
+                    .map_or(0, |task_def| task_def.effective_weight(concurrency));
 
                 for dep_index in self
                     .task_graph
@@ -200,29 +269,69 @@ impl Engine<Built> {
                     }
                 }
 
-                Ok(is_persistent)
+                Ok(persistent_slots)
             })
-            .fold((0, Vec::new()), |(mut count, mut errs), result| {
+            .fold((0, Vec::new()), |(mut slots, mut errs), result| {
                 match result {
-                    Ok(true) => count += 1,
-                    Ok(false) => (),
+                    Ok(task_slots) => slots += task_slots,
                     Err(e) => errs.push(e),
                 }
-                (count, errs)
+                (slots, errs)
             });
 
-        if persistent_count >= concurrency {
+        if persistent_slots >= concurrency {
             validation_errors.push(ValidateError::PersistentTasksExceedConcurrency {
-                persistent_count,
+                persistent_slots,
                 concurrency,
             })
         }
 
+        validation_errors.extend(self.validate_mutexes());
+
         match validation_errors.is_empty() {
             true => Ok(()),
             false => Err(validation_errors),
         }
     }
+
+    /// Persistent tasks never finish, so they never release their mutexes.
+    /// Any other task sharing a mutex with one would wait forever.
+    fn validate_mutexes(&self) -> Vec<ValidateError> {
+        let mut holders: BTreeMap<&str, Vec<(&TaskId<'static>, bool)>> = BTreeMap::new();
+        for task_id in self.task_lookup.keys() {
+            let Some(task_definition) = self.task_definitions.get(task_id) else {
+                continue;
+            };
+            for mutex in &task_definition.mutexes {
+                holders
+                    .entry(mutex)
+                    .or_default()
+                    .push((task_id, task_definition.persistent));
+            }
+        }
+
+        let mut errors = Vec::new();
+        for (mutex, mut tasks) in holders {
+            if tasks.len() < 2 {
+                continue;
+            }
+            tasks.sort();
+            let Some((persistent_task, _)) = tasks.iter().find(|(_, persistent)| *persistent)
+            else {
+                continue;
+            };
+            for (task_id, _) in &tasks {
+                if task_id != persistent_task {
+                    errors.push(ValidateError::MutexSharedWithPersistentTask {
+                        persistent_task: persistent_task.to_string(),
+                        task: task_id.to_string(),
+                        mutex: mutex.to_string(),
+                    });
+                }
+            }
+        }
+        errors
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -240,13 +349,23 @@ pub enum ValidateError {
         dependant: String,
     },
     #[error(
-        "You have {persistent_count} persistent tasks but `nxpkg` is configured for concurrency \
-         of {concurrency}. Set --concurrency to at least {}", persistent_count+1
+        "Your persistent tasks take up {persistent_slots} concurrency slots but `nxpkg` is \
+         configured for concurrency of {concurrency}. Set --concurrency to at least {}",
+        persistent_slots+1
     )]
     PersistentTasksExceedConcurrency {
-        persistent_count: u32,
+        persistent_slots: u32,
         concurrency: u32,
     },
+    #[error(
+        "\"{persistent_task}\" is a persistent task and never releases the mutex \"{mutex}\", \
+         \"{task}\" cannot use it"
+    )]
+    MutexSharedWithPersistentTask {
+        persistent_task: String,
+        task: String,
+        mutex: String,
+    },
 }
 
 impl fmt::Display for TaskNode {
@@ -257,3 +376,174 @@ impl fmt::Display for TaskNode {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    // a#build <- b#build <- c#build, d#build is unrelated
+    fn engine() -> Engine {
+        let mut engine = Engine::new();
+        let a = TaskId::new("a", "build");
+        let b = TaskId::new("b", "build");
+        let c = TaskId::new("c", "build");
+        let d = TaskId::new("d", "build");
+        for task in [&a, &b, &c, &d] {
+            engine.add_definition(task.clone(), TaskDefinition::default());
+        }
+        let a_index = engine.get_index(&a);
+        let b_index = engine.get_index(&b);
+        let c_index = engine.get_index(&c);
+        engine.task_graph.add_edge(b_index, a_index, ());
+        engine.task_graph.add_edge(c_index, b_index, ());
+        engine.connect_to_root(&a);
+        engine.connect_to_root(&d);
+        engine.seal()
+    }
+
+    #[test]
+    fn test_tasks_with_dependents() {
+        let engine = engine();
+        let tasks = engine.tasks_with_dependents([&TaskId::new("b", "build")]);
+        assert_eq!(
+            tasks,
+            HashSet::from([TaskId::new("b", "build"), TaskId::new("c", "build")])
+        );
+    }
+
+    #[test]
+    fn test_subgraph() {
+        let engine = engine();
+        let tasks = HashSet::from([TaskId::new("b", "build"), TaskId::new("c", "build")]);
+        let subgraph = engine.create_engine_for_subgraph(&tasks);
+
+        assert_eq!(subgraph.task_definitions().len(), 2);
+        assert_eq!(
+            subgraph.dependencies(&TaskId::new("c", "build")),
+            Some(HashSet::from([&TaskNode::Task(TaskId::new("b", "build"))]))
+        );
+        // b's dependency isn't part of the subgraph so it now depends on root
+        assert_eq!(
+            subgraph.dependencies(&TaskId::new("b", "build")),
+            Some(HashSet::from([&TaskNode::Root]))
+        );
+        assert!(subgraph.dependencies(&TaskId::new("d", "build")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_mutexes_serialize_tasks() {
+        let mut engine = Engine::new();
+        let definition = TaskDefinition {
+            mutexes: vec!["database".to_string()],
+            ..Default::default()
+        };
+        for task in [TaskId::new("a", "test"), TaskId::new("b", "test")] {
+            engine.add_definition(task.clone(), definition.clone());
+            engine.connect_to_root(&task);
+        }
+        let engine = Arc::new(engine.seal());
+
+        let (sender, mut receiver) = mpsc::channel(2);
+        let handle = tokio::spawn(engine.execute(ExecutionOptions::new(false, 2), sender));
+
+        let first = receiver.recv().await.unwrap();
+        // There's room for both tasks, but the second one can't start until the
+        // first one releases the mutex
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), receiver.recv())
+                .await
+                .is_err()
+        );
+        first.callback.send(Ok(())).unwrap();
+
+        let second = receiver.recv().await.unwrap();
+        assert_ne!(first.info, second.info);
+        second.callback.send(Ok(())).unwrap();
+
+        handle.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_mutex_shared_with_persistent_task() {
+        let mut engine = Engine::new();
+        let dev = TaskId::new("a", "dev");
+        let test = TaskId::new("b", "test");
+        let lint = TaskId::new("c", "lint");
+        engine.add_definition(
+            dev.clone(),
+            TaskDefinition {
+                persistent: true,
+                mutexes: vec!["port-3000".to_string(), "own".to_string()],
+                ..Default::default()
+            },
+        );
+        engine.add_definition(
+            test.clone(),
+            TaskDefinition {
+                mutexes: vec!["port-3000".to_string()],
+                ..Default::default()
+            },
+        );
+        engine.add_definition(
+            lint.clone(),
+            TaskDefinition {
+                mutexes: vec!["unrelated".to_string()],
+                ..Default::default()
+            },
+        );
+        for task in [&dev, &test, &lint] {
+            engine.connect_to_root(task);
+        }
+        let engine = engine.seal();
+
+        let errors = engine.validate_mutexes();
+
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            &errors[0],
+            ValidateError::MutexSharedWithPersistentTask { persistent_task, task, mutex }
+                if persistent_task == "a#dev" && task == "b#test" && mutex == "port-3000"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_weight_takes_up_slots() {
+        let mut engine = Engine::new();
+        let heavy = TaskId::new("a", "e2e");
+        let light = TaskId::new("b", "lint");
+        engine.add_definition(
+            heavy.clone(),
+            TaskDefinition {
+                weight: 2,
+                ..Default::default()
+            },
+        );
+        engine.add_definition(light.clone(), TaskDefinition::default());
+        for task in [&heavy, &light] {
+            engine.connect_to_root(task);
+        }
+        let engine = Arc::new(engine.seal());
+
+        let (sender, mut receiver) = mpsc::channel(2);
+        let handle = tokio::spawn(engine.execute(ExecutionOptions::new(false, 2), sender));
+
+        // Whichever task starts first, the other one has to wait for it since
+        // together they need 3 slots
+        let first = receiver.recv().await.unwrap();
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), receiver.recv())
+                .await
+                .is_err()
+        );
+        first.callback.send(Ok(())).unwrap();
+
+        let second = receiver.recv().await.unwrap();
+        second.callback.send(Ok(())).unwrap();
+
+        handle.await.unwrap().unwrap();
+    }
+}
@@ -21,6 +21,7 @@ pub(crate) mod globwatcher;
 mod hash;
 mod opts;
 mod process;
+mod query;
 mod rewrite_json;
 mod run;
 mod shim;
@@ -83,7 +83,13 @@ impl<'a> TryFrom<&'a Args> for Opts<'a> {
     type Error = self::Error;
 
     fn try_from(args: &'a Args) -> Result<Self, Self::Error> {
-        let Some(Command::Run(run_args)) = &args.command else {
+        let Some(
+            Command::Run(run_args)
+            | Command::Watch(run_args)
+            | Command::Query { run_args, .. }
+            | Command::CompareRuns { run_args, .. },
+        ) = &args.command
+        else {
             return Err(Error::ExpectedRun);
         };
         let run_opts = RunOpts::try_from(run_args.as_ref())?;
@@ -313,6 +319,7 @@ pub struct ScopeOpts {
     pub global_deps: Vec<String>,
     pub filter_patterns: Vec<String>,
     pub ignore_patterns: Vec<String>,
+    pub scm_deepen: bool,
 }
 
 impl<'a> TryFrom<&'a RunArgs> for ScopeOpts {
@@ -337,6 +344,7 @@ impl<'a> TryFrom<&'a RunArgs> for ScopeOpts {
             legacy_filter,
             filter_patterns: args.filter.clone(),
             ignore_patterns: args.ignore.clone(),
+            scm_deepen: args.scm_deepen,
         })
     }
 }
@@ -541,6 +549,7 @@ mod test {
             global_deps: vec![],
             filter_patterns: opts_input.filter_patterns,
             ignore_patterns: vec![],
+            scm_deepen: false,
         };
         let opts = Opts {
             run_opts,
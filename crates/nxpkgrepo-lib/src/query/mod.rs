@@ -0,0 +1,509 @@
+//! A small query language over the package graph and the task graph.
+//!
+//! A query is a set expression. Package names (`web`, `@repo/ui`, or `//`
+//! for the root) evaluate to a set of packages and task ids (`web#build`)
+//! evaluate to a set of tasks. Sets of the same kind can be combined with `|`
+//! (union), `&` (intersection) and `-` (difference), and transformed with
+//! functions:
+//!
+//! - `all()`: every package in the repository
+//! - `dependencies(P)`, `dependents(P)`: the packages `P` transitively depends
+//!   on, or that transitively depend on `P`
+//! - `direct_dependencies(P)`, `direct_dependents(P)`: as above, but only
+//!   following a single edge
+//! - `closure(P)`: `P` and all of its dependencies
+//! - `changed("from")`, `changed("from", "to")`: the packages changed between
+//!   two refs, `to` defaults to `HEAD`
+//! - `tasks(P)`, `tasks(P, "build")`: the tasks in the task graph belonging to
+//!   `P`, optionally only those with the given name
+//! - `packages(T)`: the packages the tasks in `T` belong to
+//! - `task_dependencies(T)`, `task_dependents(T)`: the tasks that `T` directly
+//!   depends on, or that directly depend on `T`
+//!
+//! e.g. `dependents(ui) & changed("main")` are the packages that depend on
+//! `ui` and have changed since `main`.
+mod parser;
+
+use std::{collections::BTreeSet, fmt};
+
+pub use parser::{parse, Expr, ParseError};
+use thiserror::Error;
+use nxpkgrepo_repository::package_graph::{PackageGraph, WorkspaceName, WorkspaceNode};
+
+use crate::{
+    engine::{Engine, TaskNode},
+    run::{
+        scope::{ChangeDetectError, PackageChangeDetector},
+        task_id::TaskId,
+    },
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("invalid query: {0}")]
+    Parse(#[from] ParseError),
+    #[error("unknown function '{0}'")]
+    UnknownFunction(String),
+    #[error("{function}() takes {expected} arguments but {actual} were given")]
+    Arguments {
+        function: String,
+        expected: &'static str,
+        actual: usize,
+    },
+    #[error("{function}() expects a string argument")]
+    ExpectedString { function: String },
+    #[error("expected a set of {expected} but found a set of {actual}")]
+    Kind { expected: Kind, actual: Kind },
+    #[error("unknown package '{0}'")]
+    UnknownPackage(String),
+    #[error("task '{0}' is not in the task graph")]
+    UnknownTask(String),
+    #[error(
+        "querying tasks requires a task graph, pass the tasks to include after the query e.g. \
+         `nxpkg query 'tasks(all())' build`"
+    )]
+    NoTaskGraph,
+    #[error("unable to detect changed packages: {0}")]
+    ChangeDetect(#[from] ChangeDetectError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Packages,
+    Tasks,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Packages => f.write_str("packages"),
+            Kind::Tasks => f.write_str("tasks"),
+        }
+    }
+}
+
+/// The result of evaluating a query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Packages(BTreeSet<WorkspaceName>),
+    Tasks(BTreeSet<TaskId<'static>>),
+}
+
+impl Value {
+    pub fn kind(&self) -> Kind {
+        match self {
+            Value::Packages(_) => Kind::Packages,
+            Value::Tasks(_) => Kind::Tasks,
+        }
+    }
+
+    fn into_packages(self) -> Result<BTreeSet<WorkspaceName>, Error> {
+        match self {
+            Value::Packages(packages) => Ok(packages),
+            Value::Tasks(_) => Err(Error::Kind {
+                expected: Kind::Packages,
+                actual: Kind::Tasks,
+            }),
+        }
+    }
+
+    fn into_tasks(self) -> Result<BTreeSet<TaskId<'static>>, Error> {
+        match self {
+            Value::Tasks(tasks) => Ok(tasks),
+            Value::Packages(_) => Err(Error::Kind {
+                expected: Kind::Tasks,
+                actual: Kind::Packages,
+            }),
+        }
+    }
+}
+
+/// Evaluates queries against a package graph and, if tasks were requested, a
+/// task graph.
+pub struct Query<'a, T> {
+    pkg_graph: &'a PackageGraph,
+    engine: Option<&'a Engine>,
+    change_detector: T,
+}
+
+impl<'a, T: PackageChangeDetector> Query<'a, T> {
+    pub fn new(
+        pkg_graph: &'a PackageGraph,
+        engine: Option<&'a Engine>,
+        change_detector: T,
+    ) -> Self {
+        Self {
+            pkg_graph,
+            engine,
+            change_detector,
+        }
+    }
+
+    pub fn evaluate(&self, query: &str) -> Result<Value, Error> {
+        self.eval(&parse(query)?)
+    }
+
+    fn eval(&self, expr: &Expr) -> Result<Value, Error> {
+        match expr {
+            Expr::Literal(name) => self.literal(name),
+            Expr::Call(function, args) => self.call(function, args),
+            Expr::Union(lhs, rhs) => {
+                self.combine(lhs, rhs, |lhs, rhs| lhs | rhs, |lhs, rhs| lhs | rhs)
+            }
+            Expr::Intersection(lhs, rhs) => {
+                self.combine(lhs, rhs, |lhs, rhs| lhs & rhs, |lhs, rhs| lhs & rhs)
+            }
+            Expr::Difference(lhs, rhs) => {
+                self.combine(lhs, rhs, |lhs, rhs| lhs - rhs, |lhs, rhs| lhs - rhs)
+            }
+        }
+    }
+
+    fn combine(
+        &self,
+        lhs: &Expr,
+        rhs: &Expr,
+        packages: impl Fn(&BTreeSet<WorkspaceName>, &BTreeSet<WorkspaceName>) -> BTreeSet<WorkspaceName>,
+        tasks: impl Fn(
+            &BTreeSet<TaskId<'static>>,
+            &BTreeSet<TaskId<'static>>,
+        ) -> BTreeSet<TaskId<'static>>,
+    ) -> Result<Value, Error> {
+        match self.eval(lhs)? {
+            Value::Packages(lhs) => {
+                let rhs = self.eval(rhs)?.into_packages()?;
+                Ok(Value::Packages(packages(&lhs, &rhs)))
+            }
+            Value::Tasks(lhs) => {
+                let rhs = self.eval(rhs)?.into_tasks()?;
+                Ok(Value::Tasks(tasks(&lhs, &rhs)))
+            }
+        }
+    }
+
+    fn literal(&self, name: &str) -> Result<Value, Error> {
+        if let Ok(task_id) = TaskId::try_from(name) {
+            let engine = self.engine()?;
+            let task_id = task_id.into_owned();
+            if engine.dependencies(&task_id).is_none() {
+                return Err(Error::UnknownTask(name.to_string()));
+            }
+            return Ok(Value::Tasks(BTreeSet::from([task_id])));
+        }
+
+        let workspace = WorkspaceName::from(name);
+        if self.pkg_graph.workspace_info(&workspace).is_none() {
+            return Err(Error::UnknownPackage(name.to_string()));
+        }
+        Ok(Value::Packages(BTreeSet::from([workspace])))
+    }
+
+    fn call(&self, function: &str, args: &[Expr]) -> Result<Value, Error> {
+        let arity = |expected: &'static str, range: std::ops::RangeInclusive<usize>| {
+            if range.contains(&args.len()) {
+                Ok(())
+            } else {
+                Err(Error::Arguments {
+                    function: function.to_string(),
+                    expected,
+                    actual: args.len(),
+                })
+            }
+        };
+
+        match function {
+            "all" => {
+                arity("no", 0..=0)?;
+                Ok(Value::Packages(
+                    self.pkg_graph
+                        .workspaces()
+                        .map(|(name, _)| name.clone())
+                        .collect(),
+                ))
+            }
+            "dependencies"
+            | "dependents"
+            | "direct_dependencies"
+            | "direct_dependents"
+            | "closure" => {
+                arity("1", 1..=1)?;
+                let packages = self.eval(&args[0])?.into_packages()?;
+                Ok(Value::Packages(self.related_packages(function, &packages)))
+            }
+            "changed" => {
+                arity("1 or 2", 1..=2)?;
+                let from_ref = string_argument(function, &args[0])?;
+                let to_ref = args
+                    .get(1)
+                    .map(|arg| string_argument(function, arg))
+                    .transpose()?
+                    .unwrap_or("HEAD");
+                Ok(Value::Packages(
+                    self.change_detector
+                        .changed_packages(from_ref, to_ref)?
+                        .into_iter()
+                        .collect(),
+                ))
+            }
+            "tasks" => {
+                arity("1 or 2", 1..=2)?;
+                let packages = self.eval(&args[0])?.into_packages()?;
+                let task_name = args
+                    .get(1)
+                    .map(|arg| string_argument(function, arg))
+                    .transpose()?;
+                let engine = self.engine()?;
+                Ok(Value::Tasks(
+                    engine
+                        .tasks()
+                        .filter_map(|node| match node {
+                            TaskNode::Root => None,
+                            TaskNode::Task(task_id) => Some(task_id),
+                        })
+                        .filter(|task_id| {
+                            packages.contains(&task_id.to_workspace_name())
+                                && task_name.map_or(true, |name| task_id.task() == name)
+                        })
+                        .cloned()
+                        .collect(),
+                ))
+            }
+            "packages" => {
+                arity("1", 1..=1)?;
+                let tasks = self.eval(&args[0])?.into_tasks()?;
+                Ok(Value::Packages(
+                    tasks
+                        .iter()
+                        .map(|task_id| task_id.to_workspace_name())
+                        .collect(),
+                ))
+            }
+            "task_dependencies" | "task_dependents" => {
+                arity("1", 1..=1)?;
+                let tasks = self.eval(&args[0])?.into_tasks()?;
+                let engine = self.engine()?;
+                let mut related = BTreeSet::new();
+                for task_id in &tasks {
+                    let neighbors = match function {
+                        "task_dependencies" => engine.dependencies(task_id),
+                        _ => engine.dependents(task_id),
+                    };
+                    related.extend(
+                        neighbors
+                            .into_iter()
+                            .flatten()
+                            .filter_map(|node| match node {
+                                TaskNode::Root => None,
+                                TaskNode::Task(task_id) => Some(task_id.clone()),
+                            }),
+                    );
+                }
+                Ok(Value::Tasks(related))
+            }
+            _ => Err(Error::UnknownFunction(function.to_string())),
+        }
+    }
+
+    fn related_packages(
+        &self,
+        function: &str,
+        packages: &BTreeSet<WorkspaceName>,
+    ) -> BTreeSet<WorkspaceName> {
+        let nodes = packages
+            .iter()
+            .map(|name| WorkspaceNode::Workspace(name.clone()))
+            .collect::<Vec<_>>();
+        let related = match function {
+            "closure" => self.pkg_graph.transitive_closure(&nodes),
+            _ => nodes
+                .iter()
+                .flat_map(|node| match function {
+                    "dependencies" => self.pkg_graph.dependencies(node),
+                    "dependents" => self.pkg_graph.ancestors(node),
+                    "direct_dependencies" => self
+                        .pkg_graph
+                        .immediate_dependencies(node)
+                        .unwrap_or_default(),
+                    _ => self.pkg_graph.immediate_ancestors(node).unwrap_or_default(),
+                })
+                .collect(),
+        };
+        related
+            .into_iter()
+            .filter_map(|node| match node {
+                WorkspaceNode::Root => None,
+                WorkspaceNode::Workspace(name) => Some(name.clone()),
+            })
+            .collect()
+    }
+
+    fn engine(&self) -> Result<&'a Engine, Error> {
+        self.engine.ok_or(Error::NoTaskGraph)
+    }
+}
+
+fn string_argument<'e>(function: &str, arg: &'e Expr) -> Result<&'e str, Error> {
+    match arg {
+        Expr::Literal(string) => Ok(string),
+        _ => Err(Error::ExpectedString {
+            function: function.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        assert_matches::assert_matches,
+        collections::{HashMap, HashSet},
+    };
+
+    use test_case::test_case;
+    use nxpkgpath::AbsoluteSystemPath;
+    use nxpkgrepo_repository::{package_json::PackageJson, package_manager::PackageManager};
+
+    use super::*;
+    use crate::task_graph::TaskDefinition;
+
+    struct TestChangeDetector(HashSet<WorkspaceName>);
+
+    impl PackageChangeDetector for TestChangeDetector {
+        fn changed_packages(
+            &self,
+            from_ref: &str,
+            to_ref: &str,
+        ) -> Result<HashSet<WorkspaceName>, ChangeDetectError> {
+            assert_eq!((from_ref, to_ref), ("main", "HEAD"));
+            Ok(self.0.clone())
+        }
+    }
+
+    // app -> ui -> utils, docs -> ui, tools is unrelated
+    fn package_graph(root: &AbsoluteSystemPath) -> PackageGraph {
+        let dependencies: &[(&str, &[&str])] = &[
+            ("app", &["ui"]),
+            ("docs", &["ui"]),
+            ("ui", &["utils"]),
+            ("utils", &[]),
+            ("tools", &[]),
+        ];
+        let package_jsons = dependencies
+            .iter()
+            .map(|(name, deps)| {
+                (
+                    root.join_components(&["packages", name, "package.json"]),
+                    PackageJson {
+                        name: Some(name.to_string()),
+                        dependencies: Some(
+                            deps.iter()
+                                .map(|dep| (dep.to_string(), "*".to_string()))
+                                .collect(),
+                        ),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect::<HashMap<_, _>>();
+
+        PackageGraph::builder(root, Default::default())
+            .with_package_jsons(Some(package_jsons))
+            .with_package_manger(Some(PackageManager::Pnpm6))
+            .build()
+            .unwrap()
+    }
+
+    // app#build -> ui#build -> utils#build
+    fn engine() -> Engine {
+        let mut engine = Engine::new();
+        let app = TaskId::new("app", "build");
+        let ui = TaskId::new("ui", "build");
+        let utils = TaskId::new("utils", "build");
+        let lint = TaskId::new("ui", "lint");
+        for task in [&app, &ui, &utils, &lint] {
+            engine.add_definition(task.clone(), TaskDefinition::default());
+        }
+        engine.add_dependency(&app, &ui);
+        engine.add_dependency(&ui, &utils);
+        engine.connect_to_root(&utils);
+        engine.connect_to_root(&lint);
+        engine.seal()
+    }
+
+    fn packages(names: &[&str]) -> Value {
+        Value::Packages(
+            names
+                .iter()
+                .map(|name| WorkspaceName::from(*name))
+                .collect(),
+        )
+    }
+
+    fn tasks(ids: &[&'static str]) -> Value {
+        Value::Tasks(
+            ids.iter()
+                .map(|id| TaskId::try_from(*id).unwrap())
+                .collect(),
+        )
+    }
+
+    #[test_case("app", packages(&["app"]) ; "literal")]
+    #[test_case("dependencies(app)", packages(&["ui", "utils"]) ; "dependencies")]
+    #[test_case("dependents(utils)", packages(&["app", "docs", "ui"]) ; "dependents")]
+    #[test_case("direct_dependents(utils)", packages(&["ui"]) ; "direct dependents")]
+    #[test_case("closure(ui)", packages(&["ui", "utils"]) ; "closure")]
+    #[test_case("dependents(ui) & changed(\"main\")", packages(&["docs"]) ; "changed dependents")]
+    #[test_case("all() - closure(app) - //", packages(&["docs", "tools"]) ; "difference")]
+    #[test_case("tasks(ui)", tasks(&["ui#build", "ui#lint"]) ; "tasks")]
+    #[test_case("tasks(all(), \"build\")", tasks(&["app#build", "ui#build", "utils#build"]) ; "tasks by name")]
+    #[test_case("task_dependencies(app#build)", tasks(&["ui#build"]) ; "task dependencies")]
+    #[test_case("task_dependents(ui#build) | ui#lint", tasks(&["app#build", "ui#lint"]) ; "task dependents")]
+    #[test_case("packages(task_dependents(utils#build))", packages(&["ui"]) ; "packages of tasks")]
+    fn test_evaluate(query: &str, expected: Value) {
+        let root = tempfile::tempdir().unwrap();
+        let pkg_graph = package_graph(AbsoluteSystemPath::from_std_path(root.path()).unwrap());
+        let engine = engine();
+        let graph_query = Query::new(
+            &pkg_graph,
+            Some(&engine),
+            TestChangeDetector(HashSet::from(["docs".into(), "tools".into()])),
+        );
+        assert_eq!(graph_query.evaluate(query).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_evaluate_errors() {
+        let root = tempfile::tempdir().unwrap();
+        let pkg_graph = package_graph(AbsoluteSystemPath::from_std_path(root.path()).unwrap());
+        let query = Query::new(&pkg_graph, None, TestChangeDetector(HashSet::new()));
+
+        assert_matches!(
+            query.evaluate("missing"),
+            Err(Error::UnknownPackage(name)) if name == "missing"
+        );
+        assert_matches!(query.evaluate("tasks(app)"), Err(Error::NoTaskGraph));
+        assert_matches!(
+            query.evaluate("dependencies(app, ui)"),
+            Err(Error::Arguments { actual: 2, .. })
+        );
+        assert_matches!(
+            query.evaluate("changed(app | ui)"),
+            Err(Error::ExpectedString { .. })
+        );
+        assert_matches!(query.evaluate("nope()"), Err(Error::UnknownFunction(_)));
+
+        let engine = engine();
+        let query = Query::new(
+            &pkg_graph,
+            Some(&engine),
+            TestChangeDetector(HashSet::new()),
+        );
+        assert_matches!(
+            query.evaluate("app | app#build"),
+            Err(Error::Kind {
+                expected: Kind::Packages,
+                actual: Kind::Tasks
+            })
+        );
+        assert_matches!(query.evaluate("docs#build"), Err(Error::UnknownTask(_)));
+    }
+}
@@ -0,0 +1,289 @@
+use std::{iter::Peekable, str::CharIndices};
+
+use thiserror::Error;
+
+/// A parsed query expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// A package name, task id or string argument, e.g. `web`, `web#build`
+    /// or `"main"`
+    Literal(String),
+    Call(String, Vec<Expr>),
+    Union(Box<Expr>, Box<Expr>),
+    Intersection(Box<Expr>, Box<Expr>),
+    Difference(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("unexpected character '{0}' at position {1}")]
+    UnexpectedCharacter(char, usize),
+    #[error("unterminated string starting at position {0}")]
+    UnterminatedString(usize),
+    #[error("expected {expected} at position {position}, found {found}")]
+    Expected {
+        expected: &'static str,
+        found: String,
+        position: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    String(String),
+    OpenParen,
+    CloseParen,
+    Comma,
+    Pipe,
+    Ampersand,
+    Minus,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Word(word) => format!("'{word}'"),
+            Token::String(string) => format!("\"{string}\""),
+            Token::OpenParen => "'('".to_string(),
+            Token::CloseParen => "')'".to_string(),
+            Token::Comma => "','".to_string(),
+            Token::Pipe => "'|'".to_string(),
+            Token::Ampersand => "'&'".to_string(),
+            Token::Minus => "'-'".to_string(),
+        }
+    }
+}
+
+// Characters that can appear in a bare package name or task id. A '-' is
+// allowed anywhere but the start of a word so `a-b` is one name, while
+// `a - b` is a difference.
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '@' | '/' | '.' | '_' | '#' | ':' | '~' | '-')
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars: Peekable<CharIndices> = input.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            ',' => Token::Comma,
+            '|' => Token::Pipe,
+            '&' => Token::Ampersand,
+            '-' => Token::Minus,
+            '"' | '\'' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, end)) if end == c => break,
+                        Some((_, c)) => string.push(c),
+                        None => return Err(ParseError::UnterminatedString(position)),
+                    }
+                }
+                Token::String(string)
+            }
+            c if is_word_char(c) => {
+                let mut word = c.to_string();
+                while let Some((_, c)) = chars.next_if(|(_, c)| is_word_char(*c)) {
+                    word.push(c);
+                }
+                Token::Word(word)
+            }
+            c => return Err(ParseError::UnexpectedCharacter(c, position)),
+        };
+        tokens.push((position, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+    end: usize,
+}
+
+/// Parses a query. `|` and `-` are left associative and bind looser than `&`,
+/// so `a | b & c` is `a | (b & c)`.
+pub fn parse(input: &str) -> Result<Expr, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        index: 0,
+        end: input.len(),
+    };
+    let expr = parser.expr()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(_) => Err(parser.expected("an operator")),
+    }
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.index).map(|(_, token)| token.clone());
+        self.index += 1;
+        token
+    }
+
+    fn expected(&self, expected: &'static str) -> ParseError {
+        match self.tokens.get(self.index) {
+            Some((position, token)) => ParseError::Expected {
+                expected,
+                found: token.describe(),
+                position: *position,
+            },
+            None => ParseError::Expected {
+                expected,
+                found: "end of query".to_string(),
+                position: self.end,
+            },
+        }
+    }
+
+    fn expect(&mut self, token: Token, expected: &'static str) -> Result<(), ParseError> {
+        if self.peek() == Some(&token) {
+            self.index += 1;
+            Ok(())
+        } else {
+            Err(self.expected(expected))
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.intersection()?;
+        loop {
+            lhs = match self.peek() {
+                Some(Token::Pipe) => {
+                    self.index += 1;
+                    Expr::Union(Box::new(lhs), Box::new(self.intersection()?))
+                }
+                Some(Token::Minus) => {
+                    self.index += 1;
+                    Expr::Difference(Box::new(lhs), Box::new(self.intersection()?))
+                }
+                _ => return Ok(lhs),
+            };
+        }
+    }
+
+    fn intersection(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.term()?;
+        while self.peek() == Some(&Token::Ampersand) {
+            self.index += 1;
+            lhs = Expr::Intersection(Box::new(lhs), Box::new(self.term()?));
+        }
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Result<Expr, ParseError> {
+        match self.peek() {
+            Some(Token::OpenParen) => {
+                self.index += 1;
+                let expr = self.expr()?;
+                self.expect(Token::CloseParen, "')'")?;
+                Ok(expr)
+            }
+            Some(Token::String(_)) => {
+                let Some(Token::String(string)) = self.next() else {
+                    unreachable!("peeked a string")
+                };
+                Ok(Expr::Literal(string))
+            }
+            Some(Token::Word(_)) => {
+                let Some(Token::Word(word)) = self.next() else {
+                    unreachable!("peeked a word")
+                };
+                if self.peek() != Some(&Token::OpenParen) {
+                    return Ok(Expr::Literal(word));
+                }
+                self.index += 1;
+                let mut args = Vec::new();
+                if self.peek() == Some(&Token::CloseParen) {
+                    self.index += 1;
+                    return Ok(Expr::Call(word, args));
+                }
+                loop {
+                    args.push(self.expr()?);
+                    match self.peek() {
+                        Some(Token::Comma) => self.index += 1,
+                        Some(Token::CloseParen) => {
+                            self.index += 1;
+                            return Ok(Expr::Call(word, args));
+                        }
+                        _ => return Err(self.expected("',' or ')'")),
+                    }
+                }
+            }
+            _ => Err(self.expected("a package, task or function")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn literal(s: &str) -> Box<Expr> {
+        Box::new(Expr::Literal(s.to_string()))
+    }
+
+    #[test_case("web", Expr::Literal("web".into()) ; "bare name")]
+    #[test_case("@repo/ui", Expr::Literal("@repo/ui".into()) ; "scoped name")]
+    #[test_case("web#build", Expr::Literal("web#build".into()) ; "task id")]
+    #[test_case("\"my app\"", Expr::Literal("my app".into()) ; "quoted name")]
+    #[test_case("eslint-config", Expr::Literal("eslint-config".into()) ; "name with dash")]
+    #[test_case("a - b", Expr::Difference(literal("a"), literal("b")) ; "difference")]
+    #[test_case(
+        "a | b & c",
+        Expr::Union(literal("a"), Box::new(Expr::Intersection(literal("b"), literal("c"))))
+        ; "intersection binds tighter"
+    )]
+    #[test_case(
+        "(a | b) & c",
+        Expr::Intersection(Box::new(Expr::Union(literal("a"), literal("b"))), literal("c"))
+        ; "parens"
+    )]
+    #[test_case(
+        "a - b | c",
+        Expr::Union(Box::new(Expr::Difference(literal("a"), literal("b"))), literal("c"))
+        ; "left associative"
+    )]
+    #[test_case("all()", Expr::Call("all".into(), vec![]) ; "no arguments")]
+    #[test_case(
+        "dependents(ui) & changed('main')",
+        Expr::Intersection(
+            Box::new(Expr::Call("dependents".into(), vec![Expr::Literal("ui".into())])),
+            Box::new(Expr::Call("changed".into(), vec![Expr::Literal("main".into())])),
+        )
+        ; "functions"
+    )]
+    #[test_case(
+        "tasks(a | b, \"build\")",
+        Expr::Call(
+            "tasks".into(),
+            vec![Expr::Union(literal("a"), literal("b")), Expr::Literal("build".into())]
+        )
+        ; "multiple arguments"
+    )]
+    fn test_parse(input: &str, expected: Expr) {
+        assert_eq!(parse(input).unwrap(), expected);
+    }
+
+    #[test_case("", ParseError::Expected { expected: "a package, task or function", found: "end of query".into(), position: 0 } ; "empty")]
+    #[test_case("a b", ParseError::Expected { expected: "an operator", found: "'b'".into(), position: 2 } ; "missing operator")]
+    #[test_case("(a | b", ParseError::Expected { expected: "')'", found: "end of query".into(), position: 6 } ; "unclosed paren")]
+    #[test_case("tasks(a b)", ParseError::Expected { expected: "',' or ')'", found: "'b'".into(), position: 8 } ; "missing comma")]
+    #[test_case("changed(\"main)", ParseError::UnterminatedString(8) ; "unterminated string")]
+    #[test_case("a ^ b", ParseError::UnexpectedCharacter('^', 2) ; "unknown operator")]
+    fn test_parse_error(input: &str, expected: ParseError) {
+        assert_eq!(parse(input).unwrap_err(), expected);
+    }
+}
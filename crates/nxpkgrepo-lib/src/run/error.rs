@@ -12,6 +12,11 @@ use crate::{
 pub enum Error {
     #[error("failed to open graph file {0}")]
     OpenGraphFile(#[source] std::io::Error, AbsoluteSystemPathBuf),
+    #[error(
+        "unsupported graph file extension for {0}, use .html, .mmd, .mermaid, .dot or .gv, or \
+         omit the filename to print dot output"
+    )]
+    UnsupportedGraphFile(AbsoluteSystemPathBuf),
     #[error("failed to produce graph output")]
     GraphOutput(#[source] std::io::Error),
     #[error("error preparing engine: Invalid persistent task configuration:\n{0}")]
@@ -33,6 +38,14 @@ pub enum Error {
     #[error(transparent)]
     DaemonConnector(#[from] daemon::DaemonConnectorError),
     #[error(transparent)]
+    Daemon(#[from] daemon::DaemonError),
+    #[error("nxpkg watch requires the daemon, remove --no-daemon to use it")]
+    WatchRequiresDaemon,
+    #[error("daemon stopped sending file changes")]
+    FileChangesClosed,
+    #[error("invalid glob: {0}")]
+    InvalidGlob(#[from] wax::BuildError),
+    #[error(transparent)]
     Cache(#[from] nxpkgrepo_cache::CacheError),
     #[error(transparent)]
     Path(#[from] nxpkgpath::PathError),
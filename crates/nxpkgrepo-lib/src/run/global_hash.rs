@@ -11,6 +11,7 @@ use nxpkgrepo_scm::SCM;
 
 use crate::{
     cli::EnvMode,
+    engine::Engine,
     hash::{GlobalHashable, NxpkgHash},
 };
 
@@ -158,13 +159,39 @@ impl<'a> GlobalHashableInputs<'a> {
             _ => {}
         }
 
-        self.calculate_global_hash()
+        self.calculate_global_hash(&self.global_file_hash_map)
     }
 
-    fn calculate_global_hash(&self) -> String {
+    // The global hash used by cross-platform tasks, which hashes global files
+    // with their line endings normalized. This is `None` if none of the
+    // engine's tasks are cross-platform, so the global files don't need
+    // rehashing. Expects the env mode to have been resolved by
+    // `calculate_global_hash_from_inputs`.
+    pub fn calculate_cross_platform_global_hash(
+        &self,
+        engine: &Engine,
+        scm: &SCM,
+        root_path: &AbsoluteSystemPath,
+    ) -> Result<Option<String>, Error> {
+        if !engine
+            .task_definitions()
+            .values()
+            .any(|definition| definition.cross_platform)
+        {
+            return Ok(None);
+        }
+        let global_file_hash_map =
+            scm.normalize_line_endings(root_path, self.global_file_hash_map.clone())?;
+        Ok(Some(self.calculate_global_hash(&global_file_hash_map)))
+    }
+
+    fn calculate_global_hash(
+        &self,
+        global_file_hash_map: &HashMap<RelativeUnixPathBuf, String>,
+    ) -> String {
         let global_hashable = GlobalHashable {
             global_cache_key: self.global_cache_key,
-            global_file_hash_map: &self.global_file_hash_map,
+            global_file_hash_map,
             root_external_dependencies_hash: self.root_external_dependencies_hash,
             env: self.env,
             resolved_env_vars: self
@@ -3,9 +3,10 @@
 mod cache;
 mod error;
 pub(crate) mod global_hash;
-mod scope;
+pub(crate) mod scope;
 pub(crate) mod summary;
 pub mod task_id;
+mod watch;
 
 use std::{
     collections::HashSet,
@@ -33,22 +34,43 @@ use nxpkgrepo_scm::SCM;
 use nxpkgrepo_ui::{cprint, cprintln, ColorSelector, BOLD_GREY, GREY};
 
 use self::task_id::TaskName;
+pub use self::watch::WatchClient;
 pub use crate::run::error::Error;
 use crate::{
     cli::{DryRunMode, EnvMode},
     commands::CommandBase,
     config::NxpkgJson,
-    daemon::DaemonConnector,
+    daemon::{DaemonClient, DaemonConnector},
     engine::{Engine, EngineBuilder},
     opts::{GraphOpts, Opts},
     process::ProcessManager,
-    run::{global_hash::get_global_hash_inputs, summary::RunTracker},
+    run::{
+        global_hash::{get_global_hash_inputs, GlobalHashableInputs},
+        summary::RunTracker,
+    },
     shim::NxpkgState,
     signal::SignalSubscriber,
     task_graph::Visitor,
-    task_hash::{get_external_deps_hash, PackageInputsHashes, TaskHashTrackerState},
+    task_hash::{get_external_deps_hash, GlobalDotEnv, PackageInputsHashes, TaskHashTrackerState},
 };
 
+/// The package graph and task graph of a run, along with the hashes its tasks
+/// would have if they were executed.
+pub struct RunGraphs {
+    pub pkg_dep_graph: Arc<PackageGraph>,
+    pub engine: Arc<Engine>,
+    pub global_hash: String,
+    pub task_hashes: TaskHashTrackerState,
+}
+
+/// Output formats for `--graph=<file>`, picked from the file extension
+#[derive(Debug, Clone, Copy)]
+enum GraphFormat {
+    Dot,
+    Html,
+    Mermaid,
+}
+
 #[derive(Debug)]
 pub struct Run<'a> {
     base: &'a CommandBase,
@@ -110,8 +132,8 @@ impl<'a> Run<'a> {
             cprint!(self.base.ui, GREY, " in {} packages\n", filtered_pkgs.len());
         }
 
-        let use_http_cache = !opts.cache_opts.skip_remote;
-        if use_http_cache {
+        let use_remote_cache = !opts.cache_opts.skip_remote || !opts.cache_opts.backends.is_empty();
+        if use_remote_cache {
             cprintln!(self.base.ui, GREY, "• Remote caching enabled");
         } else {
             cprintln!(self.base.ui, GREY, "• Remote caching disabled");
@@ -160,20 +182,6 @@ impl<'a> Run<'a> {
         let root_package_json = PackageJson::load(&package_json_path)?;
         let mut opts = self.opts()?;
 
-        let config = self.base.config()?;
-
-        // Pulled from initAnalyticsClient in run.go
-        let is_linked = api_auth
-            .as_ref()
-            .map_or(false, |api_auth| api_auth.is_linked());
-        if !is_linked {
-            opts.cache_opts.skip_remote = true;
-        } else if let Some(enabled) = config.enabled {
-            // We're linked, but if the user has explicitly enabled or disabled, use that
-            // value
-            opts.cache_opts.skip_remote = !enabled;
-        }
-
         let _is_structured_output = opts.run_opts.graph.is_some()
             || matches!(opts.run_opts.dry_run, Some(DryRunMode::Json));
 
@@ -187,19 +195,7 @@ impl<'a> Run<'a> {
         let root_nxpkg_json =
             NxpkgJson::load(&self.base.repo_root, &root_package_json, is_single_package)?;
 
-        let team_id = root_nxpkg_json
-            .remote_cache
-            .as_ref()
-            .and_then(|configuration_options| configuration_options.team_id.clone())
-            .unwrap_or_default();
-
-        let signature = root_nxpkg_json
-            .remote_cache
-            .as_ref()
-            .and_then(|configuration_options| configuration_options.signature)
-            .unwrap_or_default();
-
-        opts.cache_opts.remote_cache_opts = Some(RemoteCacheOpts::new(team_id, signature));
+        self.configure_cache(&mut opts, api_auth.as_ref(), &root_nxpkg_json)?;
 
         if opts.run_opts.experimental_space_id.is_none() {
             opts.run_opts.experimental_space_id = root_nxpkg_json.space_id.clone();
@@ -238,42 +234,11 @@ impl<'a> Run<'a> {
 
         let scm = SCM::new(&self.base.repo_root);
 
-        let filtered_pkgs = {
-            let mut filtered_pkgs = scope::resolve_packages(
-                &opts.scope_opts,
-                &self.base.repo_root,
-                &pkg_dep_graph,
-                &scm,
-            )?;
-
-            if filtered_pkgs.len() != pkg_dep_graph.len() {
-                for target in self.targets() {
-                    let mut task_name = TaskName::from(target.as_str());
-                    // If it's not a package task, we convert to a root task
-                    if !task_name.is_package_task() {
-                        task_name = task_name.into_root_task()
-                    }
-
-                    if root_nxpkg_json.pipeline.contains_key(&task_name) {
-                        filtered_pkgs.insert(WorkspaceName::Root);
-                        break;
-                    }
-                }
-            };
-
-            filtered_pkgs
-        };
+        let filtered_pkgs =
+            self.filtered_packages(&opts, &pkg_dep_graph, &root_nxpkg_json, &scm)?;
 
         let env_at_execution_start = EnvironmentVariableMap::infer();
 
-        let async_cache = AsyncCache::new(
-            &opts.cache_opts,
-            &self.base.repo_root,
-            api_client.clone(),
-            api_auth.clone(),
-            analytics_sender,
-        )?;
-
         let mut engine =
             self.build_engine(&pkg_dep_graph, &opts, &root_nxpkg_json, &filtered_pkgs)?;
 
@@ -281,51 +246,30 @@ impl<'a> Run<'a> {
             self.print_run_prelude(&opts, &filtered_pkgs);
         }
 
-        let root_workspace = pkg_dep_graph
-            .workspace_info(&WorkspaceName::Root)
-            .expect("must have root workspace");
-
-        let is_monorepo = !opts.run_opts.single_package;
-
         let root_external_dependencies_hash =
-            is_monorepo.then(|| get_external_deps_hash(&root_workspace.transitive_dependencies));
-
-        let mut global_hash_inputs = get_global_hash_inputs(
+            Self::root_external_dependencies_hash(&opts, &pkg_dep_graph);
+        let mut global_hash_inputs = self.global_hash_inputs(
+            &opts,
+            &pkg_dep_graph,
+            &root_nxpkg_json,
             root_external_dependencies_hash.as_deref(),
-            &self.base.repo_root,
-            pkg_dep_graph.package_manager(),
-            pkg_dep_graph.lockfile(),
-            &root_nxpkg_json.global_deps,
             &env_at_execution_start,
-            &root_nxpkg_json.global_env,
-            root_nxpkg_json.global_pass_through_env.as_deref(),
-            opts.run_opts.env_mode,
-            opts.run_opts.framework_inference,
-            root_nxpkg_json.global_dot_env.as_deref(),
         )?;
 
         let global_hash = global_hash_inputs.calculate_global_hash_from_inputs();
 
         debug!("global hash: {}", global_hash);
 
-        let color_selector = ColorSelector::default();
-
-        let runcache = Arc::new(RunCache::new(
-            async_cache,
-            &self.base.repo_root,
-            &opts.runcache_opts,
-            color_selector,
+        let runcache = self.run_cache(
+            &opts,
+            api_client.clone(),
+            api_auth.clone(),
+            analytics_sender,
             daemon,
-            self.base.ui,
             opts.run_opts.dry_run.is_some(),
-        ));
+        )?;
 
-        let mut global_env_mode = opts.run_opts.env_mode;
-        if matches!(global_env_mode, EnvMode::Infer)
-            && root_nxpkg_json.global_pass_through_env.is_some()
-        {
-            global_env_mode = EnvMode::Strict;
-        }
+        let global_env_mode = Self::global_env_mode(&opts, &root_nxpkg_json);
 
         let workspaces = pkg_dep_graph.workspaces().collect();
         let package_inputs_hashes = PackageInputsHashes::calculate_file_hashes(
@@ -336,6 +280,12 @@ impl<'a> Run<'a> {
             &self.base.repo_root,
         )?;
 
+        let cross_platform_global_hash = global_hash_inputs.calculate_cross_platform_global_hash(
+            &engine,
+            &scm,
+            &self.base.repo_root,
+        )?;
+
         if opts.run_opts.parallel {
             pkg_dep_graph.remove_workspace_dependencies();
             engine = self.build_engine(&pkg_dep_graph, &opts, &root_nxpkg_json, &filtered_pkgs)?;
@@ -346,11 +296,25 @@ impl<'a> Run<'a> {
                 GraphOpts::File(graph_file) => {
                     let graph_file =
                         AbsoluteSystemPathBuf::from_unknown(self.base.cwd(), graph_file);
+                    let format = match graph_file.extension() {
+                        Some("html") => GraphFormat::Html,
+                        Some("mmd" | "mermaid") => GraphFormat::Mermaid,
+                        Some("dot" | "gv") => GraphFormat::Dot,
+                        _ => return Err(Error::UnsupportedGraphFile(graph_file)),
+                    };
                     let file = graph_file
-                        .open()
+                        .create()
                         .map_err(|e| Error::OpenGraphFile(e, graph_file.clone()))?;
-                    let _writer = BufWriter::new(file);
-                    todo!("Need to implement different format support");
+                    let mut writer = BufWriter::new(file);
+                    let single_package = opts.run_opts.single_package;
+                    match format {
+                        GraphFormat::Html => engine.html_graph(&mut writer, single_package),
+                        GraphFormat::Mermaid => engine.mermaid_graph(&mut writer, single_package),
+                        GraphFormat::Dot => engine.dot_graph(&mut writer, single_package),
+                    }
+                    .and_then(|_| writer.flush())
+                    .map_err(Error::GraphOutput)?;
+                    cprintln!(self.base.ui, GREY, "\nGenerated task graph in {graph_file}");
                 }
                 GraphOpts::Stdout => {
                     engine
@@ -367,15 +331,7 @@ impl<'a> Run<'a> {
         let pkg_dep_graph = Arc::new(pkg_dep_graph);
         let engine = Arc::new(engine);
 
-        let global_env = {
-            let mut env = env_at_execution_start
-                .from_wildcards(global_hash_inputs.pass_through_env.unwrap_or_default())
-                .map_err(Error::Env)?;
-            if let Some(resolved_global) = &global_hash_inputs.resolved_env_vars {
-                env.union(&resolved_global.all);
-            }
-            env
-        };
+        let global_env = Self::global_env(&env_at_execution_start, &global_hash_inputs)?;
 
         let run_tracker = RunTracker::new(
             start_at,
@@ -398,6 +354,8 @@ impl<'a> Run<'a> {
             package_inputs_hashes,
             &env_at_execution_start,
             &global_hash,
+            cross_platform_global_hash.as_deref(),
+            global_dot_env(&root_nxpkg_json),
             global_env_mode,
             self.base.ui,
             false,
@@ -446,8 +404,31 @@ impl<'a> Run<'a> {
     }
 
     #[tokio::main]
-    #[tracing::instrument(skip(self))]
     pub async fn get_hashes(&self) -> Result<(String, TaskHashTrackerState), Error> {
+        let RunGraphs {
+            global_hash,
+            task_hashes,
+            ..
+        } = self.graphs().await?;
+        Ok((global_hash, task_hashes))
+    }
+
+    /// Builds the package graph and task graph and hashes every task in it,
+    /// without executing anything.
+    pub async fn graphs(&self) -> Result<RunGraphs, Error> {
+        let (graphs, _) = self.dry_run(false).await?;
+        Ok(graphs)
+    }
+
+    /// Renders the summary a `--dry=json` run would print, for comparing the
+    /// current state of the repo against a previous run.
+    pub async fn dry_run_summary(&self) -> Result<String, Error> {
+        let (_, summary) = self.dry_run(true).await?;
+        Ok(summary.expect("summary is rendered when requested"))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn dry_run(&self, summarize: bool) -> Result<(RunGraphs, Option<String>), Error> {
         let started_at = Local::now();
         let env_at_execution_start = EnvironmentVariableMap::infer();
 
@@ -466,26 +447,14 @@ impl<'a> Run<'a> {
         let root_nxpkg_json =
             NxpkgJson::load(&self.base.repo_root, &root_package_json, is_single_package)?;
 
-        let root_workspace = pkg_dep_graph
-            .workspace_info(&WorkspaceName::Root)
-            .expect("must have root workspace");
-
-        let is_monorepo = !opts.run_opts.single_package;
         let root_external_dependencies_hash =
-            is_monorepo.then(|| get_external_deps_hash(&root_workspace.transitive_dependencies));
-
-        let mut global_hash_inputs = get_global_hash_inputs(
+            Self::root_external_dependencies_hash(&opts, &pkg_dep_graph);
+        let mut global_hash_inputs = self.global_hash_inputs(
+            &opts,
+            &pkg_dep_graph,
+            &root_nxpkg_json,
             root_external_dependencies_hash.as_deref(),
-            &self.base.repo_root,
-            pkg_dep_graph.package_manager(),
-            pkg_dep_graph.lockfile(),
-            &root_nxpkg_json.global_deps,
             &env_at_execution_start,
-            &root_nxpkg_json.global_env,
-            root_nxpkg_json.global_pass_through_env.as_deref(),
-            opts.run_opts.env_mode,
-            opts.run_opts.framework_inference,
-            root_nxpkg_json.global_dot_env.as_deref(),
         )?;
 
         let scm = SCM::new(&self.base.repo_root);
@@ -536,12 +505,7 @@ impl<'a> Run<'a> {
         )
         .build()?;
 
-        let mut global_env_mode = opts.run_opts.env_mode;
-        if matches!(global_env_mode, EnvMode::Infer)
-            && root_nxpkg_json.global_pass_through_env.is_some()
-        {
-            global_env_mode = EnvMode::Strict;
-        }
+        let global_env_mode = Self::global_env_mode(&opts, &root_nxpkg_json);
 
         let package_inputs_hashes = PackageInputsHashes::calculate_file_hashes(
             &scm,
@@ -551,6 +515,12 @@ impl<'a> Run<'a> {
             &self.base.repo_root,
         )?;
 
+        let cross_platform_global_hash = global_hash_inputs.calculate_cross_platform_global_hash(
+            &engine,
+            &scm,
+            &self.base.repo_root,
+        )?;
+
         if opts.run_opts.parallel {
             pkg_dep_graph.remove_workspace_dependencies();
             engine = self.build_engine(&pkg_dep_graph, &opts, &root_nxpkg_json, &filtered_pkgs)?;
@@ -560,26 +530,15 @@ impl<'a> Run<'a> {
         let engine = Arc::new(engine);
         let api_client = self.base.api_client()?;
 
-        let async_cache = AsyncCache::new(
-            &opts.cache_opts,
-            &self.base.repo_root,
+        // Always dry run when getting hashes
+        let runcache = self.run_cache(
+            &opts,
             api_client.clone(),
             api_auth.clone(),
             None,
-        )?;
-
-        let color_selector = ColorSelector::default();
-
-        let runcache = Arc::new(RunCache::new(
-            async_cache,
-            &self.base.repo_root,
-            &opts.runcache_opts,
-            color_selector,
             None,
-            self.base.ui,
-            // Always dry run when getting hashes
             true,
-        ));
+        )?;
 
         let run_tracker = RunTracker::new(
             started_at,
@@ -602,6 +561,8 @@ impl<'a> Run<'a> {
             package_inputs_hashes,
             &env_at_execution_start,
             &global_hash,
+            cross_platform_global_hash.as_deref(),
+            global_dot_env(&root_nxpkg_json),
             global_env_mode,
             self.base.ui,
             true,
@@ -615,9 +576,189 @@ impl<'a> Run<'a> {
         visitor.dry_run();
 
         visitor.visit(engine.clone()).await?;
-        let task_hash_tracker = visitor.into_task_hash_tracker();
+        let (task_hashes, summary) = if summarize {
+            let (summary, task_hashes) = visitor
+                .summary_json(
+                    filtered_pkgs,
+                    global_hash_inputs,
+                    &engine,
+                    &env_at_execution_start,
+                )
+                .await?;
+            (task_hashes, Some(summary))
+        } else {
+            (visitor.into_task_hash_tracker(), None)
+        };
+
+        Ok((
+            RunGraphs {
+                pkg_dep_graph,
+                engine,
+                global_hash,
+                task_hashes,
+            },
+            summary,
+        ))
+    }
+
+    /// Applies the remote cache configuration of the repository to
+    /// `opts.cache_opts`.
+    fn configure_cache(
+        &self,
+        opts: &mut Opts,
+        api_auth: Option<&APIAuth>,
+        root_nxpkg_json: &NxpkgJson,
+    ) -> Result<(), Error> {
+        let config = self.base.config()?;
+
+        // Pulled from initAnalyticsClient in run.go
+        let is_linked = api_auth.map_or(false, |api_auth| api_auth.is_linked());
+        if !is_linked {
+            opts.cache_opts.skip_remote = true;
+        } else if let Some(enabled) = config.enabled {
+            // We're linked, but if the user has explicitly enabled or disabled, use that
+            // value
+            opts.cache_opts.skip_remote = !enabled;
+        }
+        opts.cache_opts.fs_eviction = config.cache_eviction_policy();
+        opts.cache_opts.backends = config.cache_backends().to_vec();
+
+        let team_id = root_nxpkg_json
+            .remote_cache
+            .as_ref()
+            .and_then(|configuration_options| configuration_options.team_id.clone())
+            .unwrap_or_default();
+
+        let signature = root_nxpkg_json
+            .remote_cache
+            .as_ref()
+            .and_then(|configuration_options| configuration_options.signature)
+            .unwrap_or_default();
+        let content_addressed = root_nxpkg_json
+            .remote_cache
+            .as_ref()
+            .and_then(|configuration_options| configuration_options.content_addressed)
+            .unwrap_or_default();
+
+        opts.cache_opts.remote_cache_opts =
+            Some(RemoteCacheOpts::new(team_id, signature, content_addressed));
+
+        Ok(())
+    }
+
+    fn root_external_dependencies_hash(
+        opts: &Opts,
+        pkg_dep_graph: &PackageGraph,
+    ) -> Option<String> {
+        let root_workspace = pkg_dep_graph
+            .workspace_info(&WorkspaceName::Root)
+            .expect("must have root workspace");
 
-        Ok((global_hash, task_hash_tracker))
+        let is_monorepo = !opts.run_opts.single_package;
+        is_monorepo.then(|| get_external_deps_hash(&root_workspace.transitive_dependencies))
+    }
+
+    fn global_hash_inputs<'b>(
+        &self,
+        opts: &Opts,
+        pkg_dep_graph: &PackageGraph,
+        root_nxpkg_json: &'b NxpkgJson,
+        root_external_dependencies_hash: Option<&'b str>,
+        env_at_execution_start: &'b EnvironmentVariableMap,
+    ) -> Result<GlobalHashableInputs<'b>, Error> {
+        get_global_hash_inputs(
+            root_external_dependencies_hash,
+            &self.base.repo_root,
+            pkg_dep_graph.package_manager(),
+            pkg_dep_graph.lockfile(),
+            &root_nxpkg_json.global_deps,
+            env_at_execution_start,
+            &root_nxpkg_json.global_env,
+            root_nxpkg_json.global_pass_through_env.as_deref(),
+            opts.run_opts.env_mode,
+            opts.run_opts.framework_inference,
+            root_nxpkg_json.global_dot_env.as_deref(),
+        )
+    }
+
+    fn global_env_mode(opts: &Opts, root_nxpkg_json: &NxpkgJson) -> EnvMode {
+        let mut global_env_mode = opts.run_opts.env_mode;
+        if matches!(global_env_mode, EnvMode::Infer)
+            && root_nxpkg_json.global_pass_through_env.is_some()
+        {
+            global_env_mode = EnvMode::Strict;
+        }
+        global_env_mode
+    }
+
+    /// The environment variables every task gets, on top of its own
+    fn global_env(
+        env_at_execution_start: &EnvironmentVariableMap,
+        global_hash_inputs: &GlobalHashableInputs,
+    ) -> Result<EnvironmentVariableMap, Error> {
+        let mut env = env_at_execution_start
+            .from_wildcards(global_hash_inputs.pass_through_env.unwrap_or_default())
+            .map_err(Error::Env)?;
+        if let Some(resolved_global) = &global_hash_inputs.resolved_env_vars {
+            env.union(&resolved_global.all);
+        }
+        Ok(env)
+    }
+
+    fn run_cache(
+        &self,
+        opts: &Opts,
+        api_client: APIClient,
+        api_auth: Option<APIAuth>,
+        analytics_sender: Option<AnalyticsSender>,
+        daemon: Option<DaemonClient<DaemonConnector>>,
+        dry_run: bool,
+    ) -> Result<Arc<RunCache>, Error> {
+        let async_cache = AsyncCache::new(
+            &opts.cache_opts,
+            &self.base.repo_root,
+            api_client,
+            api_auth,
+            analytics_sender,
+        )?;
+
+        Ok(Arc::new(RunCache::new(
+            async_cache,
+            &self.base.repo_root,
+            &opts.runcache_opts,
+            ColorSelector::default(),
+            daemon,
+            self.base.ui,
+            dry_run,
+        )))
+    }
+
+    fn filtered_packages(
+        &self,
+        opts: &Opts,
+        pkg_dep_graph: &PackageGraph,
+        root_nxpkg_json: &NxpkgJson,
+        scm: &SCM,
+    ) -> Result<HashSet<WorkspaceName>, Error> {
+        let mut filtered_pkgs =
+            scope::resolve_packages(&opts.scope_opts, &self.base.repo_root, pkg_dep_graph, scm)?;
+
+        if filtered_pkgs.len() != pkg_dep_graph.len() {
+            for target in self.targets() {
+                let mut task_name = TaskName::from(target.as_str());
+                // If it's not a package task, we convert to a root task
+                if !task_name.is_package_task() {
+                    task_name = task_name.into_root_task()
+                }
+
+                if root_nxpkg_json.pipeline.contains_key(&task_name) {
+                    filtered_pkgs.insert(WorkspaceName::Root);
+                    break;
+                }
+            }
+        };
+
+        Ok(filtered_pkgs)
     }
 
     fn build_engine(
@@ -665,3 +806,19 @@ impl<'a> Run<'a> {
         Ok(engine)
     }
 }
+
+// The root nxpkg.json's `globalDotEnv` files are loaded into every task along
+// with its own `dotEnv` files
+fn global_dot_env(root_nxpkg_json: &NxpkgJson) -> GlobalDotEnv<'_> {
+    GlobalDotEnv {
+        files: root_nxpkg_json
+            .global_dot_env
+            .as_deref()
+            .unwrap_or_default(),
+        env: &root_nxpkg_json.global_env,
+        pass_through_env: root_nxpkg_json
+            .global_pass_through_env
+            .as_deref()
+            .unwrap_or_default(),
+    }
+}
@@ -3,6 +3,7 @@ use std::collections::HashSet;
 use nxpkgpath::{AbsoluteSystemPath, AnchoredSystemPath, AnchoredSystemPathBuf};
 use nxpkgrepo_repository::package_graph::{ChangedPackagesError, PackageGraph, WorkspaceName};
 use nxpkgrepo_scm::SCM;
+use tracing::{debug, warn};
 use wax::Pattern;
 
 pub trait PackageChangeDetector {
@@ -22,6 +23,8 @@ pub struct SCMChangeDetector<'a> {
 
     global_deps: Vec<String>,
     ignore_patterns: Vec<String>,
+    // Whether a shallow clone may be deepened to reach the merge base
+    deepen: bool,
 }
 
 impl<'a> PackageChangeDetector for SCMChangeDetector<'a> {
@@ -32,6 +35,18 @@ impl<'a> PackageChangeDetector for SCMChangeDetector<'a> {
     ) -> Result<HashSet<WorkspaceName>, ChangeDetectError> {
         let mut changed_files = HashSet::new();
         if !from_ref.is_empty() {
+            if !self.history_reaches_merge_base(from_ref, to_ref) {
+                warn!(
+                    "the shallow clone doesn't contain the merge base of {}...{}, treating all \
+                     packages as changed. Fetch more history or pass --scm-deepen to compare them",
+                    from_ref, to_ref
+                );
+                return Ok(self
+                    .pkg_graph
+                    .workspaces()
+                    .map(|(n, _)| n.to_owned())
+                    .collect());
+            }
             changed_files = self
                 .scm
                 .changed_files(self.nxpkg_root, Some(from_ref), to_ref)?;
@@ -71,6 +86,9 @@ impl<'a> PackageChangeDetector for SCMChangeDetector<'a> {
 
 impl<'a> SCMChangeDetector<'a> {
     const DEFAULT_GLOBAL_DEPS: [&'static str; 2] = ["package.json", "nxpkg.json"];
+    // Commits fetched by the first deepening, doubled on every further attempt
+    const DEEPEN_STEP: u32 = 100;
+    const MAX_DEEPEN_ATTEMPTS: u32 = 4;
 
     pub fn new(
         nxpkg_root: &'a AbsoluteSystemPath,
@@ -79,14 +97,57 @@ impl<'a> SCMChangeDetector<'a> {
         pkg_graph: &'a PackageGraph,
         global_deps: Vec<String>,
         ignore_patterns: Vec<String>,
+        deepen: bool,
     ) -> Self {
+        let checkout = scm.checkout();
+        if checkout.worktree || checkout.sparse {
+            debug!("detecting changes in {:?}", checkout);
+        }
         Self {
             nxpkg_root,
             scm,
             pkg_graph,
             global_deps,
             ignore_patterns,
+            deepen,
+        }
+    }
+
+    // A full clone always has the merge base, a shallow one only if enough
+    // history was fetched. When allowed, fetch more until it's there.
+    fn history_reaches_merge_base(&self, from_ref: &str, to_ref: &str) -> bool {
+        if !self.scm.checkout().shallow {
+            return true;
+        }
+        let has_merge_base = || {
+            self.scm
+                .has_merge_base(from_ref, to_ref)
+                .unwrap_or_else(|e| {
+                    debug!(
+                        "unable to find merge base of {}...{}: {}",
+                        from_ref, to_ref, e
+                    );
+                    false
+                })
+        };
+        if has_merge_base() {
+            return true;
+        }
+        if !self.deepen {
+            return false;
+        }
+        for attempt in 0..Self::MAX_DEEPEN_ATTEMPTS {
+            let depth = Self::DEEPEN_STEP << attempt;
+            debug!("deepening shallow clone by {} commits", depth);
+            if let Err(e) = self.scm.deepen(depth) {
+                warn!("failed to deepen shallow clone: {}", e);
+                return false;
+            }
+            if has_merge_base() {
+                return true;
+            }
         }
+        false
     }
 
     fn repo_global_file_has_changed(
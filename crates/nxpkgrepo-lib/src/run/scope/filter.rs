@@ -108,6 +108,7 @@ impl<'a> FilterResolver<'a, SCMChangeDetector<'a>> {
             pkg_graph,
             opts.global_deps.clone(),
             opts.ignore_patterns.clone(),
+            opts.scm_deepen,
         );
         Self::new_with_change_detector(pkg_graph, nxpkg_root, inference, scm, change_detector)
     }
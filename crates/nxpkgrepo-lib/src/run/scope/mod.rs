@@ -9,7 +9,9 @@ use filter::{FilterResolver, PackageInference};
 use nxpkgpath::AbsoluteSystemPath;
 use nxpkgrepo_repository::package_graph::{PackageGraph, WorkspaceName};
 use nxpkgrepo_scm::SCM;
+use tracing::debug;
 
+pub use self::change_detector::{ChangeDetectError, PackageChangeDetector, SCMChangeDetector};
 use crate::opts::ScopeOpts;
 pub use crate::run::scope::filter::ResolutionError;
 
@@ -24,8 +26,24 @@ pub fn resolve_packages(
         PackageInference::calculate(nxpkg_root, pkg_inference_path, pkg_graph)
     });
 
-    let filtered_packages = FilterResolver::new(opts, pkg_graph, nxpkg_root, pkg_inference, scm)
-        .resolve(&opts.get_filters())?;
+    let mut filtered_packages =
+        FilterResolver::new(opts, pkg_graph, nxpkg_root, pkg_inference, scm)
+            .resolve(&opts.get_filters())?;
+
+    // Packages left out of a sparse checkout can't be built here. They still
+    // count as changed while filtering, so their dependents are selected.
+    filtered_packages.retain(|name| {
+        let Some(info) = pkg_graph.workspace_info(name) else {
+            return true;
+        };
+        let excluded = scm
+            .sparse_checkout_excluding(nxpkg_root, info.package_path())
+            .is_some();
+        if excluded {
+            debug!("skipping {name}, it's outside of the sparse checkout");
+        }
+        !excluded
+    });
 
     Ok(filtered_packages)
 }
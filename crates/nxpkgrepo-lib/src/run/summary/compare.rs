@@ -0,0 +1,773 @@
+//! Compares the hash inputs recorded in two run summaries to explain why a
+//! task's hash, and therefore its cache key, changed between them.
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
+
+use serde::Deserialize;
+use serde_json::Value;
+use nxpkgrepo_lockfiles::PackageChanges;
+
+/// The subset of a run summary that feeds into task hashes. Summaries are
+/// read back leniently so ones written by older versions can still be
+/// compared.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedRunSummary {
+    #[serde(default)]
+    nxpkg_version: Option<String>,
+    #[serde(rename = "globalCacheInputs")]
+    global_inputs: GlobalInputs,
+    tasks: Vec<SavedTaskSummary>,
+    #[serde(default)]
+    scm: SavedScm,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SavedScm {
+    sha: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct GlobalInputs {
+    root_key: String,
+    files: BTreeMap<String, String>,
+    hash_of_external_dependencies: String,
+    global_dot_env: Option<Vec<String>>,
+    environment_variables: EnvVars,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct EnvVars {
+    configured: Option<Vec<String>>,
+    inferred: Option<Vec<String>>,
+    passthrough: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedTaskSummary {
+    pub task_id: String,
+    pub hash: String,
+    #[serde(default)]
+    inputs: BTreeMap<String, String>,
+    #[serde(default)]
+    hash_of_external_dependencies: String,
+    #[serde(default)]
+    cli_arguments: Vec<String>,
+    #[serde(default)]
+    dependencies: Vec<String>,
+    #[serde(default)]
+    resolved_task_definition: BTreeMap<String, Value>,
+    #[serde(default)]
+    framework: String,
+    #[serde(default)]
+    env_mode: Option<String>,
+    #[serde(default)]
+    environment_variables: EnvVars,
+    #[serde(default)]
+    dot_env: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// A single hash input that differs between the two runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    NxpkgVersion {
+        before: String,
+        after: String,
+    },
+    RootKey,
+    GlobalFile {
+        path: String,
+        kind: ChangeKind,
+    },
+    GlobalEnvVar {
+        name: String,
+        kind: ChangeKind,
+    },
+    GlobalDotEnv,
+    RootExternalDependencies,
+    File {
+        path: String,
+        kind: ChangeKind,
+    },
+    EnvVar {
+        name: String,
+        kind: ChangeKind,
+    },
+    DotEnv,
+    ExternalDependencies,
+    /// An external package in the lockfile that the task's workspace, or the
+    /// root for global changes, depends on. It has no version in the run it's
+    /// missing from.
+    ExternalPackage {
+        key: String,
+        before: Option<String>,
+        after: Option<String>,
+    },
+    Dependency {
+        task_id: String,
+        kind: ChangeKind,
+    },
+    PassThroughArgs {
+        before: Vec<String>,
+        after: Vec<String>,
+    },
+    TaskDefinition {
+        field: String,
+    },
+    EnvMode {
+        before: String,
+        after: String,
+    },
+    Framework {
+        before: String,
+        after: String,
+    },
+}
+
+/// How a task differs between the two runs
+#[derive(Debug, PartialEq, Eq)]
+pub enum TaskComparison {
+    /// The task has the same hash in both runs
+    Unchanged { hash: String },
+    /// The task only ran in the `after` run
+    Added { hash: String },
+    /// The task only ran in the `before` run
+    Removed { hash: String },
+    Changed {
+        before: String,
+        after: String,
+        changes: Vec<Change>,
+    },
+}
+
+#[derive(Debug)]
+pub struct RunComparison {
+    /// Changes to the global hash, which affect every task
+    pub global: Vec<Change>,
+    pub tasks: BTreeMap<String, TaskComparison>,
+}
+
+impl SavedRunSummary {
+    pub fn task(&self, task_id: &str) -> Option<&SavedTaskSummary> {
+        self.tasks.iter().find(|task| task.task_id == task_id)
+    }
+
+    pub fn task_with_hash(&self, hash: &str) -> Option<&SavedTaskSummary> {
+        self.tasks.iter().find(|task| task.hash == hash)
+    }
+
+    /// The commit the run was made at, if it was recorded
+    pub fn sha(&self) -> Option<&str> {
+        self.scm.sha.as_deref()
+    }
+
+    /// Whether the lockfile entries of the root or of any task's workspace
+    /// differ between the two runs
+    pub fn external_dependencies_changed(&self, other: &SavedRunSummary) -> bool {
+        let external_hashes = |summary: &SavedRunSummary| {
+            summary
+                .tasks
+                .iter()
+                .map(|task| task.hash_of_external_dependencies.as_str())
+                .chain([summary.global_inputs.hash_of_external_dependencies.as_str()])
+                .collect::<BTreeSet<_>>()
+        };
+        external_hashes(self) != external_hashes(other)
+    }
+
+    fn task_hashes(&self) -> BTreeMap<&str, &str> {
+        self.tasks
+            .iter()
+            .map(|task| (task.task_id.as_str(), task.hash.as_str()))
+            .collect()
+    }
+}
+
+/// Compares every task present in either summary. If `task_id` is given only
+/// that task is compared. `lockfile_changes` maps workspace names to how their
+/// external dependencies changed between the runs, and is used to explain
+/// changes to the lockfile.
+pub fn compare(
+    before: &SavedRunSummary,
+    after: &SavedRunSummary,
+    task_id: Option<&str>,
+    lockfile_changes: &HashMap<String, PackageChanges>,
+) -> RunComparison {
+    let global = compare_global(before, after, lockfile_changes);
+
+    let before_hashes = before.task_hashes();
+    let after_hashes = after.task_hashes();
+    let task_ids = before_hashes
+        .keys()
+        .chain(after_hashes.keys())
+        .copied()
+        .filter(|id| task_id.map_or(true, |task_id| task_id == *id))
+        .collect::<BTreeSet<_>>();
+
+    let tasks = task_ids
+        .into_iter()
+        .map(|id| {
+            let comparison = match (before.task(id), after.task(id)) {
+                (Some(b), Some(a)) if b.hash == a.hash => TaskComparison::Unchanged {
+                    hash: a.hash.clone(),
+                },
+                (Some(b), Some(a)) => TaskComparison::Changed {
+                    before: b.hash.clone(),
+                    after: a.hash.clone(),
+                    changes: compare_task(b, a, &before_hashes, &after_hashes, lockfile_changes),
+                },
+                (Some(b), None) => TaskComparison::Removed {
+                    hash: b.hash.clone(),
+                },
+                (None, Some(a)) => TaskComparison::Added {
+                    hash: a.hash.clone(),
+                },
+                (None, None) => unreachable!("task ids are collected from both summaries"),
+            };
+            (id.to_string(), comparison)
+        })
+        .collect();
+
+    RunComparison { global, tasks }
+}
+
+fn compare_global(
+    before: &SavedRunSummary,
+    after: &SavedRunSummary,
+    lockfile_changes: &HashMap<String, PackageChanges>,
+) -> Vec<Change> {
+    let mut changes = Vec::new();
+    if let (Some(b), Some(a)) = (&before.nxpkg_version, &after.nxpkg_version) {
+        if b != a {
+            changes.push(Change::NxpkgVersion {
+                before: b.clone(),
+                after: a.clone(),
+            });
+        }
+    }
+
+    let (b, a) = (&before.global_inputs, &after.global_inputs);
+    if b.root_key != a.root_key {
+        changes.push(Change::RootKey);
+    }
+    changes.extend(
+        diff_maps(&b.files, &a.files).map(|(path, kind)| Change::GlobalFile { path, kind }),
+    );
+    if b.hash_of_external_dependencies != a.hash_of_external_dependencies {
+        changes.push(Change::RootExternalDependencies);
+        changes.extend(package_changes(lockfile_changes.get(ROOT_WORKSPACE)));
+    }
+    changes.extend(
+        diff_env_vars(&b.environment_variables, &a.environment_variables)
+            .into_iter()
+            .map(|(name, kind)| Change::GlobalEnvVar { name, kind }),
+    );
+    if b.global_dot_env != a.global_dot_env {
+        changes.push(Change::GlobalDotEnv);
+    }
+
+    changes
+}
+
+fn compare_task(
+    before: &SavedTaskSummary,
+    after: &SavedTaskSummary,
+    before_hashes: &BTreeMap<&str, &str>,
+    after_hashes: &BTreeMap<&str, &str>,
+    lockfile_changes: &HashMap<String, PackageChanges>,
+) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    changes.extend(
+        diff_maps(&before.inputs, &after.inputs).map(|(path, kind)| Change::File { path, kind }),
+    );
+    if before.hash_of_external_dependencies != after.hash_of_external_dependencies {
+        changes.push(Change::ExternalDependencies);
+        let workspace = after
+            .task_id
+            .split_once('#')
+            .map_or(after.task_id.as_str(), |(workspace, _)| workspace);
+        changes.extend(package_changes(lockfile_changes.get(workspace)));
+    }
+    changes.extend(
+        diff_env_vars(&before.environment_variables, &after.environment_variables)
+            .into_iter()
+            .map(|(name, kind)| Change::EnvVar { name, kind }),
+    );
+    if before.dot_env != after.dot_env {
+        changes.push(Change::DotEnv);
+    }
+
+    // A dependency's hash feeds into this task's hash, so a dependency that
+    // changed is reported here and explained in its own entry
+    let before_deps = dependency_hashes(&before.dependencies, before_hashes);
+    let after_deps = dependency_hashes(&after.dependencies, after_hashes);
+    changes.extend(
+        diff_maps(&before_deps, &after_deps)
+            .map(|(task_id, kind)| Change::Dependency { task_id, kind }),
+    );
+
+    if before.cli_arguments != after.cli_arguments {
+        changes.push(Change::PassThroughArgs {
+            before: before.cli_arguments.clone(),
+            after: after.cli_arguments.clone(),
+        });
+    }
+    changes.extend(
+        diff_maps(
+            &before.resolved_task_definition,
+            &after.resolved_task_definition,
+        )
+        .map(|(field, _)| Change::TaskDefinition { field }),
+    );
+    if let (Some(b), Some(a)) = (&before.env_mode, &after.env_mode) {
+        if b != a {
+            changes.push(Change::EnvMode {
+                before: b.clone(),
+                after: a.clone(),
+            });
+        }
+    }
+    if before.framework != after.framework {
+        changes.push(Change::Framework {
+            before: before.framework.clone(),
+            after: after.framework.clone(),
+        });
+    }
+
+    changes
+}
+
+// The name run summaries use for the root workspace in task ids
+const ROOT_WORKSPACE: &str = "//";
+
+fn package_changes(changes: Option<&PackageChanges>) -> Vec<Change> {
+    let Some(changes) = changes else {
+        return Vec::new();
+    };
+    let removed = changes
+        .removed
+        .iter()
+        .map(|package| Change::ExternalPackage {
+            key: package.key.clone(),
+            before: Some(package.version.clone()),
+            after: None,
+        });
+    let changed = changes
+        .changed
+        .iter()
+        .map(|(previous, current)| Change::ExternalPackage {
+            key: current.key.clone(),
+            before: Some(previous.version.clone()),
+            after: Some(current.version.clone()),
+        });
+    let added = changes.added.iter().map(|package| Change::ExternalPackage {
+        key: package.key.clone(),
+        before: None,
+        after: Some(package.version.clone()),
+    });
+    removed.chain(changed).chain(added).collect()
+}
+
+// Dependencies that didn't run in a summary have no hash to compare, so they
+// are tracked with an empty one and only show up if they were added or removed
+fn dependency_hashes<'a>(
+    dependencies: &'a [String],
+    hashes: &BTreeMap<&str, &'a str>,
+) -> BTreeMap<&'a str, &'a str> {
+    dependencies
+        .iter()
+        .map(|id| (id.as_str(), hashes.get(id.as_str()).copied().unwrap_or("")))
+        .collect()
+}
+
+fn diff_maps<'a, K, V>(
+    before: &'a BTreeMap<K, V>,
+    after: &'a BTreeMap<K, V>,
+) -> impl Iterator<Item = (String, ChangeKind)> + 'a
+where
+    K: Ord + ToString,
+    V: PartialEq,
+{
+    let removed = before
+        .keys()
+        .filter(|key| !after.contains_key(*key))
+        .map(|key| (key.to_string(), ChangeKind::Removed));
+    let changed = after.iter().filter_map(|(key, value)| {
+        let kind = match before.get(key) {
+            None => ChangeKind::Added,
+            Some(previous) if previous != value => ChangeKind::Modified,
+            Some(_) => return None,
+        };
+        Some((key.to_string(), kind))
+    });
+    removed.chain(changed)
+}
+
+// Environment variables are recorded as `NAME=<hash of value>` pairs, so only
+// the names of changed variables can be reported
+fn diff_env_vars(before: &EnvVars, after: &EnvVars) -> Vec<(String, ChangeKind)> {
+    let before = env_var_pairs(before);
+    let after = env_var_pairs(after);
+    diff_maps(&before, &after).collect()
+}
+
+fn env_var_pairs(env_vars: &EnvVars) -> BTreeMap<&str, &str> {
+    [
+        &env_vars.configured,
+        &env_vars.inferred,
+        &env_vars.passthrough,
+    ]
+    .into_iter()
+    .flatten()
+    .flatten()
+    .map(|pair| pair.split_once('=').unwrap_or((pair.as_str(), "")))
+    .collect()
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Modified => "changed",
+        })
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::NxpkgVersion { before, after } => {
+                write!(f, "nxpkg version changed from {before} to {after}")
+            }
+            Change::RootKey => f.write_str("global cache key changed"),
+            Change::GlobalFile { path, kind } => write!(f, "global file {kind}: {path}"),
+            Change::GlobalEnvVar { name, kind } => {
+                write!(f, "global environment variable {kind}: {name}")
+            }
+            Change::GlobalDotEnv => f.write_str("globalDotEnv files changed"),
+            Change::RootExternalDependencies => {
+                f.write_str("root dependencies in the lockfile changed")
+            }
+            Change::File { path, kind } => write!(f, "file {kind}: {path}"),
+            Change::EnvVar { name, kind } => write!(f, "environment variable {kind}: {name}"),
+            Change::DotEnv => f.write_str("dotEnv files changed"),
+            Change::ExternalDependencies => {
+                f.write_str("package dependencies in the lockfile changed")
+            }
+            Change::ExternalPackage { key, before, after } => match (before, after) {
+                (Some(before), Some(after)) => {
+                    write!(f, "external package changed: {key} {before} -> {after}")
+                }
+                (None, Some(version)) => write!(f, "external package added: {key}@{version}"),
+                (Some(version), None) => write!(f, "external package removed: {key}@{version}"),
+                (None, None) => write!(f, "external package changed: {key}"),
+            },
+            Change::Dependency { task_id, kind } => write!(f, "dependency {kind}: {task_id}"),
+            Change::PassThroughArgs { before, after } => write!(
+                f,
+                "pass-through args changed from {:?} to {:?}",
+                before.join(" "),
+                after.join(" ")
+            ),
+            Change::TaskDefinition { field } => write!(f, "task definition changed: {field}"),
+            Change::EnvMode { before, after } => {
+                write!(f, "env mode changed from {before} to {after}")
+            }
+            Change::Framework { before, after } => {
+                write!(f, "framework changed from {before:?} to {after:?}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use nxpkgrepo_lockfiles::Package;
+
+    use super::*;
+
+    fn summary(value: Value) -> SavedRunSummary {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn task(id: &str, hash: &str, extra: Value) -> Value {
+        let mut task = json!({
+            "taskId": id,
+            "hash": hash,
+            "inputs": {},
+            "hashOfExternalDependencies": "ext",
+            "cliArguments": [],
+            "dependencies": [],
+            "resolvedTaskDefinition": { "outputs": ["dist/**"] },
+            "framework": "",
+            "envMode": "loose",
+            "environmentVariables": {
+                "specified": { "env": [], "passThroughEnv": null },
+                "configured": [],
+                "inferred": [],
+                "passthrough": null,
+            },
+        });
+        task.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        task
+    }
+
+    fn run(tasks: Vec<Value>) -> Value {
+        json!({
+            "nxpkgVersion": "1.0.0",
+            "globalCacheInputs": {
+                "rootKey": "key",
+                "files": { "nxpkg.json": "a" },
+                "hashOfExternalDependencies": "root",
+                "environmentVariables": { "configured": [], "inferred": [], "passthrough": null },
+            },
+            "tasks": tasks,
+        })
+    }
+
+    #[test]
+    fn test_reports_task_inputs() {
+        let before = summary(run(vec![
+            task("ui#build", "u1", json!({})),
+            task(
+                "web#build",
+                "w1",
+                json!({
+                    "inputs": { "src/a.ts": "1", "src/b.ts": "2" },
+                    "dependencies": ["ui#build"],
+                    "environmentVariables": { "configured": ["API_URL=1", "TOKEN=2"] },
+                }),
+            ),
+        ]));
+        let after = summary(run(vec![
+            task(
+                "ui#build",
+                "u2",
+                json!({ "hashOfExternalDependencies": "ext2" }),
+            ),
+            task(
+                "web#build",
+                "w2",
+                json!({
+                    "inputs": { "src/a.ts": "3", "src/c.ts": "4" },
+                    "dependencies": ["ui#build"],
+                    "cliArguments": ["--prod"],
+                    "resolvedTaskDefinition": { "outputs": ["build/**"] },
+                    "environmentVariables": { "configured": ["API_URL=5"] },
+                }),
+            ),
+        ]));
+
+        let comparison = compare(&before, &after, None, &HashMap::new());
+        assert!(comparison.global.is_empty());
+        assert_eq!(
+            comparison.tasks["ui#build"],
+            TaskComparison::Changed {
+                before: "u1".into(),
+                after: "u2".into(),
+                changes: vec![Change::ExternalDependencies],
+            }
+        );
+        assert_eq!(
+            comparison.tasks["web#build"],
+            TaskComparison::Changed {
+                before: "w1".into(),
+                after: "w2".into(),
+                changes: vec![
+                    Change::File {
+                        path: "src/b.ts".into(),
+                        kind: ChangeKind::Removed
+                    },
+                    Change::File {
+                        path: "src/a.ts".into(),
+                        kind: ChangeKind::Modified
+                    },
+                    Change::File {
+                        path: "src/c.ts".into(),
+                        kind: ChangeKind::Added
+                    },
+                    Change::EnvVar {
+                        name: "TOKEN".into(),
+                        kind: ChangeKind::Removed
+                    },
+                    Change::EnvVar {
+                        name: "API_URL".into(),
+                        kind: ChangeKind::Modified
+                    },
+                    Change::Dependency {
+                        task_id: "ui#build".into(),
+                        kind: ChangeKind::Modified
+                    },
+                    Change::PassThroughArgs {
+                        before: vec![],
+                        after: vec!["--prod".into()]
+                    },
+                    Change::TaskDefinition {
+                        field: "outputs".into()
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_reports_global_inputs() {
+        let before = summary(run(vec![task("web#build", "w1", json!({}))]));
+        let mut after = run(vec![task("web#build", "w2", json!({}))]);
+        after["nxpkgVersion"] = json!("1.1.0");
+        after["globalCacheInputs"]["hashOfExternalDependencies"] = json!("root2");
+        after["globalCacheInputs"]["environmentVariables"]["inferred"] = json!(["CI=1"]);
+        let after = summary(after);
+
+        let comparison = compare(&before, &after, None, &HashMap::new());
+        assert_eq!(
+            comparison.global,
+            vec![
+                Change::NxpkgVersion {
+                    before: "1.0.0".into(),
+                    after: "1.1.0".into()
+                },
+                Change::RootExternalDependencies,
+                Change::GlobalEnvVar {
+                    name: "CI".into(),
+                    kind: ChangeKind::Added
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_lockfile_changes() {
+        let before = summary(run(vec![
+            task("ui#build", "u1", json!({})),
+            task("web#build", "w1", json!({})),
+        ]));
+        let mut after = run(vec![
+            task(
+                "ui#build",
+                "u2",
+                json!({ "hashOfExternalDependencies": "ext2" }),
+            ),
+            task("web#build", "w1", json!({})),
+        ]);
+        after["globalCacheInputs"]["hashOfExternalDependencies"] = json!("root2");
+        let after = summary(after);
+        assert!(before.external_dependencies_changed(&after));
+
+        let lockfile_changes = HashMap::from([
+            (
+                "ui".to_string(),
+                PackageChanges {
+                    added: [Package::new("is-odd", "3.0.1")].into(),
+                    removed: [Package::new("is-even", "1.0.0")].into(),
+                    changed: [(
+                        Package::new("react", "18.2.0"),
+                        Package::new("react", "18.3.1"),
+                    )]
+                    .into(),
+                },
+            ),
+            (
+                "//".to_string(),
+                PackageChanges {
+                    added: [Package::new("nxpkg", "1.1.0")].into(),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let comparison = compare(&before, &after, None, &lockfile_changes);
+        assert_eq!(
+            comparison.global,
+            vec![
+                Change::RootExternalDependencies,
+                Change::ExternalPackage {
+                    key: "nxpkg".into(),
+                    before: None,
+                    after: Some("1.1.0".into())
+                },
+            ]
+        );
+        assert_eq!(
+            comparison.tasks["ui#build"],
+            TaskComparison::Changed {
+                before: "u1".into(),
+                after: "u2".into(),
+                changes: vec![
+                    Change::ExternalDependencies,
+                    Change::ExternalPackage {
+                        key: "is-even".into(),
+                        before: Some("1.0.0".into()),
+                        after: None
+                    },
+                    Change::ExternalPackage {
+                        key: "react".into(),
+                        before: Some("18.2.0".into()),
+                        after: Some("18.3.1".into())
+                    },
+                    Change::ExternalPackage {
+                        key: "is-odd".into(),
+                        before: None,
+                        after: Some("3.0.1".into())
+                    },
+                ],
+            }
+        );
+        assert_eq!(
+            comparison.tasks["web#build"],
+            TaskComparison::Unchanged { hash: "w1".into() }
+        );
+    }
+
+    #[test]
+    fn test_filters_to_task() {
+        let before = summary(run(vec![
+            task("docs#build", "d1", json!({})),
+            task("web#build", "w1", json!({})),
+        ]));
+        let after = summary(run(vec![
+            task("web#build", "w1", json!({})),
+            task("web#lint", "l1", json!({})),
+        ]));
+
+        let comparison = compare(&before, &after, None, &HashMap::new());
+        assert_eq!(
+            comparison.tasks.into_iter().collect::<Vec<_>>(),
+            vec![
+                (
+                    "docs#build".to_string(),
+                    TaskComparison::Removed { hash: "d1".into() }
+                ),
+                (
+                    "web#build".to_string(),
+                    TaskComparison::Unchanged { hash: "w1".into() }
+                ),
+                (
+                    "web#lint".to_string(),
+                    TaskComparison::Added { hash: "l1".into() }
+                ),
+            ]
+        );
+
+        let comparison = compare(&before, &after, Some("web#build"), &HashMap::new());
+        assert_eq!(comparison.tasks.len(), 1);
+    }
+}
@@ -241,6 +241,8 @@ pub struct TaskTracker<T> {
     sender: mpsc::Sender<Message>,
     started_at: T,
     task_id: TaskId<'static>,
+    retries: u32,
+    timeouts: u32,
 }
 
 #[derive(Debug, Clone)]
@@ -268,6 +270,16 @@ pub struct TaskExecutionSummary {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
     pub exit_code: Option<i32>,
+    // number of times the task was run again after failing
+    #[serde(skip_serializing_if = "is_zero")]
+    pub retries: u32,
+    // number of runs that were killed for exceeding the task's timeout
+    #[serde(skip_serializing_if = "is_zero")]
+    pub timeouts: u32,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
 }
 
 impl TaskExecutionSummary {
@@ -310,6 +322,8 @@ impl ExecutionTracker {
             sender: self.sender.clone(),
             task_id,
             started_at: (),
+            retries: 0,
+            timeouts: 0,
         }
     }
 
@@ -348,6 +362,8 @@ impl TaskTracker<()> {
             sender,
             started_at,
             task_id,
+            retries: 0,
+            timeouts: 0,
         }
     }
 
@@ -375,11 +391,23 @@ impl TaskTracker<chrono::DateTime<Local>> {
     // internal nxpkg error
     pub fn cancel(self) {}
 
+    // Track that the task failed and is being run again
+    pub fn retried(&mut self) {
+        self.retries += 1;
+    }
+
+    // Track that a run of the task was killed for exceeding its timeout
+    pub fn timed_out(&mut self) {
+        self.timeouts += 1;
+    }
+
     pub async fn cached(self) -> TaskExecutionSummary {
         let Self {
             sender,
             started_at,
             task_id,
+            retries,
+            timeouts,
         } = self;
 
         let ended_at = Local::now();
@@ -389,6 +417,8 @@ impl TaskTracker<chrono::DateTime<Local>> {
             // Go synthesizes a zero exit code on cache hits
             exit_code: Some(0),
             error: None,
+            retries,
+            timeouts,
         };
 
         let state = TaskState {
@@ -410,6 +440,8 @@ impl TaskTracker<chrono::DateTime<Local>> {
             sender,
             started_at,
             task_id,
+            retries,
+            timeouts,
         } = self;
 
         let ended_at = Local::now();
@@ -418,6 +450,8 @@ impl TaskTracker<chrono::DateTime<Local>> {
             end_time: ended_at.timestamp_millis(),
             exit_code: Some(exit_code),
             error: None,
+            retries,
+            timeouts,
         };
 
         let state = TaskState {
@@ -443,6 +477,8 @@ impl TaskTracker<chrono::DateTime<Local>> {
             sender,
             started_at,
             task_id,
+            retries,
+            timeouts,
         } = self;
 
         let ended_at = Local::now();
@@ -451,6 +487,8 @@ impl TaskTracker<chrono::DateTime<Local>> {
             end_time: ended_at.timestamp_millis(),
             exit_code,
             error: Some(error.to_string()),
+            retries,
+            timeouts,
         };
 
         let state = TaskState {
@@ -482,6 +520,7 @@ mod test {
         let bar = TaskId::new("bar", "build");
         let baz = TaskId::new("baz", "build");
         let boo = TaskId::new("boo", "build");
+        let qux = TaskId::new("qux", "build");
         let mut tasks = Vec::new();
         {
             let tracker = summary.task_tracker(foo.clone());
@@ -504,6 +543,15 @@ mod test {
                 tracker.build_failed(Some(1), "big bad error").await;
             }));
         }
+        {
+            let tracker = summary.task_tracker(qux.clone());
+            tasks.push(tokio::spawn(async move {
+                let mut tracker = tracker.start().await;
+                tracker.timed_out();
+                tracker.retried();
+                tracker.build_succeeded(0).await;
+            }));
+        }
         {
             let tracker = summary.task_tracker(boo.clone());
             tasks.push(tokio::spawn(async move {
@@ -516,16 +564,20 @@ mod test {
         }
 
         let state = summary.finish().await.unwrap();
-        assert_eq!(state.attempted, 4);
+        assert_eq!(state.attempted, 5);
         assert_eq!(state.cached, 1);
         assert_eq!(state.failed, 1);
-        assert_eq!(state.success, 1);
+        assert_eq!(state.success, 2);
         let foo_state = state.tasks.iter().find(|task| task.task_id == foo).unwrap();
         assert_eq!(foo_state.execution.as_ref().unwrap().exit_code, Some(0));
         let bar_state = state.tasks.iter().find(|task| task.task_id == bar).unwrap();
         assert_eq!(bar_state.execution.as_ref().unwrap().exit_code, Some(0));
         let baz_state = state.tasks.iter().find(|task| task.task_id == baz).unwrap();
         assert_eq!(baz_state.execution.as_ref().unwrap().exit_code, Some(1));
+        let qux_state = state.tasks.iter().find(|task| task.task_id == qux).unwrap();
+        let qux_execution = qux_state.execution.as_ref().unwrap();
+        assert_eq!(qux_execution.retries, 1);
+        assert_eq!(qux_execution.timeouts, 1);
         let boo_state = state.tasks.iter().find(|task| task.task_id == boo);
         assert!(
             boo_state.is_none(),
@@ -563,7 +615,9 @@ mod test {
             start_time: 123,
             end_time: 234,
             exit_code: Some(0),
-            error: None
+            error: None,
+            retries: 0,
+            timeouts: 0,
         },
         json!({ "startTime": 123, "endTime": 234, "exitCode": 0 })
         ; "success"
@@ -574,10 +628,31 @@ mod test {
             end_time: 234,
             exit_code: Some(1),
             error: Some("cannot find anything".into()),
+            retries: 0,
+            timeouts: 0,
         },
         json!({ "startTime": 123, "endTime": 234, "exitCode": 1, "error": "cannot find anything" })
         ; "failure"
     )]
+    #[test_case(
+        TaskExecutionSummary {
+            start_time: 123,
+            end_time: 234,
+            exit_code: None,
+            error: Some("timed out".into()),
+            retries: 2,
+            timeouts: 3,
+        },
+        json!({
+            "startTime": 123,
+            "endTime": 234,
+            "exitCode": null,
+            "error": "timed out",
+            "retries": 2,
+            "timeouts": 3
+        })
+        ; "retried"
+    )]
     fn test_serialization(value: impl serde::Serialize, expected: serde_json::Value) {
         assert_eq!(serde_json::to_value(value).unwrap(), expected);
     }
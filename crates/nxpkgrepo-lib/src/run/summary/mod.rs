@@ -3,6 +3,7 @@
 //! A tracker tracks the live data and then gets turned into a summary for
 //! displaying it We have this split because the tracker representation is not
 //! exactly what we want to display to the user.
+pub(crate) mod compare;
 #[allow(dead_code)]
 mod execution;
 mod global_hash;
@@ -18,6 +19,7 @@ pub use global_hash::GlobalHashSummary;
 use itertools::Itertools;
 use serde::Serialize;
 pub use spaces::{SpacesTaskClient, SpacesTaskInformation};
+pub use task::TaskSummaryTaskDefinition;
 use svix_ksuid::{Ksuid, KsuidLike};
 use tabwriter::TabWriter;
 use thiserror::Error;
@@ -277,26 +279,20 @@ impl RunTracker {
     ) -> Result<(), Error> {
         let end_time = Local::now();
 
-        let task_factory = TaskSummaryFactory::new(
-            pkg_dep_graph,
-            engine,
-            hash_tracker,
-            env_at_execution_start,
-            run_opts,
-            global_env_mode,
-        );
-
-        let run_summary: RunSummary = self
-            .to_summary(
-                repo_root,
-                package_inference_root,
+        let run_summary = self
+            .summarize(
                 exit_code,
                 end_time,
+                pkg_dep_graph,
+                repo_root,
+                package_inference_root,
                 run_opts,
                 packages,
                 global_hash_summary,
-                global_env_mode.into(),
-                task_factory,
+                global_env_mode,
+                engine,
+                hash_tracker,
+                env_at_execution_start,
             )
             .await?;
 
@@ -305,6 +301,81 @@ impl RunTracker {
             .await
     }
 
+    /// Renders the summary as JSON instead of printing or saving it, in the
+    /// same format as `--dry=json`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn to_json<'a>(
+        self,
+        pkg_dep_graph: &PackageGraph,
+        repo_root: &'a AbsoluteSystemPath,
+        package_inference_root: Option<&AnchoredSystemPath>,
+        run_opts: &RunOpts<'a>,
+        packages: HashSet<WorkspaceName>,
+        global_hash_summary: GlobalHashSummary<'a>,
+        global_env_mode: cli::EnvMode,
+        engine: &'a Engine,
+        hash_tracker: TaskHashTracker,
+        env_at_execution_start: &'a EnvironmentVariableMap,
+    ) -> Result<String, Error> {
+        let mut run_summary = self
+            .summarize(
+                0,
+                Local::now(),
+                pkg_dep_graph,
+                repo_root,
+                package_inference_root,
+                run_opts,
+                packages,
+                global_hash_summary,
+                global_env_mode,
+                engine,
+                hash_tracker,
+                env_at_execution_start,
+            )
+            .await?;
+
+        run_summary.format_json()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn summarize<'a>(
+        self,
+        exit_code: i32,
+        end_time: DateTime<Local>,
+        pkg_dep_graph: &'a PackageGraph,
+        repo_root: &'a AbsoluteSystemPath,
+        package_inference_root: Option<&'a AnchoredSystemPath>,
+        run_opts: &'a RunOpts<'a>,
+        packages: HashSet<WorkspaceName>,
+        global_hash_summary: GlobalHashSummary<'a>,
+        global_env_mode: cli::EnvMode,
+        engine: &'a Engine,
+        hash_tracker: TaskHashTracker,
+        env_at_execution_start: &'a EnvironmentVariableMap,
+    ) -> Result<RunSummary<'a>, Error> {
+        let task_factory = TaskSummaryFactory::new(
+            pkg_dep_graph,
+            engine,
+            hash_tracker,
+            env_at_execution_start,
+            run_opts,
+            global_env_mode,
+        );
+
+        self.to_summary(
+            repo_root,
+            package_inference_root,
+            exit_code,
+            end_time,
+            run_opts,
+            packages,
+            global_hash_summary,
+            global_env_mode.into(),
+            task_factory,
+        )
+        .await
+    }
+
     pub fn track_task(&self, task_id: TaskId<'static>) -> TaskTracker<()> {
         self.execution_tracker.task_tracker(task_id)
     }
@@ -641,6 +712,20 @@ impl<'a> RunSummary<'a> {
             };
 
             cwriteln!(tab_writer, ui, GREY, "  Dependents\t=\t{}", dependents)?;
+            cwriteln!(
+                tab_writer,
+                ui,
+                GREY,
+                "  Weight\t=\t{}",
+                task.shared.resolved_task_definition.weight()
+            )?;
+            cwriteln!(
+                tab_writer,
+                ui,
+                GREY,
+                "  Mutexes\t=\t{}",
+                task.shared.resolved_task_definition.mutexes().join(", ")
+            )?;
             cwriteln!(
                 tab_writer,
                 ui,
@@ -704,6 +789,18 @@ impl<'a> RunSummary<'a> {
                     .as_ref()
                     .map_or_else(String::new, |vars| vars.join(", "))
             )?;
+            cwriteln!(
+                tab_writer,
+                ui,
+                GREY,
+                "  .env Vars\t=\t{}",
+                task.shared
+                    .environment_variables
+                    .dot_env
+                    .iter()
+                    .map(|(name, file)| format!("{name} ({file})"))
+                    .join(", ")
+            )?;
 
             // If there's an error, we can silently ignore it, we don't need to block the
             // entire print.
@@ -2,12 +2,25 @@ use serde::{Deserialize, Serialize};
 use nxpkgpath::AbsoluteSystemPath;
 use nxpkgrepo_ci::Vendor;
 use nxpkgrepo_env::EnvironmentVariableMap;
-use nxpkgrepo_scm::SCM;
+use nxpkgrepo_scm::{VcsKind, SCM};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 enum SCMType {
     Git,
+    Mercurial,
+    Jujutsu,
+}
+
+impl SCMType {
+    // Runs outside of any repository have always been reported as git
+    fn of(kind: Option<VcsKind>) -> Self {
+        match kind {
+            Some(VcsKind::Git) | None => SCMType::Git,
+            Some(VcsKind::Mercurial) => SCMType::Mercurial,
+            Some(VcsKind::Jujutsu) => SCMType::Jujutsu,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -38,9 +51,11 @@ impl SCMState {
             }
         }
 
-        // Fall back to using `git`
+        // Fall back to asking the repository. Opening it can be slow, e. g. jj
+        // snapshots the working copy, so otherwise its kind is only detected.
         if state.branch.is_none() && state.sha.is_none() {
             let scm = SCM::new(dir);
+            state.ty = SCMType::of(scm.kind());
 
             if state.branch.is_none() {
                 state.branch = scm.get_current_branch(dir).ok();
@@ -48,6 +63,8 @@ impl SCMState {
             if state.sha.is_none() {
                 state.sha = scm.get_current_sha(dir).ok();
             }
+        } else {
+            state.ty = SCMType::of(VcsKind::detect(dir));
         }
 
         state
@@ -83,10 +83,22 @@ pub(crate) struct SharedTaskSummary<T> {
     pub env_mode: EnvMode,
     pub environment_variables: TaskEnvVarSummary,
     pub dot_env: Option<Vec<RelativeUnixPathBuf>>,
+    pub hash_normalization: Vec<HashNormalization>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub execution: Option<TaskExecutionSummary>,
 }
 
+// Canonicalizations applied to a task's hash inputs so that it hashes the
+// same on every platform
+#[derive(Debug, Serialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum HashNormalization {
+    // CRLF line endings in input files are hashed as LF
+    LineEndings,
+    // Backslashes in pass through arguments are hashed as forward slashes
+    PathSeparators,
+}
+
 #[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct TaskEnvConfiguration {
@@ -103,6 +115,9 @@ pub struct TaskSummaryTaskDefinition {
     inputs: Vec<String>,
     output_mode: OutputLogsMode,
     persistent: bool,
+    weight: u32,
+    mutexes: Vec<String>,
+    cross_platform: bool,
     env: Vec<String>,
     pass_through_env: Option<Vec<String>>,
     dot_env: Option<Vec<RelativeUnixPathBuf>>,
@@ -116,6 +131,8 @@ pub struct TaskEnvVarSummary {
     pub inferred: Vec<String>,
     #[serde(rename = "passthrough")]
     pub pass_through: Option<Vec<String>>,
+    // The dotEnv file each variable loaded into the task came from
+    pub dot_env: BTreeMap<String, RelativeUnixPathBuf>,
 }
 
 impl TaskCacheSummary {
@@ -169,11 +186,22 @@ impl From<nxpkgrepo_cache::CacheSource> for CacheSource {
     }
 }
 
+impl HashNormalization {
+    pub fn for_task(task_definition: &TaskDefinition) -> Vec<Self> {
+        if task_definition.cross_platform {
+            vec![Self::LineEndings, Self::PathSeparators]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
 impl TaskEnvVarSummary {
     pub fn new(
         task_definition: &TaskDefinition,
         env_vars: DetailedMap,
         env_at_execution_start: &EnvironmentVariableMap,
+        dot_env: BTreeMap<String, RelativeUnixPathBuf>,
     ) -> Result<Self, nxpkgrepo_env::Error> {
         // TODO: this operation differs from the actual env that gets passed in during
         // task execution it should be unified, but first we should copy Go's
@@ -196,6 +224,7 @@ impl TaskEnvVarSummary {
             configured: env_vars.by_source.explicit.to_secret_hashable(),
             inferred: env_vars.by_source.matching.to_secret_hashable(),
             pass_through,
+            dot_env,
         })
     }
 }
@@ -234,6 +263,7 @@ impl From<SharedTaskSummary<TaskId<'static>>> for SharedTaskSummary<String> {
             env_mode,
             environment_variables,
             dot_env,
+            hash_normalization,
             ..
         } = value;
         Self {
@@ -264,6 +294,7 @@ impl From<SharedTaskSummary<TaskId<'static>>> for SharedTaskSummary<String> {
             env_mode,
             environment_variables,
             dot_env,
+            hash_normalization,
         }
     }
 }
@@ -285,6 +316,11 @@ impl From<TaskDefinition> for TaskSummaryTaskDefinition {
             mut inputs,
             output_mode,
             persistent,
+            retries: _,
+            timeout: _,
+            weight,
+            mutexes,
+            cross_platform,
         } = value;
 
         let mut outputs = inclusions;
@@ -316,6 +352,9 @@ impl From<TaskDefinition> for TaskSummaryTaskDefinition {
             inputs,
             output_mode,
             persistent,
+            weight,
+            mutexes,
+            cross_platform,
             env,
             pass_through_env,
             // This should _not_ be sorted.
@@ -324,6 +363,23 @@ impl From<TaskDefinition> for TaskSummaryTaskDefinition {
     }
 }
 
+impl TaskSummaryTaskDefinition {
+    // Reports the weight the task is actually scheduled with, which is capped
+    // at the run's concurrency
+    pub fn with_concurrency(mut self, concurrency: u32) -> Self {
+        self.weight = self.weight.clamp(1, concurrency.max(1));
+        self
+    }
+
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    pub fn mutexes(&self) -> &[String] {
+        &self.mutexes
+    }
+}
+
 #[cfg(test)]
 mod test {
     use serde_json::json;
@@ -366,6 +422,7 @@ mod test {
         TaskSummaryTaskDefinition {
             outputs: vec!["foo".into()],
             cache: true,
+            weight: 1,
             ..Default::default()
         },
         json!({
@@ -375,12 +432,23 @@ mod test {
             "inputs": [],
             "outputMode": "full",
             "persistent": false,
+            "weight": 1,
+            "mutexes": [],
+            "crossPlatform": false,
             "env": [],
             "passThroughEnv": null,
             "dotEnv": null,
         })
         ; "resolved task definition"
     )]
+    #[test_case(
+        HashNormalization::for_task(&TaskDefinition {
+            cross_platform: true,
+            ..Default::default()
+        }),
+        json!(["lineEndings", "pathSeparators"])
+        ; "cross platform hash normalization"
+    )]
     fn test_serialization(value: impl serde::Serialize, expected: serde_json::Value) {
         assert_eq!(serde_json::to_value(value).unwrap(), expected);
     }
@@ -5,7 +5,7 @@ use nxpkgrepo_repository::package_graph::{PackageGraph, WorkspaceInfo, Workspace
 
 use super::{
     execution::TaskExecutionSummary,
-    task::{SharedTaskSummary, TaskEnvVarSummary},
+    task::{HashNormalization, SharedTaskSummary, TaskEnvVarSummary, TaskSummaryTaskDefinition},
     EnvMode, SinglePackageTaskSummary, TaskSummary,
 };
 use crate::{
@@ -180,7 +180,8 @@ impl<'a> TaskSummaryFactory<'a> {
             },
             log_file,
             directory: Some(workspace_info.package_path().to_string()),
-            resolved_task_definition: task_definition.clone().into(),
+            resolved_task_definition: TaskSummaryTaskDefinition::from(task_definition.clone())
+                .with_concurrency(self.run_opts.concurrency),
             expanded_outputs,
             framework,
             dependencies,
@@ -205,9 +206,13 @@ impl<'a> TaskSummaryFactory<'a> {
                 task_definition,
                 env_vars,
                 self.env_at_start,
+                self.hash_tracker
+                    .dot_env_sources(task_id)
+                    .unwrap_or_default(),
             )
             .expect("invalid glob in task definition should have been caught earlier"),
             dot_env: task_definition.dot_env.clone(),
+            hash_normalization: HashNormalization::for_task(task_definition),
             execution,
         })
     }
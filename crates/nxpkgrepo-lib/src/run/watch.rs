@@ -0,0 +1,779 @@
+//! Watch mode
+//!
+//! `nxpkg watch` runs the requested tasks and then keeps the package graph
+//! and engine around, re-running tasks as the daemon reports file changes.
+//! A change only re-runs the tasks whose inputs matched along with the tasks
+//! that depend on them. Changes to package.json files, nxpkg.json files, the
+//! lockfile or global dependencies rebuild everything from scratch.
+//!
+//! Every batch of tasks is executed as its own run, with a [TaskControl] that
+//! tracks each of its tasks. If a change affects tasks that haven't finished
+//! yet, including persistent tasks, only those tasks are cancelled and they
+//! are restarted by the new batch. The other tasks of the batch, e.g.
+//! unrelated dev servers, keep running.
+
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::Local;
+use futures::{stream::FuturesUnordered, Stream, StreamExt};
+use rayon::iter::ParallelBridge;
+use tracing::{debug, error, warn};
+use wax::{Glob, Pattern};
+use nxpkgrepo_api_client::{APIAuth, APIClient};
+use nxpkgrepo_ci::Vendor;
+use nxpkgrepo_env::EnvironmentVariableMap;
+use nxpkgrepo_repository::{
+    package_graph::{PackageGraph, WorkspaceName},
+    package_json::PackageJson,
+};
+use nxpkgrepo_scm::SCM;
+use nxpkgrepo_ui::{cprintln, BOLD_GREY, BOLD_RED, GREY};
+
+use super::{global_dot_env, summary::RunTracker, Error, Run, RunCache};
+use crate::{
+    cli::EnvMode,
+    commands::CommandBase,
+    config::NxpkgJson,
+    daemon::{proto::FileChangesResponse, DaemonClient, DaemonConnector, DaemonError},
+    engine::{Engine, TaskNode},
+    opts::Opts,
+    process::ProcessManager,
+    run::task_id::TaskId,
+    signal::{SignalSubscriber, SubscriberGuard},
+    task_graph::{TaskControl, Visitor},
+    task_hash::PackageInputsHashes,
+};
+
+/// How long to wait for more file changes before re-running tasks. Editors
+/// tend to produce several events for a single save.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Directories that never contain task inputs, but that nxpkg and package
+/// managers write to while tasks run.
+const IGNORED_DIRECTORIES: &[&str] = &[".git", ".nxpkg", "node_modules"];
+
+pub struct WatchClient<'a> {
+    base: &'a CommandBase,
+    run: Run<'a>,
+}
+
+/// Everything that stays resident between re-runs
+struct WatchState<'a> {
+    opts: Opts<'a>,
+    pkg_dep_graph: Arc<PackageGraph>,
+    root_nxpkg_json: NxpkgJson,
+    filtered_pkgs: HashSet<WorkspaceName>,
+    engine: Engine,
+    scm: SCM,
+    run_cache: Arc<RunCache>,
+    env_at_execution_start: EnvironmentVariableMap,
+    global_env_mode: EnvMode,
+    api_client: APIClient,
+    api_auth: Option<APIAuth>,
+    change_mapper: ChangeMapper,
+}
+
+/// A batch of tasks that is currently executing
+struct RunningTasks {
+    tasks: HashSet<TaskId<'static>>,
+    control: TaskControl,
+}
+
+impl RunningTasks {
+    /// Whether the task is part of this batch and hasn't finished yet
+    fn is_pending(&self, task_id: &TaskId) -> bool {
+        self.tasks.contains(task_id) && !self.control.is_done(task_id)
+    }
+}
+
+enum WatchExit {
+    /// The package graph or global configuration changed and needs to be
+    /// rebuilt
+    Rediscover,
+    /// We received a signal and all tasks have been stopped
+    Interrupted(SubscriberGuard),
+}
+
+type RunFuture<'b> = Pin<Box<dyn Future<Output = (usize, Result<i32, Error>)> + 'b>>;
+
+impl<'a> WatchClient<'a> {
+    pub fn new(base: &'a CommandBase) -> Self {
+        Self {
+            base,
+            run: Run::new(base),
+        }
+    }
+
+    pub async fn start(&self, signal_subscriber: SignalSubscriber) -> Result<i32, Error> {
+        let opts = self.run.opts()?;
+        if opts.run_opts.no_daemon {
+            return Err(Error::WatchRequiresDaemon);
+        }
+
+        let connector = DaemonConnector {
+            can_start_server: true,
+            can_kill_server: true,
+            pid_file: self.base.daemon_file_root().join_component("nxpkgd.pid"),
+            sock_file: self.base.daemon_file_root().join_component("nxpkgd.sock"),
+        };
+        let mut daemon = connector.connect().await?;
+        let changes = daemon.file_changes().await?;
+        tokio::pin!(changes);
+
+        let signal = signal_subscriber.listen();
+        tokio::pin!(signal);
+
+        loop {
+            let state = self.prepare(daemon.clone()).await?;
+            match self.watch(&state, &mut changes, signal.as_mut()).await? {
+                WatchExit::Rediscover => {
+                    cprintln!(
+                        self.base.ui,
+                        GREY,
+                        "• Workspace configuration changed, restarting"
+                    );
+                }
+                WatchExit::Interrupted(guard) => {
+                    drop(guard);
+                    return Ok(0);
+                }
+            }
+        }
+    }
+
+    /// Builds the package graph, engine and caches used by every re-run
+    async fn prepare(
+        &self,
+        daemon: DaemonClient<DaemonConnector>,
+    ) -> Result<WatchState<'a>, Error> {
+        let package_json_path = self.base.repo_root.join_component("package.json");
+        let root_package_json = PackageJson::load(&package_json_path)?;
+        let mut opts = self.run.opts()?;
+
+        let api_auth = self.base.api_auth()?;
+        let api_client = self.base.api_client()?;
+
+        let mut pkg_dep_graph =
+            PackageGraph::builder(&self.base.repo_root, root_package_json.clone())
+                .with_single_package_mode(opts.run_opts.single_package)
+                .build()?;
+        pkg_dep_graph.validate()?;
+
+        let root_nxpkg_json = NxpkgJson::load(
+            &self.base.repo_root,
+            &root_package_json,
+            opts.run_opts.single_package,
+        )?;
+        self.run
+            .configure_cache(&mut opts, api_auth.as_ref(), &root_nxpkg_json)?;
+
+        let scm = SCM::new(&self.base.repo_root);
+        let filtered_pkgs =
+            self.run
+                .filtered_packages(&opts, &pkg_dep_graph, &root_nxpkg_json, &scm)?;
+
+        if opts.run_opts.parallel {
+            pkg_dep_graph.remove_workspace_dependencies();
+        }
+        let engine =
+            self.run
+                .build_engine(&pkg_dep_graph, &opts, &root_nxpkg_json, &filtered_pkgs)?;
+        let change_mapper = ChangeMapper::new(&engine, &pkg_dep_graph, &root_nxpkg_json)?;
+
+        let run_cache = self.run.run_cache(
+            &opts,
+            api_client.clone(),
+            api_auth.clone(),
+            None,
+            Some(daemon),
+            false,
+        )?;
+        let global_env_mode = Run::global_env_mode(&opts, &root_nxpkg_json);
+
+        self.run.print_run_prelude(&opts, &filtered_pkgs);
+        cprintln!(self.base.ui, GREY, "• Watching for changes");
+
+        Ok(WatchState {
+            opts,
+            pkg_dep_graph: Arc::new(pkg_dep_graph),
+            root_nxpkg_json,
+            filtered_pkgs,
+            engine,
+            scm,
+            run_cache,
+            env_at_execution_start: EnvironmentVariableMap::infer(),
+            global_env_mode,
+            api_client,
+            api_auth,
+            change_mapper,
+        })
+    }
+
+    async fn watch<'b>(
+        &'b self,
+        state: &'b WatchState<'a>,
+        changes: &mut (impl Stream<Item = Result<FileChangesResponse, DaemonError>> + Unpin),
+        mut signal: Pin<&mut impl Future<Output = SubscriberGuard>>,
+    ) -> Result<WatchExit, Error> {
+        let mut running: HashMap<usize, RunningTasks> = HashMap::new();
+        let mut runs: FuturesUnordered<RunFuture<'b>> = FuturesUnordered::new();
+        let mut next_id = 0;
+
+        let all_tasks = state
+            .engine
+            .task_definitions()
+            .keys()
+            .cloned()
+            .collect::<HashSet<_>>();
+        self.spawn_run(state, all_tasks, &mut next_id, &mut running, &mut runs);
+
+        let exit = loop {
+            tokio::select! {
+                guard = &mut signal => {
+                    break WatchExit::Interrupted(guard);
+                }
+                Some((id, result)) = runs.next(), if !runs.is_empty() => {
+                    // Runs that were stopped have already been removed
+                    if running.remove(&id).is_some() {
+                        match result {
+                            Ok(exit_code) => debug!("watch run {id} exited with {exit_code}"),
+                            Err(err) => {
+                                error!("run failed: {err}");
+                            }
+                        }
+                    }
+                }
+                response = changes.next() => {
+                    let Some(response) = response else {
+                        stop_all(&mut running).await;
+                        return Err(Error::FileChangesClosed);
+                    };
+                    let mut paths = HashSet::new();
+                    let mut lost_events = collect_response(response?, &mut paths);
+                    // Wait for the rest of this burst of changes
+                    while let Ok(response) = tokio::time::timeout(DEBOUNCE, changes.next()).await {
+                        let Some(response) = response else {
+                            break;
+                        };
+                        lost_events |= collect_response(response?, &mut paths);
+                    }
+
+                    if lost_events {
+                        warn!("missed file changes, restarting watch");
+                        break WatchExit::Rediscover;
+                    }
+
+                    let tasks = match state
+                        .change_mapper
+                        .changed_tasks(paths.iter().map(|path| path.as_str()))
+                    {
+                        ChangedTasks::Rediscover => break WatchExit::Rediscover,
+                        ChangedTasks::Tasks(tasks) if tasks.is_empty() => continue,
+                        ChangedTasks::Tasks(tasks) => tasks,
+                    };
+                    let tasks = tasks_to_restart(&state.engine, &tasks, |task_id| {
+                        running.values().any(|run| run.is_pending(task_id))
+                    });
+
+                    // Cancel the affected tasks that haven't finished yet, so that they are
+                    // restarted by the new batch instead of running twice
+                    for run in running.values() {
+                        run.control.cancel(&tasks).await;
+                    }
+
+                    self.spawn_run(state, tasks, &mut next_id, &mut running, &mut runs);
+                }
+            }
+        };
+
+        stop_all(&mut running).await;
+        // Let the stopped runs wrap up before the state they borrow goes away
+        while runs.next().await.is_some() {}
+
+        Ok(exit)
+    }
+
+    fn spawn_run<'b>(
+        &'b self,
+        state: &'b WatchState<'a>,
+        tasks: HashSet<TaskId<'static>>,
+        next_id: &mut usize,
+        running: &mut HashMap<usize, RunningTasks>,
+        runs: &mut FuturesUnordered<RunFuture<'b>>,
+    ) {
+        let id = *next_id;
+        *next_id += 1;
+        let control = TaskControl::default();
+        running.insert(
+            id,
+            RunningTasks {
+                tasks: tasks.clone(),
+                control: control.clone(),
+            },
+        );
+        runs.push(Box::pin(async move {
+            (id, self.run_tasks(state, tasks, control).await)
+        }));
+    }
+
+    async fn run_tasks(
+        &self,
+        state: &WatchState<'a>,
+        tasks: HashSet<TaskId<'static>>,
+        control: TaskControl,
+    ) -> Result<i32, Error> {
+        let start_at = Local::now();
+        let opts = &state.opts;
+        let pkg_dep_graph = &state.pkg_dep_graph;
+        let root_nxpkg_json = &state.root_nxpkg_json;
+        let engine = Arc::new(state.engine.create_engine_for_subgraph(&tasks));
+
+        // The global hash is recalculated for every run since global dependencies
+        // might have changed since the previous one.
+        let root_external_dependencies_hash =
+            Run::root_external_dependencies_hash(opts, pkg_dep_graph);
+        let mut global_hash_inputs = self.run.global_hash_inputs(
+            opts,
+            pkg_dep_graph,
+            root_nxpkg_json,
+            root_external_dependencies_hash.as_deref(),
+            &state.env_at_execution_start,
+        )?;
+        let global_hash = global_hash_inputs.calculate_global_hash_from_inputs();
+
+        let package_inputs_hashes = PackageInputsHashes::calculate_file_hashes(
+            &state.scm,
+            engine.tasks().par_bridge(),
+            pkg_dep_graph.workspaces().collect(),
+            engine.task_definitions(),
+            &self.base.repo_root,
+        )?;
+        let cross_platform_global_hash = global_hash_inputs.calculate_cross_platform_global_hash(
+            &engine,
+            &state.scm,
+            &self.base.repo_root,
+        )?;
+
+        let global_env = Run::global_env(&state.env_at_execution_start, &global_hash_inputs)?;
+
+        let run_tracker = RunTracker::new(
+            start_at,
+            opts.synthesize_command(),
+            opts.scope_opts.pkg_inference_root.as_deref(),
+            &state.env_at_execution_start,
+            &self.base.repo_root,
+            self.base.version(),
+            opts.run_opts.experimental_space_id.clone(),
+            state.api_client.clone(),
+            state.api_auth.clone(),
+            Vendor::get_user(),
+        );
+
+        let mut visitor = Visitor::new(
+            pkg_dep_graph.clone(),
+            state.run_cache.clone(),
+            run_tracker,
+            opts,
+            package_inputs_hashes,
+            &state.env_at_execution_start,
+            &global_hash,
+            cross_platform_global_hash.as_deref(),
+            global_dot_env(root_nxpkg_json),
+            state.global_env_mode,
+            self.base.ui,
+            false,
+            ProcessManager::new(),
+            &self.base.repo_root,
+            global_env,
+        );
+        visitor.task_control(control);
+
+        cprintln!(
+            self.base.ui,
+            BOLD_GREY,
+            "• Running {} tasks",
+            engine.task_definitions().len()
+        );
+        let errors = visitor.visit(engine.clone()).await?;
+
+        let exit_code = errors
+            .iter()
+            .filter_map(|err| err.exit_code())
+            .max()
+            .unwrap_or(if errors.is_empty() { 0 } else { 1 });
+        for err in &errors {
+            cprintln!(self.base.ui, BOLD_RED, "{err}");
+        }
+
+        visitor
+            .finish(
+                exit_code,
+                state.filtered_pkgs.clone(),
+                global_hash_inputs,
+                &engine,
+                &state.env_at_execution_start,
+            )
+            .await?;
+
+        Ok(exit_code)
+    }
+}
+
+async fn stop_all(running: &mut HashMap<usize, RunningTasks>) {
+    for (_, run) in running.drain() {
+        run.control.cancel(&run.tasks).await;
+    }
+}
+
+/// Returns the tasks to run for a change to `changed`: the changed tasks and
+/// their dependents. Dependencies of those tasks that haven't finished yet, as
+/// reported by `is_pending`, are restarted along with them, since the new
+/// batch can't wait for tasks of another batch.
+fn tasks_to_restart(
+    engine: &Engine,
+    changed: &HashSet<TaskId<'static>>,
+    is_pending: impl Fn(&TaskId<'static>) -> bool,
+) -> HashSet<TaskId<'static>> {
+    let mut tasks = engine.tasks_with_dependents(changed.iter());
+    loop {
+        let pending_dependencies = tasks
+            .iter()
+            .flat_map(|task_id| engine.dependencies(task_id).into_iter().flatten())
+            .filter_map(|dependency| match dependency {
+                TaskNode::Task(dependency) if !tasks.contains(dependency) => Some(dependency),
+                _ => None,
+            })
+            .filter(|&dependency| is_pending(dependency))
+            .cloned()
+            .collect::<Vec<_>>();
+        if pending_dependencies.is_empty() {
+            return tasks;
+        }
+        tasks.extend(engine.tasks_with_dependents(pending_dependencies.iter()));
+    }
+}
+
+/// Adds the paths of the response to `paths` and returns if the daemon
+/// dropped any events.
+fn collect_response(response: FileChangesResponse, paths: &mut HashSet<String>) -> bool {
+    paths.extend(response.paths);
+    response.lost_events
+}
+
+#[derive(Debug, PartialEq)]
+enum ChangedTasks {
+    /// The change requires rebuilding the package graph
+    Rediscover,
+    Tasks(HashSet<TaskId<'static>>),
+}
+
+/// The globs of a task, relative to its package directory
+struct TaskGlobs {
+    task_id: TaskId<'static>,
+    inclusions: Vec<Glob<'static>>,
+    exclusions: Vec<Glob<'static>>,
+}
+
+struct PackageGlobs {
+    /// The package directory relative to the repo root with unix separators.
+    /// This is empty for the root package.
+    dir: String,
+    /// Outputs of any task in the package. Changes to outputs are caused by
+    /// running tasks and never trigger a re-run.
+    outputs: Vec<Glob<'static>>,
+    tasks: Vec<TaskGlobs>,
+}
+
+/// Maps changed files to the tasks whose inputs they are part of
+struct ChangeMapper {
+    // Sorted so that nested packages come before the packages containing them
+    packages: Vec<PackageGlobs>,
+    global_deps: Vec<Glob<'static>>,
+    lockfile: String,
+}
+
+impl ChangeMapper {
+    fn new(
+        engine: &Engine,
+        pkg_dep_graph: &PackageGraph,
+        root_nxpkg_json: &NxpkgJson,
+    ) -> Result<Self, Error> {
+        let mut packages: HashMap<WorkspaceName, PackageGlobs> = HashMap::new();
+        for (task_id, definition) in engine.task_definitions() {
+            let workspace = WorkspaceName::from(task_id.package());
+            let Some(dir) = pkg_dep_graph.workspace_dir(&workspace) else {
+                continue;
+            };
+            let package = packages.entry(workspace).or_insert_with(|| PackageGlobs {
+                dir: dir.to_unix().to_string(),
+                outputs: Vec::new(),
+                tasks: Vec::new(),
+            });
+
+            for output in &definition.outputs.inclusions {
+                package.outputs.push(compile_glob(output)?);
+            }
+
+            let mut inclusions = Vec::new();
+            let mut exclusions = Vec::new();
+            for input in &definition.inputs {
+                match input.strip_prefix('!') {
+                    Some(exclusion) => exclusions.push(compile_glob(exclusion)?),
+                    None => inclusions.push(compile_glob(input)?),
+                }
+            }
+            // dotEnv files are hashed as inputs in addition to the inputs globs
+            if !inclusions.is_empty() {
+                for dot_env in definition.dot_env.iter().flatten() {
+                    inclusions.push(compile_glob(dot_env.as_str())?);
+                }
+            }
+
+            package.tasks.push(TaskGlobs {
+                task_id: task_id.clone(),
+                inclusions,
+                exclusions,
+            });
+        }
+
+        let mut packages = packages.into_values().collect::<Vec<_>>();
+        packages.sort_by(|a, b| b.dir.len().cmp(&a.dir.len()));
+
+        let global_deps = root_nxpkg_json
+            .global_deps
+            .iter()
+            .map(|glob| compile_glob(glob))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            packages,
+            global_deps,
+            lockfile: pkg_dep_graph.package_manager().lockfile_name().to_string(),
+        })
+    }
+
+    fn changed_tasks<'b>(&self, paths: impl Iterator<Item = &'b str>) -> ChangedTasks {
+        let mut tasks = HashSet::new();
+        for path in paths {
+            if path
+                .split('/')
+                .any(|segment| IGNORED_DIRECTORIES.contains(&segment))
+            {
+                continue;
+            }
+
+            let file_name = path.rsplit('/').next().unwrap_or(path);
+            if file_name == "package.json"
+                || file_name == "nxpkg.json"
+                || path == self.lockfile
+                || self.global_deps.iter().any(|glob| glob.is_match(path))
+            {
+                return ChangedTasks::Rediscover;
+            }
+
+            let Some((package, relative_path)) = self.package_for_path(path) else {
+                continue;
+            };
+            if package
+                .outputs
+                .iter()
+                .any(|glob| glob.is_match(relative_path))
+            {
+                continue;
+            }
+
+            for task in &package.tasks {
+                let is_input = task.inclusions.is_empty()
+                    || task
+                        .inclusions
+                        .iter()
+                        .any(|glob| glob.is_match(relative_path));
+                let is_excluded = task
+                    .exclusions
+                    .iter()
+                    .any(|glob| glob.is_match(relative_path));
+                if is_input && !is_excluded {
+                    tasks.insert(task.task_id.clone());
+                }
+            }
+        }
+
+        ChangedTasks::Tasks(tasks)
+    }
+
+    fn package_for_path<'b>(&self, path: &'b str) -> Option<(&PackageGlobs, &'b str)> {
+        self.packages.iter().find_map(|package| {
+            if package.dir.is_empty() {
+                return Some((package, path));
+            }
+            let relative_path = path.strip_prefix(&package.dir)?.strip_prefix('/')?;
+            Some((package, relative_path))
+        })
+    }
+}
+
+fn compile_glob(glob: &str) -> Result<Glob<'static>, Error> {
+    Ok(Glob::new(glob.trim_start_matches('/'))?.into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{engine::Building, task_graph::TaskDefinition};
+
+    // ui#build <- web#build <- web#dev, docs#dev is unrelated
+    fn engine() -> Engine {
+        let mut engine = Engine::<Building>::new();
+        let ui_build = TaskId::new("ui", "build");
+        let web_build = TaskId::new("web", "build");
+        let web_dev = TaskId::new("web", "dev");
+        let docs_dev = TaskId::new("docs", "dev");
+        for task in [&ui_build, &web_build, &web_dev, &docs_dev] {
+            engine.add_definition(task.clone(), TaskDefinition::default());
+        }
+        engine.add_dependency(&web_build, &ui_build);
+        engine.add_dependency(&web_dev, &web_build);
+        engine.connect_to_root(&ui_build);
+        engine.connect_to_root(&docs_dev);
+        engine.seal()
+    }
+
+    #[test]
+    fn test_restart_only_affected_tasks() {
+        let engine = engine();
+        // Everything but the dev servers has finished
+        let pending = HashSet::from([TaskId::new("web", "dev"), TaskId::new("docs", "dev")]);
+
+        let tasks = tasks_to_restart(
+            &engine,
+            &HashSet::from([TaskId::new("web", "build")]),
+            |task_id| pending.contains(task_id),
+        );
+
+        assert_eq!(
+            tasks,
+            HashSet::from([TaskId::new("web", "build"), TaskId::new("web", "dev")])
+        );
+    }
+
+    #[test]
+    fn test_restart_pending_dependencies() {
+        let engine = engine();
+        // ui#build is still running, so web#build can't wait for it
+        let pending = HashSet::from([TaskId::new("ui", "build"), TaskId::new("docs", "dev")]);
+
+        let tasks = tasks_to_restart(
+            &engine,
+            &HashSet::from([TaskId::new("web", "dev")]),
+            |task_id| pending.contains(task_id),
+        );
+
+        assert_eq!(
+            tasks,
+            HashSet::from([
+                TaskId::new("ui", "build"),
+                TaskId::new("web", "build"),
+                TaskId::new("web", "dev")
+            ])
+        );
+    }
+
+    fn task_globs(package: &'static str, task: &'static str, inputs: &[&str]) -> TaskGlobs {
+        TaskGlobs {
+            task_id: TaskId::new(package, task),
+            inclusions: inputs
+                .iter()
+                .map(|glob| compile_glob(glob).unwrap())
+                .collect(),
+            exclusions: Vec::new(),
+        }
+    }
+
+    fn mapper() -> ChangeMapper {
+        let mut packages = vec![
+            PackageGlobs {
+                dir: String::new(),
+                outputs: Vec::new(),
+                tasks: vec![task_globs("//", "lint", &[])],
+            },
+            PackageGlobs {
+                dir: "packages/ui".to_string(),
+                outputs: vec![compile_glob("dist/**").unwrap()],
+                tasks: vec![
+                    task_globs("ui", "build", &[]),
+                    task_globs("ui", "test", &["src/**/*.test.ts"]),
+                ],
+            },
+            PackageGlobs {
+                dir: "apps/web".to_string(),
+                outputs: vec![compile_glob(".next/**").unwrap()],
+                tasks: vec![task_globs("web", "build", &[])],
+            },
+        ];
+        packages.sort_by(|a, b| b.dir.len().cmp(&a.dir.len()));
+        ChangeMapper {
+            packages,
+            global_deps: vec![compile_glob(".env").unwrap()],
+            lockfile: "package-lock.json".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_changed_tasks_uses_inputs() {
+        let mapper = mapper();
+        assert_eq!(
+            mapper.changed_tasks(["packages/ui/src/button.tsx"].into_iter()),
+            ChangedTasks::Tasks(HashSet::from([TaskId::new("ui", "build")]))
+        );
+        assert_eq!(
+            mapper.changed_tasks(["packages/ui/src/button.test.ts"].into_iter()),
+            ChangedTasks::Tasks(HashSet::from([
+                TaskId::new("ui", "build"),
+                TaskId::new("ui", "test")
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_changed_tasks_ignores_outputs() {
+        let mapper = mapper();
+        assert_eq!(
+            mapper.changed_tasks(
+                [
+                    "packages/ui/dist/index.js",
+                    "apps/web/.nxpkg/nxpkg-build.log",
+                    "apps/web/node_modules/ui/package.json",
+                ]
+                .into_iter()
+            ),
+            ChangedTasks::Tasks(HashSet::new())
+        );
+    }
+
+    #[test]
+    fn test_changed_tasks_root_package() {
+        let mapper = mapper();
+        assert_eq!(
+            mapper.changed_tasks(["README.md"].into_iter()),
+            ChangedTasks::Tasks(HashSet::from([TaskId::new("//", "lint")]))
+        );
+    }
+
+    #[test]
+    fn test_changed_tasks_rediscover() {
+        let mapper = mapper();
+        for path in ["apps/web/package.json", "package-lock.json", ".env"] {
+            assert_eq!(
+                mapper.changed_tasks([path].into_iter()),
+                ChangedTasks::Rediscover,
+                "{path} should trigger a rediscover"
+            );
+        }
+    }
+}
@@ -1,10 +1,13 @@
 mod visitor;
 
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
 use serde::{Deserialize, Serialize};
 use nxpkgpath::{AnchoredSystemPath, AnchoredSystemPathBuf, RelativeUnixPathBuf};
-pub use visitor::{Error as VisitorError, Visitor};
+pub use visitor::{Error as VisitorError, TaskControl, Visitor};
 
 use crate::{
     cli::OutputLogsMode,
@@ -47,6 +50,11 @@ pub struct TaskDefinitionStable {
     pub(crate) inputs: Vec<String>,
     pub(crate) output_mode: OutputLogsMode,
     pub(crate) persistent: bool,
+    pub(crate) retries: u32,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) weight: u32,
+    pub(crate) mutexes: Vec<String>,
+    pub(crate) cross_platform: bool,
     pub(crate) env: Vec<String>,
     pub(crate) pass_through_env: Option<Vec<String>>,
     pub(crate) dot_env: Option<Vec<RelativeUnixPathBuf>>,
@@ -62,6 +70,11 @@ impl Default for TaskDefinitionStable {
             inputs: Vec::new(),
             output_mode: OutputLogsMode::default(),
             persistent: false,
+            retries: 0,
+            timeout: None,
+            weight: 1,
+            mutexes: Vec::new(),
+            cross_platform: false,
             env: Vec::new(),
             pass_through_env: None,
             dot_env: None,
@@ -104,6 +117,26 @@ pub struct TaskDefinition {
     // Persistent indicates whether the Task is expected to exit or not
     // Tasks marked Persistent do not exit (e.g. --watch mode or dev servers)
     pub persistent: bool,
+
+    // Retries is the number of times a failing Task is run again before the
+    // failure is reported
+    pub(crate) retries: u32,
+
+    // Timeout is how long the Task can run before it is killed. Each retry
+    // gets the full timeout. Persistent Tasks are never killed.
+    pub(crate) timeout: Option<Duration>,
+
+    // Weight is the number of concurrency slots the Task occupies while it
+    // runs. It is capped at the run's concurrency.
+    pub(crate) weight: u32,
+
+    // Mutexes are named locks the Task holds while it runs. Tasks that share
+    // a mutex never run at the same time.
+    pub(crate) mutexes: Vec<String>,
+
+    // CrossPlatform normalizes line endings and path separators in the Task's
+    // hash inputs so the Task gets the same hash on every platform.
+    pub(crate) cross_platform: bool,
 }
 
 impl BookkeepingTaskDefinition {
@@ -150,6 +183,11 @@ impl Default for TaskDefinition {
             inputs: Default::default(),
             output_mode: Default::default(),
             persistent: Default::default(),
+            retries: Default::default(),
+            timeout: Default::default(),
+            weight: 1,
+            mutexes: Default::default(),
+            cross_platform: Default::default(),
             dot_env: Default::default(),
         }
     }
@@ -230,6 +268,13 @@ impl TaskDefinition {
         repo_relative_globs
     }
 
+    // The number of concurrency slots the Task occupies in a run that allows
+    // `concurrency` slots. A Task heavier than the whole run gets all of them
+    // instead of never being scheduled.
+    pub fn effective_weight(&self, concurrency: u32) -> u32 {
+        self.weight.clamp(1, concurrency.max(1))
+    }
+
     // merge accepts a BookkeepingTaskDefinitions and
     // merges it into TaskDefinition. It uses the bookkeeping
     // defined_fields to determine which fields should be overwritten and when
@@ -246,6 +291,11 @@ impl TaskDefinition {
                 inputs,
                 output_mode,
                 persistent,
+                retries,
+                timeout,
+                weight,
+                mutexes,
+                cross_platform,
                 env,
                 pass_through_env,
                 dot_env,
@@ -260,6 +310,11 @@ impl TaskDefinition {
         set_field!(self, meta, inputs, "Inputs");
         set_field!(self, meta, output_mode, "OutputMode");
         set_field!(self, meta, persistent, "Persistent");
+        set_field!(self, meta, retries, "Retries");
+        set_field!(self, meta, timeout, "Timeout");
+        set_field!(self, meta, weight, "Weight");
+        set_field!(self, meta, mutexes, "Mutexes");
+        set_field!(self, meta, cross_platform, "CrossPlatform");
         set_field!(self, meta, env, "Env");
         set_field!(self, meta, pass_through_env, "PassThroughEnv");
         set_field!(self, meta, dot_env, "DotEnv");
@@ -1,12 +1,14 @@
 use std::{
     borrow::Cow,
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     io::Write,
+    path::Path,
     process::Stdio,
     sync::{Arc, Mutex, OnceLock},
     time::Duration,
 };
 
+use chrono::{DateTime, Local};
 use console::{Style, StyledObject};
 use futures::{stream::FuturesUnordered, StreamExt};
 use regex::Regex;
@@ -39,7 +41,9 @@ use crate::{
         task_id::TaskId,
         RunCache, TaskCache,
     },
-    task_hash::{self, PackageInputsHashes, TaskHashTracker, TaskHashTrackerState, TaskHasher},
+    task_hash::{
+        self, GlobalDotEnv, PackageInputsHashes, TaskHashTracker, TaskHashTrackerState, TaskHasher,
+    },
 };
 
 // This holds the whole world
@@ -55,10 +59,83 @@ pub struct Visitor<'a> {
     run_cache: Arc<RunCache>,
     run_tracker: RunTracker,
     sink: OutputSink<StdWriter>,
+    task_control: Option<TaskControl>,
     task_hasher: TaskHasher<'a>,
     ui: UI,
 }
 
+/// Lets watch mode follow and cancel the individual tasks of a run. Every
+/// task gets its own process manager, so stopping one task doesn't affect
+/// the others. Cancelled tasks count as succeeded for the engine, so the
+/// tasks that don't depend on them keep running.
+#[derive(Debug, Clone, Default)]
+pub struct TaskControl(Arc<Mutex<TaskControlState>>);
+
+#[derive(Debug, Default)]
+struct TaskControlState {
+    running: HashMap<TaskId<'static>, ProcessManager>,
+    finished: HashSet<TaskId<'static>>,
+    cancelled: HashSet<TaskId<'static>>,
+}
+
+impl TaskControl {
+    /// Whether the task has run to completion, or has been cancelled
+    pub fn is_done(&self, task_id: &TaskId) -> bool {
+        let state = self.0.lock().expect("lock poisoned");
+        state.finished.contains(task_id) || state.cancelled.contains(task_id)
+    }
+
+    /// Cancels the given tasks, unless they have already finished. Running
+    /// tasks are stopped and tasks that haven't started yet are skipped.
+    pub async fn cancel<'b>(&self, tasks: impl IntoIterator<Item = &'b TaskId<'static>>) {
+        let managers = {
+            let mut state = self.0.lock().expect("lock poisoned");
+            let mut managers = Vec::new();
+            for task_id in tasks {
+                if state.finished.contains(task_id) {
+                    continue;
+                }
+                if let Some(manager) = state.running.get(task_id) {
+                    managers.push(manager.clone());
+                }
+                state.cancelled.insert(task_id.clone());
+            }
+            managers
+        };
+        for manager in managers {
+            manager.stop().await;
+        }
+    }
+
+    /// Returns the process manager for a task that is about to start, or
+    /// `None` if the task was cancelled.
+    fn start(&self, task_id: &TaskId<'static>) -> Option<ProcessManager> {
+        let mut state = self.0.lock().expect("lock poisoned");
+        if state.cancelled.contains(task_id) {
+            return None;
+        }
+        let manager = ProcessManager::new();
+        state.running.insert(task_id.clone(), manager.clone());
+        Some(manager)
+    }
+
+    fn finish(&self, task_id: &TaskId<'static>) {
+        let mut state = self.0.lock().expect("lock poisoned");
+        state.running.remove(task_id);
+        if !state.cancelled.contains(task_id) {
+            state.finished.insert(task_id.clone());
+        }
+    }
+
+    fn is_cancelled(&self, task_id: &TaskId) -> bool {
+        self.0
+            .lock()
+            .expect("lock poisoned")
+            .cancelled
+            .contains(task_id)
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("cannot find package {package_name} for task {task_id}")]
@@ -93,6 +170,8 @@ impl<'a> Visitor<'a> {
         package_inputs_hashes: PackageInputsHashes,
         env_at_execution_start: &'a EnvironmentVariableMap,
         global_hash: &'a str,
+        cross_platform_global_hash: Option<&'a str>,
+        global_dot_env: GlobalDotEnv<'a>,
         global_env_mode: EnvMode,
         ui: UI,
         silent: bool,
@@ -105,6 +184,8 @@ impl<'a> Visitor<'a> {
             opts,
             env_at_execution_start,
             global_hash,
+            cross_platform_global_hash,
+            global_dot_env,
         );
         let sink = Self::sink(opts, silent);
         let color_cache = ColorSelector::default();
@@ -120,6 +201,7 @@ impl<'a> Visitor<'a> {
             run_cache,
             run_tracker,
             sink,
+            task_control: None,
             task_hasher,
             ui,
             global_env,
@@ -202,9 +284,18 @@ impl<'a> Visitor<'a> {
             // We do this calculation earlier than we do in Go due to the `task_hasher`
             // being !Send. In the future we can look at doing this right before
             // task execution instead.
-            let execution_env =
+            let mut execution_env =
                 self.task_hasher
                     .env(&info, task_env_mode, task_definition, &self.global_env)?;
+            // Variables nxpkg was started with take precedence over the ones
+            // from dotEnv files
+            execution_env.union_missing(&self.task_hasher.dot_env(
+                &info,
+                task_env_mode,
+                task_definition,
+                self.repo_root,
+                workspace_info.package_path(),
+            )?);
 
             let task_cache = self.run_cache.task_cache(
                 task_definition,
@@ -233,6 +324,18 @@ impl<'a> Visitor<'a> {
                         continue;
                     }
 
+                    let manager = match &self.task_control {
+                        Some(task_control) => match task_control.start(&info) {
+                            Some(manager) => manager,
+                            None => {
+                                // Cancelled before it started
+                                callback.send(Ok(())).ok();
+                                continue;
+                            }
+                        },
+                        None => self.manager.clone(),
+                    };
+
                     let workspace_directory = self.repo_root.resolve(workspace_info.package_path());
 
                     let mut exec_context = factory.exec_context(
@@ -241,12 +344,14 @@ impl<'a> Visitor<'a> {
                         task_cache,
                         workspace_directory,
                         execution_env,
+                        manager,
                     );
 
                     let output_client = self.output_client(&info);
                     let tracker = self.run_tracker.track_task(info.clone().into_owned());
                     let spaces_client = self.run_tracker.spaces_task_client();
                     let parent_span = Span::current();
+                    let task_control = self.task_control.clone();
 
                     tasks.push(tokio::spawn(async move {
                         exec_context
@@ -258,6 +363,9 @@ impl<'a> Visitor<'a> {
                                 spaces_client,
                             )
                             .await;
+                        if let Some(task_control) = task_control {
+                            task_control.finish(&info);
+                        }
                     }));
                 }
             }
@@ -327,6 +435,45 @@ impl<'a> Visitor<'a> {
             .await?)
     }
 
+    /// Creates the run summary of a dry run and returns it as JSON instead of
+    /// printing it, along with the task hashes it was built from.
+    pub(crate) async fn summary_json(
+        self,
+        packages: HashSet<WorkspaceName>,
+        global_hash_inputs: GlobalHashableInputs<'_>,
+        engine: &Engine,
+        env_at_execution_start: &EnvironmentVariableMap,
+    ) -> Result<(String, TaskHashTrackerState), Error> {
+        let Self {
+            package_graph,
+            opts,
+            repo_root,
+            global_env_mode,
+            task_hasher,
+            ..
+        } = self;
+
+        let global_hash_summary = GlobalHashSummary::try_from(global_hash_inputs)?;
+
+        let summary = self
+            .run_tracker
+            .to_json(
+                &package_graph,
+                repo_root,
+                opts.scope_opts.pkg_inference_root.as_deref(),
+                &opts.run_opts,
+                packages,
+                global_hash_summary,
+                global_env_mode,
+                engine,
+                task_hasher.task_hash_tracker(),
+                env_at_execution_start,
+            )
+            .await?;
+
+        Ok((summary, task_hasher.into_task_hash_tracker_state()))
+    }
+
     fn sink(opts: &Opts, silent: bool) -> OutputSink<StdWriter> {
         let (out, err) = if silent {
             (std::io::sink().into(), std::io::sink().into())
@@ -412,6 +559,12 @@ impl<'a> Visitor<'a> {
     pub fn dry_run(&mut self) {
         self.dry = true;
     }
+
+    /// Runs every task with its own process manager, so that `task_control`
+    /// can cancel them individually.
+    pub fn task_control(&mut self, task_control: TaskControl) {
+        self.task_control = Some(task_control);
+    }
 }
 
 // A tiny enum that allows us to use the same type for stdout and stderr without
@@ -480,6 +633,8 @@ enum TaskErrorCause {
     Spawn { msg: String },
     #[error("command {command} exited ({exit_code})")]
     Exit { command: String, exit_code: i32 },
+    #[error("command {command} timed out after {}s", .timeout.as_secs())]
+    Timeout { command: String, timeout: Duration },
 }
 
 impl TaskError {
@@ -517,6 +672,10 @@ impl TaskErrorCause {
     fn from_execution(command: String, exit_code: i32) -> Self {
         TaskErrorCause::Exit { command, exit_code }
     }
+
+    fn from_timeout(command: String, timeout: Duration) -> Self {
+        TaskErrorCause::Timeout { command, timeout }
+    }
 }
 
 struct ExecContextFactory<'a> {
@@ -548,9 +707,19 @@ impl<'a> ExecContextFactory<'a> {
         task_cache: TaskCache,
         workspace_directory: AbsoluteSystemPathBuf,
         execution_env: EnvironmentVariableMap,
+        manager: ProcessManager,
     ) -> ExecContext {
         let task_id_for_display = self.visitor.display_task_id(&task_id);
         let pass_through_args = self.visitor.opts.run_opts.args_for_task(&task_id);
+        let (retries, timeout) = match self.engine.task_definition(&task_id) {
+            // A persistent task can still get a timeout from a definition it was merged
+            // with, but it isn't expected to exit so it's never killed
+            Some(definition) => (
+                definition.retries,
+                definition.timeout.filter(|_| !definition.persistent),
+            ),
+            None => (0, None),
+        };
         ExecContext {
             engine: self.engine.clone(),
             ui: self.visitor.ui,
@@ -565,12 +734,15 @@ impl<'a> ExecContextFactory<'a> {
             hash_tracker: self.visitor.task_hasher.task_hash_tracker(),
             package_manager: *self.visitor.package_graph.package_manager(),
             workspace_directory,
-            manager: self.manager.clone(),
+            manager,
             task_hash,
             execution_env,
             continue_on_error: self.visitor.opts.run_opts.continue_on_error,
             pass_through_args,
             errors: self.errors.clone(),
+            retries,
+            timeout,
+            task_control: self.visitor.task_control.clone(),
         }
     }
 
@@ -604,6 +776,9 @@ struct ExecContext {
     continue_on_error: bool,
     pass_through_args: Option<Vec<String>>,
     errors: Arc<Mutex<Vec<TaskError>>>,
+    retries: u32,
+    timeout: Option<Duration>,
+    task_control: Option<TaskControl>,
 }
 
 enum ExecOutcome {
@@ -640,8 +815,10 @@ impl ExecContext {
         callback: oneshot::Sender<Result<(), StopExecution>>,
         spaces_client: Option<SpacesTaskClient>,
     ) {
-        let tracker = tracker.start().await;
-        let mut result = self.execute_inner(parent_span_id, &output_client).await;
+        let mut tracker = tracker.start().await;
+        let mut result = self
+            .execute_inner(parent_span_id, &output_client, &mut tracker)
+            .await;
 
         let logs = match output_client.finish() {
             Ok(logs) => logs,
@@ -652,6 +829,18 @@ impl ExecContext {
             }
         };
 
+        // A cancelled task was stopped on purpose and will run again, so it
+        // neither fails nor stops the run
+        if self
+            .task_control
+            .as_ref()
+            .is_some_and(|task_control| task_control.is_cancelled(&self.task_id))
+        {
+            tracker.cancel();
+            callback.send(Ok(())).ok();
+            return;
+        }
+
         match result {
             ExecOutcome::Success(outcome) => {
                 let task_summary = match outcome {
@@ -708,6 +897,7 @@ impl ExecContext {
         &mut self,
         parent_span_id: Option<tracing::Id>,
         output_client: &OutputClient<impl std::io::Write>,
+        tracker: &mut TaskTracker<DateTime<Local>>,
     ) -> ExecOutcome {
         let span = tracing::debug_span!("execute_task", task = %self.task_id.task());
         span.follows_from(parent_span_id);
@@ -741,6 +931,36 @@ impl ExecContext {
             return ExecOutcome::Internal;
         };
 
+        let mut attempt = 0;
+        loop {
+            let can_retry = attempt < self.retries;
+            if let Some(outcome) = self
+                .run_command(
+                    &package_manager_binary,
+                    output_client,
+                    &mut prefixed_ui,
+                    tracker,
+                    can_retry,
+                )
+                .await
+            {
+                return outcome;
+            }
+            attempt += 1;
+            tracker.retried();
+        }
+    }
+
+    // Runs the task's command once. Returns `None` if the command failed and
+    // should be run again.
+    async fn run_command<W: std::io::Write>(
+        &mut self,
+        package_manager_binary: &Path,
+        output_client: &OutputClient<W>,
+        prefixed_ui: &mut PrefixedUI<OutputWriter<'_, W>>,
+        tracker: &mut TaskTracker<DateTime<Local>>,
+        can_retry: bool,
+    ) -> Option<ExecOutcome> {
         let mut cmd = Command::new(package_manager_binary);
         let mut args = vec!["run".to_string(), self.task_id.task().to_string()];
         if let Some(pass_through_args) = &self.pass_through_args {
@@ -769,7 +989,7 @@ impl ExecContext {
             Ok(w) => w,
             Err(e) => {
                 error!("failed to capture outputs for \"{}\": {e}", self.task_id);
-                return ExecOutcome::Internal;
+                return Some(ExecOutcome::Internal);
             }
         };
 
@@ -784,32 +1004,58 @@ impl ExecContext {
                     .lock()
                     .expect("lock poisoned")
                     .push(TaskError::from_spawn(self.task_id_for_display.clone(), e));
-                return ExecOutcome::Task {
+                return Some(ExecOutcome::Task {
                     exit_code: None,
                     message: error_string,
-                };
+                });
             }
             // Nxpkg is shutting down
             None => {
-                return ExecOutcome::Internal;
+                return Some(ExecOutcome::Internal);
             }
         };
 
-        let exit_status = match process
-            .wait_with_piped_outputs(&mut stdout_writer, None)
+        let wait_result = match self.timeout {
+            Some(timeout) => match tokio::time::timeout(
+                timeout,
+                process.wait_with_piped_outputs(&mut stdout_writer, None),
+            )
             .await
-        {
+            {
+                Ok(wait_result) => wait_result,
+                Err(_) => {
+                    // The command is hung, kill it rather than letting it block the run
+                    process.kill().await;
+                    tracker.timed_out();
+                    if let Err(e) = stdout_writer.flush() {
+                        error!("error flushing logs: {e}");
+                    }
+                    if let Err(e) = self.task_cache.on_error(prefixed_ui) {
+                        error!("error reading logs: {e}");
+                    }
+                    let error = TaskErrorCause::from_timeout(process.label().to_string(), timeout);
+                    return self.task_failed(prefixed_ui, error, None, can_retry);
+                }
+            },
+            None => {
+                process
+                    .wait_with_piped_outputs(&mut stdout_writer, None)
+                    .await
+            }
+        };
+
+        let exit_status = match wait_result {
             Ok(Some(exit_status)) => exit_status,
             Err(e) => {
                 error!("unable to pipe outputs from command: {e}");
-                return ExecOutcome::Internal;
+                return Some(ExecOutcome::Internal);
             }
             Ok(None) => {
                 // TODO: how can this happen? we only update the
                 // exit status with Some and it is only initialized with
                 // None. Is it still running?
                 error!("unable to determine why child exited");
-                return ExecOutcome::Internal;
+                return Some(ExecOutcome::Internal);
             }
         };
 
@@ -819,7 +1065,7 @@ impl ExecContext {
                     error!("{e}");
                 } else if let Err(e) = self
                     .task_cache
-                    .save_outputs(&mut prefixed_ui, Duration::from_secs(1))
+                    .save_outputs(prefixed_ui, Duration::from_secs(1))
                     .await
                 {
                     error!("error caching output: {e}");
@@ -830,38 +1076,51 @@ impl ExecContext {
                     );
                 }
 
-                ExecOutcome::Success(SuccessOutcome::Run)
+                Some(ExecOutcome::Success(SuccessOutcome::Run))
             }
             ChildExit::Finished(Some(code)) => {
                 // If there was an error, flush the buffered output
                 if let Err(e) = stdout_writer.flush() {
                     error!("error flushing logs: {e}");
                 }
-                if let Err(e) = self.task_cache.on_error(&mut prefixed_ui) {
+                if let Err(e) = self.task_cache.on_error(prefixed_ui) {
                     error!("error reading logs: {e}");
                 }
                 let error = TaskErrorCause::from_execution(process.label().to_string(), code);
-                let message = error.to_string();
-                if self.continue_on_error {
-                    prefixed_ui.warn("command finished with error, but continuing...");
-                } else {
-                    prefixed_ui.error(format!("command finished with error: {error}"));
-                }
-                self.errors.lock().expect("lock poisoned").push(TaskError {
-                    task_id: self.task_id_for_display.clone(),
-                    cause: error,
-                });
-                ExecOutcome::Task {
-                    exit_code: Some(code),
-                    message,
-                }
+                self.task_failed(prefixed_ui, error, Some(code), can_retry)
             }
             // All of these indicate a failure where we don't know how to recover
             ChildExit::Finished(None)
             | ChildExit::Killed
             | ChildExit::KilledExternal
-            | ChildExit::Failed => ExecOutcome::Internal,
+            | ChildExit::Failed => Some(ExecOutcome::Internal),
+        }
+    }
+
+    // Reports a failed run of the task's command, unless it will be retried
+    fn task_failed(
+        &self,
+        prefixed_ui: &mut PrefixedUI<impl std::io::Write>,
+        error: TaskErrorCause,
+        exit_code: Option<i32>,
+        can_retry: bool,
+    ) -> Option<ExecOutcome> {
+        if can_retry {
+            prefixed_ui.warn(format!("command finished with error, retrying: {error}"));
+            return None;
+        }
+
+        let message = error.to_string();
+        if self.continue_on_error {
+            prefixed_ui.warn("command finished with error, but continuing...");
+        } else {
+            prefixed_ui.error(format!("command finished with error: {error}"));
         }
+        self.errors.lock().expect("lock poisoned").push(TaskError {
+            task_id: self.task_id_for_display.clone(),
+            cause: error,
+        });
+        Some(ExecOutcome::Task { exit_code, message })
     }
 
     fn spaces_task_info(
@@ -901,3 +1160,191 @@ impl DryRunExecContext {
         tracker.dry_run().await;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use nxpkgpath::AnchoredSystemPathBuf;
+    use nxpkgrepo_api_client::APIClient;
+    use nxpkgrepo_cache::{AsyncCache, CacheOpts};
+    use nxpkgrepo_repository::package_graph::WorkspaceInfo;
+    use nxpkgrepo_ui::OutputClientBehavior;
+
+    use super::*;
+    use crate::{opts::RunCacheOpts, task_graph::TaskDefinition};
+
+    // Scripts are run by npm with node, so tests that run them are skipped
+    // where those aren't installed
+    fn has_node_and_npm() -> bool {
+        let available = which("node").is_ok() && which("npm").is_ok();
+        if !available {
+            eprintln!("skipping test: node and npm are required to run scripts");
+        }
+        available
+    }
+
+    // Runs the `build` script of a package with the given contents as the
+    // script, returning the outcome and the execution summary of the task
+    async fn run_build(
+        script: &str,
+        retries: u32,
+        timeout: Option<Duration>,
+    ) -> (ExecOutcome, TaskExecutionSummary) {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo_root = AbsoluteSystemPathBuf::try_from(tmp.path()).unwrap();
+        repo_root
+            .join_component("build.js")
+            .create_with_contents(script)
+            .unwrap();
+        repo_root
+            .join_component("package.json")
+            .create_with_contents(r#"{ "name": "test", "scripts": { "build": "node build.js" } }"#)
+            .unwrap();
+
+        let task_id = TaskId::new("test", "build").into_owned();
+        let api_client = APIClient::new("http://localhost:3000", 2, "", false).unwrap();
+        let cache_opts = CacheOpts {
+            skip_remote: true,
+            skip_filesystem: true,
+            workers: 1,
+            ..Default::default()
+        };
+        let cache =
+            AsyncCache::new(&cache_opts, &repo_root, api_client.clone(), None, None).unwrap();
+        let run_cache = Arc::new(RunCache::new(
+            cache,
+            &repo_root,
+            &RunCacheOpts::default(),
+            ColorSelector::default(),
+            None,
+            UI::new(true),
+            false,
+        ));
+        let task_definition = TaskDefinition {
+            cache: false,
+            ..Default::default()
+        };
+        let workspace_info = WorkspaceInfo {
+            package_json_path: AnchoredSystemPathBuf::from_raw("package.json").unwrap(),
+            ..Default::default()
+        };
+        let task_cache =
+            run_cache.task_cache(&task_definition, &workspace_info, task_id.clone(), "hash");
+
+        let mut exec_context = ExecContext {
+            engine: Arc::new(Engine::new().seal()),
+            ui: UI::new(true),
+            is_github_actions: false,
+            pretty_prefix: Style::new().apply_to("test:build".to_string()),
+            task_id: task_id.clone(),
+            task_id_for_display: task_id.to_string(),
+            task_cache,
+            hash_tracker: TaskHashTracker::default(),
+            package_manager: PackageManager::Npm,
+            workspace_directory: repo_root.clone(),
+            manager: ProcessManager::new(),
+            task_hash: "hash".to_string(),
+            execution_env: EnvironmentVariableMap::infer(),
+            continue_on_error: false,
+            pass_through_args: None,
+            errors: Default::default(),
+            retries,
+            timeout,
+            task_control: None,
+        };
+
+        let run_tracker = RunTracker::new(
+            Local::now(),
+            "nxpkg run build".to_string(),
+            None,
+            &EnvironmentVariableMap::default(),
+            &repo_root,
+            "",
+            None,
+            api_client,
+            None,
+            String::new(),
+        );
+        let mut tracker = run_tracker.track_task(task_id).start().await;
+        let output_client = OutputSink::new(std::io::sink(), std::io::sink())
+            .logger(OutputClientBehavior::Passthrough);
+
+        let outcome = exec_context
+            .execute_inner(None, &output_client, &mut tracker)
+            .await;
+        let summary = tracker.build_failed(None, "").await;
+        (outcome, summary)
+    }
+
+    #[tokio::test]
+    async fn test_retries() {
+        if !has_node_and_npm() {
+            return;
+        }
+        // Fails the first time it's run
+        let script = r#"
+            const fs = require("fs");
+            if (!fs.existsSync("attempted")) {
+                fs.writeFileSync("attempted", "");
+                process.exit(1);
+            }
+        "#;
+
+        let (outcome, summary) = run_build(script, 1, None).await;
+        assert!(matches!(outcome, ExecOutcome::Success(SuccessOutcome::Run)));
+        assert_eq!(summary.retries, 1);
+        assert_eq!(summary.timeouts, 0);
+
+        let (outcome, summary) = run_build("process.exit(2);", 2, None).await;
+        assert!(matches!(
+            outcome,
+            ExecOutcome::Task {
+                exit_code: Some(2),
+                ..
+            }
+        ));
+        assert_eq!(summary.retries, 2);
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        if !has_node_and_npm() {
+            return;
+        }
+        let script = "setTimeout(() => {}, 10000);";
+
+        let (outcome, summary) = run_build(script, 1, Some(Duration::from_millis(500))).await;
+        assert!(matches!(
+            outcome,
+            ExecOutcome::Task {
+                exit_code: None,
+                ..
+            }
+        ));
+        // Each attempt is killed once it runs for longer than the timeout
+        assert_eq!(summary.retries, 1);
+        assert_eq!(summary.timeouts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_task_control() {
+        let control = TaskControl::default();
+        let finished = TaskId::new("a", "build");
+        let running = TaskId::new("b", "dev");
+        let pending = TaskId::new("c", "build");
+
+        control.start(&finished).unwrap();
+        control.finish(&finished);
+        control.start(&running).unwrap();
+
+        control.cancel([&finished, &running, &pending]).await;
+
+        // Finished tasks aren't affected by cancelling them
+        assert!(control.is_done(&finished));
+        assert!(!control.is_cancelled(&finished));
+        assert!(control.is_cancelled(&running));
+        // A cancelled task doesn't start
+        assert!(control.start(&pending).is_none());
+        control.finish(&running);
+        assert!(control.is_done(&running));
+    }
+}
@@ -1,5 +1,7 @@
 use std::{
-    collections::{HashMap, HashSet},
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    io,
     sync::{Arc, Mutex},
 };
 
@@ -7,9 +9,14 @@ use rayon::prelude::*;
 use serde::Serialize;
 use thiserror::Error;
 use tracing::{debug, Span};
-use nxpkgpath::{AbsoluteSystemPath, AnchoredSystemPath, AnchoredSystemPathBuf};
+use nxpkgpath::{
+    AbsoluteSystemPath, AbsoluteSystemPathBuf, AnchoredSystemPath, AnchoredSystemPathBuf,
+    RelativeUnixPathBuf,
+};
 use nxpkgrepo_cache::CacheHitMetadata;
-use nxpkgrepo_env::{BySource, DetailedMap, EnvironmentVariableMap, ResolvedEnvMode};
+use nxpkgrepo_env::{
+    parse_dot_env, BySource, DetailedMap, EnvironmentVariableMap, ResolvedEnvMode,
+};
 use nxpkgrepo_repository::package_graph::{WorkspaceInfo, WorkspaceName};
 use nxpkgrepo_scm::SCM;
 
@@ -44,6 +51,10 @@ pub enum Error {
     Regex(#[from] regex::Error),
     #[error(transparent)]
     Path(#[from] nxpkgpath::PathError),
+    #[error("failed to read {1}: {0}")]
+    DotEnvRead(#[source] io::Error, AbsoluteSystemPathBuf),
+    #[error("failed to parse {1}: {0}")]
+    DotEnvParse(#[source] nxpkgrepo_env::Error, AbsoluteSystemPathBuf),
 }
 
 impl TaskHashable<'_> {
@@ -132,6 +143,15 @@ impl PackageInputsHashes {
                     }
                 }
 
+                if task_definition.cross_platform {
+                    hash_object = match scm
+                        .normalize_line_endings(&repo_root.resolve(package_path), hash_object)
+                    {
+                        Ok(hash_object) => hash_object,
+                        Err(err) => return Some(Err(err.into())),
+                    };
+                }
+
                 let file_hashes = FileHashes(hash_object);
                 let hash = file_hashes.clone().hash();
 
@@ -167,6 +187,17 @@ pub struct TaskHashTrackerState {
     package_task_cache: HashMap<TaskId<'static>, CacheHitMetadata>,
     #[serde(skip)]
     package_task_inputs_expanded_hashes: HashMap<TaskId<'static>, FileHashes>,
+    #[serde(skip)]
+    package_task_dot_env_sources: HashMap<TaskId<'static>, BTreeMap<String, RelativeUnixPathBuf>>,
+}
+
+/// The root `globalDotEnv` files, along with the global env patterns that
+/// decide which of their variables a task sees in strict mode.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GlobalDotEnv<'a> {
+    pub files: &'a [RelativeUnixPathBuf],
+    pub env: &'a [String],
+    pub pass_through_env: &'a [String],
 }
 
 /// Caches package-inputs hashes, and package-task hashes.
@@ -175,6 +206,10 @@ pub struct TaskHasher<'a> {
     opts: &'a Opts<'a>,
     env_at_execution_start: &'a EnvironmentVariableMap,
     global_hash: &'a str,
+    // Global hash with line endings normalized, used by cross-platform tasks.
+    // Only calculated when at least one task is cross-platform.
+    cross_platform_global_hash: Option<&'a str>,
+    global_dot_env: GlobalDotEnv<'a>,
     task_hash_tracker: TaskHashTracker,
 }
 
@@ -184,6 +219,8 @@ impl<'a> TaskHasher<'a> {
         opts: &'a Opts,
         env_at_execution_start: &'a EnvironmentVariableMap,
         global_hash: &'a str,
+        cross_platform_global_hash: Option<&'a str>,
+        global_dot_env: GlobalDotEnv<'a>,
     ) -> Self {
         let PackageInputsHashes {
             hashes,
@@ -194,6 +231,8 @@ impl<'a> TaskHasher<'a> {
             opts,
             env_at_execution_start,
             global_hash,
+            cross_platform_global_hash,
+            global_dot_env,
             task_hash_tracker: TaskHashTracker::new(expanded_hashes),
         }
     }
@@ -307,8 +346,23 @@ impl<'a> TaskHasher<'a> {
         // We wrap in an Option to mimic Go's serialization of nullable values
         let optional_package_dir = (!is_root_package).then_some(package_dir);
 
+        let pass_through_args: Cow<[String]> = if task_definition.cross_platform {
+            self.opts
+                .run_opts
+                .pass_through_args
+                .iter()
+                .map(|arg| arg.replace('\\', "/"))
+                .collect()
+        } else {
+            Cow::Borrowed(self.opts.run_opts.pass_through_args)
+        };
+        let global_hash = match self.cross_platform_global_hash {
+            Some(global_hash) if task_definition.cross_platform => global_hash,
+            _ => self.global_hash,
+        };
+
         let task_hashable = TaskHashable {
-            global_hash: self.global_hash,
+            global_hash,
             task_dependency_hashes,
             package_dir: optional_package_dir,
             hash_of_files,
@@ -316,7 +370,7 @@ impl<'a> TaskHasher<'a> {
             task: task_id.task(),
             outputs,
 
-            pass_through_args: self.opts.run_opts.pass_through_args,
+            pass_through_args: &pass_through_args,
             env: &task_definition.env,
             resolved_env_vars: hashable_env_pairs,
             pass_through_env: task_definition
@@ -382,6 +436,79 @@ impl<'a> TaskHasher<'a> {
         self.task_hash_tracker.clone()
     }
 
+    /// Loads the variables defined in the root `globalDotEnv` files and the
+    /// task's `dotEnv` files. Files listed first take precedence within each
+    /// list, and the task's files take precedence over the global ones.
+    /// Missing files are skipped. In strict mode only variables the task is
+    /// allowed to see are returned.
+    pub fn dot_env(
+        &self,
+        task_id: &TaskId<'static>,
+        task_env_mode: ResolvedEnvMode,
+        task_definition: &TaskDefinition,
+        repo_root: &AbsoluteSystemPath,
+        package_path: &AnchoredSystemPath,
+    ) -> Result<EnvironmentVariableMap, Error> {
+        let global_files = self
+            .global_dot_env
+            .files
+            .iter()
+            .rev()
+            .map(|file| file.to_anchored_system_path_buf());
+        let task_files = task_definition.dot_env.iter().flatten().rev().map(|file| {
+            package_path
+                .to_owned()
+                .join(&file.to_anchored_system_path_buf())
+        });
+
+        let mut vars = EnvironmentVariableMap::default();
+        let mut sources = BTreeMap::new();
+        // Loaded from lowest to highest precedence, so later files override
+        // earlier ones and can reference their variables
+        for file in global_files.chain(task_files) {
+            let path = repo_root.resolve(&file);
+            let contents = match path.read_to_string() {
+                Ok(contents) => contents,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(Error::DotEnvRead(e, path)),
+            };
+            let parsed = parse_dot_env(
+                &contents,
+                |name| self.env_at_execution_start.get(name).cloned(),
+                |name| vars.get(name).cloned(),
+            )
+            .map_err(|e| Error::DotEnvParse(e, path.clone()))?;
+
+            let source = file.to_unix();
+            for (name, value) in parsed {
+                sources.insert(name.clone(), source.clone());
+                vars.insert(name, value);
+            }
+        }
+
+        if matches!(task_env_mode, ResolvedEnvMode::Strict) {
+            let mut allowed = EnvironmentVariableMap::default();
+            for patterns in [
+                task_definition.env.as_slice(),
+                task_definition
+                    .pass_through_env
+                    .as_deref()
+                    .unwrap_or_default(),
+                self.global_dot_env.env,
+                self.global_dot_env.pass_through_env,
+            ] {
+                allowed.union(&vars.from_wildcards(patterns)?);
+            }
+            sources.retain(|name, _| allowed.contains_key(name));
+            vars = allowed;
+        }
+
+        self.task_hash_tracker
+            .insert_dot_env_sources(task_id.clone(), sources);
+
+        Ok(vars)
+    }
+
     pub fn env(
         &self,
         task_id: &TaskId,
@@ -446,6 +573,14 @@ pub fn get_external_deps_hash(
     LockFilePackages(transitive_deps).hash()
 }
 
+impl TaskHashTrackerState {
+    pub fn hash(&self, task_id: &TaskId) -> Option<&str> {
+        self.package_task_hashes
+            .get(task_id)
+            .map(|hash| hash.as_str())
+    }
+}
+
 impl TaskHashTracker {
     pub fn new(input_expanded_hashes: HashMap<TaskId<'static>, FileHashes>) -> Self {
         Self {
@@ -485,6 +620,23 @@ impl TaskHashTracker {
         state.package_task_env_vars.get(task_id).cloned()
     }
 
+    fn insert_dot_env_sources(
+        &self,
+        task_id: TaskId<'static>,
+        sources: BTreeMap<String, RelativeUnixPathBuf>,
+    ) {
+        let mut state = self.state.lock().expect("hash tracker mutex poisoned");
+        state.package_task_dot_env_sources.insert(task_id, sources);
+    }
+
+    pub fn dot_env_sources(
+        &self,
+        task_id: &TaskId,
+    ) -> Option<BTreeMap<String, RelativeUnixPathBuf>> {
+        let state = self.state.lock().expect("hash tracker mutex poisoned");
+        state.package_task_dot_env_sources.get(task_id).cloned()
+    }
+
     pub fn framework(&self, task_id: &TaskId) -> Option<String> {
         let state = self.state.lock().expect("hash tracker mutex poisoned");
         state.package_task_framework.get(task_id).cloned()
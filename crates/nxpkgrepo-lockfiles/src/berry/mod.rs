@@ -84,8 +84,11 @@ struct BerryPackage {
     conditions: Option<String>,
 }
 
+// Plug'n'Play installs rely on these flags, so they're written back exactly as
+// they were read, including the ones set to false
 #[derive(Debug, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
 struct DependencyMeta {
+    built: Option<bool>,
     optional: Option<bool>,
     unplugged: Option<bool>,
 }
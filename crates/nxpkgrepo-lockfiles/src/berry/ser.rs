@@ -147,28 +147,28 @@ where
     let mut string = String::new();
     let mut first = true;
 
-    let mut add_line = |dependency: &str, field: &str| {
+    for (dependency, meta) in metadata {
+        let fields = [
+            ("built", meta.built),
+            ("optional", meta.optional),
+            ("unplugged", meta.unplugged),
+        ];
+        // All of a dependency's flags are listed under a single key
+        let mut entry = String::new();
+        for (field, value) in fields {
+            if let Some(value) = value {
+                entry.push_str(&format!("\n      {field}: {value}"));
+            }
+        }
+        if entry.is_empty() {
+            continue;
+        }
+
         if !first {
             string.push('\n');
         }
-
-        string.push_str(&format!(
-            "    {}:\n      {}: true",
-            wrap_string(dependency),
-            wrap_string(field)
-        ));
-
+        string.push_str(&format!("    {}:{entry}", wrap_string(dependency.as_ref())));
         first = false;
-    };
-
-    for (dependency, meta) in metadata {
-        let dependency = dependency.as_ref();
-        if meta.optional.unwrap_or_default() {
-            add_line(dependency, "optional");
-        }
-        if meta.unplugged.unwrap_or_default() {
-            add_line(dependency, "unplugged");
-        }
     }
 
     string
@@ -218,6 +218,48 @@ mod test {
         assert("@babel/core", "\"@babel/core\"");
     }
 
+    #[test]
+    fn test_dependencies_meta_display() {
+        let package = BerryPackage {
+            version: "0.0.0-use.local".into(),
+            resolution: "web@workspace:apps/web".into(),
+            dependencies_meta: Some(
+                [
+                    (
+                        "esbuild".to_string(),
+                        DependencyMeta {
+                            built: Some(false),
+                            optional: None,
+                            unplugged: Some(true),
+                        },
+                    ),
+                    (
+                        "fsevents".to_string(),
+                        DependencyMeta {
+                            built: None,
+                            optional: Some(true),
+                            unplugged: None,
+                        },
+                    ),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+            ..Default::default()
+        };
+        assert_eq!(
+            package.to_string(),
+            "  version: 0.0.0-use.local
+  resolution: \"web@workspace:apps/web\"
+  dependenciesMeta:
+    esbuild:
+      built: false
+      unplugged: true
+    fsevents:
+      optional: true"
+        );
+    }
+
     #[test]
     fn test_long_key_gets_wrapped() {
         let long_key = "a".repeat(1025);
@@ -1,40 +1,49 @@
-use std::{any::Any, str::FromStr};
+use std::{any::Any, collections::HashMap, fmt, iter, str::FromStr};
 
-use serde::Deserialize;
+use serde_json::{value::RawValue, Value};
 
-use crate::Lockfile;
-
-mod de;
+use crate::{Lockfile, Package};
 
 type Map<K, V> = std::collections::BTreeMap<K, V>;
 
+const INDENT: &str = "  ";
+// Only dependencies that get installed alongside a package are followed, the
+// fields are listed in the order bun writes them.
+const DEPENDENCY_FIELDS: &[&str] = &["dependencies", "optionalDependencies", "peerDependencies"];
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
-    #[error("unable to parse: {0}")]
-    SymlParse(String),
-    #[error("unable to convert to structured syml: {0}")]
-    SymlStructure(#[from] serde_json::Error),
-    #[error("unexpected non-utf8 yarn.lock")]
+    #[error("unable to parse bun.lock: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("unexpected non-utf8 bun.lock")]
     NonUTF8(#[from] std::str::Utf8Error),
-    #[error("Nxpkgrepo cannot serialize Bun lockfiles.")]
-    NotImplemented(),
+    #[error("bun.lock is missing its lockfileVersion")]
+    MissingVersion,
+    #[error("invalid bun.lock entry for '{0}'")]
+    InvalidPackage(String),
 }
 
+/// A text `bun.lock`.
+///
+/// Entries in `packages` are keyed by their install path, e.g. `react` for a
+/// hoisted package, `web/react` for a copy nested under the `web` workspace
+/// and `loose-envify/js-tokens` for one nested under another package.
 pub struct BunLockfile {
-    inner: Map<String, Entry>,
+    lockfile_version: Box<RawValue>,
+    // Workspace directory -> contents, the root workspace has an empty path
+    workspaces: Map<String, Map<String, Value>>,
+    packages: Map<String, Entry>,
+    // Top level fields we don't need to understand e.g. overrides or
+    // trustedDependencies
+    other: Map<String, Value>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[derive(Debug, Clone)]
 struct Entry {
-    name: Option<String>,
-    version: String,
-    uid: Option<String>,
-    resolved: Option<String>,
-    integrity: Option<String>,
-    registry: Option<String>,
-    dependencies: Option<Map<String, String>>,
-    optional_dependencies: Option<Map<String, String>>,
+    // Kept as written so entries survive pruning untouched
+    raw: Box<RawValue>,
+    ident: String,
+    dependencies: Map<String, String>,
 }
 
 impl BunLockfile {
@@ -42,118 +51,421 @@ impl BunLockfile {
         let input = std::str::from_utf8(input).map_err(Error::from)?;
         Self::from_str(input)
     }
+
+    fn workspace_name(&self, workspace_path: &str) -> Result<Option<&str>, crate::Error> {
+        let workspace = self
+            .workspaces
+            .get(workspace_path)
+            .ok_or_else(|| crate::Error::MissingWorkspace(workspace_path.to_string()))?;
+        Ok(workspace.get("name").and_then(Value::as_str))
+    }
+
+    fn package(&self, key: String) -> Option<Package> {
+        let entry = self.packages.get(&key)?;
+        Some(Package {
+            version: entry.version().to_string(),
+            key,
+        })
+    }
 }
 
 impl FromStr for BunLockfile {
     type Err = super::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let value = de::parse_syml(s)?;
-        let inner = serde_json::from_value(value)?;
-        Ok(Self { inner })
+        let json = strip_trailing_commas(s);
+        let mut document: Map<String, Box<RawValue>> =
+            serde_json::from_str(&json).map_err(Error::from)?;
+
+        let lockfile_version = document
+            .remove("lockfileVersion")
+            .ok_or(Error::MissingVersion)?;
+        let workspaces = document
+            .remove("workspaces")
+            .map(|raw| serde_json::from_str(raw.get()))
+            .transpose()
+            .map_err(Error::from)?
+            .unwrap_or_default();
+        let packages = document
+            .remove("packages")
+            .map(|raw| serde_json::from_str::<Map<String, Box<RawValue>>>(raw.get()))
+            .transpose()
+            .map_err(Error::from)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, raw)| {
+                let entry = Entry::new(&key, raw)?;
+                Ok((key, entry))
+            })
+            .collect::<Result<_, Error>>()?;
+        let other = document
+            .into_iter()
+            .map(|(key, raw)| Ok((key, serde_json::from_str(raw.get())?)))
+            .collect::<Result<_, serde_json::Error>>()
+            .map_err(Error::from)?;
+
+        Ok(Self {
+            lockfile_version,
+            workspaces,
+            packages,
+            other,
+        })
+    }
+}
+
+impl Entry {
+    fn new(key: &str, raw: Box<RawValue>) -> Result<Self, Error> {
+        let invalid = || Error::InvalidPackage(key.to_string());
+        // Entries are tuples of the package identifier followed by the
+        // resolution specific fields, the package info is the only object.
+        let fields: Vec<Value> = serde_json::from_str(raw.get()).map_err(|_| invalid())?;
+        let ident = fields
+            .first()
+            .and_then(Value::as_str)
+            .ok_or_else(invalid)?
+            .to_string();
+        let dependencies = fields
+            .iter()
+            .find_map(Value::as_object)
+            .into_iter()
+            .flat_map(|info| {
+                DEPENDENCY_FIELDS
+                    .iter()
+                    .filter_map(|field| info.get(*field)?.as_object())
+                    .flatten()
+            })
+            .map(|(name, range)| {
+                let range = range.as_str().ok_or_else(invalid)?;
+                Ok((name.clone(), range.to_string()))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Self {
+            raw,
+            ident,
+            dependencies,
+        })
+    }
+
+    // The identifier is `name@version`, where the name might be scoped
+    fn version(&self) -> &str {
+        self.ident
+            .get(1..)
+            .and_then(|ident| ident.split_once('@'))
+            .map_or("", |(_, version)| version)
     }
 }
 
 impl Lockfile for BunLockfile {
     fn resolve_package(
         &self,
-        _workspace_path: &str,
+        workspace_path: &str,
         name: &str,
         version: &str,
-    ) -> Result<Option<crate::Package>, crate::Error> {
-        for key in possible_keys(name, version) {
-            if let Some(entry) = self.inner.get(&key) {
-                return Ok(Some(crate::Package {
-                    key,
-                    version: entry.version.clone(),
-                }));
-            }
+    ) -> Result<Option<Package>, crate::Error> {
+        // `all_dependencies` returns install paths along with the resolved
+        // version, these don't need to be resolved again
+        if self
+            .packages
+            .get(name)
+            .is_some_and(|entry| entry.version() == version)
+        {
+            return Ok(self.package(name.to_string()));
         }
 
-        Ok(None)
+        // Otherwise a workspace uses its own copy of a package if one is nested
+        // under it, before falling back to the hoisted one
+        let workspace_name = self.workspace_name(workspace_path)?;
+        Ok(workspace_name
+            .map(|workspace| format!("{workspace}/{name}"))
+            .into_iter()
+            .chain(iter::once(name.to_string()))
+            .find_map(|key| self.package(key)))
     }
 
-    fn all_dependencies(
-        &self,
-        key: &str,
-    ) -> Result<Option<std::collections::HashMap<String, String>>, crate::Error> {
-        let Some(entry) = self.inner.get(key) else {
+    fn all_dependencies(&self, key: &str) -> Result<Option<HashMap<String, String>>, crate::Error> {
+        let Some(entry) = self.packages.get(key) else {
             return Ok(None);
         };
 
-        let all_deps: std::collections::HashMap<_, _> = entry.dependency_entries().collect();
-        Ok(match all_deps.is_empty() {
-            false => Some(all_deps),
-            true => None,
-        })
+        // Like node_modules, a dependency is looked up next to the package
+        // first and then in each of its parents
+        Ok(Some(
+            entry
+                .dependencies
+                .keys()
+                .filter_map(|name| {
+                    iter::successors(Some(key), |key| parent_key(key))
+                        .map(|parent| format!("{parent}/{name}"))
+                        .chain(iter::once(name.clone()))
+                        .find_map(|key| self.package(key))
+                        .map(|package| (package.key, package.version))
+                })
+                .collect(),
+        ))
     }
 
     fn subgraph(
         &self,
-        _workspace_packages: &[String],
+        workspace_packages: &[String],
         packages: &[String],
-    ) -> Result<Box<dyn Lockfile>, super::Error> {
-        let mut inner = Map::new();
-
-        for (key, entry) in packages.iter().filter_map(|key| {
-            let entry = self.inner.get(key)?;
-            Some((key, entry))
-        }) {
-            inner.insert(key.clone(), entry.clone());
+    ) -> Result<Box<dyn Lockfile>, crate::Error> {
+        let workspaces = self
+            .workspaces
+            .iter()
+            .filter(|(path, _)| path.is_empty() || workspace_packages.contains(path))
+            .map(|(path, workspace)| (path.clone(), workspace.clone()))
+            .collect::<Map<_, _>>();
+
+        let mut pruned_packages = Map::new();
+        for key in packages {
+            let entry = self
+                .packages
+                .get(key)
+                .ok_or_else(|| crate::Error::MissingPackage(key.clone()))?;
+            pruned_packages.insert(key.clone(), entry.clone());
+        }
+        // Workspaces are also listed as packages, keyed by their name
+        for (key, entry) in &self.packages {
+            if let Some(path) = entry.version().strip_prefix("workspace:") {
+                if workspaces.contains_key(path) {
+                    pruned_packages.insert(key.clone(), entry.clone());
+                }
+            }
         }
 
-        Ok(Box::new(Self { inner }))
+        Ok(Box::new(Self {
+            lockfile_version: self.lockfile_version.clone(),
+            workspaces,
+            packages: pruned_packages,
+            other: self.other.clone(),
+        }))
     }
 
     fn encode(&self) -> Result<Vec<u8>, crate::Error> {
-        Err(crate::Error::Bun(Error::NotImplemented()))
+        Ok(self.to_string().into_bytes())
     }
 
     fn global_change(&self, other: &dyn Lockfile) -> bool {
         let any_other = other as &dyn Any;
         // Downcast returns none if the concrete type doesn't match
         // if the types don't match then we changed package managers
-        any_other.downcast_ref::<Self>().is_none()
+        any_other.downcast_ref::<Self>().map_or(true, |other| {
+            self.lockfile_version.get() != other.lockfile_version.get() || self.other != other.other
+        })
     }
 }
 
-impl Entry {
-    fn dependency_entries(&self) -> impl Iterator<Item = (String, String)> + '_ {
-        self.dependencies
-            .iter()
-            .flatten()
-            .chain(self.optional_dependencies.iter().flatten())
-            .map(|(k, v)| (k.clone(), v.clone()))
+impl fmt::Display for BunLockfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!(
+            "{{\n{INDENT}\"lockfileVersion\": {},\n{INDENT}\"workspaces\": {{\n",
+            self.lockfile_version
+        ))?;
+        for (path, workspace) in &self.workspaces {
+            f.write_fmt(format_args!(
+                "{INDENT}{INDENT}{}: ",
+                Value::from(path.as_str())
+            ))?;
+            // bun leads with the name and version of a workspace
+            let fields = ["name", "version"]
+                .into_iter()
+                .filter_map(|field| workspace.get_key_value(field))
+                .chain(
+                    workspace
+                        .iter()
+                        .filter(|(field, _)| !matches!(field.as_str(), "name" | "version")),
+                );
+            write_object(f, fields, 2)?;
+            f.write_str(",\n")?;
+        }
+        f.write_fmt(format_args!("{INDENT}}},\n"))?;
+
+        for (key, value) in &self.other {
+            f.write_fmt(format_args!("{INDENT}{}: ", Value::from(key.as_str())))?;
+            write_value(f, value, 1)?;
+            f.write_str(",\n")?;
+        }
+
+        f.write_fmt(format_args!("{INDENT}\"packages\": {{\n"))?;
+        for (i, (key, entry)) in self.packages.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            f.write_fmt(format_args!(
+                "{INDENT}{INDENT}{}: {},\n",
+                Value::from(key.as_str()),
+                entry.raw
+            ))?;
+        }
+        f.write_fmt(format_args!("{INDENT}}}\n}}\n"))
     }
 }
 
-const PROTOCOLS: &[&str] = ["", "npm:", "file:", "workspace:", "yarn:"].as_slice();
+/// Parses either a text `bun.lock` or a binary `bun.lockb` as printed by `bun
+/// bun.lockb`, which uses the yarn v1 lockfile format.
+pub fn bun_lockfile_from_bytes(input: &[u8]) -> Result<Box<dyn Lockfile>, super::Error> {
+    match input.iter().find(|byte| !byte.is_ascii_whitespace()) {
+        Some(b'{') => Ok(Box::new(BunLockfile::from_bytes(input)?)),
+        _ => Ok(Box::new(crate::Yarn1Lockfile::from_bytes(input)?)),
+    }
+}
+
+// The install path that a package is nested under, scoped package names
+// contain a slash themselves
+fn parent_key(key: &str) -> Option<&str> {
+    let (parent, _) = key.rsplit_once('/')?;
+    let last_segment = parent
+        .rsplit_once('/')
+        .map_or(parent, |(_, segment)| segment);
+    match last_segment.starts_with('@') {
+        true => parent.rsplit_once('/').map(|(parent, _)| parent),
+        false => Some(parent),
+    }
+}
 
-fn possible_keys<'a>(name: &'a str, version: &'a str) -> impl Iterator<Item = String> + 'a {
-    PROTOCOLS
-        .iter()
-        .copied()
-        .map(move |protocol| format!("{name}@{protocol}{version}"))
+// bun.lock is JSONC, the only extension bun writes are trailing commas
+fn strip_trailing_commas(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in input.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => (),
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = input[i + 1..].trim_start().chars().next();
+            if matches!(next, Some('}' | ']')) {
+                continue;
+            }
+        }
+        output.push(c);
+    }
+    output
+}
+
+fn write_object<'a>(
+    f: &mut fmt::Formatter<'_>,
+    fields: impl Iterator<Item = (&'a String, &'a Value)>,
+    depth: usize,
+) -> fmt::Result {
+    let mut fields = fields.peekable();
+    if fields.peek().is_none() {
+        return f.write_str("{}");
+    }
+    f.write_str("{\n")?;
+    for (key, value) in fields {
+        f.write_fmt(format_args!(
+            "{}{}: ",
+            INDENT.repeat(depth + 1),
+            Value::from(key.as_str())
+        ))?;
+        write_value(f, value, depth + 1)?;
+        f.write_str(",\n")?;
+    }
+    f.write_fmt(format_args!("{}}}", INDENT.repeat(depth)))
+}
+
+fn write_value(f: &mut fmt::Formatter<'_>, value: &Value, depth: usize) -> fmt::Result {
+    match value {
+        Value::Object(object) => write_object(f, object.iter(), depth),
+        Value::Array(array) if !array.is_empty() => {
+            f.write_str("[\n")?;
+            for value in array {
+                f.write_str(&INDENT.repeat(depth + 1))?;
+                write_value(f, value, depth + 1)?;
+                f.write_str(",\n")?;
+            }
+            f.write_fmt(format_args!("{}]", INDENT.repeat(depth)))
+        }
+        value => fmt::Display::fmt(value, f),
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use pretty_assertions::assert_eq;
+
     use super::*;
-    const FULL: &str = include_str!("../../fixtures/yarn1full.lock");
+    use crate::transitive_closure;
+
+    const BUN: &str = include_str!("../../fixtures/bun.lock");
+    const BUN_WEB: &str = include_str!("../../fixtures/bun-web.lock");
 
     #[test]
-    fn test_key_splitting() {
-        let lockfile = BunLockfile::from_str(FULL).unwrap();
-        for key in [
-            "@babel/types@^7.18.10",
-            "@babel/types@^7.18.6",
-            "@babel/types@^7.19.0",
-        ] {
-            assert!(
-                lockfile.inner.contains_key(key),
-                "missing {} in lockfile",
-                key
-            );
-        }
+    fn test_roundtrip() {
+        let lockfile = BunLockfile::from_str(BUN).unwrap();
+        assert_eq!(lockfile.to_string(), BUN);
+    }
+
+    #[test]
+    fn test_resolves_nested_workspace_packages() {
+        let lockfile = BunLockfile::from_str(BUN).unwrap();
+        assert_eq!(
+            lockfile
+                .resolve_package("apps/web", "react", "18.2.0")
+                .unwrap(),
+            Some(Package::new("web/react", "18.2.0"))
+        );
+        assert_eq!(
+            lockfile
+                .resolve_package("apps/docs", "react", "^18.3.1")
+                .unwrap(),
+            Some(Package::new("react", "18.3.1"))
+        );
+        assert!(lockfile
+            .resolve_package("apps/missing", "react", "^18.3.1")
+            .is_err());
+    }
+
+    #[test]
+    fn test_subgraph() {
+        let lockfile = BunLockfile::from_str(BUN).unwrap();
+        let closure = transitive_closure(
+            &lockfile,
+            "apps/web",
+            HashMap::from_iter([("react".to_string(), "18.2.0".to_string())]),
+        )
+        .unwrap();
+        let mut keys = closure.into_iter().map(|pkg| pkg.key).collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, vec!["js-tokens", "loose-envify", "web/react"]);
+
+        let pruned = lockfile
+            .subgraph(&["apps/web".to_string()], &keys)
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert_eq!(String::from_utf8(pruned).unwrap(), BUN_WEB);
+    }
+
+    #[test]
+    fn test_parent_key() {
+        assert_eq!(parent_key("react"), None);
+        assert_eq!(parent_key("@babel/core"), None);
+        assert_eq!(parent_key("web/react"), Some("web"));
+        assert_eq!(parent_key("@babel/core/semver"), Some("@babel/core"));
+        assert_eq!(parent_key("web/@babel/core"), Some("web"));
+    }
+
+    #[test]
+    fn test_reads_printed_binary_lockfile() {
+        let lockfile = bun_lockfile_from_bytes(
+            b"# bun ./bun.lockb --hash: 1234\n# yarn lockfile v1\n\n\nreact@^18.2.0:\n  version \
+              \"18.2.0\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            lockfile
+                .resolve_package("apps/web", "react", "^18.2.0")
+                .unwrap(),
+            Some(Package::new("react@^18.2.0", "18.2.0"))
+        );
     }
 }
@@ -10,11 +10,11 @@ mod yarn1;
 
 use std::{
     any::Any,
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
 };
 
 pub use berry::{Error as BerryError, *};
-pub use bun::BunLockfile;
+pub use bun::{bun_lockfile_from_bytes, BunLockfile};
 pub use error::Error;
 pub use npm::*;
 pub use pnpm::{pnpm_global_change, pnpm_subgraph, PnpmLockfile};
@@ -28,6 +28,16 @@ pub struct Package {
     pub version: String,
 }
 
+/// How the external dependencies of a workspace differ between two lockfiles
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize)]
+pub struct PackageChanges {
+    pub added: BTreeSet<Package>,
+    pub removed: BTreeSet<Package>,
+    /// Packages whose key resolves to a different version, as (previous,
+    /// current) pairs
+    pub changed: BTreeSet<(Package, Package)>,
+}
+
 // This trait will only be used when migrating the Go lockfile implementations
 // to Rust. Once the migration is complete we will leverage petgraph for doing
 // our graph calculations.
@@ -59,6 +69,19 @@ pub trait Lockfile: Send + Sync + Any {
 
     /// Determine if there's a global change between two lockfiles
     fn global_change(&self, other: &dyn Lockfile) -> bool;
+
+    /// Given a previous lockfile and a map of workspace directory paths ->
+    /// (package name, version), returns how the transitive closure of each
+    /// workspace changed. Workspaces without any changes are omitted.
+    fn diff(
+        &self,
+        previous: &dyn Lockfile,
+        workspaces: HashMap<String, HashMap<String, String>>,
+    ) -> Result<HashMap<String, PackageChanges>, Error> {
+        let previous_closures = all_transitive_closures(previous, workspaces.clone())?;
+        let current_closures = all_transitive_closures(self, workspaces)?;
+        Ok(diff_closures(&previous_closures, &current_closures))
+    }
 }
 
 /// Takes a lockfile, and a map of workspace directory paths -> (package name,
@@ -76,6 +99,28 @@ pub fn all_transitive_closures<L: Lockfile + ?Sized>(
         .collect()
 }
 
+/// Compares two sets of transitive closures, as returned by
+/// `all_transitive_closures`, and returns the changes for every workspace that
+/// has any
+pub fn diff_closures(
+    previous: &HashMap<String, HashSet<Package>>,
+    current: &HashMap<String, HashSet<Package>>,
+) -> HashMap<String, PackageChanges> {
+    let empty = HashSet::new();
+    previous
+        .keys()
+        .chain(current.keys())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .filter_map(|workspace| {
+            let previous = previous.get(workspace).unwrap_or(&empty);
+            let current = current.get(workspace).unwrap_or(&empty);
+            let changes = PackageChanges::new(previous, current);
+            (!changes.is_empty()).then(|| (workspace.clone(), changes))
+        })
+        .collect()
+}
+
 // this should get replaced by petgraph in the future :)
 pub fn transitive_closure<L: Lockfile + ?Sized>(
     lockfile: &L,
@@ -129,3 +174,100 @@ impl Package {
         Self { key, version }
     }
 }
+
+impl PackageChanges {
+    fn new(previous: &HashSet<Package>, current: &HashSet<Package>) -> Self {
+        let mut removed: BTreeSet<_> = previous.difference(current).cloned().collect();
+        let mut added: BTreeSet<_> = current.difference(previous).cloned().collect();
+
+        // A package that kept its key but not its version was changed rather
+        // than swapped out for another one
+        let previous_by_key: HashMap<_, _> = removed
+            .iter()
+            .map(|package| (package.key.clone(), package.clone()))
+            .collect();
+        let changed: BTreeSet<_> = added
+            .iter()
+            .filter_map(|package| {
+                previous_by_key
+                    .get(&package.key)
+                    .map(|previous| (previous.clone(), package.clone()))
+            })
+            .collect();
+        for (previous, current) in &changed {
+            removed.remove(previous);
+            added.remove(current);
+        }
+
+        Self {
+            added,
+            removed,
+            changed,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn closures(workspaces: &[(&str, &[(&str, &str)])]) -> HashMap<String, HashSet<Package>> {
+        workspaces
+            .iter()
+            .map(|(workspace, packages)| {
+                (
+                    workspace.to_string(),
+                    packages
+                        .iter()
+                        .map(|(key, version)| Package::new(*key, *version))
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_closures() {
+        let previous = closures(&[
+            ("apps/web", &[("react", "18.0.0"), ("left-pad", "1.0.0")]),
+            ("apps/docs", &[("react", "17.0.0")]),
+            ("packages/ui", &[("clsx", "2.0.0")]),
+        ]);
+        let current = closures(&[
+            ("apps/web", &[("react", "18.2.0"), ("right-pad", "1.0.0")]),
+            ("apps/docs", &[("react", "17.0.0")]),
+            ("packages/ui", &[("clsx", "2.0.0")]),
+        ]);
+
+        let diff = diff_closures(&previous, &current);
+        assert_eq!(diff.len(), 1, "only apps/web changed");
+        assert_eq!(
+            diff["apps/web"],
+            PackageChanges {
+                added: [Package::new("right-pad", "1.0.0")].into(),
+                removed: [Package::new("left-pad", "1.0.0")].into(),
+                changed: [(
+                    Package::new("react", "18.0.0"),
+                    Package::new("react", "18.2.0")
+                )]
+                .into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_diff_closures_new_workspace() {
+        let previous = closures(&[]);
+        let current = closures(&[("apps/web", &[("react", "18.2.0")])]);
+
+        let diff = diff_closures(&previous, &current);
+        assert_eq!(
+            diff["apps/web"].added,
+            [Package::new("react", "18.2.0")].into()
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use std::{any::Any, collections::HashMap};
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+};
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -14,18 +17,22 @@ type Map<K, V> = std::collections::BTreeMap<K, V>;
 pub struct NpmLockfile {
     #[serde(rename = "lockfileVersion")]
     lockfile_version: i32,
+    // Version 1 lockfiles don't have this field, the packages get derived from
+    // `dependencies` instead
+    #[serde(default)]
     packages: Map<String, NpmPackage>,
     // We parse this so it doesn't end up in 'other' and we don't need to worry
-    // about accidentally serializing it.
+    // about accidentally serializing it. Only version 1 lockfiles write it back,
+    // see `encode`.
     #[serde(skip_serializing, default)]
-    dependencies: Map<String, Value>,
+    dependencies: Map<String, NpmLegacyDependency>,
     // We want to reserialize any additional fields, but we don't use them
     // we keep them as raw values to avoid describing the correct schema.
     #[serde(flatten)]
     other: Map<String, Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct NpmPackage {
     version: Option<String>,
@@ -44,6 +51,30 @@ struct NpmPackage {
     other: Map<String, Value>,
 }
 
+// An entry of the nested dependency tree that version 1 lockfiles use in place
+// of `packages`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct NpmLegacyDependency {
+    #[serde(default)]
+    version: String,
+    #[serde(flatten)]
+    other: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    requires: Map<String, String>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    dependencies: Map<String, NpmLegacyDependency>,
+}
+
+// The layout a version 1 lockfile gets written in
+#[derive(Serialize)]
+struct NpmLegacyLockfile<'a> {
+    #[serde(flatten)]
+    other: &'a Map<String, Value>,
+    #[serde(rename = "lockfileVersion")]
+    lockfile_version: i32,
+    dependencies: &'a Map<String, NpmLegacyDependency>,
+}
+
 impl Lockfile for NpmLockfile {
     fn resolve_package(
         &self,
@@ -124,6 +155,19 @@ impl Lockfile for NpmLockfile {
                 }
             }
         }
+
+        // Version 1 lockfiles stay in their format so older npm versions can
+        // still install from them
+        if self.lockfile_version == 1 {
+            let keys = pruned_packages.keys().map(String::as_str).collect();
+            return Ok(Box::new(Self {
+                lockfile_version: 1,
+                dependencies: prune_legacy_dependencies(&self.dependencies, "", &keys),
+                packages: pruned_packages,
+                other: self.other.clone(),
+            }));
+        }
+
         Ok(Box::new(Self {
             lockfile_version: 3,
             packages: pruned_packages,
@@ -133,6 +177,13 @@ impl Lockfile for NpmLockfile {
     }
 
     fn encode(&self) -> Result<Vec<u8>, crate::Error> {
+        if self.lockfile_version == 1 {
+            return Ok(serde_json::to_vec_pretty(&NpmLegacyLockfile {
+                other: &self.other,
+                lockfile_version: self.lockfile_version,
+                dependencies: &self.dependencies,
+            })?);
+        }
         Ok(serde_json::to_vec_pretty(&self)?)
     }
 
@@ -149,13 +200,17 @@ impl Lockfile for NpmLockfile {
 
 impl NpmLockfile {
     pub fn load(content: &[u8]) -> Result<Self, Error> {
-        let lockfile: NpmLockfile = serde_json::from_slice(content)?;
+        let mut lockfile: NpmLockfile = serde_json::from_slice(content)?;
+
+        if lockfile.lockfile_version == 1 {
+            lockfile.packages = lockfile.legacy_packages();
+            return Ok(lockfile);
+        }
 
-        // We don't support lockfiles without 'packages' as older versions
-        // required reading through the contents of node_modules in order
-        // to resolve dependencies.
+        // Newer lockfiles without 'packages' would require reading through the
+        // contents of node_modules in order to resolve dependencies.
         // See https://github.com/npm/cli/blob/9609e9eed87c735f0319ac0af265f4d406cbf800/workspaces/arborist/lib/shrinkwrap.js#L674
-        if lockfile.lockfile_version <= 1
+        if lockfile.lockfile_version < 1
             || (lockfile.packages.is_empty() && !lockfile.dependencies.is_empty())
         {
             Err(Error::UnsupportedNpmVersion)
@@ -164,6 +219,25 @@ impl NpmLockfile {
         }
     }
 
+    // Lays out the dependency tree of a version 1 lockfile the way `packages`
+    // does in later versions, keyed by the package's location on disk
+    fn legacy_packages(&self) -> Map<String, NpmPackage> {
+        let mut packages = Map::new();
+        packages.insert(
+            "".to_string(),
+            NpmPackage {
+                version: self
+                    .other
+                    .get("version")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                ..Default::default()
+            },
+        );
+        add_legacy_packages(&mut packages, "", &self.dependencies);
+        packages
+    }
+
     fn get_package(&self, package: impl AsRef<str>) -> Result<&NpmPackage, Error> {
         let pkg_str = package.as_ref();
         self.packages
@@ -197,6 +271,89 @@ impl NpmLockfile {
     }
 }
 
+fn add_legacy_packages(
+    packages: &mut Map<String, NpmPackage>,
+    parent: &str,
+    dependencies: &Map<String, NpmLegacyDependency>,
+) {
+    for (name, dependency) in dependencies {
+        let key = format!("{parent}node_modules/{name}");
+        let package = NpmPackage {
+            version: Some(dependency.version.clone()),
+            resolved: dependency
+                .other
+                .get("resolved")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            dependencies: dependency.requires.clone(),
+            ..Default::default()
+        };
+        match legacy_link_target(dependency) {
+            // Local packages are linked to their directory, which is where
+            // their dependencies get installed
+            Some(target) => {
+                packages.insert(
+                    key,
+                    NpmPackage {
+                        resolved: Some(target.to_string()),
+                        ..Default::default()
+                    },
+                );
+                packages.insert(
+                    target.to_string(),
+                    NpmPackage {
+                        version: None,
+                        ..package
+                    },
+                );
+                add_legacy_packages(packages, &format!("{target}/"), &dependency.dependencies);
+            }
+            None => {
+                let parent = format!("{key}/");
+                packages.insert(key, package);
+                add_legacy_packages(packages, &parent, &dependency.dependencies);
+            }
+        }
+    }
+}
+
+// Keeps the parts of a version 1 dependency tree whose packages are in `keys`
+fn prune_legacy_dependencies(
+    dependencies: &Map<String, NpmLegacyDependency>,
+    parent: &str,
+    keys: &HashSet<&str>,
+) -> Map<String, NpmLegacyDependency> {
+    let mut pruned = Map::new();
+    for (name, dependency) in dependencies {
+        let key = format!("{parent}node_modules/{name}");
+        if !keys.contains(key.as_str()) {
+            continue;
+        }
+        let nested_parent = match legacy_link_target(dependency) {
+            Some(target) => format!("{target}/"),
+            None => format!("{key}/"),
+        };
+        pruned.insert(
+            name.clone(),
+            NpmLegacyDependency {
+                dependencies: prune_legacy_dependencies(
+                    &dependency.dependencies,
+                    &nested_parent,
+                    keys,
+                ),
+                ..dependency.clone()
+            },
+        );
+    }
+    pruned
+}
+
+// The directory a `file:` dependency links to, relative to the root
+fn legacy_link_target(dependency: &NpmLegacyDependency) -> Option<&str> {
+    let target = dependency.version.strip_prefix("file:")?;
+    Some(target.strip_prefix("./").unwrap_or(target))
+}
+
 impl NpmPackage {
     pub fn dep_keys(&self) -> impl Iterator<Item = &String> {
         self.dependencies
@@ -420,6 +577,55 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_legacy_lockfile() -> Result<(), Error> {
+        let lockfile = NpmLockfile::load(include_bytes!("../fixtures/npm-lock-v1.json"))?;
+
+        let tests = [
+            (
+                "packages/a",
+                "lodash",
+                "packages/a/node_modules/lodash",
+                "3.10.1",
+            ),
+            ("packages/b", "lodash", "node_modules/lodash", "4.17.21"),
+            ("packages/a", "is-odd", "node_modules/is-odd", "3.0.1"),
+        ];
+        for (workspace, name, key, version) in &tests {
+            let pkg = lockfile.resolve_package(workspace, name, "")?.unwrap();
+            assert_eq!(pkg.key, *key);
+            assert_eq!(pkg.version, *version);
+        }
+
+        let deps = lockfile.all_dependencies("node_modules/is-odd")?.unwrap();
+        assert_eq!(
+            deps.keys().collect::<Vec<_>>(),
+            vec!["node_modules/is-number"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_lockfile_subgraph() -> Result<(), Error> {
+        let lockfile = NpmLockfile::load(include_bytes!("../fixtures/npm-lock-v1.json"))?;
+        let pruned = lockfile.subgraph(&["packages/b".into()], &["node_modules/lodash".into()])?;
+        let contents: Value = serde_json::from_slice(&pruned.encode()?)?;
+
+        assert_eq!(contents["lockfileVersion"], 1);
+        assert!(contents.get("packages").is_none());
+        let dependencies = contents["dependencies"].as_object().unwrap();
+        assert_eq!(dependencies.keys().collect::<Vec<_>>(), vec!["b", "lodash"]);
+        assert_eq!(dependencies["lodash"]["version"], "4.17.21");
+
+        // The pruned lockfile can be read again
+        let pruned = NpmLockfile::load(&pruned.encode()?)?;
+        assert!(pruned
+            .resolve_package("packages/b", "lodash", "")?
+            .is_some());
+        Ok(())
+    }
+
     #[test]
     fn test_workspace_peer_dependencies() -> Result<(), Error> {
         let lockfile =
@@ -14,6 +14,8 @@ pub struct PnpmLockfile {
     #[serde(skip_serializing_if = "Option::is_none")]
     settings: Option<LockfileSettings>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    catalogs: Option<Map<String, Map<String, Dependency>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     never_built_dependencies: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     only_built_dependencies: Option<Vec<String>>,
@@ -199,10 +201,23 @@ impl PnpmLockfile {
         self.overrides
             .as_ref()
             .and_then(|o| o.get(name))
-            .map(|s| s.as_str())
+            .map(|s| {
+                // An override pointing at a catalog uses the version the catalog
+                // entry resolved to
+                self.catalog_entry(name, s)
+                    .map_or(s.as_str(), |entry| entry.version.as_str())
+            })
             .unwrap_or(specifier)
     }
 
+    // Finds the entry a `catalog:` specifier refers to
+    fn catalog_entry(&self, name: &str, specifier: &str) -> Option<&Dependency> {
+        self.catalogs
+            .as_ref()?
+            .get(catalog_name(specifier)?)?
+            .get(name)
+    }
+
     // Given a package and version specifier resolves it to an exact version
     fn resolve_specifier<'a>(
         &'a self,
@@ -215,14 +230,24 @@ impl PnpmLockfile {
         let Some((resolved_specifier, resolved_version)) =
             importer.dependencies.find_resolution(name)
         else {
-            // Check if the specifier is already an exact version
+            // Check if the specifier is already an exact version, or a catalog
+            // entry that resolved to one
+            let version = self
+                .catalog_entry(name, specifier)
+                .map_or(specifier, |entry| entry.version.as_str());
             return Ok(self
-                .get_packages(&self.format_key(name, specifier))
-                .and(Some(specifier)));
+                .get_packages(&self.format_key(name, version))
+                .and(Some(version)));
         };
 
         let override_specifier = self.apply_overrides(name, specifier);
-        if resolved_specifier == override_specifier {
+        // The importer might list the specifier a catalog entry stands for if
+        // it was written before the dependency moved to the catalog
+        let catalog_specifier = self
+            .catalog_entry(name, override_specifier)
+            .map(|entry| entry.specifier.as_str());
+        if resolved_specifier == override_specifier || Some(resolved_specifier) == catalog_specifier
+        {
             Ok(Some(resolved_version))
         } else if self
             .get_packages(&self.format_key(name, override_specifier))
@@ -252,6 +277,36 @@ impl PnpmLockfile {
         Ok(pruned_patches)
     }
 
+    // Keeps only the catalog entries that the importers or overrides use
+    fn prune_catalogs(
+        &self,
+        importers: &Map<String, ProjectSnapshot>,
+    ) -> Option<Map<String, Map<String, Dependency>>> {
+        let catalogs = self.catalogs.as_ref()?;
+        let mut pruned_catalogs: Map<String, Map<String, Dependency>> = Map::new();
+        let overrides = self
+            .overrides
+            .iter()
+            .flatten()
+            .map(|(name, specifier)| (name.as_str(), specifier.as_str()));
+        for (name, specifier) in importers
+            .values()
+            .flat_map(|importer| importer.dependencies.specifiers())
+            .chain(overrides)
+        {
+            let Some(catalog) = catalog_name(specifier) else {
+                continue;
+            };
+            if let Some(entry) = catalogs.get(catalog).and_then(|entries| entries.get(name)) {
+                pruned_catalogs
+                    .entry(catalog.to_string())
+                    .or_default()
+                    .insert(name.to_string(), entry.clone());
+            }
+        }
+        (!pruned_catalogs.is_empty()).then_some(pruned_catalogs)
+    }
+
     // Create a projection of all fields in the lockfile that could affect all
     // workspaces
     fn global_fields(&self) -> GlobalFields {
@@ -388,6 +443,7 @@ impl crate::Lockfile for PnpmLockfile {
             .as_ref()
             .map(|patches| Self::prune_patches(patches, &pruned_packages))
             .transpose()?;
+        let catalogs = self.prune_catalogs(&importers);
 
         Ok(Box::new(Self {
             importers,
@@ -396,6 +452,7 @@ impl crate::Lockfile for PnpmLockfile {
                 true => None,
             },
             lockfile_version: self.lockfile_version.clone(),
+            catalogs,
             never_built_dependencies: self.never_built_dependencies.clone(),
             only_built_dependencies: self.only_built_dependencies.clone(),
             overrides: self.overrides.clone(),
@@ -459,6 +516,27 @@ impl DependencyInfo {
         }
     }
 
+    // All of the importer's dependencies along with their specifiers
+    fn specifiers(&self) -> Vec<(&str, &str)> {
+        match self {
+            DependencyInfo::PreV6 { specifiers, .. } => specifiers
+                .iter()
+                .flatten()
+                .map(|(name, specifier)| (name.as_str(), specifier.as_str()))
+                .collect(),
+            DependencyInfo::V6 {
+                dependencies,
+                optional_dependencies,
+                dev_dependencies,
+            } => [dependencies, optional_dependencies, dev_dependencies]
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|(name, dependency)| (name.as_str(), dependency.specifier.as_str()))
+                .collect(),
+        }
+    }
+
     fn get_resolution<'a, V>(maybe_map: &'a Option<Map<String, V>>, key: &str) -> Option<&'a V> {
         maybe_map.as_ref().and_then(|maybe_map| maybe_map.get(key))
     }
@@ -471,6 +549,15 @@ impl Dependency {
     }
 }
 
+// The catalog a `catalog:` specifier refers to, a bare `catalog:` refers to the
+// default catalog
+fn catalog_name(specifier: &str) -> Option<&str> {
+    match specifier.strip_prefix("catalog:")? {
+        "" => Some("default"),
+        catalog => Some(catalog),
+    }
+}
+
 pub fn pnpm_global_change(
     prev_contents: &[u8],
     curr_contents: &[u8],
@@ -502,13 +589,14 @@ mod tests {
     const PNPM_OVERRIDE: &[u8] = include_bytes!("../../fixtures/pnpm-override.yaml").as_slice();
     const PNPM_PATCH: &[u8] = include_bytes!("../../fixtures/pnpm-patch.yaml").as_slice();
     const PNPM_PATCH_V6: &[u8] = include_bytes!("../../fixtures/pnpm-patch-v6.yaml").as_slice();
+    const PNPM_CATALOG: &[u8] = include_bytes!("../../fixtures/pnpm-catalog.yaml").as_slice();
 
     use super::*;
     use crate::{Lockfile, Package};
 
     #[test]
     fn test_roundtrip() {
-        for fixture in &[PNPM6, PNPM7, PNPM8, PNPM8_6] {
+        for fixture in &[PNPM6, PNPM7, PNPM8, PNPM8_6, PNPM_CATALOG] {
             let lockfile = PnpmLockfile::from_bytes(fixture).unwrap();
             let serialized_lockfile = serde_yaml::to_string(&lockfile).unwrap();
             let lockfile_from_serialized =
@@ -627,6 +715,38 @@ mod tests {
         Err("Workspace 'apps/bad_workspace' not found in lockfile")
         ; "v6 missing workspace"
     )]
+    #[test_case(
+        PNPM_CATALOG,
+        "packages/a",
+        "is-odd",
+        "catalog:",
+        Ok(Some("3.0.1"))
+        ; "default catalog"
+    )]
+    #[test_case(
+        PNPM_CATALOG,
+        "packages/b",
+        "is-number",
+        "catalog:legacy",
+        Ok(Some("6.0.0"))
+        ; "named catalog"
+    )]
+    #[test_case(
+        PNPM_CATALOG,
+        "packages/b",
+        "is-odd",
+        "catalog:",
+        Ok(Some("3.0.1"))
+        ; "catalog entry missing from importer"
+    )]
+    #[test_case(
+        PNPM_CATALOG,
+        "packages/a",
+        "is-even",
+        "^0.1.0",
+        Ok(Some("1.0.0"))
+        ; "override from catalog"
+    )]
     fn test_specifier_resolution(
         lockfile: &[u8],
         workspace_path: &str,
@@ -797,6 +917,36 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_prune_catalogs() {
+        let lockfile = PnpmLockfile::from_bytes(PNPM_CATALOG).unwrap();
+        let pruned = lockfile
+            .subgraph(&["packages/b".into()], &["/is-number@6.0.0".into()])
+            .unwrap();
+        let pruned = PnpmLockfile::from_bytes(&pruned.encode().unwrap()).unwrap();
+
+        let catalogs = pruned.catalogs.unwrap();
+        assert_eq!(
+            catalogs.keys().map(String::as_str).collect::<Vec<_>>(),
+            vec!["default", "legacy"]
+        );
+        // `is-even` is kept for the override that uses it
+        assert_eq!(
+            catalogs["default"]
+                .keys()
+                .map(String::as_str)
+                .collect::<Vec<_>>(),
+            vec!["is-even"]
+        );
+        assert_eq!(
+            catalogs["legacy"]
+                .keys()
+                .map(String::as_str)
+                .collect::<Vec<_>>(),
+            vec!["is-number"]
+        );
+    }
+
     #[test]
     fn test_pnpm_alias_overlap() {
         let lockfile = PnpmLockfile::from_bytes(PNPM_ABSOLUTE).unwrap();
@@ -17,7 +17,7 @@ use super::{PackageGraph, WorkspaceInfo, WorkspaceName, WorkspaceNode};
 use crate::{
     package_graph::{PackageName, PackageVersion},
     package_json::PackageJson,
-    package_manager::PackageManager,
+    package_manager::{PackageManager, PnpmWorkspace},
 };
 
 pub struct PackageGraphBuilder<'a> {
@@ -290,6 +290,7 @@ impl<'a> BuildState<'a, ResolvedPackageManager> {
 impl<'a> BuildState<'a, ResolvedWorkspaces> {
     #[tracing::instrument(skip(self))]
     fn connect_internal_dependencies(&mut self) -> Result<(), Error> {
+        let pnpm_workspace = self.package_manager.read_pnpm_workspace(self.repo_root)?;
         let split_deps = self
             .workspaces
             .iter()
@@ -301,6 +302,7 @@ impl<'a> BuildState<'a, ResolvedWorkspaces> {
                         self.repo_root,
                         &entry.package_json_path,
                         &self.workspaces,
+                        pnpm_workspace.as_ref(),
                         entry.package_json.all_dependencies(),
                     ),
                 )
@@ -467,6 +469,7 @@ impl Dependencies {
         repo_root: &AbsoluteSystemPath,
         workspace_json_path: &AnchoredSystemPathBuf,
         workspaces: &HashMap<WorkspaceName, WorkspaceInfo>,
+        pnpm_workspace: Option<&PnpmWorkspace>,
         dependencies: I,
     ) -> Self {
         let resolved_workspace_json_path = repo_root.resolve(workspace_json_path);
@@ -479,6 +482,7 @@ impl Dependencies {
             repo_root,
             workspace_dir,
             workspaces,
+            pnpm_workspace,
         };
         for (name, version) in dependencies.into_iter() {
             if let Some(workspace) = splitter.is_internal(name, version) {
@@ -491,14 +495,20 @@ impl Dependencies {
     }
 }
 
-struct DependencySplitter<'a, 'b, 'c> {
+struct DependencySplitter<'a, 'b, 'c, 'd> {
     repo_root: &'a AbsoluteSystemPath,
     workspace_dir: &'b AbsoluteSystemPath,
     workspaces: &'c HashMap<WorkspaceName, WorkspaceInfo>,
+    pnpm_workspace: Option<&'d PnpmWorkspace>,
 }
 
-impl<'a, 'b, 'c> DependencySplitter<'a, 'b, 'c> {
+impl<'a, 'b, 'c, 'd> DependencySplitter<'a, 'b, 'c, 'd> {
     fn is_internal(&self, name: &str, version: &str) -> Option<WorkspaceName> {
+        // pnpm installs overridden and catalog dependencies with the specifier
+        // from `pnpm-workspace.yaml` instead of the one in package.json
+        let version = self.pnpm_workspace.map_or(version, |workspace| {
+            workspace.resolve_specifier(name, version)
+        });
         // TODO implement borrowing for workspaces to allow for zero copy queries
         let workspace_name = WorkspaceName::Other(
             version
@@ -687,6 +697,7 @@ mod test {
             repo_root: &root,
             workspace_dir: &pkg_dir,
             workspaces: &workspaces,
+            pnpm_workspace: None,
         };
 
         assert_eq!(
@@ -695,6 +706,59 @@ mod test {
         );
     }
 
+    #[test_case("catalog:", Some("@scope/foo") ; "default catalog")]
+    #[test_case("catalog:external", None ; "named catalog")]
+    #[test_case("^1.0.0", Some("@scope/foo") ; "not a catalog specifier")]
+    fn test_pnpm_workspace_specifiers(range: &str, expected: Option<&str>) {
+        let root = AbsoluteSystemPathBuf::new(if cfg!(windows) {
+            "C:\\some\\repo"
+        } else {
+            "/some/repo"
+        })
+        .unwrap();
+        let pkg_dir = root.join_components(&["packages", "libA"]);
+        let workspaces = [(
+            WorkspaceName::Other("@scope/foo".to_string()),
+            WorkspaceInfo {
+                package_json: PackageJson {
+                    version: Some("1.2.3".to_string()),
+                    ..Default::default()
+                },
+                package_json_path: AnchoredSystemPathBuf::from_raw("unused").unwrap(),
+                unresolved_external_dependencies: None,
+                transitive_dependencies: None,
+            },
+        )]
+        .into_iter()
+        .collect();
+        let pnpm_workspace = PnpmWorkspace {
+            catalog: [("@scope/foo".to_string(), "workspace:*".to_string())]
+                .into_iter()
+                .collect(),
+            catalogs: [(
+                "external".to_string(),
+                [("@scope/foo".to_string(), "^2.0.0".to_string())]
+                    .into_iter()
+                    .collect(),
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+
+        let splitter = DependencySplitter {
+            repo_root: &root,
+            workspace_dir: &pkg_dir,
+            workspaces: &workspaces,
+            pnpm_workspace: Some(&pnpm_workspace),
+        };
+
+        assert_eq!(
+            splitter.is_internal("@scope/foo", range),
+            expected.map(WorkspaceName::from)
+        );
+    }
+
     #[test]
     fn test_duplicate_package_names() {
         let root =
@@ -7,7 +7,7 @@ use petgraph::visit::{depth_first_search, Reversed};
 use serde::Serialize;
 use nxpkgpath::{AbsoluteSystemPath, AnchoredSystemPath, AnchoredSystemPathBuf};
 use nxpkgrepo_graph_utils as graph;
-use nxpkgrepo_lockfiles::Lockfile;
+use nxpkgrepo_lockfiles::{Lockfile, PackageChanges};
 
 use crate::{package_json::PackageJson, package_manager::PackageManager};
 
@@ -297,51 +297,79 @@ impl PackageGraph {
     }
 
     /// Returns a list of changed packages based on the contents of a previous
-    /// `Lockfile`. This assumes that none of the package.json in the workspace
-    /// change, it is the responsibility of the caller to verify this.
+    /// `Lockfile`. A change to the root's dependencies changes every package.
+    /// This assumes that none of the package.json in the workspace change, it
+    /// is the responsibility of the caller to verify this.
     pub fn changed_packages(
         &self,
         previous: &dyn Lockfile,
     ) -> Result<Vec<WorkspaceName>, ChangedPackagesError> {
         let current = self.lockfile().ok_or(ChangedPackagesError::NoLockfile)?;
 
+        // A global change could affect any package, regardless of whether its
+        // dependencies changed
+        if current.global_change(previous) {
+            return Ok(self.workspaces.keys().cloned().collect());
+        }
+
+        let changes = self.lockfile_changes(previous)?;
+        // The root's dependencies are available to every package, so a change
+        // to them could affect any package as well
+        if changes.contains_key(&WorkspaceName::Root) {
+            return Ok(self.workspaces.keys().cloned().collect());
+        }
+
+        Ok(changes.into_keys().collect())
+    }
+
+    /// Returns how the external dependencies of each workspace changed
+    /// compared to a previous `Lockfile`. Workspaces whose external
+    /// dependencies are unchanged are omitted. Like `changed_packages`, this
+    /// assumes none of the package.json in the workspace changed.
+    pub fn lockfile_changes(
+        &self,
+        previous: &dyn Lockfile,
+    ) -> Result<HashMap<WorkspaceName, PackageChanges>, ChangedPackagesError> {
+        let current = self.lockfile().ok_or(ChangedPackagesError::NoLockfile)?;
+        self.lockfile_changes_between(previous, current)
+    }
+
+    /// Returns how the external dependencies of each workspace changed
+    /// between two `Lockfile`s, neither of which has to be the one this graph
+    /// was built from. The dependencies of each workspace are taken from its
+    /// current package.json.
+    pub fn lockfile_changes_between(
+        &self,
+        previous: &dyn Lockfile,
+        current: &dyn Lockfile,
+    ) -> Result<HashMap<WorkspaceName, PackageChanges>, ChangedPackagesError> {
+        let workspace_paths = self
+            .workspaces()
+            .map(|(name, info)| (info.package_path().to_unix().to_string(), name))
+            .collect::<HashMap<_, _>>();
         let external_deps = self
             .workspaces()
-            .filter_map(|(_name, info)| {
-                info.unresolved_external_dependencies.as_ref().map(|dep| {
-                    (
-                        info.package_path().to_unix().to_string(),
-                        dep.iter()
-                            .map(|(name, version)| (name.to_owned(), version.to_owned()))
-                            .collect(),
-                    )
-                })
+            .map(|(_name, info)| {
+                (
+                    info.package_path().to_unix().to_string(),
+                    info.unresolved_external_dependencies
+                        .iter()
+                        .flatten()
+                        .map(|(name, version)| (name.to_owned(), version.to_owned()))
+                        .collect(),
+                )
             })
             .collect::<HashMap<_, HashMap<_, _>>>();
 
-        let closures = nxpkgrepo_lockfiles::all_transitive_closures(previous, external_deps)?;
-
-        let global_change = current.global_change(previous);
-
-        let changed = if global_change {
-            None
-        } else {
-            self.workspaces
-                .iter()
-                .filter(|(_name, info)| {
-                    closures.get(info.package_path().as_str())
-                        != info.transitive_dependencies.as_ref()
-                })
-                .map(|(name, _info)| match name {
-                    WorkspaceName::Other(n) => Some(WorkspaceName::Other(n.to_owned())),
-                    // if the root package has changed, then we should report `None`
-                    // since all packages need to be revalidated
-                    WorkspaceName::Root => None,
-                })
-                .collect::<Option<Vec<WorkspaceName>>>()
-        };
+        let changes = current.diff(previous, external_deps)?;
 
-        Ok(changed.unwrap_or_else(|| self.workspaces.keys().cloned().collect()))
+        Ok(changes
+            .into_iter()
+            .filter_map(|(path, changes)| {
+                let name = workspace_paths.get(&path)?;
+                Some(((*name).clone(), changes))
+            })
+            .collect())
     }
 
     #[allow(dead_code)]
@@ -611,6 +639,154 @@ mod test {
         );
     }
 
+    // Resolves like `MockLockfile`, but with "b" bumped to version 2
+    struct BumpedLockfile {}
+    impl nxpkgrepo_lockfiles::Lockfile for BumpedLockfile {
+        fn resolve_package(
+            &self,
+            workspace_path: &str,
+            name: &str,
+            version: &str,
+        ) -> std::result::Result<Option<nxpkgrepo_lockfiles::Package>, nxpkgrepo_lockfiles::Error>
+        {
+            let package = MockLockfile {}.resolve_package(workspace_path, name, version)?;
+            Ok(package.map(|package| match package.key.as_str() {
+                "key:b" => nxpkgrepo_lockfiles::Package::new("key:b", "2"),
+                _ => package,
+            }))
+        }
+
+        fn all_dependencies(
+            &self,
+            key: &str,
+        ) -> std::result::Result<Option<HashMap<String, String>>, nxpkgrepo_lockfiles::Error>
+        {
+            MockLockfile {}.all_dependencies(key)
+        }
+
+        fn subgraph(
+            &self,
+            _workspace_packages: &[String],
+            _packages: &[String],
+        ) -> std::result::Result<Box<dyn Lockfile>, nxpkgrepo_lockfiles::Error> {
+            unreachable!("lockfile pruning not necessary for change detection")
+        }
+
+        fn encode(&self) -> std::result::Result<Vec<u8>, nxpkgrepo_lockfiles::Error> {
+            unreachable!("lockfile encoding not necessary for change detection")
+        }
+
+        fn global_change(&self, _other: &dyn Lockfile) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_lockfile_changes() {
+        let root =
+            AbsoluteSystemPathBuf::new(if cfg!(windows) { r"C:\repo" } else { "/repo" }).unwrap();
+        let pkg_graph = PackageGraph::builder(
+            &root,
+            PackageJson::from_value(json!({ "name": "root" })).unwrap(),
+        )
+        .with_package_manger(Some(PackageManager::Npm))
+        .with_package_jsons(Some({
+            let mut map = HashMap::new();
+            map.insert(
+                root.join_components(&["package_a", "package.json"]),
+                PackageJson::from_value(json!({
+                    "name": "foo",
+                    "dependencies": {
+                        "a": "1"
+                    }
+                }))
+                .unwrap(),
+            );
+            map.insert(
+                root.join_components(&["package_b", "package.json"]),
+                PackageJson::from_value(json!({
+                    "name": "bar",
+                    "dependencies": {
+                        "b": "1",
+                    }
+                }))
+                .unwrap(),
+            );
+            map
+        }))
+        .with_lockfile(Some(Box::new(BumpedLockfile {})))
+        .build()
+        .unwrap();
+
+        // Only the workspace depending on "b" is affected by the bump
+        let changed = pkg_graph.changed_packages(&MockLockfile {}).unwrap();
+        assert_eq!(changed, vec![WorkspaceName::from("bar")]);
+
+        let changes = pkg_graph.lockfile_changes(&MockLockfile {}).unwrap();
+        assert_eq!(
+            changes[&WorkspaceName::from("bar")].changed,
+            [(
+                nxpkgrepo_lockfiles::Package::new("key:b", "1"),
+                nxpkgrepo_lockfiles::Package::new("key:b", "2"),
+            )]
+            .into()
+        );
+
+        // Lockfiles other than the graph's own can be compared as well
+        let changes = pkg_graph
+            .lockfile_changes_between(&BumpedLockfile {}, &MockLockfile {})
+            .unwrap();
+        assert_eq!(
+            changes[&WorkspaceName::from("bar")].changed,
+            [(
+                nxpkgrepo_lockfiles::Package::new("key:b", "2"),
+                nxpkgrepo_lockfiles::Package::new("key:b", "1"),
+            )]
+            .into()
+        );
+    }
+
+    #[test]
+    fn test_root_lockfile_changes() {
+        let root =
+            AbsoluteSystemPathBuf::new(if cfg!(windows) { r"C:\repo" } else { "/repo" }).unwrap();
+        let pkg_graph = PackageGraph::builder(
+            &root,
+            PackageJson::from_value(json!({
+                "name": "root",
+                "dependencies": {
+                    "b": "1"
+                }
+            }))
+            .unwrap(),
+        )
+        .with_package_manger(Some(PackageManager::Npm))
+        .with_package_jsons(Some({
+            let mut map = HashMap::new();
+            map.insert(
+                root.join_components(&["package_a", "package.json"]),
+                PackageJson::from_value(json!({
+                    "name": "foo",
+                    "dependencies": {
+                        "a": "1"
+                    }
+                }))
+                .unwrap(),
+            );
+            map
+        }))
+        .with_lockfile(Some(Box::new(BumpedLockfile {})))
+        .build()
+        .unwrap();
+
+        // "foo" doesn't depend on "b", but the root's dependencies changed
+        let changed = pkg_graph.changed_packages(&MockLockfile {}).unwrap();
+        assert_eq!(
+            changed.into_iter().collect::<HashSet<_>>(),
+            HashSet::from([WorkspaceName::Root, WorkspaceName::from("foo")])
+        );
+    }
+
     #[test]
     fn test_circular_dependency() {
         let root =
@@ -2,7 +2,9 @@ use nxpkgpath::AbsoluteSystemPath;
 
 use crate::package_manager::{Error, PackageManager};
 
-pub const LOCKFILE: &str = "bun.lockb";
+pub const LOCKFILE: &str = "bun.lock";
+// Written by bun before 1.2, only bun itself can read it
+pub const BINARY_LOCKFILE: &str = "bun.lockb";
 
 pub struct BunDetector<'a> {
     repo_root: &'a AbsoluteSystemPath,
@@ -27,9 +29,10 @@ impl<'a> Iterator for BunDetector<'a> {
         }
 
         self.found = true;
-        let package_json = self.repo_root.join_component(LOCKFILE);
-
-        if package_json.exists() {
+        if [LOCKFILE, BINARY_LOCKFILE]
+            .into_iter()
+            .any(|lockfile| self.repo_root.join_component(lockfile).exists())
+        {
             Some(Ok(PackageManager::Bun))
         } else {
             None
@@ -43,17 +46,19 @@ mod tests {
 
     use anyhow::Result;
     use tempfile::tempdir;
+    use test_case::test_case;
     use nxpkgpath::AbsoluteSystemPathBuf;
 
-    use super::LOCKFILE;
+    use super::{BINARY_LOCKFILE, LOCKFILE};
     use crate::package_manager::PackageManager;
 
-    #[test]
-    fn test_detect_bun() -> Result<()> {
+    #[test_case(LOCKFILE ; "text lockfile")]
+    #[test_case(BINARY_LOCKFILE ; "binary lockfile")]
+    fn test_detect_bun(lockfile: &str) -> Result<()> {
         let repo_root = tempdir()?;
         let repo_root_path = AbsoluteSystemPathBuf::try_from(repo_root.path())?;
 
-        let lockfile_path = repo_root.path().join(LOCKFILE);
+        let lockfile_path = repo_root.path().join(lockfile);
         File::create(lockfile_path)?;
         let package_manager = PackageManager::detect_package_manager(&repo_root_path)?;
         assert_eq!(package_manager, PackageManager::Bun);
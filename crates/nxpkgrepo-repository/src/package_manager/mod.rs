@@ -21,16 +21,12 @@ use nxpkgrepo_lockfiles::Lockfile;
 use wax::{Any, Glob, Pattern};
 use which::which;
 
+pub use self::pnpm::PnpmWorkspace;
 use crate::{
     package_json::PackageJson,
     package_manager::{bun::BunDetector, npm::NpmDetector, pnpm::PnpmDetector, yarn::YarnDetector},
 };
 
-#[derive(Debug, Deserialize)]
-struct PnpmWorkspace {
-    pub packages: Vec<String>,
-}
-
 #[derive(Debug, Deserialize)]
 struct PackageJsonWorkspaces {
     workspaces: Workspaces,
@@ -471,7 +467,7 @@ impl PackageManager {
     ) -> Result<Box<dyn Lockfile>, Error> {
         let lockfile_path = self.lockfile_path(root_path);
         let contents = match self {
-            PackageManager::Bun => {
+            PackageManager::Bun if lockfile_path.ends_with(bun::BINARY_LOCKFILE) => {
                 Command::new(which("bun")?)
                     .arg(lockfile_path.to_string())
                     .current_dir(root_path.to_string())
@@ -497,9 +493,7 @@ impl PackageManager {
             PackageManager::Yarn => {
                 Box::new(nxpkgrepo_lockfiles::Yarn1Lockfile::from_bytes(contents)?)
             }
-            PackageManager::Bun => {
-                Box::new(nxpkgrepo_lockfiles::BunLockfile::from_bytes(contents)?)
-            }
+            PackageManager::Bun => nxpkgrepo_lockfiles::bun_lockfile_from_bytes(contents)?,
             PackageManager::Berry => Box::new(nxpkgrepo_lockfiles::BerryLockfile::load(
                 contents,
                 Some(nxpkgrepo_lockfiles::BerryManifest::with_resolutions(
@@ -513,6 +507,28 @@ impl PackageManager {
         })
     }
 
+    /// The catalogs and overrides that pnpm reads from `pnpm-workspace.yaml`.
+    /// Returns `None` for other package managers or if the file is missing.
+    pub fn read_pnpm_workspace(
+        &self,
+        root_path: &AbsoluteSystemPath,
+    ) -> Result<Option<PnpmWorkspace>, Error> {
+        match self {
+            PackageManager::Pnpm | PackageManager::Pnpm6 => {
+                let workspace_yaml = root_path.join_component("pnpm-workspace.yaml");
+                if !workspace_yaml.exists() {
+                    return Ok(None);
+                }
+                let pnpm_workspace = serde_yaml::from_str(&fs::read_to_string(workspace_yaml)?)?;
+                Ok(Some(pnpm_workspace))
+            }
+            PackageManager::Npm
+            | PackageManager::Berry
+            | PackageManager::Yarn
+            | PackageManager::Bun => Ok(None),
+        }
+    }
+
     pub fn prune_patched_packages<R: AsRef<RelativeUnixPath>>(
         &self,
         package_json: &PackageJson,
@@ -530,7 +546,14 @@ impl PackageManager {
     }
 
     pub fn lockfile_path(&self, nxpkg_root: &AbsoluteSystemPath) -> AbsoluteSystemPathBuf {
-        nxpkg_root.join_component(self.lockfile_name())
+        let lockfile_path = nxpkg_root.join_component(self.lockfile_name());
+        match self {
+            // Repositories that haven't migrated to a text lockfile yet
+            PackageManager::Bun if !lockfile_path.exists() => {
+                nxpkg_root.join_component(bun::BINARY_LOCKFILE)
+            }
+            _ => lockfile_path,
+        }
     }
 
     pub fn arg_separator(&self, user_args: &[String]) -> Option<&str> {
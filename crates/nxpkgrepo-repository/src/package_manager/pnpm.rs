@@ -1,6 +1,7 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 
 use node_semver::{Range, Version};
+use serde::Deserialize;
 use nxpkgpath::{AbsoluteSystemPath, RelativeUnixPath};
 
 use crate::{
@@ -10,6 +11,45 @@ use crate::{
 
 pub const LOCKFILE: &str = "pnpm-lock.yaml";
 
+/// The contents of `pnpm-workspace.yaml`
+#[derive(Debug, Default, Deserialize)]
+pub struct PnpmWorkspace {
+    pub packages: Vec<String>,
+    /// The default catalog, referred to with `catalog:`
+    #[serde(default)]
+    pub catalog: BTreeMap<String, String>,
+    /// Named catalogs, referred to with `catalog:<name>`
+    #[serde(default)]
+    pub catalogs: BTreeMap<String, BTreeMap<String, String>>,
+    #[serde(default)]
+    pub overrides: BTreeMap<String, String>,
+}
+
+impl PnpmWorkspace {
+    /// Resolves the specifier pnpm installs a dependency with, after applying
+    /// overrides and looking up `catalog:` specifiers
+    pub fn resolve_specifier<'a>(&'a self, name: &str, specifier: &'a str) -> &'a str {
+        let specifier = self
+            .overrides
+            .get(name)
+            .map_or(specifier, |specifier| specifier.as_str());
+        self.catalog_specifier(name, specifier).unwrap_or(specifier)
+    }
+
+    /// Looks up the catalog entry of a `catalog:` specifier
+    pub fn catalog_specifier(&self, name: &str, specifier: &str) -> Option<&str> {
+        let entry = match specifier.strip_prefix("catalog:")? {
+            // The default catalog can be defined either way
+            "" | "default" => self
+                .catalog
+                .get(name)
+                .or_else(|| self.catalogs.get("default")?.get(name)),
+            catalog => self.catalogs.get(catalog)?.get(name),
+        };
+        entry.map(|specifier| specifier.as_str())
+    }
+}
+
 pub struct PnpmDetector<'a> {
     found: bool,
     repo_root: &'a AbsoluteSystemPath,
@@ -99,6 +139,38 @@ mod test {
 
     use super::*;
 
+    #[test]
+    fn test_catalog_resolution() {
+        let workspace: PnpmWorkspace = serde_yaml::from_str(
+            "packages:
+  - packages/*
+catalog:
+  react: ^18.2.0
+catalogs:
+  legacy:
+    react: ^17.0.2
+overrides:
+  lodash: 'catalog:legacy'
+",
+        )
+        .unwrap();
+        assert_eq!(workspace.resolve_specifier("react", "catalog:"), "^18.2.0");
+        assert_eq!(
+            workspace.resolve_specifier("react", "catalog:default"),
+            "^18.2.0"
+        );
+        assert_eq!(
+            workspace.resolve_specifier("react", "catalog:legacy"),
+            "^17.0.2"
+        );
+        assert_eq!(workspace.resolve_specifier("react", "^16.0.0"), "^16.0.0");
+        // An override pointing at a catalog without an entry is left as is
+        assert_eq!(
+            workspace.resolve_specifier("lodash", "^4.17.21"),
+            "catalog:legacy"
+        );
+    }
+
     #[test]
     fn test_patch_pruning() {
         let package_json: PackageJson = serde_json::from_value(json!({
@@ -0,0 +1,423 @@
+use std::process::{Command, Stdio};
+
+use git2::{IndexEntryExtendedFlag, Repository};
+use globwalk::fix_glob_pattern;
+use tracing::debug;
+use nxpkgpath::{
+    AbsoluteSystemPath, AbsoluteSystemPathBuf, AnchoredSystemPath, IntoUnix, RelativeUnixPathBuf,
+};
+use wax::{any, Glob, Pattern};
+
+use crate::{libgit::LibGit, package_deps::GitHashes, Error, Git, SCM};
+
+/// How a repository is checked out. Shallow and sparse checkouts leave out
+/// history or files that change detection and hashing otherwise rely on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Checkout {
+    /// Only part of the history has been fetched
+    pub shallow: bool,
+    /// Only part of the tree is in the working directory
+    pub sparse: bool,
+    /// A linked worktree, sharing its repository with another checkout
+    pub worktree: bool,
+}
+
+impl Checkout {
+    pub(crate) fn detect(git_root: &AbsoluteSystemPath) -> Self {
+        let repo = match Repository::open(git_root) {
+            Ok(repo) => repo,
+            Err(e) => {
+                debug!("unable to inspect checkout at {}: {}", git_root, e);
+                return Self::default();
+            }
+        };
+        let sparse = repo
+            .config()
+            .and_then(|config| config.get_bool("core.sparseCheckout"))
+            .unwrap_or(false);
+
+        Self {
+            shallow: repo.is_shallow(),
+            sparse,
+            worktree: repo.is_worktree(),
+        }
+    }
+
+    /// Whether the checkout has the full history and working tree
+    pub fn is_complete(&self) -> bool {
+        !self.shallow && !self.sparse
+    }
+}
+
+/// The files a sparse checkout leaves out of the working directory, with the
+/// blob hashes the index has for them
+#[derive(Debug)]
+pub struct SparseCheckout {
+    git_root: AbsoluteSystemPathBuf,
+    excluded: GitHashes,
+}
+
+impl SparseCheckout {
+    pub fn excludes(&self, path: &AbsoluteSystemPath) -> bool {
+        self.git_root
+            .anchor(path)
+            .map_or(false, |path| self.excluded.contains_key(&path.to_unix()))
+    }
+
+    // Packages are left out of a sparse checkout as a whole, so checking their
+    // package.json is enough
+    fn excludes_package(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        package_path: &AnchoredSystemPath,
+    ) -> bool {
+        self.excludes(
+            &nxpkg_root
+                .resolve(package_path)
+                .join_component("package.json"),
+        )
+    }
+
+    // The files of a package that's left out of the checkout can't be read,
+    // so they're hashed with the blob hashes from the index. As when hashing
+    // with inputs from the working directory, package.json and nxpkg.json are
+    // always included.
+    pub(crate) fn package_file_hashes<S: AsRef<str>>(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        package_path: &AnchoredSystemPath,
+        inputs: &[S],
+    ) -> Result<GitHashes, Error> {
+        let package_prefix = self
+            .git_root
+            .anchor(&nxpkg_root.resolve(package_path))?
+            .to_unix();
+
+        let mut includes = Vec::new();
+        let mut excludes = Vec::new();
+        for pattern in inputs {
+            let pattern = pattern.as_ref();
+            if let Some(exclusion) = pattern.strip_prefix('!') {
+                let glob = fix_glob_pattern(exclusion).into_unix();
+                excludes.push(Glob::new(glob.as_str()).map(|g| g.into_owned())?);
+            } else {
+                let glob = fix_glob_pattern(pattern).into_unix();
+                includes.push(Glob::new(glob.as_str()).map(|g| g.into_owned())?);
+            }
+        }
+        let include_pattern = if includes.is_empty() {
+            None
+        } else {
+            includes.push(Glob::new("package.json")?);
+            includes.push(Glob::new("nxpkg.json")?);
+            Some(any(includes)?)
+        };
+        let exclude_pattern = if excludes.is_empty() {
+            None
+        } else {
+            Some(any(excludes)?)
+        };
+
+        Ok(self
+            .excluded
+            .iter()
+            .filter_map(|(path, hash)| {
+                let path = path.strip_prefix(&package_prefix).ok()?;
+                let included = include_pattern
+                    .as_ref()
+                    .map_or(true, |pattern| pattern.is_match(path.as_str()));
+                let excluded = exclude_pattern
+                    .as_ref()
+                    .map_or(false, |pattern| pattern.is_match(path.as_str()));
+                (included && !excluded).then(|| (path, hash.clone()))
+            })
+            .collect())
+    }
+}
+
+impl SCM {
+    pub fn checkout(&self) -> Checkout {
+        match self {
+            Self::Vcs(vcs) => vcs.checkout(),
+            Self::Manual => Checkout::default(),
+        }
+    }
+
+    /// The files left out of a sparse checkout, or `None` if the checkout
+    /// isn't sparse
+    pub fn sparse_checkout(&self) -> Option<&SparseCheckout> {
+        match self {
+            Self::Vcs(vcs) => vcs.sparse_checkout(),
+            Self::Manual => None,
+        }
+    }
+
+    /// The sparse checkout, if it leaves out the given package
+    pub fn sparse_checkout_excluding(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        package_path: &AnchoredSystemPath,
+    ) -> Option<&SparseCheckout> {
+        self.sparse_checkout()
+            .filter(|sparse_checkout| sparse_checkout.excludes_package(nxpkg_root, package_path))
+    }
+
+    /// Whether the fetched history contains a merge base of the two refs,
+    /// which a shallow clone might not reach back to
+    pub fn has_merge_base(&self, from_ref: &str, to_ref: &str) -> Result<bool, Error> {
+        match self {
+            Self::Vcs(vcs) => vcs.has_merge_base(from_ref, to_ref),
+            Self::Manual => Err(Error::git_error("no repository to find a merge base in")),
+        }
+    }
+
+    /// Fetches `depth` more commits of history into a shallow clone
+    pub fn deepen(&self, depth: u32) -> Result<(), Error> {
+        match self {
+            Self::Vcs(vcs) => vcs.deepen(depth),
+            Self::Manual => Err(Error::git_error(
+                "deepening a shallow clone requires the git binary",
+            )),
+        }
+    }
+}
+
+impl Git {
+    pub(crate) fn read_sparse_checkout(&self) -> Option<SparseCheckout> {
+        self.list_skip_worktree()
+            .map_err(|e| debug!("unable to read sparse checkout: {}", e))
+            .ok()
+    }
+
+    // `git ls-files -t` tags files with the skip-worktree bit, the ones the
+    // sparse checkout leaves out, with `S`. With `-s` each entry is followed
+    // by `<mode> <hash> <stage>\t<path>`.
+    fn list_skip_worktree(&self) -> Result<SparseCheckout, Error> {
+        let output = Command::new(self.bin.as_std_path())
+            .args(["ls-files", "-t", "-s", "-z"])
+            .current_dir(&self.root)
+            .stderr(Stdio::piped())
+            .output()?;
+        if !output.status.success() {
+            return Err(Error::git_error(format!(
+                "git ls-files -t failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        let mut excluded = GitHashes::new();
+        for entry in output.stdout.split(|byte| *byte == b'\0') {
+            let Some(entry) = entry.strip_prefix(b"S ") else {
+                continue;
+            };
+            let entry = String::from_utf8(entry.to_vec())?;
+            let Some((hash, path)) = entry
+                .split_once('\t')
+                .and_then(|(stage, path)| Some((stage.split(' ').nth(1)?, path)))
+            else {
+                return Err(Error::git_error(format!(
+                    "failed to parse git ls-files entry: {}",
+                    entry
+                )));
+            };
+            excluded.insert(RelativeUnixPathBuf::new(path)?, hash.to_string());
+        }
+
+        Ok(SparseCheckout {
+            git_root: self.root.clone(),
+            excluded,
+        })
+    }
+}
+
+impl LibGit {
+    pub(crate) fn read_sparse_checkout(&self) -> Option<SparseCheckout> {
+        self.list_skip_worktree()
+            .map_err(|e| debug!("unable to read sparse checkout: {}", e))
+            .ok()
+    }
+
+    fn list_skip_worktree(&self) -> Result<SparseCheckout, Error> {
+        let repo = Repository::open(self.root())
+            .map_err(|e| Error::git2_error_context(e, self.root().to_string()))?;
+        let index = repo
+            .index()
+            .map_err(|e| Error::git2_error_context(e, "index".to_string()))?;
+
+        let mut excluded = GitHashes::new();
+        for entry in index.iter() {
+            if entry.flags_extended & IndexEntryExtendedFlag::SKIP_WORKTREE.bits() != 0 {
+                excluded.insert(
+                    RelativeUnixPathBuf::new(String::from_utf8(entry.path)?)?,
+                    entry.id.to_string(),
+                );
+            }
+        }
+
+        Ok(SparseCheckout {
+            git_root: self.root().to_owned(),
+            excluded,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{assert_matches::assert_matches, process::Command};
+
+    use super::*;
+    use crate::package_deps::GitHashes;
+
+    fn tmp_dir() -> (tempfile::TempDir, AbsoluteSystemPathBuf) {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let dir = AbsoluteSystemPathBuf::try_from(tmp_dir.path())
+            .unwrap()
+            .to_realpath()
+            .unwrap();
+        (tmp_dir, dir)
+    }
+
+    fn require_git_cmd(repo_root: &AbsoluteSystemPath, args: &[&str]) {
+        let mut cmd = Command::new("git");
+        cmd.args(args).current_dir(repo_root);
+        assert!(cmd.output().unwrap().status.success());
+    }
+
+    // A repository with two packages, `apps/web` and `apps/docs`
+    fn setup_repository() -> (tempfile::TempDir, AbsoluteSystemPathBuf) {
+        let (tmp, repo_root) = tmp_dir();
+        require_git_cmd(&repo_root, &["init", "."]);
+        require_git_cmd(&repo_root, &["config", "--local", "user.name", "test"]);
+        require_git_cmd(
+            &repo_root,
+            &["config", "--local", "user.email", "test@example.com"],
+        );
+        for package in ["web", "docs"] {
+            let package_json = repo_root.join_components(&["apps", package, "package.json"]);
+            package_json.ensure_dir().unwrap();
+            package_json.create_with_contents("{}").unwrap();
+        }
+        require_git_cmd(&repo_root, &["add", "."]);
+        require_git_cmd(&repo_root, &["commit", "-m", "first"]);
+        (tmp, repo_root)
+    }
+
+    #[test]
+    fn test_full_checkout() {
+        let (_tmp, repo_root) = setup_repository();
+        let scm = SCM::new(&repo_root);
+
+        assert_eq!(scm.checkout(), Checkout::default());
+        assert!(scm.sparse_checkout().is_none());
+    }
+
+    #[test]
+    fn test_sparse_checkout() {
+        let (_tmp, repo_root) = setup_repository();
+        for (file, contents) in [("README.md", "# docs"), ("src/index.js", "export {}")] {
+            let path = repo_root
+                .join_components(&["apps", "docs"])
+                .join_unix_path(RelativeUnixPathBuf::new(file).unwrap())
+                .unwrap();
+            path.ensure_dir().unwrap();
+            path.create_with_contents(contents).unwrap();
+        }
+        require_git_cmd(&repo_root, &["add", "."]);
+        require_git_cmd(&repo_root, &["commit", "-m", "docs"]);
+        let full_hashes = SCM::new(&repo_root)
+            .get_package_file_hashes::<&str>(
+                &repo_root,
+                &RelativeUnixPathBuf::new("apps/docs")
+                    .unwrap()
+                    .to_anchored_system_path_buf(),
+                &[],
+            )
+            .unwrap();
+        assert_eq!(full_hashes.len(), 3);
+
+        require_git_cmd(&repo_root, &["sparse-checkout", "set", "apps/web"]);
+        let scm = SCM::new(&repo_root);
+
+        assert!(scm.checkout().sparse);
+        assert_matches!(&scm, SCM::Vcs(vcs) if vcs.name() == "git");
+
+        let docs = RelativeUnixPathBuf::new("apps/docs")
+            .unwrap()
+            .to_anchored_system_path_buf();
+        let web = RelativeUnixPathBuf::new("apps/web")
+            .unwrap()
+            .to_anchored_system_path_buf();
+        assert!(scm.sparse_checkout_excluding(&repo_root, &docs).is_some());
+        assert!(scm.sparse_checkout_excluding(&repo_root, &web).is_none());
+        assert!(!repo_root.join_components(&["apps", "docs"]).exists());
+
+        // Packages outside of the sparse checkout are hashed from the index
+        let hashes = scm
+            .get_package_file_hashes::<&str>(&repo_root, &docs, &[])
+            .unwrap();
+        assert_eq!(hashes, full_hashes);
+        let hashes = scm
+            .get_package_file_hashes(&repo_root, &docs, &["src/**"])
+            .unwrap();
+        let expected: GitHashes = full_hashes
+            .iter()
+            .filter(|(path, _)| path.as_str() != "README.md")
+            .map(|(path, hash)| (path.clone(), hash.clone()))
+            .collect();
+        assert_eq!(hashes, expected);
+        let hashes = scm
+            .get_package_file_hashes(&repo_root, &docs, &["!src/**"])
+            .unwrap();
+        let expected: GitHashes = full_hashes
+            .iter()
+            .filter(|(path, _)| path.as_str() != "src/index.js")
+            .map(|(path, hash)| (path.clone(), hash.clone()))
+            .collect();
+        assert_eq!(hashes, expected);
+
+        let hashes = scm
+            .get_package_file_hashes::<&str>(&repo_root, &web, &[])
+            .unwrap();
+        assert_eq!(hashes.len(), 1);
+    }
+
+    #[test]
+    fn test_worktree() {
+        let (_tmp, repo_root) = setup_repository();
+        let (_worktree_tmp, worktree_parent) = tmp_dir();
+        let worktree = worktree_parent.join_component("worktree");
+        require_git_cmd(&repo_root, &["worktree", "add", worktree.as_str()]);
+        let scm = SCM::new(&worktree);
+
+        assert!(scm.checkout().worktree);
+        assert_matches!(&scm, SCM::Vcs(vcs) if vcs.name() == "libgit2");
+    }
+
+    #[test]
+    fn test_shallow_merge_base() {
+        let (_tmp, origin) = setup_repository();
+        for message in ["second", "third"] {
+            require_git_cmd(&origin, &["commit", "--allow-empty", "-m", message]);
+        }
+        let (_clone_tmp, clone_parent) = tmp_dir();
+        let clone = clone_parent.join_component("clone");
+        require_git_cmd(
+            &clone_parent,
+            &[
+                "clone",
+                "--depth",
+                "1",
+                &format!("file://{}", origin),
+                clone.as_str(),
+            ],
+        );
+        let scm = SCM::new(&clone);
+
+        assert!(scm.checkout().shallow);
+        assert_matches!(&scm, SCM::Vcs(vcs) if vcs.name() == "git");
+        assert!(scm.has_merge_base("HEAD~2", "HEAD").is_err());
+
+        scm.deepen(2).unwrap();
+        assert!(scm.has_merge_base("HEAD~2", "HEAD").unwrap());
+    }
+}
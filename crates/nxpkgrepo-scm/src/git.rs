@@ -1,22 +1,26 @@
 use std::{backtrace::Backtrace, collections::HashSet, path::PathBuf, process::Command};
 
 use nxpkgpath::{
-    AbsoluteSystemPath, AbsoluteSystemPathBuf, AnchoredSystemPathBuf, RelativeUnixPath,
+    AbsoluteSystemPath, AbsoluteSystemPathBuf, AnchoredSystemPath, AnchoredSystemPathBuf,
+    RelativeUnixPath,
 };
 
-use crate::{Error, Git, SCM};
+use crate::{
+    package_deps::{get_package_file_hashes_from_inputs, GitHashes},
+    Checkout, Error, Git, SourceControl, SparseCheckout, VcsKind, SCM,
+};
 
 impl SCM {
     pub fn get_current_branch(&self, path: &AbsoluteSystemPath) -> Result<String, Error> {
         match self {
-            Self::Git(git) => git.get_current_branch(),
+            Self::Vcs(vcs) => vcs.get_current_branch(),
             Self::Manual => Err(Error::GitRequired(path.to_owned())),
         }
     }
 
     pub fn get_current_sha(&self, path: &AbsoluteSystemPath) -> Result<String, Error> {
         match self {
-            Self::Git(git) => git.get_current_sha(),
+            Self::Vcs(vcs) => vcs.get_current_sha(),
             Self::Manual => Err(Error::GitRequired(path.to_owned())),
         }
     }
@@ -28,7 +32,7 @@ impl SCM {
         to_commit: &str,
     ) -> Result<HashSet<AnchoredSystemPathBuf>, Error> {
         match self {
-            Self::Git(git) => git.changed_files(nxpkg_root, from_commit, to_commit),
+            Self::Vcs(vcs) => vcs.changed_files(nxpkg_root, from_commit, to_commit),
             Self::Manual => Err(Error::GitRequired(nxpkg_root.to_owned())),
         }
     }
@@ -39,7 +43,7 @@ impl SCM {
         file_path: &AbsoluteSystemPath,
     ) -> Result<Vec<u8>, Error> {
         match self {
-            Self::Git(git) => git.previous_content(from_commit, file_path),
+            Self::Vcs(vcs) => vcs.previous_content(from_commit, file_path),
             Self::Manual => Err(Error::GitRequired(file_path.to_owned())),
         }
     }
@@ -49,9 +53,9 @@ impl SCM {
 /// (unstaged changes) and between two commits. Includes untracked files,
 /// i.e. files not yet in git.
 ///
-/// We shell out to git instead of using a git2 library because git2 doesn't
-/// support shallow clones, and therefore errors on repositories that
-/// are shallow cloned.
+/// Repositories are read in-process with libgit2 where possible. Shallow
+/// clones, which libgit2 can't walk the history of, fall back to shelling out
+/// to git. Mercurial and jj repositories are read with `hg` and `jj`.
 ///
 /// # Arguments
 ///
@@ -79,7 +83,19 @@ pub fn changed_files(
         .collect::<HashSet<_>>())
 }
 
-impl Git {
+impl SourceControl for Git {
+    fn name(&self) -> &str {
+        "git"
+    }
+
+    fn kind(&self) -> VcsKind {
+        VcsKind::Git
+    }
+
+    fn root(&self) -> &AbsoluteSystemPath {
+        &self.root
+    }
+
     fn get_current_branch(&self) -> Result<String, Error> {
         let output = self.execute_git_command(&["branch", "--show-current"], "")?;
         let output = String::from_utf8(output)?;
@@ -128,6 +144,91 @@ impl Git {
         Ok(files)
     }
 
+    fn previous_content(
+        &self,
+        from_commit: &str,
+        file_path: &AbsoluteSystemPath,
+    ) -> Result<Vec<u8>, Error> {
+        let anchored_file_path = self.root.anchor(file_path)?;
+        let mut command = Command::new(self.bin.as_std_path());
+        let command = command
+            .arg("show")
+            .arg(format!("{}:{}", from_commit, anchored_file_path.as_str()))
+            .current_dir(&self.root);
+
+        let output = command.output()?;
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(Error::Git(
+                String::from_utf8_lossy(&output.stderr).to_string(),
+                Backtrace::capture(),
+            ))
+        }
+    }
+
+    fn get_package_file_hashes(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        package_path: &AnchoredSystemPath,
+        inputs: &[&str],
+    ) -> Result<GitHashes, Error> {
+        if inputs.is_empty() {
+            self.get_package_file_hashes_from_index(nxpkg_root, package_path)
+        } else {
+            get_package_file_hashes_from_inputs(&self.root, nxpkg_root, package_path, inputs)
+        }
+    }
+
+    fn checkout(&self) -> Checkout {
+        self.checkout
+    }
+
+    fn sparse_checkout(&self) -> Option<&SparseCheckout> {
+        if !self.checkout.sparse {
+            return None;
+        }
+        self.sparse_checkout
+            .get_or_init(|| self.read_sparse_checkout())
+            .as_ref()
+    }
+
+    fn has_merge_base(&self, from_ref: &str, to_ref: &str) -> Result<bool, Error> {
+        let output = Command::new(self.bin.as_std_path())
+            .args(["merge-base", from_ref, to_ref])
+            .current_dir(&self.root)
+            .output()?;
+        // merge-base exits with 1 when the commits have no common ancestor
+        match output.status.code() {
+            Some(0) => Ok(true),
+            Some(1) => Ok(false),
+            _ => Err(Error::git_error(format!(
+                "git merge-base {} {} failed: {}",
+                from_ref,
+                to_ref,
+                String::from_utf8_lossy(&output.stderr)
+            ))),
+        }
+    }
+
+    fn deepen(&self, depth: u32) -> Result<(), Error> {
+        let output = Command::new(self.bin.as_std_path())
+            .args(["fetch", &format!("--deepen={}", depth)])
+            .current_dir(&self.root)
+            .output()?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(Error::git_error(format!(
+                "git fetch --deepen={} failed: {}",
+                depth,
+                String::from_utf8_lossy(&output.stderr)
+            )))
+        }
+    }
+}
+
+impl Git {
     fn execute_git_command(&self, args: &[&str], pathspec: &str) -> Result<Vec<u8>, Error> {
         let mut command = Command::new(self.bin.as_std_path());
         command.args(args).current_dir(&self.root);
@@ -171,29 +272,6 @@ impl Git {
         let anchored_to_nxpkg_root_file_path = nxpkg_root.anchor(&absolute_file_path)?;
         Ok(anchored_to_nxpkg_root_file_path)
     }
-
-    fn previous_content(
-        &self,
-        from_commit: &str,
-        file_path: &AbsoluteSystemPath,
-    ) -> Result<Vec<u8>, Error> {
-        let anchored_file_path = self.root.anchor(file_path)?;
-        let mut command = Command::new(self.bin.as_std_path());
-        let command = command
-            .arg("show")
-            .arg(format!("{}:{}", from_commit, anchored_file_path.as_str()))
-            .current_dir(&self.root);
-
-        let output = command.output()?;
-        if output.status.success() {
-            Ok(output.stdout)
-        } else {
-            Err(Error::Git(
-                String::from_utf8_lossy(&output.stderr).to_string(),
-                Backtrace::capture(),
-            ))
-        }
-    }
 }
 
 /// Finds the content of a file at a previous commit. Assumes file is in a git
@@ -0,0 +1,338 @@
+use std::{backtrace::Backtrace, collections::HashSet, process::Command};
+
+use nxpkgpath::{
+    AbsoluteSystemPath, AbsoluteSystemPathBuf, AnchoredSystemPath, AnchoredSystemPathBuf,
+};
+
+use crate::{
+    hash_object::hash_objects,
+    package_deps::{get_package_file_hashes_from_inputs, GitHashes},
+    quote_revset_symbol, split_ancestry, Error, SourceControl, VcsKind,
+};
+
+/// A jj repository, read by running `jj`. Colocated repositories, which also
+/// have a `.git` directory, are read with jj as well, since git can't make
+/// sense of jj's revisions.
+#[derive(Debug)]
+pub struct Jujutsu {
+    root: AbsoluteSystemPathBuf,
+    bin: AbsoluteSystemPathBuf,
+}
+
+impl Jujutsu {
+    pub(crate) fn find(root: &AbsoluteSystemPath) -> Result<Self, Error> {
+        let bin = which::which("jj").map_err(|e| {
+            Error::Jujutsu(
+                format!("could not find jj binary: {}", e),
+                Backtrace::capture(),
+            )
+        })?;
+        let bin = AbsoluteSystemPathBuf::try_from(bin.as_path())?;
+        let jj = Self {
+            root: root.to_owned(),
+            bin,
+        };
+        // Every other command skips snapshotting the working copy, so that they
+        // can run concurrently without racing to record it. Snapshot it once
+        // up front instead, which also starts tracking new files.
+        jj.execute_jj_command(&["log", "-r", "@", "--no-graph", "-T", "commit_id"], true)?;
+        Ok(jj)
+    }
+
+    fn execute_jj_command(&self, args: &[&str], snapshot: bool) -> Result<Vec<u8>, Error> {
+        let mut command = Command::new(self.bin.as_std_path());
+        command
+            .args(args)
+            .args(["--color", "never"])
+            .current_dir(&self.root);
+        if !snapshot {
+            command.arg("--ignore-working-copy");
+        }
+
+        let output = command.output()?;
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(Error::Jujutsu(
+                String::from_utf8_lossy(&output.stderr).to_string(),
+                Backtrace::capture(),
+            ))
+        }
+    }
+
+    // A fileset matching exactly one file
+    fn root_file(&self, path: &AbsoluteSystemPath) -> Result<String, Error> {
+        Ok(format!(
+            "root-file:{:?}",
+            self.root.anchor(path)?.to_unix().as_str()
+        ))
+    }
+}
+
+// jj prints paths relative to the directory it runs in, which is the root
+fn files_from_stdout(stdout: Vec<u8>) -> Result<Vec<AnchoredSystemPathBuf>, Error> {
+    String::from_utf8(stdout)?
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| Ok(AnchoredSystemPathBuf::from_raw(line)?))
+        .collect()
+}
+
+// The revset of a git ref. `HEAD`, which is the default upper bound of a
+// range, is the parent of the working copy commit `@` in jj, other refs are
+// quoted. Ancestors are selected with `-`, which follows all parents, so only
+// first parents (`~N`, `^` and `^1`) can be selected; other refs are kept
+// whole, for jj to report.
+fn revision(git_ref: &str) -> String {
+    let (name, ancestry) = split_ancestry(git_ref);
+    let mut revision = match name {
+        "HEAD" => "@-".to_owned(),
+        name => quote_revset_symbol(name),
+    };
+    let mut operators = ancestry
+        .char_indices()
+        .filter(|(_, c)| matches!(c, '~' | '^'));
+    let mut next = operators.next();
+    while let Some((start, operator)) = next {
+        next = operators.next();
+        let end = next.map_or(ancestry.len(), |(end, _)| end);
+        let generations = match (operator, &ancestry[start + 1..end]) {
+            ('~', "") | ('^', "" | "1") => 1,
+            ('^', "0") => 0,
+            ('~', n) => match n.parse() {
+                Ok(n) => n,
+                Err(_) => return quote_revset_symbol(git_ref),
+            },
+            _ => return quote_revset_symbol(git_ref),
+        };
+        revision.push_str(&"-".repeat(generations));
+    }
+    revision
+}
+
+impl SourceControl for Jujutsu {
+    fn name(&self) -> &str {
+        "jj"
+    }
+
+    fn kind(&self) -> VcsKind {
+        VcsKind::Jujutsu
+    }
+
+    fn root(&self) -> &AbsoluteSystemPath {
+        &self.root
+    }
+
+    // jj has no current branch, the closest bookmark behind the working copy
+    // stands in for it
+    fn get_current_branch(&self) -> Result<String, Error> {
+        let output = self.execute_jj_command(
+            &[
+                "log",
+                "-r",
+                "latest(::@ & bookmarks())",
+                "--no-graph",
+                "-T",
+                r#"local_bookmarks.map(|b| b.name()).join("\n")"#,
+            ],
+            false,
+        )?;
+        let output = String::from_utf8(output)?;
+        Ok(output.lines().next().unwrap_or_default().to_owned())
+    }
+
+    fn get_current_sha(&self) -> Result<String, Error> {
+        let output = self.execute_jj_command(
+            &[
+                "log",
+                "-r",
+                &revision("HEAD"),
+                "--no-graph",
+                "--limit",
+                "1",
+                "-T",
+                "commit_id",
+            ],
+            false,
+        )?;
+        Ok(String::from_utf8(output)?.trim().to_owned())
+    }
+
+    fn changed_files(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        from_commit: Option<&str>,
+        to_commit: &str,
+    ) -> Result<HashSet<AnchoredSystemPathBuf>, Error> {
+        let to_commit = revision(to_commit);
+        // The working copy is a commit, so untracked files are part of it
+        let mut ranges = vec![(to_commit.clone(), "@".to_owned())];
+        if let Some(from_commit) = from_commit {
+            let merge_base = format!(
+                "latest(heads(::({}) & ::({})))",
+                revision(from_commit),
+                to_commit
+            );
+            ranges.push((merge_base, to_commit));
+        }
+
+        let mut files = HashSet::new();
+        for (from, to) in ranges {
+            let output = self.execute_jj_command(
+                &["diff", "--name-only", "--from", &from, "--to", &to],
+                false,
+            )?;
+            for path in files_from_stdout(output)? {
+                // Files outside of `nxpkg_root` are filtered out here rather
+                // than with a fileset
+                if let Ok(path) = nxpkg_root.anchor(&self.root.resolve(&path)) {
+                    files.insert(path);
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn previous_content(
+        &self,
+        from_commit: &str,
+        file_path: &AbsoluteSystemPath,
+    ) -> Result<Vec<u8>, Error> {
+        self.execute_jj_command(
+            &[
+                "file",
+                "show",
+                "-r",
+                &revision(from_commit),
+                &self.root_file(file_path)?,
+            ],
+            false,
+        )
+    }
+
+    fn get_package_file_hashes(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        package_path: &AnchoredSystemPath,
+        inputs: &[&str],
+    ) -> Result<GitHashes, Error> {
+        if !inputs.is_empty() {
+            return get_package_file_hashes_from_inputs(
+                &self.root,
+                nxpkg_root,
+                package_path,
+                inputs,
+            );
+        }
+
+        // The files in the working copy commit, the tracked ones and new ones
+        // that aren't ignored
+        let full_pkg_path = nxpkg_root.resolve(package_path);
+        let pkg_path = self.root.anchor(&full_pkg_path)?.to_unix();
+        let fileset = format!("root:{:?}", pkg_path.as_str());
+        let mut args = vec!["file", "list", "-r", "@"];
+        if !pkg_path.as_str().is_empty() {
+            args.push(&fileset);
+        }
+        let output = self.execute_jj_command(&args, false)?;
+        let to_hash = files_from_stdout(output)?
+            .iter()
+            .map(|path| path.to_unix())
+            .collect();
+        let mut hashes = GitHashes::new();
+        hash_objects(&self.root, &full_pkg_path, to_hash, &mut hashes)?;
+        Ok(hashes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::process::Command;
+
+    use nxpkgpath::{RelativeUnixPath, RelativeUnixPathBuf};
+
+    use super::*;
+    use crate::SCM;
+
+    fn require_jj_cmd(repo_root: &AbsoluteSystemPath, args: &[&str]) {
+        let mut cmd = Command::new("jj");
+        cmd.args(args)
+            .env("JJ_USER", "test")
+            .env("JJ_EMAIL", "test@example.com")
+            .current_dir(repo_root);
+        assert!(cmd.output().unwrap().status.success());
+    }
+
+    fn write_file(repo_root: &AbsoluteSystemPath, path: &str, contents: &str) {
+        let path = repo_root
+            .join_unix_path(RelativeUnixPath::new(path).unwrap())
+            .unwrap();
+        path.ensure_dir().unwrap();
+        path.create_with_contents(contents).unwrap();
+    }
+
+    // A colocated repository
+    fn setup_repository() -> (tempfile::TempDir, AbsoluteSystemPathBuf) {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo_root = AbsoluteSystemPathBuf::try_from(tmp.path())
+            .unwrap()
+            .to_realpath()
+            .unwrap();
+        require_jj_cmd(&repo_root, &["git", "init", "--colocate"]);
+        write_file(&repo_root, ".gitignore", "*.log");
+        write_file(&repo_root, "my-pkg/package.json", "{}");
+        write_file(&repo_root, "my-pkg/file", "original");
+        require_jj_cmd(&repo_root, &["commit", "-m", "first"]);
+        require_jj_cmd(&repo_root, &["bookmark", "create", "-r", "@-", "main"]);
+        write_file(&repo_root, "my-pkg/file", "modified");
+        write_file(&repo_root, "my-pkg/new-file", "new");
+        write_file(&repo_root, "my-pkg/debug.log", "ignored");
+        (tmp, repo_root)
+    }
+
+    #[test]
+    fn test_revision() {
+        assert_eq!(revision("HEAD"), "@-");
+        assert_eq!(revision("HEAD~2"), "@---");
+        assert_eq!(revision("main"), r#""main""#);
+        assert_eq!(revision("main~2^"), r#""main"---"#);
+        assert_eq!(revision("main^0"), r#""main""#);
+        assert_eq!(revision("main^2"), r#""main^2""#);
+        assert_eq!(revision("a | all()"), r#""a | all()""#);
+    }
+
+    #[test]
+    #[ignore = "requires jj, run with --ignored where jj is installed"]
+    fn test_jujutsu_repository() {
+        let (_tmp, repo_root) = setup_repository();
+        let scm = SCM::new(&repo_root.join_component("my-pkg"));
+        assert_eq!(scm.kind(), Some(VcsKind::Jujutsu));
+        assert_eq!(scm.get_current_branch(&repo_root).unwrap(), "main");
+
+        let changed = scm.changed_files(&repo_root, None, "HEAD").unwrap();
+        let mut changed = changed
+            .iter()
+            .map(|path| path.to_unix())
+            .collect::<Vec<_>>();
+        changed.sort();
+        assert_eq!(
+            changed,
+            [
+                RelativeUnixPathBuf::new("my-pkg/file").unwrap(),
+                RelativeUnixPathBuf::new("my-pkg/new-file").unwrap(),
+            ]
+        );
+
+        let file = repo_root.join_components(&["my-pkg", "file"]);
+        assert_eq!(scm.previous_content("HEAD", &file).unwrap(), b"original");
+
+        let package_path = AnchoredSystemPathBuf::from_raw("my-pkg").unwrap();
+        let hashes = scm
+            .get_package_file_hashes::<&str>(&repo_root, &package_path, &[])
+            .unwrap();
+        let mut files = hashes.keys().map(|path| path.as_str()).collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, ["file", "new-file", "package.json"]);
+    }
+}
@@ -5,22 +5,39 @@
 
 use std::{
     backtrace::{self, Backtrace},
+    collections::HashSet,
+    fmt,
     io::Read,
     process::{Child, Command},
+    sync::OnceLock,
 };
 
 use bstr::io::BufReadExt;
 use thiserror::Error;
 use tracing::debug;
-use nxpkgpath::{AbsoluteSystemPath, AbsoluteSystemPathBuf, PathError, RelativeUnixPathBuf};
+use nxpkgpath::{
+    AbsoluteSystemPath, AbsoluteSystemPathBuf, AnchoredSystemPath, AnchoredSystemPathBuf,
+    PathError, RelativeUnixPathBuf,
+};
 
+mod checkout;
 pub mod git;
 mod hash_object;
+mod jujutsu;
+mod libgit;
 mod ls_tree;
 pub mod manual;
+mod mercurial;
 pub mod package_deps;
 mod status;
 
+pub use checkout::{Checkout, SparseCheckout};
+pub use jujutsu::Jujutsu;
+pub use libgit::LibGit;
+pub use mercurial::Mercurial;
+
+use crate::package_deps::GitHashes;
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("git error on {1}: {0}")]
@@ -31,6 +48,10 @@ pub enum Error {
     ),
     #[error("git error: {0}")]
     Git(String, #[backtrace] backtrace::Backtrace),
+    #[error("hg error: {0}")]
+    Mercurial(String, #[backtrace] backtrace::Backtrace),
+    #[error("jj error: {0}")]
+    Jujutsu(String, #[backtrace] backtrace::Backtrace),
     #[error(
         "{0} is not part of a git repository. git is required for operations based on source \
          control"
@@ -130,6 +151,8 @@ pub(crate) fn wait_for_success<R: Read, T>(
 pub struct Git {
     root: AbsoluteSystemPathBuf,
     bin: AbsoluteSystemPathBuf,
+    checkout: Checkout,
+    sparse_checkout: OnceLock<Option<SparseCheckout>>,
 }
 
 #[derive(Debug, Error)]
@@ -154,7 +177,13 @@ impl Git {
         });
         let root =
             find_git_root(path_in_repo).map_err(|e| GitError::Root(path_in_repo.to_owned(), e))?;
-        Ok(Self { root, bin })
+        let checkout = Checkout::detect(&root);
+        Ok(Self {
+            root,
+            bin,
+            checkout,
+            sparse_checkout: OnceLock::new(),
+        })
     }
 }
 
@@ -185,24 +214,213 @@ fn find_git_root(nxpkg_root: &AbsoluteSystemPath) -> Result<AbsoluteSystemPathBu
     }
 }
 
+/// Splits the `~N` and `^N` suffixes that select an ancestor off a git ref,
+/// e. g. `main~2^` into `main` and `~2^`.
+pub(crate) fn split_ancestry(git_ref: &str) -> (&str, &str) {
+    let bytes = git_ref.as_bytes();
+    let mut end = bytes.len();
+    loop {
+        let digits = bytes[..end]
+            .iter()
+            .rev()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count();
+        match end.checked_sub(digits + 1) {
+            Some(operator) if operator > 0 && matches!(bytes[operator], b'~' | b'^') => {
+                end = operator;
+            }
+            _ => break,
+        }
+    }
+    git_ref.split_at(end)
+}
+
+/// Quotes a ref for Mercurial and jj revsets, which read a string literal as
+/// a symbol, so that a ref can't be read as a revset expression.
+pub(crate) fn quote_revset_symbol(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The version control system a repository is managed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VcsKind {
+    Git,
+    Mercurial,
+    Jujutsu,
+}
+
+impl VcsKind {
+    /// The version control system of the repository `path_in_repo` is in,
+    /// going by its metadata directory like [SCM::new], but without opening
+    /// the repository or looking for the binary.
+    pub fn detect(path_in_repo: &AbsoluteSystemPath) -> Option<VcsKind> {
+        find_repository(path_in_repo).map(|(_, kind)| kind)
+    }
+}
+
+// The nearest repository `path_in_repo` is in. jj is preferred over git in
+// colocated repositories.
+fn find_repository(path_in_repo: &AbsoluteSystemPath) -> Option<(&AbsoluteSystemPath, VcsKind)> {
+    path_in_repo.ancestors().find_map(|dir| {
+        [
+            (".jj", VcsKind::Jujutsu),
+            (".hg", VcsKind::Mercurial),
+            (".git", VcsKind::Git),
+        ]
+        .into_iter()
+        .find(|(metadata, _)| dir.join_component(metadata).exists())
+        .map(|(_, kind)| (dir, kind))
+    })
+}
+
+/// The operations nxpkg needs from a version control system.
+///
+/// File hashes are always git blob hashes, whichever system the repository
+/// uses, so that cache keys don't depend on it.
+pub trait SourceControl: fmt::Debug + Send + Sync {
+    /// Name of the backend, used when logging
+    fn name(&self) -> &str;
+
+    fn kind(&self) -> VcsKind;
+
+    /// Root of the repository's working copy
+    fn root(&self) -> &AbsoluteSystemPath;
+
+    fn get_current_branch(&self) -> Result<String, Error>;
+
+    fn get_current_sha(&self) -> Result<String, Error>;
+
+    /// Files changed in the working copy since `to_commit` and, given a
+    /// `from_commit`, the ones changed between the commits' merge base and
+    /// `to_commit`. Untracked files are included. Only files inside
+    /// `nxpkg_root` are returned, relative to it.
+    fn changed_files(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        from_commit: Option<&str>,
+        to_commit: &str,
+    ) -> Result<HashSet<AnchoredSystemPathBuf>, Error>;
+
+    /// The content of a file at a previous commit
+    fn previous_content(
+        &self,
+        from_commit: &str,
+        file_path: &AbsoluteSystemPath,
+    ) -> Result<Vec<u8>, Error>;
+
+    /// Hashes the files of a package that aren't ignored. Given `inputs`, only
+    /// the files matching them are hashed.
+    fn get_package_file_hashes(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        package_path: &AnchoredSystemPath,
+        inputs: &[&str],
+    ) -> Result<GitHashes, Error>;
+
+    fn hash_files(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        files: &[&AnchoredSystemPath],
+    ) -> Result<GitHashes, Error> {
+        package_deps::hash_files(self.root(), nxpkg_root, files.iter())
+    }
+
+    fn checkout(&self) -> Checkout {
+        Checkout::default()
+    }
+
+    fn sparse_checkout(&self) -> Option<&SparseCheckout> {
+        None
+    }
+
+    // Only a shallow clone can be missing the merge base
+    fn has_merge_base(&self, _from_ref: &str, _to_ref: &str) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    fn deepen(&self, _depth: u32) -> Result<(), Error> {
+        Err(Error::git_error(
+            "deepening a shallow clone requires the git binary",
+        ))
+    }
+}
+
 #[derive(Debug)]
 pub enum SCM {
-    Git(Git),
+    Vcs(Box<dyn SourceControl>),
     Manual,
 }
 
 impl SCM {
+    /// Finds the repository `path_in_repo` is in. Mercurial and jj
+    /// repositories are recognized by their `.hg` and `.jj` directories, the
+    /// nearest one wins. jj is preferred over git in colocated repositories.
+    /// Without a repository, files are hashed manually.
     #[tracing::instrument]
     pub fn new(path_in_repo: &AbsoluteSystemPath) -> SCM {
-        Git::find(path_in_repo).map(SCM::Git).unwrap_or_else(|e| {
-            debug!("{}, continuing with manual hashing", e);
-            SCM::Manual
-        })
+        match find_repository(path_in_repo) {
+            Some((repo_root, VcsKind::Jujutsu)) => match Jujutsu::find(repo_root) {
+                Ok(jj) => return SCM::Vcs(Box::new(jj)),
+                Err(e) => debug!("{}, looking for a git repository", e),
+            },
+            Some((repo_root, VcsKind::Mercurial)) => match Mercurial::find(repo_root) {
+                Ok(hg) => return SCM::Vcs(Box::new(hg)),
+                Err(e) => debug!("{}, looking for a git repository", e),
+            },
+            Some((_, VcsKind::Git)) | None => {}
+        }
+
+        match Self::find_git(path_in_repo) {
+            Some(git) => SCM::Vcs(git),
+            None => SCM::Manual,
+        }
+    }
+
+    /// Reads the repository in-process when possible. The `git` binary is
+    /// only used for shallow and sparse checkouts, whose history and working
+    /// tree libgit2 doesn't fully support.
+    fn find_git(path_in_repo: &AbsoluteSystemPath) -> Option<Box<dyn SourceControl>> {
+        let lib_git = match LibGit::find(path_in_repo) {
+            Ok(lib_git) if lib_git.checkout().is_complete() => return Some(Box::new(lib_git)),
+            Ok(lib_git) => Some(lib_git),
+            Err(e) => {
+                debug!("failed to open repository with libgit2: {}", e);
+                None
+            }
+        };
+
+        match Git::find(path_in_repo) {
+            Ok(git) => Some(Box::new(git)),
+            // Without a git binary a partial checkout is still better served
+            // by libgit2 than by manual hashing
+            Err(e) => match lib_git {
+                Some(lib_git) => {
+                    debug!(
+                        "{}, continuing with libgit2 on a {:?}",
+                        e,
+                        lib_git.checkout()
+                    );
+                    Some(Box::new(lib_git))
+                }
+                None => {
+                    debug!("{}, continuing with manual hashing", e);
+                    None
+                }
+            },
+        }
     }
 
     pub fn is_manual(&self) -> bool {
         matches!(self, SCM::Manual)
     }
+
+    /// The version control system in use, or `None` when hashing manually
+    pub fn kind(&self) -> Option<VcsKind> {
+        match self {
+            SCM::Vcs(vcs) => Some(vcs.kind()),
+            SCM::Manual => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -211,8 +429,41 @@ mod tests {
 
     use nxpkgpath::{AbsoluteSystemPath, AbsoluteSystemPathBuf};
 
-    use super::find_git_root;
-    use crate::Error;
+    use super::{find_git_root, quote_revset_symbol, split_ancestry};
+    use crate::{Error, VcsKind};
+
+    #[test]
+    fn test_detect_vcs_kind() {
+        let (_tmp, root) = tmp_dir();
+        let package = root.join_components(&["packages", "a"]);
+        package.create_dir_all().unwrap();
+
+        root.join_component(".git").create_dir_all().unwrap();
+        assert_eq!(VcsKind::detect(&package), Some(VcsKind::Git));
+        // Colocated jj repositories are read with jj
+        root.join_component(".jj").create_dir_all().unwrap();
+        assert_eq!(VcsKind::detect(&package), Some(VcsKind::Jujutsu));
+        // The nearest repository wins
+        package.join_component(".hg").create_dir_all().unwrap();
+        assert_eq!(VcsKind::detect(&package), Some(VcsKind::Mercurial));
+    }
+
+    #[test]
+    fn test_split_ancestry() {
+        assert_eq!(split_ancestry("main"), ("main", ""));
+        assert_eq!(split_ancestry("HEAD~1"), ("HEAD", "~1"));
+        assert_eq!(split_ancestry("main~2^"), ("main", "~2^"));
+        assert_eq!(split_ancestry("v1.2^2"), ("v1.2", "^2"));
+        assert_eq!(split_ancestry("release-10"), ("release-10", ""));
+        assert_eq!(split_ancestry("~1"), ("~1", ""));
+    }
+
+    #[test]
+    fn test_quote_revset_symbol() {
+        assert_eq!(quote_revset_symbol("main"), r#""main""#);
+        assert_eq!(quote_revset_symbol("a|b"), r#""a|b""#);
+        assert_eq!(quote_revset_symbol(r#"it's "x"\"#), r#""it's \"x\"\\""#);
+    }
 
     fn tmp_dir() -> (tempfile::TempDir, AbsoluteSystemPathBuf) {
         let tmp_dir = tempfile::tempdir().unwrap();
@@ -0,0 +1,666 @@
+use std::{collections::HashSet, path::Path, sync::OnceLock};
+
+use git2::{
+    Diff, DiffOptions, ErrorCode, ObjectType, Repository, Status, StatusOptions, TreeWalkMode,
+    TreeWalkResult,
+};
+use nxpkgpath::{
+    AbsoluteSystemPath, AbsoluteSystemPathBuf, AnchoredSystemPath, AnchoredSystemPathBuf,
+    RelativeUnixPath, RelativeUnixPathBuf,
+};
+
+use crate::{
+    hash_object::hash_objects,
+    package_deps::{get_package_file_hashes_from_inputs, GitHashes},
+    Checkout, Error, SourceControl, SparseCheckout, VcsKind,
+};
+
+/// Reads a git repository in-process with libgit2 instead of running the
+/// `git` binary.
+///
+/// Every operation mirrors a git command that `Git` runs, and produces the
+/// same results.
+#[derive(Debug)]
+pub struct LibGit {
+    root: AbsoluteSystemPathBuf,
+    checkout: Checkout,
+    sparse_checkout: OnceLock<Option<SparseCheckout>>,
+}
+
+fn context(context: &str) -> impl FnOnce(git2::Error) -> Error + '_ {
+    move |e| Error::git2_error_context(e, context.to_string())
+}
+
+impl LibGit {
+    pub(crate) fn find(path_in_repo: &AbsoluteSystemPath) -> Result<Self, Error> {
+        // We look for the root ourselves instead of letting libgit2 discover it,
+        // so that symlinks in the path are kept like `git rev-parse --show-cdup`
+        // keeps them
+        let root = path_in_repo
+            .ancestors()
+            .find(|dir| dir.join_component(".git").exists())
+            .ok_or_else(|| Error::GitRequired(path_in_repo.to_owned()))?;
+        Repository::open(root).map_err(context(root.as_str()))?;
+
+        Ok(Self {
+            root: root.to_owned(),
+            checkout: Checkout::detect(root),
+            sparse_checkout: OnceLock::new(),
+        })
+    }
+
+    pub fn root(&self) -> &AbsoluteSystemPath {
+        &self.root
+    }
+
+    pub fn checkout(&self) -> Checkout {
+        self.checkout
+    }
+
+    // `git2::Repository` isn't `Sync`, so rather than locking a shared one,
+    // each operation opens the repository itself
+    fn open(&self) -> Result<Repository, Error> {
+        Repository::open(&self.root).map_err(context(self.root.as_str()))
+    }
+
+    pub(crate) fn has_merge_base(&self, from_ref: &str, to_ref: &str) -> Result<bool, Error> {
+        let repo = self.open()?;
+        let from = repo
+            .revparse_single(from_ref)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(context(from_ref))?;
+        let to = repo
+            .revparse_single(to_ref)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(context(to_ref))?;
+        match repo.merge_base(from.id(), to.id()) {
+            Ok(_) => Ok(true),
+            Err(e) if e.code() == ErrorCode::NotFound => Ok(false),
+            Err(e) => Err(Error::git2_error_context(e, "merge-base".to_string())),
+        }
+    }
+
+    pub(crate) fn get_package_file_hashes<S: AsRef<str>>(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        package_path: &AnchoredSystemPath,
+        inputs: &[S],
+    ) -> Result<GitHashes, Error> {
+        if inputs.is_empty() {
+            self.get_package_file_hashes_from_index(nxpkg_root, package_path)
+        } else {
+            get_package_file_hashes_from_inputs(&self.root, nxpkg_root, package_path, inputs)
+        }
+    }
+
+    #[tracing::instrument(skip(self, nxpkg_root))]
+    fn get_package_file_hashes_from_index(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        package_path: &AnchoredSystemPath,
+    ) -> Result<GitHashes, Error> {
+        let repo = self.open()?;
+        let full_pkg_path = nxpkg_root.resolve(package_path);
+        let pkg_prefix = self.root.anchor(&full_pkg_path)?.to_unix();
+        let mut hashes = self.head_tree_hashes(&repo, &pkg_prefix)?;
+        // Note: to_hash is *git repo relative*
+        let to_hash = self.status(&repo, &pkg_prefix, &mut hashes)?;
+        hash_objects(&self.root, &full_pkg_path, to_hash, &mut hashes)?;
+        Ok(hashes)
+    }
+
+    // Equivalent to `git ls-tree -r HEAD` run in the package: the hashes of
+    // every committed file in the package, keyed by their package relative path
+    fn head_tree_hashes(
+        &self,
+        repo: &Repository,
+        pkg_prefix: &RelativeUnixPathBuf,
+    ) -> Result<GitHashes, Error> {
+        let head_tree = repo
+            .head()
+            .and_then(|head| head.peel_to_tree())
+            .map_err(context("HEAD"))?;
+        let tree = if pkg_prefix.as_str().is_empty() {
+            head_tree
+        } else {
+            match head_tree.get_path(Path::new(pkg_prefix.as_str())) {
+                Ok(entry) if entry.kind() == Some(ObjectType::Tree) => {
+                    repo.find_tree(entry.id())
+                        .map_err(context(pkg_prefix.as_str()))?
+                }
+                // Nothing in the package has been committed yet
+                Ok(_) => return Ok(GitHashes::new()),
+                Err(e) if e.code() == ErrorCode::NotFound => return Ok(GitHashes::new()),
+                Err(e) => return Err(Error::git2_error_context(e, pkg_prefix.to_string())),
+            }
+        };
+
+        let mut hashes = GitHashes::new();
+        let mut walk_error = None;
+        let walk_result = tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() == Some(ObjectType::Tree) {
+                return TreeWalkResult::Ok;
+            }
+            let path = String::from_utf8([dir.as_bytes(), entry.name_bytes()].concat())
+                .map_err(Error::from)
+                .and_then(|path| Ok(RelativeUnixPathBuf::new(path)?));
+            match path {
+                Ok(path) => {
+                    hashes.insert(path, entry.id().to_string());
+                    TreeWalkResult::Ok
+                }
+                Err(e) => {
+                    walk_error = Some(e);
+                    TreeWalkResult::Abort
+                }
+            }
+        });
+        if let Some(e) = walk_error {
+            return Err(e);
+        }
+        walk_result.map_err(context(pkg_prefix.as_str()))?;
+
+        Ok(hashes)
+    }
+
+    // Equivalent to `git status --untracked-files --no-renames -z -- .` run in
+    // the package. Deleted files are removed from `hashes`, and the git root
+    // relative paths of every other changed file are returned to be hashed.
+    fn status(
+        &self,
+        repo: &Repository,
+        pkg_prefix: &RelativeUnixPathBuf,
+        hashes: &mut GitHashes,
+    ) -> Result<Vec<RelativeUnixPathBuf>, Error> {
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_ignored(false)
+            .renames_head_to_index(false)
+            .renames_index_to_workdir(false)
+            .disable_pathspec_match(true);
+        if !pkg_prefix.as_str().is_empty() {
+            options.pathspec(pkg_prefix.as_str());
+        }
+        let statuses = repo
+            .statuses(Some(&mut options))
+            .map_err(context("status"))?;
+
+        let mut to_hash = Vec::new();
+        for entry in statuses.iter() {
+            let path = RelativeUnixPathBuf::new(String::from_utf8(entry.path_bytes().to_vec())?)?;
+            // The pathspec already limits the entries to the package, this is
+            // only a safeguard
+            let Ok(package_relative_path) = path.strip_prefix(pkg_prefix) else {
+                continue;
+            };
+            let status = entry.status();
+            // A file deleted from the index can be back in the working tree as
+            // an untracked file, which `git status` lists as a separate entry
+            if status.intersects(Status::INDEX_DELETED | Status::WT_DELETED)
+                && !status.contains(Status::WT_NEW)
+            {
+                hashes.remove(&package_relative_path);
+            } else {
+                to_hash.push(path);
+            }
+        }
+        Ok(to_hash)
+    }
+
+    // Equivalent to `git branch --show-current`
+    pub(crate) fn get_current_branch(&self) -> Result<String, Error> {
+        let repo = self.open()?;
+        let head = match repo.head() {
+            Ok(head) => head,
+            // A branch without any commits yet is still the current branch
+            Err(e) if e.code() == ErrorCode::UnbornBranch => {
+                let head = repo.find_reference("HEAD").map_err(context("HEAD"))?;
+                return Ok(head
+                    .symbolic_target()
+                    .and_then(|target| target.strip_prefix("refs/heads/"))
+                    .unwrap_or_default()
+                    .to_owned());
+            }
+            Err(e) => return Err(Error::git2_error_context(e, "HEAD".to_string())),
+        };
+        // A detached HEAD has no current branch
+        match head.is_branch() {
+            true => Ok(head.shorthand().unwrap_or_default().to_owned()),
+            false => Ok(String::new()),
+        }
+    }
+
+    // Equivalent to `git rev-parse HEAD`
+    pub(crate) fn get_current_sha(&self) -> Result<String, Error> {
+        let repo = self.open()?;
+        let commit = repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .map_err(context("HEAD"))?;
+        Ok(commit.id().to_string())
+    }
+
+    pub(crate) fn changed_files(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        from_commit: Option<&str>,
+        to_commit: &str,
+    ) -> Result<HashSet<AnchoredSystemPathBuf>, Error> {
+        let repo = self.open()?;
+        let pathspec = self.root.anchor(nxpkg_root)?.to_unix();
+        let diff_options = || {
+            let mut options = DiffOptions::new();
+            options.disable_pathspec_match(true);
+            if !pathspec.as_str().is_empty() {
+                options.pathspec(pathspec.as_str());
+            }
+            options
+        };
+
+        let mut files = HashSet::new();
+
+        // `git diff --name-only <to_commit>`, the working tree against a commit
+        let to_tree = repo
+            .revparse_single(to_commit)
+            .and_then(|object| object.peel_to_tree())
+            .map_err(context(to_commit))?;
+        let diff = repo
+            .diff_tree_to_workdir_with_index(Some(&to_tree), Some(&mut diff_options()))
+            .map_err(context("diff"))?;
+        self.add_files_from_diff(&mut files, nxpkg_root, &diff)?;
+
+        // `git diff --name-only <from_commit>...<to_commit>`, the changes since
+        // the commits' merge base
+        if let Some(from_commit) = from_commit {
+            let from = repo
+                .revparse_single(from_commit)
+                .and_then(|object| object.peel_to_commit())
+                .map_err(context(from_commit))?;
+            let to = repo
+                .revparse_single(to_commit)
+                .and_then(|object| object.peel_to_commit())
+                .map_err(context(to_commit))?;
+            let merge_base = repo
+                .merge_base(from.id(), to.id())
+                .and_then(|oid| repo.find_commit(oid))
+                .and_then(|commit| commit.tree())
+                .map_err(context("merge-base"))?;
+            let diff = repo
+                .diff_tree_to_tree(
+                    Some(&merge_base),
+                    Some(&to.tree().map_err(context(to_commit))?),
+                    Some(&mut diff_options()),
+                )
+                .map_err(context("diff"))?;
+            self.add_files_from_diff(&mut files, nxpkg_root, &diff)?;
+        }
+
+        // `git ls-files --others --exclude-standard`, files that aren't tracked
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_ignored(false)
+            .disable_pathspec_match(true);
+        if !pathspec.as_str().is_empty() {
+            options.pathspec(pathspec.as_str());
+        }
+        let statuses = repo
+            .statuses(Some(&mut options))
+            .map_err(context("status"))?;
+        for entry in statuses
+            .iter()
+            .filter(|entry| entry.status().contains(Status::WT_NEW))
+        {
+            let path = String::from_utf8(entry.path_bytes().to_vec())?;
+            files.insert(self.reanchor_path_from_git_root_to_nxpkg_root(
+                nxpkg_root,
+                RelativeUnixPath::new(&path)?,
+            )?);
+        }
+
+        Ok(files)
+    }
+
+    fn add_files_from_diff(
+        &self,
+        files: &mut HashSet<AnchoredSystemPathBuf>,
+        nxpkg_root: &AbsoluteSystemPath,
+        diff: &Diff,
+    ) -> Result<(), Error> {
+        for delta in diff.deltas() {
+            // Renames aren't detected, so both sides have the same path, unless
+            // one of them doesn't exist
+            for file in [delta.old_file(), delta.new_file()] {
+                let Some(path) = file.path_bytes() else {
+                    continue;
+                };
+                let path = String::from_utf8(path.to_vec())?;
+                files.insert(self.reanchor_path_from_git_root_to_nxpkg_root(
+                    nxpkg_root,
+                    RelativeUnixPath::new(&path)?,
+                )?);
+            }
+        }
+        Ok(())
+    }
+
+    fn reanchor_path_from_git_root_to_nxpkg_root(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        path: &RelativeUnixPath,
+    ) -> Result<AnchoredSystemPathBuf, Error> {
+        let absolute_file_path = self.root.join_unix_path(path)?;
+        let anchored_to_nxpkg_root_file_path = nxpkg_root.anchor(&absolute_file_path)?;
+        Ok(anchored_to_nxpkg_root_file_path)
+    }
+
+    // Equivalent to `git show <from_commit>:<file_path>`
+    pub(crate) fn previous_content(
+        &self,
+        from_commit: &str,
+        file_path: &AbsoluteSystemPath,
+    ) -> Result<Vec<u8>, Error> {
+        let repo = self.open()?;
+        let anchored_file_path = self.root.anchor(file_path)?.to_unix();
+        let spec = format!("{}:{}", from_commit, anchored_file_path);
+        let blob = repo
+            .revparse_single(&spec)
+            .and_then(|object| object.peel_to_blob())
+            .map_err(context(&spec))?;
+        Ok(blob.content().to_vec())
+    }
+}
+
+impl SourceControl for LibGit {
+    fn name(&self) -> &str {
+        "libgit2"
+    }
+
+    fn kind(&self) -> VcsKind {
+        VcsKind::Git
+    }
+
+    fn root(&self) -> &AbsoluteSystemPath {
+        &self.root
+    }
+
+    fn get_current_branch(&self) -> Result<String, Error> {
+        LibGit::get_current_branch(self)
+    }
+
+    fn get_current_sha(&self) -> Result<String, Error> {
+        LibGit::get_current_sha(self)
+    }
+
+    fn changed_files(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        from_commit: Option<&str>,
+        to_commit: &str,
+    ) -> Result<HashSet<AnchoredSystemPathBuf>, Error> {
+        LibGit::changed_files(self, nxpkg_root, from_commit, to_commit)
+    }
+
+    fn previous_content(
+        &self,
+        from_commit: &str,
+        file_path: &AbsoluteSystemPath,
+    ) -> Result<Vec<u8>, Error> {
+        LibGit::previous_content(self, from_commit, file_path)
+    }
+
+    fn get_package_file_hashes(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        package_path: &AnchoredSystemPath,
+        inputs: &[&str],
+    ) -> Result<GitHashes, Error> {
+        LibGit::get_package_file_hashes(self, nxpkg_root, package_path, inputs)
+    }
+
+    fn checkout(&self) -> Checkout {
+        self.checkout
+    }
+
+    fn sparse_checkout(&self) -> Option<&SparseCheckout> {
+        if !self.checkout.sparse {
+            return None;
+        }
+        self.sparse_checkout
+            .get_or_init(|| self.read_sparse_checkout())
+            .as_ref()
+    }
+
+    fn has_merge_base(&self, from_ref: &str, to_ref: &str) -> Result<bool, Error> {
+        LibGit::has_merge_base(self, from_ref, to_ref)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{assert_matches::assert_matches, process::Command};
+
+    use nxpkgpath::{AbsoluteSystemPathBuf, AnchoredSystemPathBuf};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{Git, SCM};
+
+    fn tmp_dir() -> (tempfile::TempDir, AbsoluteSystemPathBuf) {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let dir = AbsoluteSystemPathBuf::try_from(tmp_dir.path())
+            .unwrap()
+            .to_realpath()
+            .unwrap();
+        (tmp_dir, dir)
+    }
+
+    fn require_git_cmd(repo_root: &AbsoluteSystemPath, args: &[&str]) {
+        let mut cmd = Command::new("git");
+        cmd.args(args).current_dir(repo_root);
+        assert!(cmd.output().unwrap().status.success());
+    }
+
+    fn write_file(repo_root: &AbsoluteSystemPath, path: &str, contents: &str) {
+        let path = repo_root
+            .join_unix_path(RelativeUnixPath::new(path).unwrap())
+            .unwrap();
+        path.ensure_dir().unwrap();
+        path.create_with_contents(contents).unwrap();
+    }
+
+    // Directory structure, covering everything `git status` can report:
+    // <root>/
+    //   .gitignore          <- ignores *.log
+    //   root-file
+    //   new-root-file       <- untracked
+    //   my-pkg/
+    //     package.json
+    //     committed-file
+    //     modified-file     <- modified
+    //     staged-file       <- modified and staged
+    //     deleted-file      <- deleted
+    //     removed-file      <- deleted with `git rm`
+    //     re-added-file     <- deleted with `git rm --cached`, still on disk
+    //     added-file        <- new and staged
+    //     new-file          <- untracked
+    //     debug.log         <- ignored
+    //     dir/nested-file
+    //     new-dir/new-file  <- in an untracked directory
+    //   my-pkg-other/
+    //     package.json      <- changed in the second commit
+    //     other-file        <- modified
+    //   new-pkg/
+    //     package.json      <- untracked package
+    fn setup_repository() -> (tempfile::TempDir, AbsoluteSystemPathBuf) {
+        let (tmp, repo_root) = tmp_dir();
+        for cmd in [
+            &["init", "."][..],
+            &["config", "--local", "user.name", "test"],
+            &["config", "--local", "user.email", "test@example.com"],
+        ] {
+            require_git_cmd(&repo_root, cmd);
+        }
+
+        for (path, contents) in [
+            (".gitignore", "*.log"),
+            ("root-file", "root"),
+            ("my-pkg/package.json", "{}"),
+            ("my-pkg/committed-file", "committed"),
+            ("my-pkg/modified-file", "original"),
+            ("my-pkg/staged-file", "original"),
+            ("my-pkg/deleted-file", "deleted"),
+            ("my-pkg/removed-file", "removed"),
+            ("my-pkg/re-added-file", "re-added"),
+            ("my-pkg/dir/nested-file", "nested"),
+            ("my-pkg-other/package.json", "{}"),
+            ("my-pkg-other/other-file", "original"),
+        ] {
+            write_file(&repo_root, path, contents);
+        }
+        require_git_cmd(&repo_root, &["add", "."]);
+        require_git_cmd(&repo_root, &["commit", "-m", "first"]);
+
+        write_file(
+            &repo_root,
+            "my-pkg-other/package.json",
+            r#"{"name": "other"}"#,
+        );
+        require_git_cmd(&repo_root, &["commit", "-am", "second"]);
+
+        write_file(&repo_root, "my-pkg/modified-file", "modified");
+        write_file(&repo_root, "my-pkg/staged-file", "staged");
+        require_git_cmd(&repo_root, &["add", "my-pkg/staged-file"]);
+        repo_root
+            .join_components(&["my-pkg", "deleted-file"])
+            .remove()
+            .unwrap();
+        require_git_cmd(&repo_root, &["rm", "my-pkg/removed-file"]);
+        require_git_cmd(&repo_root, &["rm", "--cached", "my-pkg/re-added-file"]);
+        write_file(&repo_root, "my-pkg/added-file", "added");
+        require_git_cmd(&repo_root, &["add", "my-pkg/added-file"]);
+        for (path, contents) in [
+            ("new-root-file", "new root"),
+            ("my-pkg/new-file", "new"),
+            ("my-pkg/debug.log", "ignored"),
+            ("my-pkg/new-dir/new-file", "new nested"),
+            ("my-pkg-other/other-file", "modified"),
+            ("new-pkg/package.json", "{}"),
+        ] {
+            write_file(&repo_root, path, contents);
+        }
+
+        (tmp, repo_root)
+    }
+
+    // The git binary and libgit2 backends for the same repository
+    fn backends(repo_root: &AbsoluteSystemPath) -> [SCM; 2] {
+        [
+            SCM::Vcs(Box::new(Git::find(repo_root).unwrap())),
+            SCM::Vcs(Box::new(LibGit::find(repo_root).unwrap())),
+        ]
+    }
+
+    #[test_case("my-pkg", &[] ; "package")]
+    #[test_case("my-pkg-other", &[] ; "package sharing a prefix")]
+    #[test_case("new-pkg", &[] ; "uncommitted package")]
+    #[test_case("", &[] ; "repository root")]
+    #[test_case("my-pkg", &["**/*-file", "!dir/**"] ; "package inputs")]
+    fn test_package_file_hashes_parity(package: &str, inputs: &[&str]) {
+        let (_tmp, repo_root) = setup_repository();
+        let package_path = AnchoredSystemPathBuf::from_raw(package).unwrap();
+
+        let [git, lib_git] = backends(&repo_root).map(|scm| {
+            scm.get_package_file_hashes(&repo_root, &package_path, inputs)
+                .unwrap()
+        });
+        assert!(!git.is_empty());
+        assert_eq!(lib_git, git);
+    }
+
+    #[test]
+    fn test_package_file_hashes_contents() {
+        let (_tmp, repo_root) = setup_repository();
+        let package_path = AnchoredSystemPathBuf::from_raw("my-pkg").unwrap();
+        let scm = SCM::Vcs(Box::new(LibGit::find(&repo_root).unwrap()));
+
+        let hashes = scm
+            .get_package_file_hashes::<&str>(&repo_root, &package_path, &[])
+            .unwrap();
+        let mut files = hashes.keys().map(|path| path.as_str()).collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                "added-file",
+                "committed-file",
+                "dir/nested-file",
+                "modified-file",
+                "new-dir/new-file",
+                "new-file",
+                "package.json",
+                "re-added-file",
+                "staged-file",
+            ]
+        );
+    }
+
+    #[test_case(None ; "working tree")]
+    #[test_case(Some("HEAD~1") ; "commit range")]
+    fn test_changed_files_parity(from_commit: Option<&str>) {
+        let (_tmp, repo_root) = setup_repository();
+
+        let [git, lib_git] = backends(&repo_root)
+            .map(|scm| scm.changed_files(&repo_root, from_commit, "HEAD").unwrap());
+        assert!(!git.is_empty());
+        assert_eq!(lib_git, git);
+    }
+
+    #[test]
+    fn test_repository_info_parity() {
+        let (_tmp, repo_root) = setup_repository();
+        let package_json = repo_root.join_components(&["my-pkg-other", "package.json"]);
+
+        let [git, lib_git] = backends(&repo_root).map(|scm| {
+            (
+                scm.get_current_branch(&repo_root).unwrap(),
+                scm.get_current_sha(&repo_root).unwrap(),
+                scm.previous_content("HEAD~1", &package_json).unwrap(),
+            )
+        });
+        assert_eq!(lib_git, git);
+    }
+
+    #[test]
+    fn test_prefers_libgit() {
+        let (_tmp, repo_root) = setup_repository();
+        let nested = repo_root.join_component("my-pkg");
+        assert_matches!(
+            SCM::new(&nested),
+            SCM::Vcs(vcs) if vcs.name() == "libgit2" && vcs.root() == &*repo_root
+        );
+    }
+
+    #[test]
+    fn test_symlinked_root() {
+        let (_tmp, tmp_root) = tmp_dir();
+        let git_root = tmp_root.join_component("actual_repo");
+        git_root.create_dir_all().unwrap();
+        require_git_cmd(&git_root, &["init", "."]);
+        git_root.join_component("inside").create_dir_all().unwrap();
+        let link = tmp_root.join_component("link");
+        link.symlink_to_dir("actual_repo").unwrap();
+
+        let git = LibGit::find(&link.join_component("inside")).unwrap();
+        assert_eq!(git.root(), &*link);
+    }
+
+    #[test]
+    fn test_no_root() {
+        let (_tmp, tmp_root) = tmp_dir();
+        assert_matches!(LibGit::find(&tmp_root), Err(Error::GitRequired(_)));
+    }
+}
@@ -10,7 +10,6 @@ use wax::{any, Glob, Pattern};
 use crate::{package_deps::GitHashes, Error};
 
 fn git_like_hash_file(path: &AbsoluteSystemPath) -> Result<String, Error> {
-    let mut hasher = Sha1::new();
     let mut f = path.open()?;
     let mut buffer = Vec::new();
     // Note that read_to_end reads the target if f is a symlink. Currently, this can
@@ -19,13 +18,65 @@ fn git_like_hash_file(path: &AbsoluteSystemPath) -> Result<String, Error> {
     // will want to ensure that the target is better accounted for in the set of
     // inputs to the task. Manual hashing, as well as global deps and other
     // places that support globs all ignore symlinks.
-    let size = f.read_to_end(&mut buffer)?;
+    f.read_to_end(&mut buffer)?;
+    Ok(git_like_hash_bytes(&buffer))
+}
+
+fn git_like_hash_bytes(contents: &[u8]) -> String {
+    let mut hasher = Sha1::new();
     hasher.update("blob ".as_bytes());
-    hasher.update(size.to_string().as_bytes());
+    hasher.update(contents.len().to_string().as_bytes());
     hasher.update([b'\0']);
-    hasher.update(buffer.as_slice());
+    hasher.update(contents);
     let result = hasher.finalize();
-    Ok(result.encode_hex::<String>())
+    result.encode_hex::<String>()
+}
+
+// Git treats a file as binary if there's a NUL byte in its first 8000 bytes
+const BINARY_SNIFF_LEN: usize = 8000;
+
+// Hashes a file as git would store it with `core.autocrlf` enabled: text files
+// have CRLF line endings converted to LF, binary files are hashed as is.
+fn git_like_hash_file_lf(path: &AbsoluteSystemPath) -> Result<String, Error> {
+    let contents = path.read()?;
+    let sniff_len = contents.len().min(BINARY_SNIFF_LEN);
+    if contents[..sniff_len].contains(&b'\0') || !contents.contains(&b'\r') {
+        return Ok(git_like_hash_bytes(&contents));
+    }
+
+    let mut normalized = Vec::with_capacity(contents.len());
+    let mut bytes = contents.iter().copied().peekable();
+    while let Some(byte) = bytes.next() {
+        if byte == b'\r' && bytes.peek() == Some(&b'\n') {
+            continue;
+        }
+        normalized.push(byte);
+    }
+    Ok(git_like_hash_bytes(&normalized))
+}
+
+// Rehashes the files in `hashes`, which are relative to `root_path`, with
+// their line endings normalized. Symlinks and files that can't be found keep
+// the hash they already have.
+pub(crate) fn normalize_line_endings(
+    root_path: &AbsoluteSystemPath,
+    hashes: GitHashes,
+) -> Result<GitHashes, Error> {
+    hashes
+        .into_iter()
+        .map(|(file, hash)| {
+            let path = root_path.resolve(&file.to_anchored_system_path_buf());
+            match path.symlink_metadata() {
+                Ok(metadata) if metadata.is_file() => {
+                    let normalized = git_like_hash_file_lf(&path)?;
+                    Ok((file, normalized))
+                }
+                Ok(_) => Ok((file, hash)),
+                Err(e) if e.is_io_error(ErrorKind::NotFound) => Ok((file, hash)),
+                Err(e) => Err(Error::from(e)),
+            }
+        })
+        .collect()
 }
 
 pub(crate) fn hash_files(
@@ -347,4 +398,26 @@ mod tests {
         .unwrap();
         assert_eq!(hashes, expected);
     }
+
+    #[test_case(b"a\nb\n", "422c2b7ab3b3c668038da977e4e93a5fc623169c" ; "lf is unchanged")]
+    #[test_case(b"a\r\nb\r\n", "422c2b7ab3b3c668038da977e4e93a5fc623169c" ; "crlf is normalized")]
+    #[test_case(b"a\0\r\nb\r\n", "328f72a9a08930e2eef50f8e4e204571ac8397aa" ; "binary is unchanged")]
+    fn test_normalize_line_endings(contents: &[u8], expected_hash: &str) {
+        let (_tmp, nxpkg_root) = tmp_dir();
+        nxpkg_root
+            .join_component("file.txt")
+            .create_with_contents(contents)
+            .unwrap();
+
+        let file = RelativeUnixPathBuf::new("file.txt").unwrap();
+        let missing = RelativeUnixPathBuf::new("missing.txt").unwrap();
+        let hashes = GitHashes::from([
+            (file.clone(), "original".to_string()),
+            (missing.clone(), "original".to_string()),
+        ]);
+
+        let normalized = normalize_line_endings(&nxpkg_root, hashes).unwrap();
+        assert_eq!(normalized.get(&file).unwrap(), expected_hash);
+        assert_eq!(normalized.get(&missing).unwrap(), "original");
+    }
 }
@@ -0,0 +1,291 @@
+use std::{backtrace::Backtrace, collections::HashSet, process::Command};
+
+use nxpkgpath::{
+    AbsoluteSystemPath, AbsoluteSystemPathBuf, AnchoredSystemPath, AnchoredSystemPathBuf,
+    RelativeUnixPathBuf,
+};
+
+use crate::{
+    hash_object::hash_objects,
+    package_deps::{get_package_file_hashes_from_inputs, GitHashes},
+    quote_revset_symbol, split_ancestry, Error, SourceControl, VcsKind,
+};
+
+/// A Mercurial repository, read by running `hg`
+#[derive(Debug)]
+pub struct Mercurial {
+    root: AbsoluteSystemPathBuf,
+    bin: AbsoluteSystemPathBuf,
+}
+
+impl Mercurial {
+    pub(crate) fn find(root: &AbsoluteSystemPath) -> Result<Self, Error> {
+        let bin = which::which("hg").map_err(|e| {
+            Error::Mercurial(
+                format!("could not find hg binary: {}", e),
+                Backtrace::capture(),
+            )
+        })?;
+        let bin = AbsoluteSystemPathBuf::try_from(bin.as_path())?;
+        Ok(Self {
+            root: root.to_owned(),
+            bin,
+        })
+    }
+
+    // HGPLAIN keeps aliases and other user configuration from changing the
+    // output, and `ui.slash` keeps paths in unix format on Windows
+    fn execute_hg_command(&self, args: &[&str]) -> Result<Vec<u8>, Error> {
+        let output = Command::new(self.bin.as_std_path())
+            .args(["--config", "ui.slash=true"])
+            .args(args)
+            .env("HGPLAIN", "1")
+            .current_dir(&self.root)
+            .output()?;
+
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(Error::Mercurial(
+                String::from_utf8_lossy(&output.stderr).to_string(),
+                Backtrace::capture(),
+            ))
+        }
+    }
+
+    // Limits a command to the files in `path`. Commands run in the root, so
+    // patterns are relative to it. The root itself needs no pattern.
+    fn pattern(&self, path: &AbsoluteSystemPath) -> Result<Option<String>, Error> {
+        let path = self.root.anchor(path)?.to_unix();
+        Ok((!path.as_str().is_empty()).then(|| format!("path:{}", path)))
+    }
+
+    fn add_files_from_stdout(
+        &self,
+        files: &mut HashSet<AnchoredSystemPathBuf>,
+        nxpkg_root: &AbsoluteSystemPath,
+        stdout: Vec<u8>,
+    ) -> Result<(), Error> {
+        for path in split_paths(&stdout) {
+            let path = self.root.join_unix_path(RelativeUnixPathBuf::new(path?)?)?;
+            files.insert(nxpkg_root.anchor(&path)?);
+        }
+        Ok(())
+    }
+}
+
+// Splits the NUL separated output of `hg status -0` and `hg files -0`
+fn split_paths(stdout: &[u8]) -> impl Iterator<Item = Result<String, Error>> + '_ {
+    stdout
+        .split(|byte| *byte == b'\0')
+        .filter(|path| !path.is_empty())
+        .map(|path| Ok(String::from_utf8(path.to_vec())?))
+}
+
+// The revset of a git ref. `HEAD`, which is the default upper bound of a
+// range, is `.` in Mercurial, other refs are quoted. `~N` and `^N` select
+// ancestors in Mercurial as well.
+fn revision(git_ref: &str) -> String {
+    let (name, ancestry) = split_ancestry(git_ref);
+    match name {
+        "HEAD" => format!(".{}", ancestry),
+        name => format!("{}{}", quote_revset_symbol(name), ancestry),
+    }
+}
+
+impl SourceControl for Mercurial {
+    fn name(&self) -> &str {
+        "hg"
+    }
+
+    fn kind(&self) -> VcsKind {
+        VcsKind::Mercurial
+    }
+
+    fn root(&self) -> &AbsoluteSystemPath {
+        &self.root
+    }
+
+    // An active bookmark is the closest thing to a git branch, named branches
+    // are the fallback
+    fn get_current_branch(&self) -> Result<String, Error> {
+        let output = self.execute_hg_command(&["log", "-r", ".", "-T", "{activebookmark}"])?;
+        let bookmark = String::from_utf8(output)?;
+        if !bookmark.trim().is_empty() {
+            return Ok(bookmark.trim().to_owned());
+        }
+        let output = self.execute_hg_command(&["branch"])?;
+        Ok(String::from_utf8(output)?.trim().to_owned())
+    }
+
+    fn get_current_sha(&self) -> Result<String, Error> {
+        let output = self.execute_hg_command(&["log", "-r", ".", "-T", "{node}"])?;
+        Ok(String::from_utf8(output)?.trim().to_owned())
+    }
+
+    fn changed_files(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        from_commit: Option<&str>,
+        to_commit: &str,
+    ) -> Result<HashSet<AnchoredSystemPathBuf>, Error> {
+        let pattern = self.pattern(nxpkg_root)?;
+        let to_commit = revision(to_commit);
+        let mut files = HashSet::new();
+
+        // The working directory against `to_commit`, including untracked files
+        let mut args = vec!["status", "-n", "-0", "--rev", &to_commit];
+        args.extend(pattern.as_deref());
+        let output = self.execute_hg_command(&args)?;
+        self.add_files_from_stdout(&mut files, nxpkg_root, output)?;
+
+        // The changes since the commits' merge base
+        if let Some(from_commit) = from_commit {
+            let merge_base = format!("ancestor({}, {})", revision(from_commit), to_commit);
+            let mut args = vec![
+                "status",
+                "-n",
+                "-0",
+                "--rev",
+                &merge_base,
+                "--rev",
+                &to_commit,
+            ];
+            args.extend(pattern.as_deref());
+            let output = self.execute_hg_command(&args)?;
+            self.add_files_from_stdout(&mut files, nxpkg_root, output)?;
+        }
+
+        Ok(files)
+    }
+
+    fn previous_content(
+        &self,
+        from_commit: &str,
+        file_path: &AbsoluteSystemPath,
+    ) -> Result<Vec<u8>, Error> {
+        let anchored_file_path = self.root.anchor(file_path)?.to_unix();
+        self.execute_hg_command(&[
+            "cat",
+            "-r",
+            &revision(from_commit),
+            &format!("path:{}", anchored_file_path),
+        ])
+    }
+
+    fn get_package_file_hashes(
+        &self,
+        nxpkg_root: &AbsoluteSystemPath,
+        package_path: &AnchoredSystemPath,
+        inputs: &[&str],
+    ) -> Result<GitHashes, Error> {
+        if !inputs.is_empty() {
+            return get_package_file_hashes_from_inputs(
+                &self.root,
+                nxpkg_root,
+                package_path,
+                inputs,
+            );
+        }
+
+        // Modified, added, clean and unknown files: everything that's on disk
+        // and not ignored
+        let full_pkg_path = nxpkg_root.resolve(package_path);
+        let pattern = self.pattern(&full_pkg_path)?;
+        let mut args = vec!["status", "-macu", "-n", "-0"];
+        args.extend(pattern.as_deref());
+        let output = self.execute_hg_command(&args)?;
+        let to_hash = split_paths(&output)
+            .map(|path| Ok(RelativeUnixPathBuf::new(path?)?))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let mut hashes = GitHashes::new();
+        hash_objects(&self.root, &full_pkg_path, to_hash, &mut hashes)?;
+        Ok(hashes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::process::Command;
+
+    use nxpkgpath::RelativeUnixPath;
+
+    use super::*;
+    use crate::SCM;
+
+    fn require_hg_cmd(repo_root: &AbsoluteSystemPath, args: &[&str]) {
+        let mut cmd = Command::new("hg");
+        cmd.args(args)
+            .args(["--config", "ui.username=test <test@example.com>"])
+            .current_dir(repo_root);
+        assert!(cmd.output().unwrap().status.success());
+    }
+
+    fn write_file(repo_root: &AbsoluteSystemPath, path: &str, contents: &str) {
+        let path = repo_root
+            .join_unix_path(RelativeUnixPath::new(path).unwrap())
+            .unwrap();
+        path.ensure_dir().unwrap();
+        path.create_with_contents(contents).unwrap();
+    }
+
+    fn setup_repository() -> (tempfile::TempDir, AbsoluteSystemPathBuf) {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo_root = AbsoluteSystemPathBuf::try_from(tmp.path())
+            .unwrap()
+            .to_realpath()
+            .unwrap();
+        require_hg_cmd(&repo_root, &["init"]);
+        write_file(&repo_root, ".hgignore", "syntax: glob\n*.log\n");
+        write_file(&repo_root, "my-pkg/package.json", "{}");
+        write_file(&repo_root, "my-pkg/file", "original");
+        require_hg_cmd(&repo_root, &["commit", "-A", "-m", "first"]);
+        write_file(&repo_root, "my-pkg/file", "modified");
+        write_file(&repo_root, "my-pkg/new-file", "new");
+        write_file(&repo_root, "my-pkg/debug.log", "ignored");
+        (tmp, repo_root)
+    }
+
+    #[test]
+    fn test_revision() {
+        assert_eq!(revision("HEAD"), ".");
+        assert_eq!(revision("HEAD~1"), ".~1");
+        assert_eq!(revision("HEADER"), r#""HEADER""#);
+        assert_eq!(revision("main"), r#""main""#);
+        assert_eq!(revision("main~2^"), r#""main"~2^"#);
+        assert_eq!(revision("a') or all() or ('"), r#""a') or all() or ('""#);
+    }
+
+    #[test]
+    #[ignore = "requires Mercurial, run with --ignored where hg is installed"]
+    fn test_mercurial_repository() {
+        let (_tmp, repo_root) = setup_repository();
+        let scm = SCM::new(&repo_root.join_component("my-pkg"));
+        assert_eq!(scm.kind(), Some(VcsKind::Mercurial));
+
+        let changed = scm.changed_files(&repo_root, None, "HEAD").unwrap();
+        let mut changed = changed
+            .iter()
+            .map(|path| path.to_unix())
+            .collect::<Vec<_>>();
+        changed.sort();
+        assert_eq!(
+            changed,
+            [
+                RelativeUnixPathBuf::new("my-pkg/file").unwrap(),
+                RelativeUnixPathBuf::new("my-pkg/new-file").unwrap(),
+            ]
+        );
+
+        let file = repo_root.join_components(&["my-pkg", "file"]);
+        assert_eq!(scm.previous_content("HEAD", &file).unwrap(), b"original");
+
+        let package_path = AnchoredSystemPathBuf::from_raw("my-pkg").unwrap();
+        let hashes = scm
+            .get_package_file_hashes::<&str>(&repo_root, &package_path, &[])
+            .unwrap();
+        let mut files = hashes.keys().map(|path| path.as_str()).collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, ["file", "new-file", "package.json"]);
+    }
+}
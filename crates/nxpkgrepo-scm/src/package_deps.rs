@@ -29,25 +29,35 @@ impl SCM {
         package_path: &AnchoredSystemPath,
         inputs: &[S],
     ) -> Result<GitHashes, Error> {
+        if let Some(sparse_checkout) = self.sparse_checkout_excluding(nxpkg_root, package_path) {
+            debug!(
+                "{} is outside of the sparse checkout, hashing its files from the index",
+                package_path
+            );
+            return sparse_checkout.package_file_hashes(nxpkg_root, package_path, inputs);
+        }
         match self {
             SCM::Manual => crate::manual::get_package_file_hashes_from_processing_gitignore(
                 nxpkg_root,
                 package_path,
                 inputs,
             ),
-            SCM::Git(git) => git
-                .get_package_file_hashes(nxpkg_root, package_path, inputs)
-                .or_else(|e| {
-                    debug!(
-                        "failed to use git to hash files: {}. Falling back to manual",
-                        e
-                    );
-                    crate::manual::get_package_file_hashes_from_processing_gitignore(
-                        nxpkg_root,
-                        package_path,
-                        inputs,
-                    )
-                }),
+            SCM::Vcs(vcs) => {
+                let vcs_inputs = inputs.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
+                vcs.get_package_file_hashes(nxpkg_root, package_path, &vcs_inputs)
+                    .or_else(|e| {
+                        debug!(
+                            "failed to use {} to hash files: {}. Falling back to manual",
+                            vcs.name(),
+                            e
+                        );
+                        crate::manual::get_package_file_hashes_from_processing_gitignore(
+                            nxpkg_root,
+                            package_path,
+                            inputs,
+                        )
+                    })
+            }
         }
     }
 
@@ -58,7 +68,11 @@ impl SCM {
     ) -> Result<GitHashes, Error> {
         match self {
             SCM::Manual => crate::manual::hash_files(nxpkg_root, files, false),
-            SCM::Git(git) => git.hash_files(nxpkg_root, files),
+            SCM::Vcs(vcs) => {
+                let files = files.collect::<Vec<_>>();
+                let files = files.iter().map(|f| f.as_ref()).collect::<Vec<_>>();
+                vcs.hash_files(nxpkg_root, &files)
+            }
         }
     }
 
@@ -72,24 +86,22 @@ impl SCM {
     ) -> Result<GitHashes, Error> {
         crate::manual::hash_files(nxpkg_root, files, true)
     }
-}
 
-impl Git {
-    fn get_package_file_hashes<S: AsRef<str>>(
+    // normalize_line_endings rehashes the given files as if every text file
+    // used LF line endings, so that checkouts with different line ending
+    // settings produce the same hashes.
+    pub fn normalize_line_endings(
         &self,
         nxpkg_root: &AbsoluteSystemPath,
-        package_path: &AnchoredSystemPath,
-        inputs: &[S],
+        hashes: GitHashes,
     ) -> Result<GitHashes, Error> {
-        if inputs.is_empty() {
-            self.get_package_file_hashes_from_index(nxpkg_root, package_path)
-        } else {
-            self.get_package_file_hashes_from_inputs(nxpkg_root, package_path, inputs)
-        }
+        crate::manual::normalize_line_endings(nxpkg_root, hashes)
     }
+}
 
+impl Git {
     #[tracing::instrument(skip(self, nxpkg_root))]
-    fn get_package_file_hashes_from_index(
+    pub(crate) fn get_package_file_hashes_from_index(
         &self,
         nxpkg_root: &AbsoluteSystemPath,
         package_path: &AnchoredSystemPath,
@@ -103,93 +115,94 @@ impl Git {
         hash_objects(&self.root, &full_pkg_path, to_hash, &mut hashes)?;
         Ok(hashes)
     }
+}
 
-    fn hash_files(
-        &self,
-        process_relative_to: &AbsoluteSystemPath,
-        files: impl Iterator<Item = impl AsRef<AnchoredSystemPath>>,
-    ) -> Result<GitHashes, Error> {
-        let mut hashes = GitHashes::new();
-        let to_hash = files
-            .map(|f| {
-                Ok(self
-                    .root
-                    .anchor(process_relative_to.resolve(f.as_ref()))?
-                    .to_unix())
-            })
-            .collect::<Result<Vec<_>, PathError>>()?;
-        // Note: to_hash is *git repo relative*
-        hash_objects(&self.root, process_relative_to, to_hash, &mut hashes)?;
-        Ok(hashes)
-    }
+// Hashes the given files with git's object hashing, without consulting the
+// index. Shared by every backend, since hashing doesn't need a git repository.
+pub(crate) fn hash_files(
+    git_root: &AbsoluteSystemPath,
+    process_relative_to: &AbsoluteSystemPath,
+    files: impl Iterator<Item = impl AsRef<AnchoredSystemPath>>,
+) -> Result<GitHashes, Error> {
+    let mut hashes = GitHashes::new();
+    let to_hash = files
+        .map(|f| {
+            Ok(git_root
+                .anchor(process_relative_to.resolve(f.as_ref()))?
+                .to_unix())
+        })
+        .collect::<Result<Vec<_>, PathError>>()?;
+    // Note: to_hash is *git repo relative*
+    hash_objects(git_root, process_relative_to, to_hash, &mut hashes)?;
+    Ok(hashes)
+}
 
-    #[tracing::instrument(skip(self, nxpkg_root, inputs))]
-    fn get_package_file_hashes_from_inputs<S: AsRef<str>>(
-        &self,
-        nxpkg_root: &AbsoluteSystemPath,
-        package_path: &AnchoredSystemPath,
-        inputs: &[S],
-    ) -> Result<GitHashes, Error> {
-        let full_pkg_path = nxpkg_root.resolve(package_path);
-        let package_unix_path_buf = package_path.to_unix();
-        let package_unix_path = package_unix_path_buf.as_str();
-
-        let mut inputs = inputs
-            .iter()
-            .map(|s| s.as_ref().to_string())
-            .collect::<Vec<String>>();
-        // Add in package.json and nxpkg.json to input patterns. Both file paths are
-        // relative to pkgPath
-        //
-        // - package.json is an input because if the `scripts` in the package.json
-        //   change (i.e. the tasks that nxpkg executes), we want a cache miss, since
-        //   any existing cache could be invalid.
-        // - nxpkg.json because it's the definition of the tasks themselves. The root
-        //   nxpkg.json is similarly included in the global hash. This file may not
-        //   exist in the workspace, but that is ok, because it will get ignored
-        //   downstream.
-        inputs.push("package.json".to_string());
-        inputs.push("nxpkg.json".to_string());
-
-        // The input patterns are relative to the package.
-        // However, we need to change the globbing to be relative to the repo root.
-        // Prepend the package path to each of the input patterns.
-        //
-        // FIXME: we don't yet error on absolute unix paths being passed in as inputs,
-        // and instead tack them on as if they were relative paths. This should be an
-        // error further upstream, but since we haven't pulled the switch yet,
-        // we need to mimic the Go behavior here and trim leading `/`
-        // characters.
-        let (inclusions, exclusions): (Vec<String>, Vec<String>) =
-            inputs.into_iter().partition_map(|raw_glob| {
-                if let Some(exclusion) = raw_glob.strip_prefix('!') {
-                    Either::Right([package_unix_path, exclusion.trim_start_matches('/')].join("/"))
-                } else {
-                    Either::Left([package_unix_path, raw_glob.trim_start_matches('/')].join("/"))
-                }
-            });
-        let files = globwalk::globwalk(
-            nxpkg_root,
-            &inclusions,
-            &exclusions,
-            globwalk::WalkType::Files,
-        )?;
-        let to_hash = files
-            .iter()
-            .map(|entry| {
-                let path = self.root.anchor(entry)?.to_unix();
-                Ok(path)
-            })
-            .collect::<Result<Vec<_>, Error>>()?;
-        let mut hashes = GitHashes::new();
-        hash_objects(&self.root, &full_pkg_path, to_hash, &mut hashes)?;
-        Ok(hashes)
-    }
+#[tracing::instrument(skip(git_root, nxpkg_root, inputs))]
+pub(crate) fn get_package_file_hashes_from_inputs<S: AsRef<str>>(
+    git_root: &AbsoluteSystemPath,
+    nxpkg_root: &AbsoluteSystemPath,
+    package_path: &AnchoredSystemPath,
+    inputs: &[S],
+) -> Result<GitHashes, Error> {
+    let full_pkg_path = nxpkg_root.resolve(package_path);
+    let package_unix_path_buf = package_path.to_unix();
+    let package_unix_path = package_unix_path_buf.as_str();
+
+    let mut inputs = inputs
+        .iter()
+        .map(|s| s.as_ref().to_string())
+        .collect::<Vec<String>>();
+    // Add in package.json and nxpkg.json to input patterns. Both file paths are
+    // relative to pkgPath
+    //
+    // - package.json is an input because if the `scripts` in the package.json
+    //   change (i.e. the tasks that nxpkg executes), we want a cache miss, since
+    //   any existing cache could be invalid.
+    // - nxpkg.json because it's the definition of the tasks themselves. The root
+    //   nxpkg.json is similarly included in the global hash. This file may not
+    //   exist in the workspace, but that is ok, because it will get ignored
+    //   downstream.
+    inputs.push("package.json".to_string());
+    inputs.push("nxpkg.json".to_string());
+
+    // The input patterns are relative to the package.
+    // However, we need to change the globbing to be relative to the repo root.
+    // Prepend the package path to each of the input patterns.
+    //
+    // FIXME: we don't yet error on absolute unix paths being passed in as inputs,
+    // and instead tack them on as if they were relative paths. This should be an
+    // error further upstream, but since we haven't pulled the switch yet,
+    // we need to mimic the Go behavior here and trim leading `/`
+    // characters.
+    let (inclusions, exclusions): (Vec<String>, Vec<String>) =
+        inputs.into_iter().partition_map(|raw_glob| {
+            if let Some(exclusion) = raw_glob.strip_prefix('!') {
+                Either::Right([package_unix_path, exclusion.trim_start_matches('/')].join("/"))
+            } else {
+                Either::Left([package_unix_path, raw_glob.trim_start_matches('/')].join("/"))
+            }
+        });
+    let files = globwalk::globwalk(
+        nxpkg_root,
+        &inclusions,
+        &exclusions,
+        globwalk::WalkType::Files,
+    )?;
+    let to_hash = files
+        .iter()
+        .map(|entry| {
+            let path = git_root.anchor(entry)?.to_unix();
+            Ok(path)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let mut hashes = GitHashes::new();
+    hash_objects(git_root, &full_pkg_path, to_hash, &mut hashes)?;
+    Ok(hashes)
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{assert_matches::assert_matches, collections::HashMap, process::Command};
+    use std::{collections::HashMap, process::Command};
 
     use nxpkgpath::{AbsoluteSystemPathBuf, AnchoredSystemPathBuf, RelativeUnixPathBuf};
 
@@ -266,8 +279,7 @@ mod tests {
 
         setup_repository(&repo_root);
         commit_all(&repo_root);
-        let git = SCM::new(&repo_root);
-        assert_matches!(git, SCM::Git(_));
+        let git = SCM::Vcs(Box::new(Git::find(&repo_root).unwrap()));
         // Remove the .git directory to trigger an error in git hashing
         repo_root.join_component(".git").remove_dir_all().unwrap();
         let pkg_path = repo_root.anchor(&my_pkg_dir).unwrap();
@@ -316,10 +328,7 @@ mod tests {
 
         setup_repository(&repo_root);
         commit_all(&repo_root);
-        let git = SCM::new(&repo_root);
-        let SCM::Git(git) = git else {
-            panic!("expected git, found {:?}", git);
-        };
+        let git = Git::find(&repo_root).unwrap();
 
         // remove a file
         deleted_file_path.remove()?;
@@ -150,7 +150,8 @@ pub async fn start_test_server(port: u16) -> Result<()> {
                     let root_path = put_tempdir_ref.path();
                     let file_path = root_path.join(&hash);
                     let mut file = OpenOptions::new()
-                        .append(true)
+                        .write(true)
+                        .truncate(true)
                         .create(true)
                         .open(&file_path)
                         .unwrap();
@@ -119,7 +119,7 @@ pub struct PreflightResponse {
     pub allow_authorization_header: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct APIError {
     pub code: String,
     pub message: String,
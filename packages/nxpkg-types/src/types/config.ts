@@ -95,7 +95,13 @@ export interface RootSchema extends BaseSchema {
 
   /**
    * A priority-ordered (most-significant to least-significant) array of project-anchored
-   * Unix-style paths to `.env` files to include in the global hash.
+   * Unix-style paths to `.env` files to include in the global hash. Their variables are
+   * loaded into the environment of every task, with lower priority than the task's own
+   * `dotEnv` files.
+   *
+   * In a workspace's `nxpkg.json` the paths are workspace-anchored and the files are
+   * loaded into every task of that workspace, with priority between the root
+   * `globalDotEnv` files and the task's `dotEnv` files.
    *
    * Documentation: https://nxpkg.build/repo/docs/reference/configuration#globalDotEnv
    *
@@ -111,6 +117,15 @@ export interface RootSchema extends BaseSchema {
    * @defaultValue `{}`
    */
   remoteCache?: RemoteCache;
+
+  /**
+   * Limits on the size of the local filesystem cache. When set, nxpkg evicts the
+   * least recently used entries from the cache in the background during runs.
+   * `nxpkg cache prune` applies the same limits on demand.
+   *
+   * @defaultValue `{}`
+   */
+  localCache?: LocalCache;
 }
 
 export interface Pipeline {
@@ -171,7 +186,11 @@ export interface Pipeline {
 
   /**
    * A priority-ordered (most-significant to least-significant) array of workspace-anchored
-   * Unix-style paths to `.env` files to include in the task hash.
+   * Unix-style paths to `.env` files to include in the task hash. Their variables are
+   * loaded into the task's environment. Variables nxpkg was started with take precedence,
+   * both as values and when referenced with `$NAME`, `${NAME}` or `${NAME:-default}`.
+   * In strict env mode only variables matched by `env`, `passThroughEnv`, `globalEnv`
+   * or `globalPassThroughEnv` are loaded.
    *
    * Documentation: https://nxpkg.build/repo/docs/reference/configuration#dotEnv
    *
@@ -249,6 +268,50 @@ export interface Pipeline {
    * @defaultValue false
    */
   persistent?: boolean;
+
+  /**
+   * The number of times to run the task again if it fails, for tasks that are
+   * known to be flaky. The failure is only reported once every retry has failed.
+   *
+   * @defaultValue 0
+   */
+  retries?: number;
+
+  /**
+   * The number of seconds the task can run before nxpkg kills it and reports it as
+   * failed. When combined with `retries`, every attempt gets the full timeout.
+   *
+   * @defaultValue undefined
+   */
+  timeout?: number;
+
+  /**
+   * The number of concurrency slots the task takes up while it runs, for
+   * tasks that are heavier than others, like bundlers or browser test suites.
+   * A weight higher than `--concurrency` takes up every slot.
+   *
+   * @defaultValue 1
+   */
+  weight?: number;
+
+  /**
+   * Names of locks the task holds while it runs. Tasks that share a mutex,
+   * for example because they bind the same port or use the same database,
+   * never run at the same time.
+   *
+   * @defaultValue []
+   */
+  mutexes?: Array<string>;
+
+  /**
+   * Whether the task should get the same hash on every platform. When
+   * `true`, CRLF line endings in the task's inputs, `dotEnv` files and
+   * `globalDependencies` are hashed as LF, and backslashes in arguments
+   * passed through to the task are hashed as forward slashes.
+   *
+   * @defaultValue false
+   */
+  crossPlatform?: boolean;
 }
 
 export interface RemoteCache {
@@ -262,6 +325,16 @@ export interface RemoteCache {
    */
   signature?: boolean;
 
+  /**
+   * Indicates if artifacts are uploaded to the remote cache in the content-addressed format,
+   * which stores file contents once across artifacts. Versions of Nxpkgrepo that don't support
+   * the format can't restore these artifacts, so only enable it once every client sharing the
+   * remote cache supports it.
+   *
+   * @defaultValue false
+   */
+  contentAddressed?: boolean;
+
   /**
    * Indicates if the remote cache is enabled. When `false`, Nxpkgrepo will disable
    * all remote cache operations, even if the repo has a valid token. If true, remote caching
@@ -271,6 +344,91 @@ export interface RemoteCache {
    * @defaultValue true
    */
   enabled?: boolean;
+
+  /**
+   * Additional caches to read artifacts from and write them to, checked in order
+   * after the local filesystem cache and before the Vercel remote cache. Artifacts
+   * found in a later cache are copied into the earlier ones.
+   *
+   * Credentials are read from the environment, never from this file.
+   *
+   * @defaultValue []
+   */
+  backends?: Array<CacheBackend>;
+}
+
+export type CacheBackend = S3CacheBackend | HttpCacheBackend;
+
+export interface S3CacheBackend {
+  type: "s3";
+
+  /**
+   * The bucket artifacts are stored in. Authenticated with the `AWS_ACCESS_KEY_ID`,
+   * `AWS_SECRET_ACCESS_KEY` and optional `AWS_SESSION_TOKEN` environment variables.
+   */
+  bucket: string;
+
+  /**
+   * The region of the bucket. Falls back to `AWS_REGION`, then `us-east-1`.
+   *
+   * @defaultValue undefined
+   */
+  region?: string;
+
+  /**
+   * The endpoint of an S3-compatible object store, such as MinIO or R2.
+   *
+   * @defaultValue undefined
+   */
+  endpoint?: string;
+
+  /**
+   * A prefix prepended to every object key.
+   *
+   * @defaultValue ""
+   */
+  prefix?: string;
+
+  /**
+   * Address the bucket as part of the path rather than the host name. Always
+   * enabled when `endpoint` is set.
+   *
+   * @defaultValue false
+   */
+  forcePathStyle?: boolean;
+}
+
+export interface HttpCacheBackend {
+  type: "http";
+
+  /**
+   * The base URL of a WebDAV server, or any server that accepts `PUT` requests
+   * and serves the stored objects back.
+   */
+  url: string;
+
+  /**
+   * The name of an environment variable holding a bearer token to authenticate with.
+   *
+   * @defaultValue undefined
+   */
+  tokenEnv?: string;
+}
+
+export interface LocalCache {
+  /**
+   * The maximum total size of the local filesystem cache, in bytes.
+   *
+   * @defaultValue undefined
+   */
+  maxSize?: number;
+
+  /**
+   * The maximum time, in seconds, a cache entry can go unused before it is evicted.
+   *
+   * @defaultValue undefined
+   */
+  maxAge?: number;
 }
 
 export type OutputMode =